@@ -0,0 +1,165 @@
+//! 打印审计日志
+//!
+//! 独立于应用日志（不受日志级别影响），每个打印请求及其结果写入一行 JSON（JSONL），
+//! 按日期轮转：`audit.jsonl` 实际写入 `audit-YYYY-MM-DD.jsonl`
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::renderer::chrono_lite_format;
+use crate::storage::write_error;
+
+/// 一条审计记录
+#[derive(Debug, Serialize)]
+pub struct AuditEntry<'a> {
+    /// 时间（UTC），例如 `2024-01-02T03:04:05Z`
+    pub timestamp: String,
+    /// 请求 ID
+    pub id: &'a str,
+    /// 目标打印机（未能确定时为空）
+    pub printer: Option<&'a str>,
+    /// 模板类型
+    pub template_type: &'a str,
+    /// 状态: success, error
+    pub status: &'a str,
+    /// 发送到打印机的字节数（单份）
+    pub bytes: usize,
+    /// 客户端 IP（非 WebSocket 请求时为空）
+    pub client_ip: Option<IpAddr>,
+    /// 错误信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 模板数据（仅在开启 `audit_include_data` 时记录）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<&'a Value>,
+}
+
+/// 审计日志写入器（保持当天文件打开，日期变化时切换到新文件）
+#[derive(Default)]
+pub struct AuditLog {
+    current: Mutex<Option<(PathBuf, File)>>,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加一条记录到 `path` 对应的当日审计文件
+    pub fn record(&self, path: &Path, entry: &AuditEntry) -> Result<(), String> {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        let date = today();
+        let path = dated_path(path, &date);
+
+        let mut current = self.current.lock().unwrap();
+        if current.as_ref().map(|(p, _)| p) != Some(&path) {
+            *current = Some((path.clone(), open_append(&path)?));
+        }
+
+        let (_, file) = current.as_mut().unwrap();
+        writeln!(file, "{}", line).map_err(|e| write_error("Failed to write audit log", &path, e))
+    }
+}
+
+/// 当前时间（UTC），格式 `YYYY-MM-DDTHH:MM:SSZ`
+pub fn timestamp() -> String {
+    format!("{}Z", chrono_lite_format(now_secs()).replace(' ', "T"))
+}
+
+/// 当前日期（UTC），格式 `YYYY-MM-DD`
+fn today() -> String {
+    timestamp()[..10].to_string()
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// 在文件名后加上日期：`logs/audit.jsonl` -> `logs/audit-2024-01-02.jsonl`
+fn dated_path(path: &Path, date: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "audit".to_string());
+    let file_name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, date, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, date),
+    };
+    path.with_file_name(file_name)
+}
+
+/// 以追加模式打开文件（必要时创建目录）
+fn open_append(path: &Path) -> Result<File, String> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| write_error("Failed to create audit log directory", parent, e))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| write_error(&format!("Failed to open audit log {:?}", path), path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_dated_path() {
+        assert_eq!(
+            dated_path(Path::new("logs/audit.jsonl"), "2024-01-02"),
+            Path::new("logs/audit-2024-01-02.jsonl")
+        );
+        assert_eq!(
+            dated_path(Path::new("audit"), "2024-01-02"),
+            Path::new("audit-2024-01-02")
+        );
+    }
+
+    #[test]
+    fn test_record_appends_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit").join("audit.jsonl");
+        let data = json!({"name": "Alice"});
+        let log = AuditLog::new();
+
+        let mut entry = AuditEntry {
+            timestamp: timestamp(),
+            id: "A001",
+            printer: Some("Label"),
+            template_type: "zpl",
+            status: "success",
+            bytes: 42,
+            client_ip: Some(IpAddr::from([127, 0, 0, 1])),
+            error: None,
+            data: None,
+        };
+        log.record(&path, &entry).unwrap();
+        entry.data = Some(&data);
+        log.record(&path, &entry).unwrap();
+
+        let content = std::fs::read_to_string(dated_path(&path, &today())).unwrap();
+        let lines: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["client_ip"], "127.0.0.1");
+        assert_eq!(lines[0]["bytes"], 42);
+        assert!(lines[0].get("data").is_none());
+        assert_eq!(lines[1]["data"]["name"], "Alice");
+    }
+}
@@ -2,35 +2,120 @@
 //!
 //! 负责应用配置的加载、保存和验证
 
+mod secrets;
 mod settings;
 
 // Re-export all config types for external use
 #[allow(unused_imports)]
-pub use settings::{AppConfig, PrinterConfig, ServerConfig, UiConfig};
+pub use settings::{
+    AppConfig, PrinterCommands, PrinterConfig, PrinterRoute, ServerConfig, UiConfig,
+};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tracing::{debug, info, warn};
 
+use crate::storage::{write_atomic, write_error};
+
+use secrets::{load_secrets, restore_secrets, snapshot_secrets, store_secrets, Keychain};
+
+/// 指定配置文件完整路径的环境变量
+const CONFIG_PATH_ENV: &str = "RPRINT_CONFIG";
+
+/// 指定运行环境的环境变量（使用 `config.<env>.json`）
+const CONFIG_ENV_ENV: &str = "RPRINT_ENV";
+
+/// 加载配置时无法从凭据库读取的敏感字段，保存配置时不会因字段为空而删除凭据库中的值
+static UNREADABLE_SECRETS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
 /// 获取配置文件路径
+///
+/// 优先使用 `RPRINT_CONFIG` 指定的路径；设置了 `RPRINT_ENV` 时使用默认目录下的
+/// `config.<env>.json`，便于在同一台机器上区分开发和生产配置
 pub fn get_config_path() -> PathBuf {
-    // Windows: %APPDATA%/rprint/config.json
-    // Linux/Mac: ~/.config/rprint/config.json
-    let config_dir = dirs::config_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("rprint");
+    let path = resolve_config_path(
+        std::env::var(CONFIG_PATH_ENV).ok(),
+        std::env::var(CONFIG_ENV_ENV).ok(),
+        &config_dir(),
+    );
 
     // 确保目录存在
-    if !config_dir.exists() {
-        if let Err(e) = std::fs::create_dir_all(&config_dir) {
+    if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+        if let Err(e) = std::fs::create_dir_all(dir) {
             warn!("Failed to create config directory: {}", e);
         }
     }
 
-    config_dir.join("config.json")
+    path
+}
+
+/// 默认配置目录
+fn config_dir() -> PathBuf {
+    // Windows: %APPDATA%/rprint/config.json
+    // Linux/Mac: ~/.config/rprint/config.json
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("rprint")
 }
 
-/// 加载配置
+/// 当前配置文件对应的凭据库（开发、生产等配置文件的敏感字段互不影响）
+fn keychain_for(config_path: &Path) -> Keychain {
+    Keychain::for_config(config_path, &resolve_config_path(None, None, &config_dir()))
+}
+
+/// 根据环境变量的值确定配置文件路径（空值视为未设置）
+fn resolve_config_path(
+    config_path: Option<String>,
+    env: Option<String>,
+    config_dir: &Path,
+) -> PathBuf {
+    if let Some(path) = config_path.filter(|p| !p.trim().is_empty()) {
+        return PathBuf::from(path);
+    }
+
+    match env.as_deref().map(str::trim) {
+        Some(env) if !env.is_empty() => config_dir.join(format!("config.{}.json", env)),
+        _ => config_dir.join("config.json"),
+    }
+}
+
+/// 获取模板目录路径
+pub fn get_templates_dir(config: &AppConfig) -> PathBuf {
+    match &config.server.templates_dir {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        // 默认：配置文件同级的 templates 目录
+        _ => get_config_path().with_file_name("templates"),
+    }
+}
+
+/// 获取打印归档目录路径
+pub fn get_archive_dir(config: &AppConfig) -> PathBuf {
+    match &config.printer.archive_dir {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        // 默认：配置文件同级的 archive 目录
+        _ => get_config_path().with_file_name("archive"),
+    }
+}
+
+/// 获取定时打印任务的保存路径（配置文件同级的 scheduled.json）
+pub fn get_scheduled_jobs_path() -> PathBuf {
+    get_config_path().with_file_name("scheduled.json")
+}
+
+/// 加载配置（敏感字段从系统凭据库读取）
 pub fn load_config() -> AppConfig {
+    let mut config = read_config_file();
+    *UNREADABLE_SECRETS.lock().unwrap() =
+        load_secrets(&mut config, &keychain_for(&get_config_path()));
+    if let Err(e) = config.server.check_webhook_url() {
+        warn!("{}, webhook disabled", e);
+        config.server.webhook_url = None;
+    }
+    config
+}
+
+/// 读取配置文件，文件不存在或无法解析时使用默认配置
+fn read_config_file() -> AppConfig {
     let path = get_config_path();
     debug!("Loading config from: {:?}", path);
 
@@ -56,24 +141,58 @@ pub fn load_config() -> AppConfig {
     AppConfig::default()
 }
 
-/// 保存配置
+/// 保存配置（敏感字段写入系统凭据库，不可用时明文保存在配置文件中）
+///
+/// 配置文件写入失败时撤销凭据库中的修改，两者保持一致
 pub fn save_config(config: &AppConfig) -> Result<(), String> {
+    config.server.check_webhook_url()?;
     let path = get_config_path();
     debug!("Saving config to: {:?}", path);
 
-    let content = serde_json::to_string_pretty(config)
-        .map_err(|e| format!("Failed to serialize config: {}", e))?;
-
-    std::fs::write(&path, content)
-        .map_err(|e| format!("Failed to write config file: {}", e))?;
+    let keychain = keychain_for(&path);
+    let mut unreadable = UNREADABLE_SECRETS.lock().unwrap();
+    let mut remaining = unreadable.clone();
+    let previous = snapshot_secrets(&keychain);
+    let result = serde_json::to_string_pretty(&store_secrets(config, &keychain, &mut remaining))
+        .map_err(|e| format!("Failed to serialize config: {}", e))
+        .and_then(|content| {
+            // 先写入临时文件再替换，磁盘写满时不会截断原配置文件
+            write_atomic(&path, content.as_bytes())
+                .map_err(|e| write_error("Failed to write config file", &path, e))
+        });
+    if let Err(e) = result {
+        restore_secrets(&previous, &keychain);
+        return Err(e);
+    }
+    *unreadable = remaining;
 
     info!("Config saved successfully");
     Ok(())
 }
 
+/// 将当前配置文件备份为 `config.json.bak`，配置文件不存在时返回 `None`
+pub fn backup_config() -> Result<Option<PathBuf>, String> {
+    backup_config_file(&get_config_path())
+}
+
+/// 将指定配置文件复制到同目录的 `<name>.bak`（已有备份时覆盖）
+fn backup_config_file(path: &Path) -> Result<Option<PathBuf>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let backup = path.with_extension("json.bak");
+    std::fs::copy(path, &backup)
+        .map_err(|e| write_error("Failed to back up config file", &backup, e))?;
+
+    info!("Config backed up to: {:?}", backup);
+    Ok(Some(backup))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     #[test]
     fn test_default_config() {
@@ -89,4 +208,156 @@ mod tests {
         let parsed: AppConfig = serde_json::from_str(&json).unwrap();
         assert_eq!(config.server.port, parsed.server.port);
     }
+
+    #[test]
+    fn test_resolve_config_path() {
+        let dir = Path::new("rprint");
+        assert_eq!(
+            resolve_config_path(None, None, dir),
+            dir.join("config.json")
+        );
+        assert_eq!(
+            resolve_config_path(None, Some("dev".to_string()), dir),
+            dir.join("config.dev.json")
+        );
+        assert_eq!(
+            resolve_config_path(
+                Some("/etc/rprint.json".to_string()),
+                Some("dev".to_string()),
+                dir
+            ),
+            PathBuf::from("/etc/rprint.json")
+        );
+        assert_eq!(
+            resolve_config_path(Some(String::new()), Some(" ".to_string()), dir),
+            dir.join("config.json")
+        );
+    }
+
+    #[test]
+    fn test_backup_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+
+        // 配置文件不存在时无需备份
+        assert_eq!(backup_config_file(&path).unwrap(), None);
+
+        std::fs::write(&path, r#"{"server":{"port":9200}}"#).unwrap();
+        let backup = backup_config_file(&path).unwrap().unwrap();
+        assert_eq!(backup, dir.path().join("config.json.bak"));
+        assert_eq!(
+            std::fs::read_to_string(&backup).unwrap(),
+            r#"{"server":{"port":9200}}"#
+        );
+
+        // 再次备份时覆盖旧备份
+        std::fs::write(&path, "{}").unwrap();
+        backup_config_file(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_receipt_width_for() {
+        let mut config = PrinterConfig::default();
+        config.receipt_widths.insert("Kitchen".to_string(), 40);
+        config.model_receipt_widths.insert("TM-T88".to_string(), 42);
+
+        // 内置型号默认不启用
+        assert_eq!(
+            config.receipt_width_for(Some("Front"), Some("EPSON TM-T20II Receipt")),
+            config.receipt_width
+        );
+
+        config.builtin_model_widths = true;
+        let width = |name: &str, driver: Option<&str>| config.receipt_width_for(Some(name), driver);
+
+        assert_eq!(config.receipt_width_for(None, None), config.receipt_width);
+        assert_eq!(width("Kitchen", Some("EPSON TM-T20")), 40);
+        // 按驱动名称匹配内置型号，配置的型号优先
+        assert_eq!(width("Front", Some("EPSON TM-T20II Receipt")), 48);
+        assert_eq!(width("Bar", Some("EPSON TM-T88V Receipt")), 42);
+        assert_eq!(width("POS-58 Printer", None), 32);
+        assert_eq!(width("Office", Some("HP LaserJet")), config.receipt_width);
+    }
+
+    #[test]
+    fn test_printer_routes() {
+        let route = |field: &str, equals: serde_json::Value, printer: &str| PrinterRoute {
+            field: field.to_string(),
+            equals,
+            printer: printer.to_string(),
+        };
+        let config = PrinterConfig {
+            routes: vec![
+                route("department", json!("kitchen"), "Kitchen"),
+                route("order.table", json!(12), "Terrace"),
+                route("department", json!("bar"), "Bar"),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.route(&json!({"department": "kitchen"})),
+            Some("Kitchen")
+        );
+        assert_eq!(config.route(&json!({"department": "bar"})), Some("Bar"));
+        // 嵌套字段，数字与数字字符串按文本比较
+        assert_eq!(
+            config.route(&json!({"order": {"table": 12}})),
+            Some("Terrace")
+        );
+        assert_eq!(
+            config.route(&json!({"order": {"table": "12"}})),
+            Some("Terrace")
+        );
+        // 第一条匹配的规则生效
+        assert_eq!(
+            config.route(&json!({"department": "kitchen", "order": {"table": 12}})),
+            Some("Kitchen")
+        );
+
+        assert_eq!(config.route(&json!({"department": "hall"})), None);
+        assert_eq!(config.route(&json!({"order": 12})), None);
+        assert_eq!(config.route(&json!(null)), None);
+    }
+
+    #[test]
+    fn test_check_webhook_url() {
+        let check = |url: Option<&str>| {
+            let config = ServerConfig {
+                webhook_url: url.map(str::to_string),
+                ..Default::default()
+            };
+            config.check_webhook_url()
+        };
+
+        assert!(check(None).is_ok());
+        assert!(check(Some("")).is_ok());
+        assert!(check(Some("http://10.0.0.5/hook")).is_ok());
+        assert!(check(Some("https://hooks.example.com/print")).is_ok());
+        assert!(check(Some("ftp://hooks.example.com/print")).is_err());
+        assert!(check(Some("file:///etc/passwd")).is_err());
+        assert!(check(Some("hooks.example.com/print")).is_err());
+    }
+
+    #[test]
+    fn test_printer_commands_wrap() {
+        let commands = PrinterCommands {
+            // ESC 7（设置浓度）/ ESC B（蜂鸣）
+            prologue: Some("GzcH".to_string()),
+            epilogue: Some("G0IDAw==".to_string()),
+        };
+        assert_eq!(
+            commands.wrap(b"hi").unwrap(),
+            [0x1b, 0x37, 0x07, b'h', b'i', 0x1b, 0x42, 0x03, 0x03]
+        );
+
+        assert_eq!(PrinterCommands::default().wrap(b"hi").unwrap(), b"hi");
+
+        let invalid = PrinterCommands {
+            prologue: Some("not base64!".to_string()),
+            epilogue: None,
+        };
+        assert!(invalid.wrap(b"hi").is_err());
+    }
 }
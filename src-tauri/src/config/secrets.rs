@@ -0,0 +1,361 @@
+//! 配置中的敏感字段存储
+//!
+//! 令牌、API key 和 webhook 地址保存在系统凭据库（Windows 凭据管理器 / macOS 钥匙串），
+//! `config.json` 中只保留非敏感配置；没有可用的凭据库时回退为明文保存并记录警告。
+//! 凭据库条目按配置文件区分，`RPRINT_CONFIG` / `RPRINT_ENV` 选择的开发配置不会覆盖生产配置的令牌
+
+use std::path::Path;
+
+use tracing::{debug, warn};
+
+use super::AppConfig;
+
+/// 凭据库中的服务名称（默认配置文件使用）
+const SERVICE: &str = "rprint";
+
+/// 保存在凭据库中的字段（凭据库中的条目名称）
+const SECRET_NAMES: [&str; 3] = ["server.api_token", "server.api_key", "server.webhook_url"];
+
+/// 敏感字段的存储后端
+pub(super) trait SecretStore {
+    /// 读取字段值，不存在时返回 `None`
+    fn get(&self, name: &str) -> Result<Option<String>, String>;
+    fn set(&self, name: &str, value: &str) -> Result<(), String>;
+    /// 删除字段，不存在时视为成功
+    fn delete(&self, name: &str) -> Result<(), String>;
+}
+
+/// 系统凭据库（条目属于某个配置文件）
+pub(super) struct Keychain {
+    #[cfg_attr(not(any(windows, target_os = "macos")), allow(dead_code))]
+    service: String,
+}
+
+impl Keychain {
+    /// 配置文件 `config_path` 的凭据库条目，`default_path` 为默认配置文件路径
+    pub(super) fn for_config(config_path: &Path, default_path: &Path) -> Self {
+        Self {
+            service: service_name(config_path, default_path),
+        }
+    }
+}
+
+/// 凭据库中的服务名称：默认配置文件沿用 `rprint`（兼容已保存的条目），其他配置文件附加其路径
+fn service_name(config_path: &Path, default_path: &Path) -> String {
+    if config_path == default_path {
+        SERVICE.to_string()
+    } else {
+        format!("{}:{}", SERVICE, config_path.display())
+    }
+}
+
+#[cfg(any(windows, target_os = "macos"))]
+impl Keychain {
+    fn entry(&self, name: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(&self.service, name).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(any(windows, target_os = "macos"))]
+impl SecretStore for Keychain {
+    fn get(&self, name: &str) -> Result<Option<String>, String> {
+        match self.entry(name)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        self.entry(name)?
+            .set_password(value)
+            .map_err(|e| e.to_string())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        match self.entry(name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// 其他平台没有可用的凭据库：读取不到任何值，写入失败（回退为明文）
+#[cfg(not(any(windows, target_os = "macos")))]
+impl SecretStore for Keychain {
+    fn get(&self, _name: &str) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    fn set(&self, _name: &str, _value: &str) -> Result<(), String> {
+        Err("no keychain available on this platform".to_string())
+    }
+
+    fn delete(&self, _name: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// 保存在凭据库中的字段（凭据库中的条目名称，字段）
+fn secret_fields(config: &mut AppConfig) -> [(&'static str, &mut Option<String>); 3] {
+    let [token, key, webhook] = SECRET_NAMES;
+    [
+        (token, &mut config.server.api_token),
+        (key, &mut config.server.api_key),
+        (webhook, &mut config.server.webhook_url),
+    ]
+}
+
+/// 从凭据库补全敏感字段，返回读取失败（如凭据库被锁定）的字段
+///
+/// `config.json` 中仍有明文值（旧版本配置或凭据库不可用时保存的配置）时保留明文，下次保存时迁移
+pub(super) fn load_secrets(config: &mut AppConfig, store: &dyn SecretStore) -> Vec<&'static str> {
+    let mut unreadable = Vec::new();
+    for (name, field) in secret_fields(config) {
+        if field.is_some() {
+            continue;
+        }
+        match store.get(name) {
+            Ok(value) => *field = value,
+            Err(e) => {
+                warn!("Failed to read {} from keychain: {}", name, e);
+                unreadable.push(name);
+            }
+        }
+    }
+    unreadable
+}
+
+/// 将敏感字段写入凭据库，返回去掉这些字段、用于写入 `config.json` 的配置
+///
+/// 字段为空时删除凭据库中的旧值，但 `unreadable` 中的字段（加载时读取失败，为空不代表用户已清空）
+/// 保留凭据库中的值；写入新值后从 `unreadable` 中移除。凭据库不可用时该字段保留明文并记录警告
+pub(super) fn store_secrets(
+    config: &AppConfig,
+    store: &dyn SecretStore,
+    unreadable: &mut Vec<&'static str>,
+) -> AppConfig {
+    let mut stripped = config.clone();
+    for (name, field) in secret_fields(&mut stripped) {
+        let result = match field.as_deref().filter(|value| !value.is_empty()) {
+            Some(value) => {
+                let result = store.set(name, value);
+                if result.is_ok() {
+                    unreadable.retain(|unread| *unread != name);
+                }
+                result
+            }
+            None if unreadable.contains(&name) => {
+                debug!(
+                    "Keeping {} in keychain, it could not be read when loading",
+                    name
+                );
+                Ok(())
+            }
+            None => store.delete(name),
+        };
+        match result {
+            Ok(()) => *field = None,
+            Err(e) => warn!(
+                "Keychain unavailable, saving {} in plaintext config: {}",
+                name, e
+            ),
+        }
+    }
+    stripped
+}
+
+/// 读取凭据库中敏感字段的当前值，保存配置失败时用于回滚（读取失败的字段不回滚）
+pub(super) fn snapshot_secrets(store: &dyn SecretStore) -> Vec<(&'static str, Option<String>)> {
+    SECRET_NAMES
+        .into_iter()
+        .filter_map(|name| store.get(name).ok().map(|value| (name, value)))
+        .collect()
+}
+
+/// 将凭据库恢复为 `snapshot_secrets` 读取的值，使其与未写入的配置文件保持一致
+pub(super) fn restore_secrets(snapshot: &[(&str, Option<String>)], store: &dyn SecretStore) {
+    for (name, value) in snapshot {
+        let result = match value {
+            Some(value) => store.set(name, value),
+            None => store.delete(name),
+        };
+        if let Err(e) = result {
+            warn!("Failed to restore {} in keychain: {}", name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MemoryStore(RefCell<HashMap<String, String>>);
+
+    impl SecretStore for MemoryStore {
+        fn get(&self, name: &str) -> Result<Option<String>, String> {
+            Ok(self.0.borrow().get(name).cloned())
+        }
+
+        fn set(&self, name: &str, value: &str) -> Result<(), String> {
+            self.0
+                .borrow_mut()
+                .insert(name.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn delete(&self, name: &str) -> Result<(), String> {
+            self.0.borrow_mut().remove(name);
+            Ok(())
+        }
+    }
+
+    struct Unavailable;
+
+    impl SecretStore for Unavailable {
+        fn get(&self, _name: &str) -> Result<Option<String>, String> {
+            Err("unavailable".to_string())
+        }
+
+        fn set(&self, _name: &str, _value: &str) -> Result<(), String> {
+            Err("unavailable".to_string())
+        }
+
+        fn delete(&self, _name: &str) -> Result<(), String> {
+            Err("unavailable".to_string())
+        }
+    }
+
+    #[test]
+    fn test_secrets_round_trip() {
+        let store = MemoryStore::default();
+        let mut config = AppConfig::default();
+        config.server.api_token = Some("s3cret".to_string());
+        config.server.webhook_url = Some("https://hooks.example.com/abc".to_string());
+
+        // 写入配置文件的内容不含敏感字段
+        let stripped = store_secrets(&config, &store, &mut Vec::new());
+        assert_eq!(stripped.server.api_token, None);
+        assert_eq!(stripped.server.webhook_url, None);
+        assert_eq!(stripped.server.port, config.server.port);
+        let json = serde_json::to_string(&stripped).unwrap();
+        assert!(!json.contains("s3cret"));
+
+        let mut loaded = stripped;
+        assert!(load_secrets(&mut loaded, &store).is_empty());
+        assert_eq!(loaded, config);
+
+        // 清空字段时删除凭据库中的旧值
+        config.server.api_token = None;
+        let mut loaded = store_secrets(&config, &store, &mut Vec::new());
+        load_secrets(&mut loaded, &store);
+        assert_eq!(loaded.server.api_token, None);
+        assert_eq!(
+            loaded.server.webhook_url.as_deref(),
+            Some("https://hooks.example.com/abc")
+        );
+    }
+
+    #[test]
+    fn test_secrets_plaintext_fallback() {
+        let mut config = AppConfig::default();
+        config.server.api_key = Some("k3y".to_string());
+
+        // 凭据库不可用时保留明文
+        let stripped = store_secrets(&config, &Unavailable, &mut Vec::new());
+        assert_eq!(stripped.server.api_key.as_deref(), Some("k3y"));
+        let mut loaded = stripped;
+        load_secrets(&mut loaded, &Unavailable);
+        assert_eq!(loaded, config);
+
+        // 配置文件中的明文优先于凭据库中的值
+        let store = MemoryStore::default();
+        store.set("server.api_key", "old").unwrap();
+        let mut loaded = config.clone();
+        load_secrets(&mut loaded, &store);
+        assert_eq!(loaded.server.api_key.as_deref(), Some("k3y"));
+    }
+
+    /// 读取失败的凭据库（如被锁定），写入和删除正常
+    #[derive(Default)]
+    struct Locked(MemoryStore);
+
+    impl SecretStore for Locked {
+        fn get(&self, _name: &str) -> Result<Option<String>, String> {
+            Err("locked".to_string())
+        }
+
+        fn set(&self, name: &str, value: &str) -> Result<(), String> {
+            self.0.set(name, value)
+        }
+
+        fn delete(&self, name: &str) -> Result<(), String> {
+            self.0.delete(name)
+        }
+    }
+
+    #[test]
+    fn test_unreadable_secrets_kept() {
+        let store = Locked::default();
+        store.set("server.api_token", "s3cret").unwrap();
+        store.set("server.api_key", "k3y").unwrap();
+
+        let mut config = AppConfig::default();
+        let mut unreadable = load_secrets(&mut config, &store);
+        assert_eq!(unreadable, SECRET_NAMES);
+        assert_eq!(config.server.api_token, None);
+
+        // 读取失败的字段为空时不删除凭据库中的值，设置了新值的字段正常写入
+        config.server.api_key = Some("new".to_string());
+        let stripped = store_secrets(&config, &store, &mut unreadable);
+        assert_eq!(stripped.server.api_token, None);
+        assert_eq!(stripped.server.api_key, None);
+        assert_eq!(
+            store.0.get("server.api_token").unwrap().as_deref(),
+            Some("s3cret")
+        );
+        assert_eq!(
+            store.0.get("server.api_key").unwrap().as_deref(),
+            Some("new")
+        );
+        assert_eq!(unreadable, ["server.api_token", "server.webhook_url"]);
+
+        // 写入新值后，再次清空时正常删除
+        config.server.api_key = None;
+        store_secrets(&config, &store, &mut unreadable);
+        assert_eq!(store.0.get("server.api_key").unwrap(), None);
+    }
+
+    #[test]
+    fn test_secrets_restore() {
+        let store = MemoryStore::default();
+        store.set("server.api_token", "old").unwrap();
+        let snapshot = snapshot_secrets(&store);
+
+        // 配置文件写入失败时撤销凭据库中的修改
+        let mut config = AppConfig::default();
+        config.server.api_key = Some("new".to_string());
+        store_secrets(&config, &store, &mut Vec::new());
+        assert_eq!(store.get("server.api_token").unwrap(), None);
+        restore_secrets(&snapshot, &store);
+        assert_eq!(
+            store.get("server.api_token").unwrap().as_deref(),
+            Some("old")
+        );
+        assert_eq!(store.get("server.api_key").unwrap(), None);
+    }
+
+    #[test]
+    fn test_service_name_per_config() {
+        let default = Path::new("rprint/config.json");
+        assert_eq!(service_name(default, default), "rprint");
+        let dev = Path::new("rprint/config.dev.json");
+        assert_eq!(
+            service_name(dev, default),
+            format!("rprint:{}", dev.display())
+        );
+    }
+}
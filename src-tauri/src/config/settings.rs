@@ -1,6 +1,14 @@
 //! 配置数据结构
 
+use std::collections::HashMap;
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::protocol::PrintDatatype;
+use crate::renderer::{HelperProfile, DEFAULT_MAX_RENDER_SIZE};
 
 /// 应用配置
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -42,6 +50,114 @@ pub struct ServerConfig {
     /// 启动时自动开始服务
     #[serde(default = "default_true")]
     pub auto_start: bool,
+
+    /// 自动启动服务前的延迟（毫秒，等待系统服务就绪）
+    #[serde(default)]
+    pub auto_start_delay_ms: u64,
+
+    /// 自动启动服务前等待至少一台打印机可用的最长时间（秒，0 表示不等待）
+    #[serde(default)]
+    pub auto_start_wait_printers_secs: u64,
+
+    /// 启动时预热（渲染一个简单模板、枚举打印机并载入模板缓存），避免第一个打印请求明显变慢
+    #[serde(default)]
+    pub warm_up_on_start: bool,
+
+    /// 模板目录（为空则使用配置目录下的 templates）
+    #[serde(default)]
+    pub templates_dir: Option<String>,
+
+    /// 停机时建议客户端的重连延迟（毫秒）
+    #[serde(default = "default_shutdown_retry_after_ms")]
+    pub shutdown_retry_after_ms: u64,
+
+    /// 停机时等待已接受的打印任务完成的最长时间（秒）
+    #[serde(default = "default_shutdown_drain_timeout_secs")]
+    pub shutdown_drain_timeout_secs: u64,
+
+    /// 保留的失败任务数量
+    #[serde(default = "default_failed_jobs_limit")]
+    pub failed_jobs_limit: usize,
+
+    /// 保留的最近打印结果数量（客户端重连后可通过 `get_result` 查询）
+    #[serde(default = "default_result_retention_limit")]
+    pub result_retention_limit: usize,
+
+    /// 打印结果的保留时长（秒）
+    #[serde(default = "default_result_retention_secs")]
+    pub result_retention_secs: u64,
+
+    /// 分块打印任务的组装超时（秒）
+    #[serde(default = "default_chunk_timeout_secs")]
+    pub chunk_timeout_secs: u64,
+
+    /// 等待打印任务完成的超时（秒，请求设置 `wait_completion` 时生效）
+    #[serde(default = "default_job_wait_timeout_secs")]
+    pub job_wait_timeout_secs: u64,
+
+    /// 单个打印请求（渲染和打印，包括等待任务完成）的默认超时（秒，请求可通过 `timeout_ms` 指定）
+    #[serde(default = "default_print_timeout_secs")]
+    pub print_timeout_secs: u64,
+
+    /// 模板渲染结果的大小上限（字节），超过时中止渲染并返回 `RENDER_TOO_LARGE`
+    #[serde(default = "default_max_render_bytes")]
+    pub max_render_bytes: usize,
+
+    /// 是否监听 TCP 端口
+    #[serde(default = "default_true")]
+    pub tcp_enabled: bool,
+
+    /// 禁用 Nagle 算法（TCP_NODELAY），小的 WebSocket 消息立即发送
+    #[serde(default = "default_true")]
+    pub tcp_nodelay: bool,
+
+    /// TCP keepalive 探测间隔（秒，0 为关闭），网络不稳定时及时发现已断开的连接
+    #[serde(default = "default_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+
+    /// Unix domain socket 路径（可选，仅 Unix 平台，本机进程无需经过网络访问服务）
+    #[serde(default)]
+    pub unix_socket: Option<String>,
+
+    /// Unix domain socket 文件权限（八进制，默认 `600` 仅当前用户可访问）
+    #[serde(default = "default_unix_socket_mode")]
+    pub unix_socket_mode: String,
+
+    /// REST 端点（`/print`、`/config`）接受的 Bearer 令牌（与 `api_key` 任一匹配即可，都为空则无需认证）
+    #[serde(default)]
+    pub api_token: Option<String>,
+
+    /// REST 端点（`/print`、`/config`）接受的 API key（与 `api_token` 任一匹配即可，WebSocket 不受影响）
+    #[serde(default)]
+    pub api_key: Option<String>,
+
+    /// 携带 API key 的请求头名称（网关注入的请求头，如 `X-API-Key`）
+    #[serde(default = "default_auth_header")]
+    pub auth_header: String,
+
+    /// 以 info 级别记录连接建立和断开（默认为 debug，客户端频繁重连时避免刷屏）
+    #[serde(default)]
+    pub log_connections: bool,
+
+    /// 审计日志路径（为空则不记录，按日期轮转，如 audit.jsonl -> audit-2024-01-02.jsonl）
+    #[serde(default)]
+    pub audit_log_path: Option<String>,
+
+    /// 审计日志是否记录模板数据（可能包含个人信息，默认不记录）
+    #[serde(default)]
+    pub audit_include_data: bool,
+
+    /// 打印完成通知地址（每个任务结束后 POST JSON，为空则不发送；支持 http:// 和 https://）
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+
+    /// 单次 Webhook 请求的超时（毫秒）
+    #[serde(default = "default_webhook_timeout_ms")]
+    pub webhook_timeout_ms: u64,
+
+    /// Webhook 失败后的重试次数
+    #[serde(default = "default_webhook_retries")]
+    pub webhook_retries: u32,
 }
 
 impl Default for ServerConfig {
@@ -50,10 +166,83 @@ impl Default for ServerConfig {
             port: default_port(),
             host: default_host(),
             auto_start: true,
+            auto_start_delay_ms: 0,
+            auto_start_wait_printers_secs: 0,
+            warm_up_on_start: false,
+            templates_dir: None,
+            shutdown_retry_after_ms: default_shutdown_retry_after_ms(),
+            shutdown_drain_timeout_secs: default_shutdown_drain_timeout_secs(),
+            failed_jobs_limit: default_failed_jobs_limit(),
+            result_retention_limit: default_result_retention_limit(),
+            result_retention_secs: default_result_retention_secs(),
+            chunk_timeout_secs: default_chunk_timeout_secs(),
+            job_wait_timeout_secs: default_job_wait_timeout_secs(),
+            print_timeout_secs: default_print_timeout_secs(),
+            max_render_bytes: default_max_render_bytes(),
+            tcp_enabled: true,
+            tcp_nodelay: true,
+            tcp_keepalive_secs: default_tcp_keepalive_secs(),
+            unix_socket: None,
+            unix_socket_mode: default_unix_socket_mode(),
+            api_token: None,
+            api_key: None,
+            auth_header: default_auth_header(),
+            log_connections: false,
+            audit_log_path: None,
+            audit_include_data: false,
+            webhook_url: None,
+            webhook_timeout_ms: default_webhook_timeout_ms(),
+            webhook_retries: default_webhook_retries(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// 检查 webhook 地址：只接受 `http://` 和 `https://`
+    pub fn check_webhook_url(&self) -> Result<(), String> {
+        let Some(url) = self.webhook_url.as_deref().filter(|url| !url.is_empty()) else {
+            return Ok(());
+        };
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|e| format!("Invalid webhook_url '{}': {}", url, e))?;
+        match parsed.scheme() {
+            "http" | "https" => Ok(()),
+            scheme => Err(format!(
+                "Unsupported webhook_url scheme '{}': only http:// and https:// are allowed",
+                scheme
+            )),
         }
     }
 }
 
+/// 常见小票打印机型号的每行字符数（默认字体，键为名称或驱动名称中的小写子串）
+pub const DEFAULT_MODEL_RECEIPT_WIDTHS: &[(&str, usize)] = &[
+    // Epson 80mm
+    ("tm-t20", 48),
+    ("tm-t70", 48),
+    ("tm-t82", 48),
+    ("tm-t88", 48),
+    ("tm-m30", 48),
+    // Epson 58mm
+    ("tm-p20", 32),
+    ("tm-t58", 32),
+    // Star 80mm
+    ("tsp100", 48),
+    ("tsp143", 48),
+    ("tsp650", 48),
+    // Bixolon 80mm
+    ("srp-350", 42),
+    // 芯烨 / 通用型号
+    ("xp-58", 32),
+    ("xp-80", 48),
+    ("pos58", 32),
+    ("pos-58", 32),
+    ("pos80", 48),
+    ("pos-80", 48),
+    ("58mm", 32),
+    ("80mm", 48),
+];
+
 /// 打印机配置
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PrinterConfig {
@@ -72,6 +261,213 @@ pub struct PrinterConfig {
     /// ZPL 打印机（标签）
     #[serde(default)]
     pub zpl_printer: Option<String>,
+
+    /// 默认纸张大小（未指定时使用，如 "A4"、"80mm 200mm"）
+    #[serde(default = "default_paper_size")]
+    pub default_paper_size: String,
+
+    /// 无法识别的纸张大小是否报错（否则使用默认纸张大小）
+    #[serde(default)]
+    pub strict_paper_size: bool,
+
+    /// 调试用：HTML/PDF 打印窗口可见并保留，便于检查渲染结果和打开开发者工具
+    #[serde(default)]
+    pub debug_print_window: bool,
+
+    /// 请求静默打印 HTML/PDF 但平台不支持时报错（`SILENT_PRINT_NOT_SUPPORTED`），而不是显示打印对话框
+    #[serde(default)]
+    pub require_silent_print: bool,
+
+    /// 是否归档所有打印内容（可被请求的 archive 选项覆盖）
+    #[serde(default)]
+    pub archive: bool,
+
+    /// 归档目录（为空则使用配置目录下的 archive）
+    #[serde(default)]
+    pub archive_dir: Option<String>,
+
+    /// 各打印机的附加指令（键为打印机名称）
+    #[serde(default)]
+    pub commands: HashMap<String, PrinterCommands>,
+
+    /// 小票宽度（每行字符数，58mm 纸为 32，80mm 纸为 48），用于 `hr` 等模板 helper
+    #[serde(default = "default_receipt_width")]
+    pub receipt_width: usize,
+
+    /// 各打印机的小票宽度（键为打印机名称），未配置的打印机按型号匹配，都没有时使用 `receipt_width`
+    #[serde(default)]
+    pub receipt_widths: HashMap<String, usize>,
+
+    /// 各型号的小票宽度（键为打印机名称或驱动名称中的子串，不区分大小写），优先于内置的常见型号
+    #[serde(default)]
+    pub model_receipt_widths: HashMap<String, usize>,
+
+    /// 是否按内置的常见型号匹配小票宽度（默认关闭，型号表可能与实际纸宽不符）
+    #[serde(default)]
+    pub builtin_model_widths: bool,
+
+    /// 各打印机的 Windows 打印数据类型（键为打印机名称，未配置时为 RAW，可被请求的 datatype 选项覆盖）
+    #[serde(default)]
+    pub datatypes: HashMap<String, PrintDatatype>,
+
+    /// 各打印机相邻两个打印任务的最小间隔（毫秒，键为打印机名称），防止廉价热敏打印机连续出纸过热或卡纸
+    #[serde(default)]
+    pub min_job_interval_ms: HashMap<String, u64>,
+
+    /// 按打印数据选择打印机的路由规则（按顺序匹配，优先于请求指定的打印机）
+    #[serde(default)]
+    pub routes: Vec<PrinterRoute>,
+
+    /// 模板 helper 使用的 profile 名称（为空或不存在时使用默认 profile）
+    #[serde(default)]
+    pub helper_profile: Option<String>,
+
+    /// 命名的模板 helper profile（不同部署可使用不同的货币符号、小数位、时区等）
+    #[serde(default)]
+    pub helper_profiles: HashMap<String, HelperProfile>,
+}
+
+impl PrinterConfig {
+    /// 获取当前选择的模板 helper profile
+    pub fn active_helper_profile(&self) -> HelperProfile {
+        self.helper_profile
+            .as_ref()
+            .and_then(|name| self.helper_profiles.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// 获取打印机的小票宽度：按名称配置的宽度优先，其次按名称和驱动名称匹配型号
+    pub fn receipt_width_for(&self, printer: Option<&str>, driver: Option<&str>) -> usize {
+        let Some(name) = printer else {
+            return self.receipt_width;
+        };
+        self.receipt_widths
+            .get(name)
+            .copied()
+            .or_else(|| self.model_receipt_width(name, driver))
+            .unwrap_or(self.receipt_width)
+    }
+
+    /// 是否按型号匹配小票宽度（配置了型号或启用了内置型号）
+    pub fn matches_models(&self) -> bool {
+        self.builtin_model_widths || !self.model_receipt_widths.is_empty()
+    }
+
+    /// 按型号匹配小票宽度（多个型号匹配时取最长的，配置的型号优先于启用的内置型号）
+    pub fn model_receipt_width(&self, name: &str, driver: Option<&str>) -> Option<usize> {
+        let text = format!("{} {}", name, driver.unwrap_or_default()).to_lowercase();
+        let longest_match = |models: &mut dyn Iterator<Item = (&str, usize)>| {
+            models
+                .filter(|(model, _)| !model.is_empty() && text.contains(&model.to_lowercase()))
+                .max_by_key(|(model, _)| model.len())
+                .map(|(_, width)| width)
+        };
+
+        longest_match(
+            &mut self
+                .model_receipt_widths
+                .iter()
+                .map(|(model, width)| (model.as_str(), *width)),
+        )
+        .or_else(|| {
+            self.builtin_model_widths
+                .then(|| longest_match(&mut DEFAULT_MODEL_RECEIPT_WIDTHS.iter().copied()))
+                .flatten()
+        })
+    }
+
+    /// 获取打印机的打印数据类型（请求指定时优先）
+    pub fn datatype_for(&self, printer: &str, requested: Option<PrintDatatype>) -> PrintDatatype {
+        requested
+            .or_else(|| self.datatypes.get(printer).copied())
+            .unwrap_or_default()
+    }
+
+    /// 获取打印机相邻任务的最小间隔（未配置时不限制）
+    pub fn min_job_interval_for(&self, printer: &str) -> Duration {
+        Duration::from_millis(self.min_job_interval_ms.get(printer).copied().unwrap_or(0))
+    }
+
+    /// 按路由规则确定打印数据的目标打印机（第一条匹配的规则生效）
+    pub fn route(&self, data: &Value) -> Option<&str> {
+        self.routes
+            .iter()
+            .find(|route| route.matches(data))
+            .map(|route| route.printer.as_str())
+    }
+}
+
+/// 打印机路由规则：数据字段等于指定值时使用对应的打印机
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PrinterRoute {
+    /// 数据字段（用 `.` 访问嵌套字段，如 `order.department`）
+    pub field: String,
+
+    /// 字段的值（字符串、数字和布尔值按文本比较，`1` 与 `"1"` 视为相等）
+    pub equals: Value,
+
+    /// 目标打印机名称
+    pub printer: String,
+}
+
+impl PrinterRoute {
+    /// 数据是否匹配该规则
+    pub fn matches(&self, data: &Value) -> bool {
+        let Some(value) = self
+            .field
+            .split('.')
+            .try_fold(data, |value, key| value.get(key))
+        else {
+            return false;
+        };
+
+        *value == self.equals
+            || scalar_text(value).is_some_and(|text| scalar_text(&self.equals) == Some(text))
+    }
+}
+
+/// 标量值的文本形式（对象、数组和 null 返回 `None`）
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// 打印机附加指令
+///
+/// 仅作用于 ESC/POS 原始打印任务，不影响 PDF/文档打印
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PrinterCommands {
+    /// 打印前发送的字节序列（base64，如设置打印浓度）
+    #[serde(default)]
+    pub prologue: Option<String>,
+
+    /// 打印后发送的字节序列（base64，如蜂鸣提示）
+    #[serde(default)]
+    pub epilogue: Option<String>,
+}
+
+impl PrinterCommands {
+    /// 在打印数据前后加上附加指令
+    pub fn wrap(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let prologue = decode_base64("prologue", self.prologue.as_deref())?;
+        let epilogue = decode_base64("epilogue", self.epilogue.as_deref())?;
+        Ok([prologue.as_slice(), data, epilogue.as_slice()].concat())
+    }
+}
+
+/// 解码 base64 指令（为空时返回空序列）
+fn decode_base64(field: &str, value: Option<&str>) -> Result<Vec<u8>, String> {
+    match value {
+        Some(value) if !value.trim().is_empty() => STANDARD
+            .decode(value.trim())
+            .map_err(|e| format!("Invalid {} (base64): {}", field, e)),
+        _ => Ok(Vec::new()),
+    }
 }
 
 impl Default for PrinterConfig {
@@ -81,6 +477,22 @@ impl Default for PrinterConfig {
             pdf_printer: None,
             escpos_printer: None,
             zpl_printer: None,
+            default_paper_size: default_paper_size(),
+            strict_paper_size: false,
+            debug_print_window: false,
+            require_silent_print: false,
+            archive: false,
+            archive_dir: None,
+            commands: HashMap::new(),
+            receipt_width: default_receipt_width(),
+            receipt_widths: HashMap::new(),
+            model_receipt_widths: HashMap::new(),
+            builtin_model_widths: false,
+            datatypes: HashMap::new(),
+            min_job_interval_ms: HashMap::new(),
+            routes: Vec::new(),
+            helper_profile: None,
+            helper_profiles: HashMap::new(),
         }
     }
 }
@@ -103,6 +515,10 @@ pub struct UiConfig {
     /// 保留历史记录数量
     #[serde(default = "default_history_limit")]
     pub history_limit: usize,
+
+    /// 打印完成时向前端推送 `print-complete` 事件（用于播放提示音）
+    #[serde(default)]
+    pub play_sound_on_complete: bool,
 }
 
 impl Default for UiConfig {
@@ -112,6 +528,7 @@ impl Default for UiConfig {
             minimize_on_close: true,
             auto_launch: false,
             history_limit: default_history_limit(),
+            play_sound_on_complete: false,
         }
     }
 }
@@ -125,6 +542,70 @@ fn default_host() -> String {
     "0.0.0.0".to_string()
 }
 
+fn default_paper_size() -> String {
+    "A4".to_string()
+}
+
+fn default_receipt_width() -> usize {
+    crate::renderer::DEFAULT_RECEIPT_WIDTH
+}
+
+fn default_shutdown_retry_after_ms() -> u64 {
+    3000
+}
+
+fn default_shutdown_drain_timeout_secs() -> u64 {
+    30
+}
+
+fn default_failed_jobs_limit() -> usize {
+    20
+}
+
+fn default_result_retention_limit() -> usize {
+    200
+}
+
+fn default_result_retention_secs() -> u64 {
+    600
+}
+
+fn default_webhook_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_webhook_retries() -> u32 {
+    3
+}
+
+fn default_unix_socket_mode() -> String {
+    "600".to_string()
+}
+
+fn default_auth_header() -> String {
+    "X-API-Key".to_string()
+}
+
+fn default_tcp_keepalive_secs() -> u64 {
+    30
+}
+
+fn default_chunk_timeout_secs() -> u64 {
+    60
+}
+
+fn default_job_wait_timeout_secs() -> u64 {
+    60
+}
+
+fn default_print_timeout_secs() -> u64 {
+    120
+}
+
+fn default_max_render_bytes() -> usize {
+    DEFAULT_MAX_RENDER_SIZE
+}
+
 fn default_true() -> bool {
     true
 }
@@ -0,0 +1,310 @@
+//! 图片转单色点阵
+//!
+//! 将 PNG / BMP 图片（如门店 logo）转换为打印机可用的单色位图，
+//! 灰度和彩色图片使用 Floyd-Steinberg 抖动，透明区域按白色处理
+
+/// 图片的最大宽度和高度（点），超过时拒绝转换，避免生成过大的打印数据
+pub const MAX_IMAGE_DIMENSION: u32 = 2000;
+
+/// 单色位图（每行按字节对齐，最高位在左，1 表示黑点）
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonoBitmap {
+    pub width: u32,
+    pub height: u32,
+    /// 点阵数据（每行 `bytes_per_row` 字节）
+    pub data: Vec<u8>,
+}
+
+impl MonoBitmap {
+    /// 每行字节数
+    pub fn bytes_per_row(&self) -> usize {
+        (self.width as usize).div_ceil(8)
+    }
+}
+
+/// 灰度图片（0 为黑，255 为白）
+struct GrayImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+/// 解码 PNG / BMP 图片并转换为单色位图
+pub fn to_monochrome(bytes: &[u8]) -> Result<MonoBitmap, String> {
+    let image = if bytes.starts_with(b"\x89PNG") {
+        decode_png(bytes)?
+    } else if bytes.starts_with(b"BM") {
+        decode_bmp(bytes)?
+    } else {
+        return Err("Unsupported image format (expected PNG or BMP)".to_string());
+    };
+    Ok(dither(&image))
+}
+
+/// 检查图片尺寸
+fn check_dimensions(width: u32, height: u32) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err("Image is empty".to_string());
+    }
+    if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        return Err(format!(
+            "Image {}x{} exceeds the limit of {} dots",
+            width, height, MAX_IMAGE_DIMENSION
+        ));
+    }
+    Ok(())
+}
+
+/// 带透明度的像素叠加到白色背景上
+fn over_white(luma: u8, alpha: u8) -> u8 {
+    let (luma, alpha) = (luma as u32, alpha as u32);
+    ((luma * alpha + 255 * (255 - alpha)) / 255) as u8
+}
+
+/// RGB 转灰度（ITU-R BT.601）
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+fn decode_png(bytes: &[u8]) -> Result<GrayImage, String> {
+    let mut decoder = png::Decoder::new(bytes);
+    // 调色板、低位深展开为 8 位，16 位截为 8 位
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| format!("Invalid PNG: {}", e))?;
+    let info = reader.info();
+    check_dimensions(info.width, info.height)?;
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(|e| format!("Invalid PNG: {}", e))?;
+    let channels = match frame.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err("Unsupported PNG color type".to_string()),
+    };
+
+    let mut pixels = Vec::with_capacity((frame.width * frame.height) as usize);
+    for row in buffer.chunks(frame.line_size).take(frame.height as usize) {
+        for px in row.chunks(channels).take(frame.width as usize) {
+            pixels.push(match px {
+                [l] => *l,
+                [l, a] => over_white(*l, *a),
+                [r, g, b] => luma(*r, *g, *b),
+                [r, g, b, a] => over_white(luma(*r, *g, *b), *a),
+                _ => 255,
+            });
+        }
+    }
+
+    Ok(GrayImage {
+        width: frame.width,
+        height: frame.height,
+        pixels,
+    })
+}
+
+/// 读取小端整数
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// 解码未压缩的 BMP（1/4/8 位调色板及 24/32 位真彩色）
+fn decode_bmp(bytes: &[u8]) -> Result<GrayImage, String> {
+    let invalid = || "Invalid BMP: truncated header".to_string();
+    let data_offset = read_u32(bytes, 10).ok_or_else(invalid)? as usize;
+    let header_size = read_u32(bytes, 14).ok_or_else(invalid)? as usize;
+    let width = read_u32(bytes, 18).ok_or_else(invalid)? as i32;
+    let height = read_u32(bytes, 22).ok_or_else(invalid)? as i32;
+    let bits = read_u16(bytes, 28).ok_or_else(invalid)?;
+    let compression = read_u32(bytes, 30).ok_or_else(invalid)?;
+    let colors_used = read_u32(bytes, 46).ok_or_else(invalid)?;
+
+    // 32 位图片的 BI_BITFIELDS 通常为标准的 BGRA 排列
+    if compression != 0 && !(compression == 3 && bits == 32) {
+        return Err("Unsupported BMP: compressed images are not supported".to_string());
+    }
+    if width <= 0 {
+        return Err("Invalid BMP: width must be positive".to_string());
+    }
+    // 高度为负表示自上而下存储
+    let top_down = height < 0;
+    let (width, height) = (width as u32, height.unsigned_abs());
+    check_dimensions(width, height)?;
+
+    let palette: Vec<u8> = if bits <= 8 {
+        let count = if colors_used == 0 {
+            1 << bits
+        } else {
+            colors_used as usize
+        };
+        let start = 14 + header_size;
+        let table = bytes
+            .get(start..start + count * 4)
+            .ok_or("Invalid BMP: truncated palette")?;
+        table
+            .chunks(4)
+            .map(|bgr| luma(bgr[2], bgr[1], bgr[0]))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let stride = (width as usize * bits as usize).div_ceil(32) * 4;
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height as usize {
+        let row_index = if top_down { y } else { height as usize - 1 - y };
+        let start = data_offset + row_index * stride;
+        let row = bytes
+            .get(start..start + stride)
+            .ok_or("Invalid BMP: truncated pixel data")?;
+        for x in 0..width as usize {
+            let value = match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits as usize;
+                    let shift = 8 - bits as usize - bit % 8;
+                    let index = (row[bit / 8] >> shift) & ((1u16 << bits) - 1) as u8;
+                    *palette.get(index as usize).unwrap_or(&255)
+                }
+                24 => luma(row[x * 3 + 2], row[x * 3 + 1], row[x * 3]),
+                32 => {
+                    let px = &row[x * 4..x * 4 + 4];
+                    // 常见的 32 位 BMP 不使用 alpha 通道（全为 0），此时视为不透明
+                    let alpha = if compression == 3 { px[3] } else { 255 };
+                    over_white(luma(px[2], px[1], px[0]), alpha)
+                }
+                _ => return Err(format!("Unsupported BMP: {} bits per pixel", bits)),
+            };
+            pixels.push(value);
+        }
+    }
+
+    Ok(GrayImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Floyd-Steinberg 抖动，转换为单色位图（黑白图片不受影响）
+fn dither(image: &GrayImage) -> MonoBitmap {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let mut values: Vec<i16> = image.pixels.iter().map(|&p| p as i16).collect();
+    let bytes_per_row = width.div_ceil(8);
+    let mut data = vec![0u8; bytes_per_row * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let old = values[i];
+            let black = old < 128;
+            if black {
+                data[y * bytes_per_row + x / 8] |= 0x80 >> (x % 8);
+            }
+            let error = old - if black { 0 } else { 255 };
+
+            let mut spread = |dx: isize, dy: usize, weight: i16| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < width && y + dy < height {
+                    let j = (y + dy) * width + nx as usize;
+                    values[j] += error * weight / 16;
+                }
+            };
+            spread(1, 0, 7);
+            spread(-1, 1, 3);
+            spread(0, 1, 5);
+            spread(1, 1, 1);
+        }
+    }
+
+    MonoBitmap {
+        width: image.width,
+        height: image.height,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构建 24 位 BMP（pixels 为自上而下的 RGB）
+    fn bmp_24(width: u32, height: u32, pixels: &[[u8; 3]]) -> Vec<u8> {
+        let stride = (width as usize * 3).div_ceil(4) * 4;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"BM");
+        bytes.extend_from_slice(&((54 + stride * height as usize) as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&54u32.to_le_bytes());
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&(width as i32).to_le_bytes());
+        bytes.extend_from_slice(&(height as i32).to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&24u16.to_le_bytes());
+        bytes.extend_from_slice(&[0; 24]);
+        // 自下而上存储
+        for row in pixels.chunks(width as usize).rev() {
+            let start = bytes.len();
+            for [r, g, b] in row {
+                bytes.extend_from_slice(&[*b, *g, *r]);
+            }
+            bytes.resize(start + stride, 0);
+        }
+        bytes
+    }
+
+    fn png_gray(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(pixels)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_bmp_to_monochrome() {
+        let (k, w) = ([0, 0, 0], [255, 255, 255]);
+        let pixels = [k, w, w, w, w, w, w, w, w, k, w, k, w, w, w, w, w, w, w, k];
+        let bitmap = to_monochrome(&bmp_24(10, 2, &pixels)).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (10, 2));
+        assert_eq!(bitmap.bytes_per_row(), 2);
+        assert_eq!(bitmap.data, [0x80, 0x40, 0x40, 0x40]);
+    }
+
+    #[test]
+    fn test_png_dithering() {
+        // 50% 灰度抖动后约一半为黑点
+        let bitmap = to_monochrome(&png_gray(16, 16, &[128; 256])).unwrap();
+        let black: u32 = bitmap.data.iter().map(|b| b.count_ones()).sum();
+        assert!((96..=160).contains(&black), "black dots: {}", black);
+
+        let bitmap = to_monochrome(&png_gray(8, 1, &[0, 255, 0, 255, 0, 255, 0, 255])).unwrap();
+        assert_eq!(bitmap.data, [0xaa]);
+    }
+
+    #[test]
+    fn test_invalid_image() {
+        assert!(to_monochrome(b"GIF89a").is_err());
+        assert!(to_monochrome(b"BM\x00\x00").is_err());
+        assert!(to_monochrome(&png_gray(1, 1, &[0])[..20]).is_err());
+        let too_wide = bmp_24(MAX_IMAGE_DIMENSION + 1, 1, &[[0, 0, 0]; 2001]);
+        assert!(to_monochrome(&too_wide).unwrap_err().contains("exceeds"));
+    }
+}
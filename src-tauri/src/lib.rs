@@ -1,42 +1,109 @@
 //! rprint - 远程打印服务
 
+mod audit;
 mod config;
+mod graphics;
+mod logs;
 mod printer;
 mod protocol;
 mod renderer;
 mod server;
+mod storage;
+mod templates;
 
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     AppHandle, Emitter, Manager,
 };
 use tauri_plugin_autostart::MacosLauncher;
-use tauri_plugin_log::{Target, TargetKind};
-use tokio::sync::RwLock;
-use tracing::info;
+use tauri_plugin_log::{fern, Target, TargetKind};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{oneshot, Mutex, RwLock};
+use tracing::{info, warn};
 
-use config::{load_config, save_config, AppConfig};
-use printer::{create_printer_manager, PrinterManager};
+use audit::AuditEntry;
+use config::{
+    backup_config, get_archive_dir, get_config_path, get_templates_dir, load_config, save_config,
+    AppConfig,
+};
+use logs::LogBuffer;
+use printer::create_printer_manager;
+use printer::pdf::{HtmlPrintResult, PrintCancel};
+use server::ServerState;
+use templates::{SavedTemplate, TemplateInfo, TemplateStore};
+
+// 自定义打印机管理器（如网络、USB 打印机）所需的类型
+pub use printer::{
+    escpos, CompositePrinterManager, NetworkPrinterManager, PrinterManager, UsbPrinterManager,
+    SCHEME_TCP, SCHEME_USB,
+};
+pub use protocol::{EscposStatus, PaperSettings, PrinterInfo, TrayInfo};
+
+// 测试用打印机管理器（`mock-printer` 特性），配合 `run_with_printer_manager` 在没有打印机的环境中运行服务
+#[cfg(feature = "mock-printer")]
+pub use printer::mock::{JobGate, MockPrinterManager, PrintCall};
+
+/// 打印完成时推送到前端的事件名
+const PRINT_COMPLETE_EVENT: &str = "print-complete";
 
 /// 应用状态
 pub struct AppState {
     /// WebSocket 服务是否运行中
     pub ws_running: Arc<RwLock<bool>>,
+    /// WebSocket 服务停止信号
+    pub ws_shutdown: Arc<Mutex<Option<oneshot::Sender<()>>>>,
     /// 应用配置
     pub config: Arc<RwLock<AppConfig>>,
     /// 打印机管理器
     pub printer_manager: Arc<Box<dyn PrinterManager>>,
+    /// WebSocket 服务状态（跨服务重启保留模板缓存、失败任务等）
+    pub server: Arc<ServerState>,
+    /// HTML/PDF 打印取消信号
+    pub pdf_cancel: Arc<PrintCancel>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
-        let config = load_config();
+        Self::with_printer_manager(create_printer_manager())
+    }
+}
+
+impl AppState {
+    /// 使用指定的打印机管理器创建应用状态（Tauri 命令与 WebSocket 服务共用同一个实例）
+    pub fn with_printer_manager(printer_manager: Box<dyn PrinterManager>) -> Self {
+        let first_run = !get_config_path().exists();
+        let mut config = load_config();
+
+        // 首次运行时根据已安装的打印机自动配置 ESC/POS、ZPL 打印机
+        if first_run {
+            if let Ok(printers) = printer_manager.list_printers() {
+                if printer::populate_routing(&mut config.printer, &printers) {
+                    info!("Printer routing detected: {:?}", config.printer);
+                    if let Err(e) = save_config(&config) {
+                        warn!("Failed to save detected printer routing: {}", e);
+                    }
+                }
+            }
+        }
+
+        let templates = Arc::new(TemplateStore::new(get_templates_dir(&config)));
+        let config = Arc::new(RwLock::new(config));
+        let printer_manager = Arc::new(printer_manager);
         Self {
             ws_running: Arc::new(RwLock::new(false)),
-            config: Arc::new(RwLock::new(config)),
-            printer_manager: Arc::new(create_printer_manager()),
+            ws_shutdown: Arc::new(Mutex::new(None)),
+            config: config.clone(),
+            printer_manager: printer_manager.clone(),
+            server: Arc::new(ServerState::with_printer_manager(
+                config,
+                templates,
+                printer_manager,
+            )),
+            pdf_cancel: Arc::new(PrintCancel::new()),
         }
     }
 }
@@ -67,6 +134,9 @@ async fn update_config(
     // 保存到文件
     save_config(&new_config)?;
 
+    // 模板目录可能已变更
+    state.server.templates.set_dir(get_templates_dir(&new_config));
+
     // 更新内存中的配置
     let mut config = state.config.write().await;
     *config = new_config;
@@ -75,6 +145,27 @@ async fn update_config(
     Ok(())
 }
 
+/// Tauri 命令：恢复默认配置（旧配置备份为 config.json.bak）
+#[tauri::command]
+async fn reset_config(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<AppConfig, String> {
+    backup_config()?;
+
+    let new_config = AppConfig::default();
+    save_config(&new_config)?;
+
+    state.server.templates.set_dir(get_templates_dir(&new_config));
+    *state.config.write().await = new_config.clone();
+
+    // 通知前端
+    let _ = app.emit("config-updated", &new_config);
+
+    info!("Configuration reset to defaults");
+    Ok(new_config)
+}
+
 /// Tauri 命令：启动 WebSocket 服务
 #[tauri::command]
 async fn start_ws_server(
@@ -90,15 +181,21 @@ async fn start_ws_server(
     *running = true;
     drop(running);
 
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    *state.ws_shutdown.lock().await = Some(shutdown_tx);
+
     // 在后台启动服务
     let ws_running = state.ws_running.clone();
+    let server_state = state.server.clone();
+    let app_handle = app.clone();
     tokio::spawn(async move {
         info!("Starting WebSocket server on port {}", port);
 
-        if let Err(e) = server::start_server(port).await {
+        if let Err(e) = server::start_server(port, server_state, shutdown_rx).await {
             tracing::error!("WebSocket server error: {}", e);
             let mut running = ws_running.write().await;
             *running = false;
+            emit_server_error(&app_handle, &e.to_string());
         }
     });
 
@@ -114,6 +211,17 @@ async fn start_ws_server(
     Ok(format!("WebSocket server started on port {}", port))
 }
 
+/// 通知前端服务已停止（如端口被占用时为 `PORT_IN_USE`）
+fn emit_server_error(app: &AppHandle, error: &str) {
+    let _ = app.emit(
+        "server-status",
+        serde_json::json!({
+            "status": "offline",
+            "error": error
+        }),
+    );
+}
+
 /// Tauri 命令：停止 WebSocket 服务
 #[tauri::command]
 async fn stop_ws_server(
@@ -125,7 +233,10 @@ async fn stop_ws_server(
         return Err("Server not running".to_string());
     }
 
-    // TODO: 实现优雅停止
+    // 优雅停止：通知客户端后关闭连接
+    if let Some(shutdown_tx) = state.ws_shutdown.lock().await.take() {
+        let _ = shutdown_tx.send(());
+    }
     *running = false;
 
     let _ = app.emit(
@@ -143,9 +254,15 @@ async fn stop_ws_server(
 async fn get_server_status(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
     let running = *state.ws_running.read().await;
     let port = state.config.read().await.server.port;
+    // 没有安装打印机时，界面可引导用户先安装打印机
+    let printers_available = state
+        .printer_manager
+        .list_printers()
+        .is_ok_and(|printers| !printers.is_empty());
     Ok(serde_json::json!({
         "running": running,
-        "port": port
+        "port": port,
+        "printers_available": printers_available
     }))
 }
 
@@ -156,102 +273,484 @@ fn list_printers(state: tauri::State<'_, AppState>) -> Result<serde_json::Value,
     Ok(serde_json::to_value(&printers).map_err(|e| e.to_string())?)
 }
 
+/// Tauri 命令：推测打印机类型 (epl, zpl, escpos, normal)
+#[tauri::command]
+fn detect_printer_type(
+    state: tauri::State<'_, AppState>,
+    printer_name: String,
+) -> Result<String, String> {
+    let printers = state.printer_manager.list_printers()?;
+    let driver = printers
+        .iter()
+        .find(|p| p.name == printer_name)
+        .and_then(|p| p.driver.as_deref());
+    Ok(printer::detect_printer_type(&printer_name, driver).to_string())
+}
+
+/// Tauri 命令：获取 ZPL 标签打印机的内置字体（供标签设计器选择字体）
+///
+/// 打印后台无法读取打印机对 `~HI` 等查询指令的响应，返回 Zebra 打印机通用的标准内置字体
+#[tauri::command]
+fn get_zpl_fonts(
+    state: tauri::State<'_, AppState>,
+    printer_name: String,
+) -> Result<Vec<renderer::zpl::ZplFont>, String> {
+    let printers = state.printer_manager.list_printers()?;
+    if !printer::printer_exists(&printers, &printer_name) {
+        return Err(format!("Printer not found: {}", printer_name));
+    }
+    Ok(renderer::zpl::RESIDENT_FONTS.to_vec())
+}
+
 /// Tauri 命令：获取默认打印机
 #[tauri::command]
 fn get_default_printer(state: tauri::State<'_, AppState>) -> Result<Option<String>, String> {
     state.printer_manager.get_default_printer()
 }
 
-/// Tauri 命令：打印原始数据 (ESC/POS, ZPL)
+/// Tauri 命令：检查打印机是否存在（按名称或唯一标识）
+#[tauri::command]
+fn printer_exists(state: tauri::State<'_, AppState>, name: String) -> Result<bool, String> {
+    let printers = state.printer_manager.list_printers()?;
+    Ok(printer::printer_exists(&printers, &name))
+}
+
+/// Tauri 命令：获取打印机的默认纸张、纸盒及可用纸盒列表
 #[tauri::command]
-fn print_raw(
+fn get_paper_settings(
+    state: tauri::State<'_, AppState>,
+    printer_name: String,
+) -> Result<protocol::PaperSettings, String> {
+    state.printer_manager.get_paper_settings(&printer_name)
+}
+
+/// Tauri 打印命令完成后通知前端，并与 WebSocket 打印请求一样写入审计日志（命令没有请求 ID，生成一个）
+fn report_command_print<T, E: std::fmt::Display>(
+    state: &AppState,
+    config: &AppConfig,
+    printer: Option<&str>,
+    template_type: &str,
+    bytes: usize,
+    data: Option<&serde_json::Value>,
+    result: &Result<T, E>,
+) {
+    state.server.notify_print_complete(None, printer, result);
+    let id = uuid::Uuid::new_v4().to_string();
+    state.server.record_audit(
+        &config.server,
+        AuditEntry {
+            timestamp: audit::timestamp(),
+            id: &id,
+            printer,
+            template_type,
+            status: if result.is_ok() { "success" } else { "error" },
+            bytes: if result.is_ok() { bytes } else { 0 },
+            client_ip: None,
+            error: result.as_ref().err().map(|e| e.to_string()),
+            data,
+        },
+    );
+}
+
+/// 持有打印机写入锁（并等待 `min_job_interval_ms` 间隔）在阻塞线程中执行打印，
+/// 与 WebSocket 打印任务一样依次写入同一打印机
+async fn print_locked<F>(
+    state: &AppState,
+    config: &AppConfig,
+    printer_name: &str,
+    print: F,
+) -> Result<(), String>
+where
+    F: FnOnce(&dyn PrinterManager) -> Result<(), protocol::PrintError> + Send + 'static,
+{
+    let min_interval = config.printer.min_job_interval_for(printer_name);
+    let guard = state
+        .server
+        .printer_locks
+        .lock_job(printer_name, min_interval)
+        .await;
+    let printer_manager = state.printer_manager.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _guard = guard;
+        print(printer_manager.as_ref().as_ref()).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Print task failed: {}", e)))
+}
+
+/// Tauri 命令：打印原始数据 (ESC/POS, ZPL)，使用该打印机配置的数据类型
+#[tauri::command]
+async fn print_raw(
     state: tauri::State<'_, AppState>,
     printer_name: String,
     data: Vec<u8>,
 ) -> Result<(), String> {
-    state.printer_manager.print_raw(&printer_name, &data)
+    let config = state.config.read().await.clone();
+    let datatype = config.printer.datatype_for(&printer_name, None);
+    let bytes = data.len();
+    let name = printer_name.clone();
+    let result = print_locked(&state, &config, &printer_name, move |manager| {
+        manager.print_raw(&name, &data, datatype, false).map(|_| ())
+    })
+    .await;
+    report_command_print(
+        &state,
+        &config,
+        Some(&printer_name),
+        "raw",
+        bytes,
+        None,
+        &result,
+    );
+    result
 }
 
 /// Tauri 命令：打印文本
 #[tauri::command]
-fn print_text(
+async fn print_text(
     state: tauri::State<'_, AppState>,
     printer_name: String,
     text: String,
 ) -> Result<(), String> {
-    state.printer_manager.print_text(&printer_name, &text)
+    let config = state.config.read().await.clone();
+    let bytes = text.len();
+    let name = printer_name.clone();
+    let result = print_locked(&state, &config, &printer_name, move |manager| {
+        manager.print_text(&name, &text)
+    })
+    .await;
+    report_command_print(
+        &state,
+        &config,
+        Some(&printer_name),
+        "text",
+        bytes,
+        None,
+        &result,
+    );
+    result
 }
 
 /// Tauri 命令：使用模板渲染并打印
+///
+/// 提供 `template_name` 时使用模板库中的模板（存在 Schema 时先校验数据），否则使用 `template`
 #[tauri::command]
-fn print_with_template(
+async fn print_with_template(
     state: tauri::State<'_, AppState>,
     printer_name: String,
-    template: String,
+    template: Option<String>,
+    template_name: Option<String>,
     data: serde_json::Value,
 ) -> Result<(), String> {
-    // 渲染模板
-    let rendered = renderer::render_template(&template, &data)?;
-    // 打印渲染后的内容
-    state.printer_manager.print_text(&printer_name, &rendered)
+    let config = state.config.read().await.clone();
+    let rendered = (|| {
+        let template = match template_name.filter(|name| !name.is_empty()) {
+            Some(name) => {
+                if let Some(schema) = state.server.templates.get_schema(&name)? {
+                    templates::check_data(&schema, &data).map_err(|e| e.to_string())?;
+                }
+                state.server.templates.get(&name)?
+            }
+            None => template.ok_or("Missing template or template_name")?,
+        };
+        // 渲染模板
+        render_with_config(&config, &template, &data)
+    })();
+    let mut bytes = 0;
+    let result = match rendered {
+        Ok(rendered) => {
+            bytes = rendered.len();
+            // 打印渲染后的内容
+            let name = printer_name.clone();
+            print_locked(&state, &config, &printer_name, move |manager| {
+                manager.print_text(&name, &rendered)
+            })
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    report_command_print(
+        &state,
+        &config,
+        Some(&printer_name),
+        "text",
+        bytes,
+        Some(&data),
+        &result,
+    );
+    result
 }
 
 /// Tauri 命令：预览模板渲染结果（不打印）
+///
+/// 提供 `schema` 时先校验数据，校验失败返回 `DATA_VALIDATION` 错误
+#[tauri::command]
+fn preview_template(
+    state: tauri::State<'_, AppState>,
+    template: String,
+    data: serde_json::Value,
+    schema: Option<serde_json::Value>,
+) -> Result<String, String> {
+    if let Some(schema) = &schema {
+        templates::check_data(schema, &data).map_err(|e| e.to_string())?;
+    }
+    render_with_config(&state.config.blocking_read(), &template, &data)
+}
+
+/// Tauri 命令：使用磁盘上的样例 JSON 数据渲染模板（不打印）
+#[tauri::command]
+fn render_template_with_file(
+    state: tauri::State<'_, AppState>,
+    template: String,
+    data_path: String,
+) -> Result<String, String> {
+    let data = templates::load_sample(Path::new(&data_path))?;
+    render_with_config(&state.config.blocking_read(), &template, &data)
+}
+
+/// 按配置的 helper profile 和渲染大小上限（`server.max_render_bytes`）渲染模板，与 WebSocket 打印一致
+fn render_with_config(
+    config: &AppConfig,
+    template: &str,
+    data: &serde_json::Value,
+) -> Result<String, String> {
+    renderer::render_template_limited(
+        template,
+        data,
+        renderer::DEFAULT_RECEIPT_WIDTH,
+        config.server.max_render_bytes,
+        &config.printer.active_helper_profile(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Tauri 命令：使用目录中的每个样例 JSON 文件渲染模板，返回各样例的渲染结果
 #[tauri::command]
-fn preview_template(template: String, data: serde_json::Value) -> Result<String, String> {
-    renderer::render_template(&template, &data)
+fn render_all_samples(
+    state: tauri::State<'_, AppState>,
+    template: String,
+    dir: String,
+) -> Result<Vec<templates::SampleResult>, String> {
+    let config = state.config.blocking_read();
+    templates::render_samples(
+        &template,
+        Path::new(&dir),
+        renderer::DEFAULT_RECEIPT_WIDTH,
+        config.server.max_render_bytes,
+        &config.printer.active_helper_profile(),
+    )
+}
+
+/// Tauri 命令：重新加载模板目录中的模板
+#[tauri::command]
+fn reload_templates(state: tauri::State<'_, AppState>) -> usize {
+    state.server.templates.reload()
+}
+
+/// Tauri 命令：保存模板到模板库（已存在时覆盖）
+#[tauri::command]
+fn save_template(
+    state: tauri::State<'_, AppState>,
+    name: String,
+    content: String,
+    template_type: String,
+) -> Result<TemplateInfo, String> {
+    state.server.templates.save(&name, &content, &template_type)
+}
+
+/// Tauri 命令：列出模板库中的模板
+#[tauri::command]
+fn list_templates(state: tauri::State<'_, AppState>) -> Result<Vec<TemplateInfo>, String> {
+    state.server.templates.list()
+}
+
+/// Tauri 命令：获取模板库中的模板内容
+#[tauri::command]
+fn get_template(state: tauri::State<'_, AppState>, name: String) -> Result<SavedTemplate, String> {
+    state.server.templates.get_saved(&name)
+}
+
+/// Tauri 命令：从模板库删除模板
+#[tauri::command]
+fn delete_template(state: tauri::State<'_, AppState>, name: String) -> Result<(), String> {
+    state.server.templates.delete(&name)
+}
+
+/// Tauri 命令：获取最近失败的打印任务（最新的在最前）
+#[tauri::command]
+fn get_failed_jobs(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    serde_json::to_value(state.server.failed_jobs.list()).map_err(|e| e.to_string())
+}
+
+/// Tauri 命令：重新执行失败的打印任务（index 0 为最近一次失败）
+#[tauri::command]
+async fn replay_job(state: tauri::State<'_, AppState>, index: usize) -> Result<(), String> {
+    server::replay_failed_job(&state.server, index)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri 命令：补打打印机最近一次打印的内容（printer_name 为空则使用默认打印机）
+#[tauri::command]
+async fn reprint_last_job(
+    state: tauri::State<'_, AppState>,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    server::reprint_last(&state.server, printer_name.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri 命令：重复打印测试任务并统计耗时（printer_name 为空则使用默认打印机）
+///
+/// dry_run 默认开启，只渲染并查询打印后台，不实际打印
+#[tauri::command]
+async fn benchmark_print(
+    state: tauri::State<'_, AppState>,
+    printer_name: Option<String>,
+    iterations: usize,
+    dry_run: Option<bool>,
+) -> Result<server::PrintBenchmark, String> {
+    server::benchmark_print(
+        &state.server,
+        printer_name.as_deref(),
+        iterations,
+        dry_run.unwrap_or(true),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// 全局开启归档时保存 HTML/PDF 打印内容
+///
+/// 支持生成 PDF 的平台返回 PDF 归档路径（打印窗口加载页面后写入），其他平台直接保存渲染后的 HTML
+fn archive_html(config: &AppConfig, html: &str) -> Result<Option<PathBuf>, String> {
+    use printer::archive::{archive_job, reserve_archive, EXT_HTML, EXT_PDF};
+
+    if !config.printer.archive {
+        return Ok(None);
+    }
+
+    let dir = get_archive_dir(config);
+    let id = uuid::Uuid::new_v4().to_string();
+    if printer::pdf::pdf_archive_supported() {
+        return reserve_archive(&dir, &id, EXT_PDF).map(Some);
+    }
+    archive_job(&dir, &id, EXT_HTML, html.as_bytes()).map(|_| None)
 }
 
 /// Tauri 命令：打印 HTML/PDF 内容
 #[tauri::command]
 async fn print_pdf(
     app: AppHandle,
+    state: tauri::State<'_, AppState>,
     html_content: String,
     paper_size: Option<String>,
     silent: Option<bool>,
-) -> Result<(), String> {
-    use printer::pdf::{print_html, wrap_html_for_print, PdfPrintOptions};
+    scale: Option<f64>,
+    dpi: Option<f64>,
+) -> Result<HtmlPrintResult, String> {
+    use printer::pdf::{print_html, print_scale, wrap_html_for_print, PdfPrintOptions};
 
-    let paper = paper_size.unwrap_or_else(|| "A4".to_string());
+    let config = state.config.read().await.clone();
+    let mut bytes = 0;
+    let result = async {
+        let scale = print_scale(scale, dpi)?;
+        let printer_config = &config.printer;
 
-    // 包装 HTML 内容以添加打印样式
-    let wrapped_html = wrap_html_for_print(&html_content, &paper);
+        // 包装 HTML 内容以添加打印样式
+        let wrapped_html =
+            wrap_html_for_print(&html_content, paper_size.as_deref(), printer_config)?;
+        bytes = wrapped_html.len();
+        let archive_pdf = archive_html(&config, &wrapped_html)?;
 
-    let options = PdfPrintOptions {
-        copies: 1,
-        paper_size: paper,
-        silent: silent.unwrap_or(false),
-    };
+        let options = PdfPrintOptions {
+            copies: 1,
+            paper_size: paper_size.unwrap_or_else(|| printer_config.default_paper_size.clone()),
+            silent: silent.unwrap_or(false),
+            require_silent: printer_config.require_silent_print,
+            window_size: None,
+            debug_visible: printer_config.debug_print_window,
+            scale,
+            archive_pdf,
+        };
 
-    print_html(&app, &wrapped_html, options).await
+        print_html(&app, &wrapped_html, options, state.pdf_cancel.token()).await
+    }
+    .await;
+    report_command_print(&state, &config, None, "pdf", bytes, None, &result);
+    result
 }
 
 /// Tauri 命令：使用模板渲染并打印为 PDF
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn print_template_as_pdf(
     app: AppHandle,
+    state: tauri::State<'_, AppState>,
     template: String,
     data: serde_json::Value,
     paper_size: Option<String>,
     silent: Option<bool>,
-) -> Result<(), String> {
-    use printer::pdf::{print_html, wrap_html_for_print, PdfPrintOptions};
+    scale: Option<f64>,
+    dpi: Option<f64>,
+) -> Result<HtmlPrintResult, String> {
+    use printer::pdf::{print_html, print_scale, wrap_html_for_print, PdfPrintOptions};
 
-    // 渲染模板
-    let rendered = renderer::render_template(&template, &data)?;
+    let config = state.config.read().await.clone();
+    let mut bytes = 0;
+    let result = async {
+        let scale = print_scale(scale, dpi)?;
+        let printer_config = &config.printer;
 
-    let paper = paper_size.unwrap_or_else(|| "A4".to_string());
+        // 渲染模板
+        let rendered = render_with_config(&config, &template, &data)?;
 
-    // 包装 HTML 内容
-    let wrapped_html = wrap_html_for_print(&rendered, &paper);
+        // 包装 HTML 内容
+        let wrapped_html = wrap_html_for_print(&rendered, paper_size.as_deref(), printer_config)?;
+        bytes = wrapped_html.len();
+        let archive_pdf = archive_html(&config, &wrapped_html)?;
 
-    let options = PdfPrintOptions {
-        copies: 1,
-        paper_size: paper,
-        silent: silent.unwrap_or(false),
-    };
+        let options = PdfPrintOptions {
+            copies: 1,
+            paper_size: paper_size.unwrap_or_else(|| printer_config.default_paper_size.clone()),
+            silent: silent.unwrap_or(false),
+            require_silent: printer_config.require_silent_print,
+            window_size: None,
+            debug_visible: printer_config.debug_print_window,
+            scale,
+            archive_pdf,
+        };
 
-    print_html(&app, &wrapped_html, options).await
+        print_html(&app, &wrapped_html, options, state.pdf_cancel.token()).await
+    }
+    .await;
+    report_command_print(&state, &config, None, "pdf", bytes, Some(&data), &result);
+    result
+}
+
+/// Tauri 命令：获取当前平台的打印能力（如 HTML/PDF 能否静默打印）
+#[tauri::command]
+fn get_print_capabilities() -> serde_json::Value {
+    serde_json::json!({
+        "silent_html_print": printer::pdf::silent_print_supported()
+    })
+}
+
+/// Tauri 命令：获取纸张大小字符串（如 "A4 landscape"、"80mm 200mm"）的实际尺寸，供界面按比例绘制打印预览
+#[tauri::command]
+async fn resolve_paper_size(
+    state: tauri::State<'_, AppState>,
+    size: String,
+) -> Result<printer::pdf::PaperDimensions, String> {
+    let config = state.config.read().await;
+    printer::pdf::resolve_paper_size(&size, &config.printer)
+}
+
+/// Tauri 命令：取消进行中的 HTML/PDF 打印（关闭打印窗口，打印命令返回 `CANCELLED`）
+#[tauri::command]
+fn cancel_pdf_print(state: tauri::State<'_, AppState>) {
+    state.pdf_cancel.cancel();
 }
 
 /// Tauri 命令：设置开机自启动
@@ -281,6 +780,12 @@ async fn get_autostart(app: AppHandle) -> Result<bool, String> {
     autostart_manager.is_enabled().map_err(|e| e.to_string())
 }
 
+/// Tauri 命令：获取最近的日志行
+#[tauri::command]
+fn get_recent_logs(logs: tauri::State<'_, Arc<LogBuffer>>, count: usize) -> Vec<String> {
+    logs.recent(count)
+}
+
 /// Tauri 命令：获取日志目录路径
 #[tauri::command]
 fn get_log_dir(app: AppHandle) -> Result<String, String> {
@@ -291,15 +796,57 @@ fn get_log_dir(app: AppHandle) -> Result<String, String> {
     Ok(path.to_string_lossy().to_string())
 }
 
+/// Tauri 命令：获取诊断信息（系统、应用版本、配置、打印机、服务状态），用于提交问题
+///
+/// 配置中的敏感字段会被脱敏；获取打印机列表失败时记录错误信息而不是整体失败
+#[tauri::command]
+async fn get_diagnostics(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let mut config =
+        serde_json::to_value(&*state.config.read().await).map_err(|e| e.to_string())?;
+    server::redact(&mut config);
+
+    let printers =
+        list_printers(state.clone()).unwrap_or_else(|e| serde_json::json!({ "error": e }));
+
+    Ok(serde_json::json!({
+        "os": {
+            "name": std::env::consts::OS,
+            "family": std::env::consts::FAMILY,
+            "arch": std::env::consts::ARCH
+        },
+        "app": get_app_info(),
+        "config": config,
+        "printers": printers,
+        "server": get_server_status(state).await?,
+        "log_dir": get_log_dir(app).ok()
+    }))
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    run_with_printer_manager(create_printer_manager())
+}
+
+/// 使用自定义的打印机管理器运行应用
+///
+/// 例如组合多个后端，按打印机名称分派到 Windows、网络或 USB 打印机
+pub fn run_with_printer_manager(printer_manager: Box<dyn PrinterManager>) {
+    let log_buffer = Arc::new(LogBuffer::new());
+    let log_sink = log_buffer.clone();
+
     tauri::Builder::default()
-        // 日志插件 - 同时输出到控制台和文件
+        // 日志插件 - 同时输出到控制台、文件和前端日志查看器
         .plugin(
             tauri_plugin_log::Builder::new()
                 .targets([
                     Target::new(TargetKind::Stdout),
                     Target::new(TargetKind::LogDir { file_name: Some("rprint".into()) }),
+                    Target::new(TargetKind::Dispatch(fern::Dispatch::new().chain(
+                        fern::Output::call(move |record| log_sink.push(record.args().to_string())),
+                    ))),
                 ])
                 .level(log::LevelFilter::Info)
                 .build(),
@@ -310,27 +857,74 @@ pub fn run() {
             Some(vec!["--minimized"]),
         ))
         .plugin(tauri_plugin_opener::init())
-        .manage(AppState::default())
+        .manage(AppState::with_printer_manager(printer_manager))
+        .manage(log_buffer)
         .invoke_handler(tauri::generate_handler![
             get_app_info,
             get_config,
             update_config,
+            reset_config,
             start_ws_server,
             stop_ws_server,
             get_server_status,
             list_printers,
             get_default_printer,
+            printer_exists,
+            get_paper_settings,
+            detect_printer_type,
+            get_zpl_fonts,
             print_raw,
             print_text,
             print_with_template,
             preview_template,
+            render_template_with_file,
+            render_all_samples,
+            reload_templates,
+            save_template,
+            list_templates,
+            get_template,
+            delete_template,
+            get_failed_jobs,
+            replay_job,
+            reprint_last_job,
+            benchmark_print,
             print_pdf,
             print_template_as_pdf,
+            cancel_pdf_print,
+            get_print_capabilities,
+            resolve_paper_size,
             set_autostart,
             get_autostart,
-            get_log_dir
+            get_log_dir,
+            get_recent_logs,
+            get_diagnostics
         ])
         .setup(|app| {
+            // 开始向前端推送日志
+            app.state::<Arc<LogBuffer>>().attach(app.handle().clone());
+            storage::attach(app.handle().clone());
+
+            // 按配置推送打印完成事件（前端据此播放提示音）
+            {
+                let state: tauri::State<AppState> = app.state();
+                let app_handle = app.handle().clone();
+                let config = state.config.clone();
+                let mut complete_rx = state.server.print_complete_tx.subscribe();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        match complete_rx.recv().await {
+                            Ok(complete) => {
+                                if config.read().await.ui.play_sound_on_complete {
+                                    let _ = app_handle.emit(PRINT_COMPLETE_EVENT, &complete);
+                                }
+                            }
+                            Err(RecvError::Lagged(_)) => continue,
+                            Err(RecvError::Closed) => break,
+                        }
+                    }
+                });
+            }
+
             // 加载配置
             let state: tauri::State<AppState> = app.state();
             let config = state.config.blocking_read();
@@ -384,7 +978,15 @@ pub fn run() {
             if config.server.auto_start {
                 let app_handle = app.handle().clone();
                 let port = config.server.port;
+                let delay = Duration::from_millis(config.server.auto_start_delay_ms);
+                let wait_printers =
+                    Duration::from_secs(config.server.auto_start_wait_printers_secs);
+                let warm_up = config.server.warm_up_on_start.then(|| config.clone());
                 let ws_running = state.ws_running.clone();
+                let server_state = state.server.clone();
+
+                let (shutdown_tx, shutdown_rx) = oneshot::channel();
+                *state.ws_shutdown.blocking_lock() = Some(shutdown_tx);
 
                 tauri::async_runtime::spawn(async move {
                     // 设置运行状态
@@ -393,14 +995,39 @@ pub fn run() {
                         *running = true;
                     }
 
+                    // 开机自启时打印后台可能尚未就绪，按配置延迟并等待打印机可用
+                    if !delay.is_zero() {
+                        log::info!("Delaying WebSocket server start by {:?}", delay);
+                        tokio::time::sleep(delay).await;
+                    }
+                    if !wait_printers.is_zero()
+                        && !server::wait_for_printers(&server_state, wait_printers).await
+                    {
+                        log::warn!(
+                            "No printers available after {:?}, starting server anyway",
+                            wait_printers
+                        );
+                    }
+
+                    // 预热完成后再启动服务，第一个打印请求无需承担初始化开销
+                    if let Some(config) = warm_up {
+                        let warm_up_state = server_state.clone();
+                        let _ = tauri::async_runtime::spawn_blocking(move || {
+                            server::warm_up(&warm_up_state, &config)
+                        })
+                        .await;
+                    }
+
                     let ws_running_inner = ws_running.clone();
+                    let error_handle = app_handle.clone();
                     tokio::spawn(async move {
                         log::info!("Auto-starting WebSocket server on port {}", port);
 
-                        if let Err(e) = server::start_server(port).await {
+                        if let Err(e) = server::start_server(port, server_state, shutdown_rx).await {
                             log::error!("WebSocket server error: {}", e);
                             let mut running = ws_running_inner.write().await;
                             *running = false;
+                            emit_server_error(&error_handle, &e.to_string());
                         }
                     });
 
@@ -412,6 +1039,12 @@ pub fn run() {
                         }),
                     );
                 });
+            } else if config.server.warm_up_on_start {
+                let server_state = state.server.clone();
+                let config = config.clone();
+                tauri::async_runtime::spawn_blocking(move || {
+                    server::warm_up(&server_state, &config)
+                });
             }
 
             log::info!("rprint application started");
@@ -0,0 +1,81 @@
+//! 日志查看
+//!
+//! 作为日志插件的一个输出目标，缓存最近的日志行供前端查询，并以 `log-line` 事件实时推送
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+use tauri::{AppHandle, Emitter};
+
+/// 最多缓存的日志行数
+const MAX_LOG_LINES: usize = 1000;
+
+/// 推送到前端的日志事件名
+pub const LOG_LINE_EVENT: &str = "log-line";
+
+/// 最近日志缓存
+#[derive(Default)]
+pub struct LogBuffer {
+    lines: Mutex<VecDeque<String>>,
+    /// 应用启动完成后设置，之后的日志行会推送到前端
+    app: OnceLock<AppHandle>,
+}
+
+impl LogBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 开始向前端推送日志事件
+    pub fn attach(&self, app: AppHandle) {
+        let _ = self.app.set(app);
+    }
+
+    /// 添加一行日志，超过上限时丢弃最旧的行
+    pub fn push(&self, line: String) {
+        {
+            let mut lines = self.lines.lock().unwrap();
+            if lines.len() >= MAX_LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.clone());
+        }
+
+        if let Some(app) = self.app.get() {
+            let _ = app.emit(LOG_LINE_EVENT, line);
+        }
+    }
+
+    /// 获取最近的 count 行日志（按时间顺序）
+    pub fn recent(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        let skip = lines.len().saturating_sub(count);
+        lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_lines_capped() {
+        let buffer = LogBuffer::new();
+        for i in 0..MAX_LOG_LINES + 5 {
+            buffer.push(format!("line {}", i));
+        }
+
+        let recent = buffer.recent(2);
+        assert_eq!(
+            recent,
+            vec![
+                format!("line {}", MAX_LOG_LINES + 3),
+                format!("line {}", MAX_LOG_LINES + 4)
+            ]
+        );
+
+        let all = buffer.recent(usize::MAX);
+        assert_eq!(all.len(), MAX_LOG_LINES);
+        assert_eq!(all[0], "line 5");
+    }
+}
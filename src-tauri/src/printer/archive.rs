@@ -0,0 +1,150 @@
+//! 打印归档
+//!
+//! 将打印内容按日期保存到归档目录：`<archive_dir>/YYYY-MM-DD/HHMMSS_<id>.<ext>`，
+//! 同一秒内重复的请求 ID 依次加上 `-2`、`-3` 等后缀，不会覆盖已有的归档
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::info;
+
+use crate::renderer::{chrono_lite_format, slugify};
+use crate::storage::write_error;
+
+/// 原始打印数据（ESC/POS、ZPL）的扩展名
+pub const EXT_RAW: &str = "prn";
+/// 文本打印的扩展名
+pub const EXT_TEXT: &str = "txt";
+/// HTML 打印的扩展名（保存渲染后的 HTML，平台不支持生成 PDF 时使用）
+pub const EXT_HTML: &str = "html";
+/// HTML/PDF 打印生成的 PDF 的扩展名
+pub const EXT_PDF: &str = "pdf";
+
+/// 同名归档文件的最大后缀序号
+const MAX_SUFFIX: u32 = 1000;
+
+/// 保存一次打印内容，返回归档文件路径
+pub fn archive_job(dir: &Path, id: &str, extension: &str, data: &[u8]) -> Result<PathBuf, String> {
+    let (mut file, path) = create_archive_file(dir, id, extension)?;
+    file.write_all(data)
+        .map_err(|e| write_error("Failed to write archive file", &path, e))?;
+
+    info!("Archived print job '{}' to {:?}", id, path);
+    Ok(path)
+}
+
+/// 为稍后写入的归档（如 WebView 生成的 PDF）创建空文件，返回其路径
+pub fn reserve_archive(dir: &Path, id: &str, extension: &str) -> Result<PathBuf, String> {
+    create_archive_file(dir, id, extension).map(|(_, path)| path)
+}
+
+/// 创建新的归档文件，文件已存在时加上序号后缀重试
+fn create_archive_file(dir: &Path, id: &str, extension: &str) -> Result<(File, PathBuf), String> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let base = archive_path(dir, secs, id, extension);
+
+    if let Some(parent) = base.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| write_error("Failed to create archive directory", parent, e))?;
+    }
+    for suffix in 1..=MAX_SUFFIX {
+        let path = with_suffix(&base, suffix);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(write_error("Failed to create archive file", &path, e)),
+        }
+    }
+    Err(format!(
+        "Failed to create archive file: too many archives named {:?}",
+        base
+    ))
+}
+
+/// 生成归档文件路径
+fn archive_path(dir: &Path, secs: i64, id: &str, extension: &str) -> PathBuf {
+    // "YYYY-MM-DD HH:MM:SS"
+    let stamp = chrono_lite_format(secs);
+    let (date, time) = stamp.split_once(' ').unwrap_or((&stamp, ""));
+    let file_name = format!(
+        "{}_{}.{}",
+        time.replace(':', ""),
+        sanitize_id(id),
+        extension
+    );
+    dir.join(date).join(file_name)
+}
+
+/// 为归档文件名加上序号后缀（序号 1 表示不加后缀）
+fn with_suffix(path: &Path, suffix: u32) -> PathBuf {
+    if suffix <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file_name = format!("{}-{}", stem, suffix);
+    if let Some(extension) = path.extension() {
+        file_name = format!("{}.{}", file_name, extension.to_string_lossy());
+    }
+    path.with_file_name(file_name)
+}
+
+/// 将请求 ID 转换为安全的文件名片段
+fn sanitize_id(id: &str) -> String {
+    let id = slugify(id);
+    if id.is_empty() {
+        "job".to_string()
+    } else {
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_path() {
+        // 2024-01-02 03:04:05 UTC
+        let path = archive_path(Path::new("archive"), 1704164645, "order/1 A", EXT_RAW);
+        assert_eq!(
+            path,
+            Path::new("archive")
+                .join("2024-01-02")
+                .join("030405_order_1_A.prn")
+        );
+        assert!(archive_path(Path::new("a"), 0, "", EXT_TEXT).ends_with("000000_job.txt"));
+    }
+
+    #[test]
+    fn test_archive_job_writes_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("archive");
+        let path = archive_job(&dir, "A001", EXT_RAW, b"\x1b@hello").unwrap();
+        assert!(path.starts_with(&dir));
+        assert_eq!(std::fs::read(&path).unwrap(), b"\x1b@hello");
+    }
+
+    #[test]
+    fn test_archive_job_does_not_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let first = archive_job(dir, "A001", EXT_RAW, b"first").unwrap();
+        let second = archive_job(dir, "A001", EXT_RAW, b"second").unwrap();
+        let reserved = reserve_archive(dir, "A001", EXT_PDF).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read(&first).unwrap(), b"first");
+        assert_eq!(std::fs::read(&second).unwrap(), b"second");
+        assert!(reserved.exists());
+
+        assert_eq!(
+            with_suffix(Path::new("a/030405_A001.prn"), 2),
+            Path::new("a/030405_A001-2.prn")
+        );
+        assert_eq!(with_suffix(Path::new("a/x.txt"), 1), Path::new("a/x.txt"));
+    }
+}
@@ -0,0 +1,144 @@
+//! 打印机类型识别
+//!
+//! 根据打印机名称和驱动名称推测打印机类型，用于自动配置打印路由
+
+use crate::config::PrinterConfig;
+use crate::protocol::PrinterInfo;
+
+/// ZPL 标签打印机
+pub const PRINTER_TYPE_ZPL: &str = "zpl";
+/// EPL 标签打印机（使用 `N`、`A50,...`、`P1` 等 EPL2 指令，不能打印 ZPL）
+pub const PRINTER_TYPE_EPL: &str = "epl";
+/// ESC/POS 热敏小票打印机
+pub const PRINTER_TYPE_ESCPOS: &str = "escpos";
+/// 普通打印机
+pub const PRINTER_TYPE_NORMAL: &str = "normal";
+
+/// EPL 打印机的名称特征（小写单词，如 Zebra EPL 驱动 "ZDesigner GK888t (EPL)"）
+const EPL_WORDS: &[&str] = &["epl", "epl2"];
+
+/// ZPL 打印机的名称特征（小写子串）
+const ZPL_PATTERNS: &[&str] = &["zdesigner", "zebra", "zpl"];
+
+/// ESC/POS 打印机的名称特征（小写子串）
+const ESCPOS_PATTERNS: &[&str] = &["tm-", "thermal", "receipt", "escpos", "esc/pos", "xprinter"];
+
+/// 推测打印机类型，返回 `epl`、`zpl`、`escpos` 或 `normal`
+pub fn detect_printer_type(name: &str, driver: Option<&str>) -> &'static str {
+    let text = format!("{} {}", name, driver.unwrap_or_default()).to_lowercase();
+    let mut words = text.split(|c: char| !c.is_ascii_alphanumeric());
+
+    // 同一型号的 EPL 驱动名称也带有 ZDesigner 等 ZPL 特征，先判断 EPL
+    if words.clone().any(|word| EPL_WORDS.contains(&word)) {
+        return PRINTER_TYPE_EPL;
+    }
+    if ZPL_PATTERNS.iter().any(|p| text.contains(p)) {
+        return PRINTER_TYPE_ZPL;
+    }
+
+    let is_escpos = ESCPOS_PATTERNS.iter().any(|p| text.contains(p)) || words.any(is_pos_word);
+
+    if is_escpos {
+        PRINTER_TYPE_ESCPOS
+    } else {
+        PRINTER_TYPE_NORMAL
+    }
+}
+
+/// 单词为 "pos" 或 "pos" 加型号数字（如 "POS58"），避免误匹配 "PostScript" 等
+fn is_pos_word(word: &str) -> bool {
+    word.strip_prefix("pos")
+        .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// 根据识别结果填充未配置的 ESC/POS、ZPL 打印机（EPL 打印机不能打印 ZPL 模板，不参与），返回配置是否有变化
+pub fn populate_routing(config: &mut PrinterConfig, printers: &[PrinterInfo]) -> bool {
+    let mut changed = false;
+
+    for printer in printers {
+        let slot = match detect_printer_type(&printer.name, printer.driver.as_deref()) {
+            PRINTER_TYPE_ZPL => &mut config.zpl_printer,
+            PRINTER_TYPE_ESCPOS => &mut config.escpos_printer,
+            _ => continue,
+        };
+
+        if slot.is_none() {
+            *slot = Some(printer.name.clone());
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printer(name: &str, driver: &str) -> PrinterInfo {
+        PrinterInfo {
+            name: name.to_string(),
+            id: format!("{}||", name),
+            is_default: false,
+            status: "ready".to_string(),
+            driver: Some(driver.to_string()),
+            port: None,
+            color: None,
+        }
+    }
+
+    #[test]
+    fn test_detect_printer_type() {
+        assert_eq!(
+            detect_printer_type("Label", Some("ZDesigner GK888t (EPL)")),
+            PRINTER_TYPE_EPL
+        );
+        assert_eq!(
+            detect_printer_type("Label", Some("ZDesigner GK888t")),
+            PRINTER_TYPE_ZPL
+        );
+        assert_eq!(detect_printer_type("EPL2 Shipping", None), PRINTER_TYPE_EPL);
+        assert_eq!(
+            detect_printer_type("Replenishment", Some("Generic Text")),
+            PRINTER_TYPE_NORMAL
+        );
+        assert_eq!(detect_printer_type("Zebra ZT410", None), PRINTER_TYPE_ZPL);
+        assert_eq!(
+            detect_printer_type("Front Desk", Some("EPSON TM-T88V Receipt")),
+            PRINTER_TYPE_ESCPOS
+        );
+        assert_eq!(detect_printer_type("POS-58", None), PRINTER_TYPE_ESCPOS);
+        assert_eq!(
+            detect_printer_type("POS58 Printer", None),
+            PRINTER_TYPE_ESCPOS
+        );
+        assert_eq!(
+            detect_printer_type("Office", Some("HP Universal Printing PostScript")),
+            PRINTER_TYPE_NORMAL
+        );
+        assert_eq!(
+            detect_printer_type("Microsoft Print to PDF", None),
+            PRINTER_TYPE_NORMAL
+        );
+    }
+
+    #[test]
+    fn test_populate_routing_keeps_existing() {
+        let mut config = PrinterConfig {
+            escpos_printer: Some("Manual".to_string()),
+            ..PrinterConfig::default()
+        };
+        let printers = vec![
+            printer("Office", "HP LaserJet"),
+            printer("Receipt", "EPSON TM-T20"),
+            printer("Shipping", "ZDesigner LP 2844 (EPL)"),
+            printer("Label", "ZDesigner ZD420"),
+            printer("Label 2", "ZDesigner ZD420"),
+        ];
+
+        assert!(populate_routing(&mut config, &printers));
+        assert_eq!(config.escpos_printer.as_deref(), Some("Manual"));
+        assert_eq!(config.zpl_printer.as_deref(), Some("Label"));
+        assert!(!populate_routing(&mut config, &printers));
+    }
+}
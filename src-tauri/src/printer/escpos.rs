@@ -0,0 +1,120 @@
+//! ESC/POS 实时状态查询
+//!
+//! 通过 `DLE EOT n` 指令在同一连接上读取打印机状态（纸将尽、开盖、错误等），
+//! 供网络、USB 等可双向通信的后端实现 `PrinterManager::query_escpos_status`
+
+use std::io::{Read, Write};
+
+use crate::protocol::EscposStatus;
+
+/// DLE EOT 指令前缀
+const DLE_EOT: [u8; 2] = [0x10, 0x04];
+
+/// 打印机状态（n = 1）
+const STATUS_PRINTER: u8 = 1;
+/// 脱机状态（n = 2）
+const STATUS_OFFLINE: u8 = 2;
+/// 纸卷传感器状态（n = 4）
+const STATUS_PAPER: u8 = 4;
+
+/// 发送 DLE EOT 1/2/4 并逐个读取单字节响应，解析为打印机状态
+pub fn query_status<S: Read + Write>(stream: &mut S) -> Result<EscposStatus, String> {
+    let printer = request_status(stream, STATUS_PRINTER)?;
+    let offline = request_status(stream, STATUS_OFFLINE)?;
+    let paper = request_status(stream, STATUS_PAPER)?;
+    Ok(decode_status(printer, offline, paper))
+}
+
+/// 发送一条 DLE EOT n 指令并读取一个字节的响应
+fn request_status<S: Read + Write>(stream: &mut S, n: u8) -> Result<u8, String> {
+    stream
+        .write_all(&[DLE_EOT[0], DLE_EOT[1], n])
+        .and_then(|_| stream.flush())
+        .map_err(|e| format!("Failed to send DLE EOT {}: {}", n, e))?;
+
+    let mut response = [0u8; 1];
+    stream
+        .read_exact(&mut response)
+        .map_err(|e| format!("No response to DLE EOT {}: {}", n, e))?;
+
+    // 状态字节的第 1、4 位固定为 1，第 0、7 位固定为 0
+    if response[0] & 0x93 != 0x12 {
+        return Err(format!(
+            "Invalid response to DLE EOT {}: 0x{:02x}",
+            n, response[0]
+        ));
+    }
+    Ok(response[0])
+}
+
+/// 解析 DLE EOT 1/2/4 的响应字节
+fn decode_status(printer: u8, offline: u8, paper: u8) -> EscposStatus {
+    EscposStatus {
+        online: printer & 0x08 == 0,
+        cover_open: offline & 0x04 != 0,
+        paper_near_end: paper & 0x0c != 0,
+        paper_end: paper & 0x60 != 0 || offline & 0x20 != 0,
+        error: offline & 0x40 != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// 按顺序返回预设响应并记录写入内容的连接
+    struct FakeConnection {
+        written: Vec<u8>,
+        responses: Cursor<Vec<u8>>,
+    }
+
+    impl Read for FakeConnection {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.responses.read(buf)
+        }
+    }
+
+    impl Write for FakeConnection {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn connection(responses: &[u8]) -> FakeConnection {
+        FakeConnection {
+            written: Vec::new(),
+            responses: Cursor::new(responses.to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_query_status() {
+        let mut conn = connection(&[0x12, 0x12, 0x12]);
+        let status = query_status(&mut conn).unwrap();
+        assert_eq!(conn.written, [0x10, 0x04, 1, 0x10, 0x04, 2, 0x10, 0x04, 4]);
+        assert!(status.online);
+        assert!(!status.cover_open && !status.paper_near_end && !status.error);
+
+        // 脱机、开盖、纸将尽
+        let mut conn = connection(&[0x1a, 0x16, 0x1e]);
+        let status = query_status(&mut conn).unwrap();
+        assert!(!status.online);
+        assert!(status.cover_open);
+        assert!(status.paper_near_end);
+        assert!(!status.paper_end);
+
+        // 缺纸并出错
+        let status = decode_status(0x12, 0x72, 0x72);
+        assert!(status.paper_end && status.error);
+
+        // 无响应或响应不是状态字节
+        assert!(query_status(&mut connection(&[0x12])).is_err());
+        assert!(query_status(&mut connection(&[0xff, 0x12, 0x12])).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! 测试用打印机管理器
+//!
+//! 不访问真实打印机：打印机列表可配置，所有打印调用都会被记录下来供断言，
+//! 没有 Windows 打印机的 CI 环境也可以端到端测试服务。
+//! 单元测试中始终可用；开启 `mock-printer` 特性时从 crate 根导出，可传给 `run_with_printer_manager`
+
+use std::collections::HashSet;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use super::{status_not_supported, PrinterManager};
+use crate::protocol::{
+    EscposStatus, Orientation, PaperSettings, PrintDatatype, PrintError, PrinterInfo,
+    PRINTER_NOT_FOUND,
+};
+
+/// 一次打印调用
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintCall {
+    /// 调用的方法: print_raw, print_text, print_document
+    pub method: &'static str,
+    /// 目标打印机
+    pub printer: String,
+    /// 发送的数据
+    pub data: Vec<u8>,
+    /// 份数（仅 print_document，其余为 1）
+    pub copies: u32,
+    /// 纸盒（仅 print_document）
+    pub tray: Option<u16>,
+    /// 纸张方向（仅 print_document）
+    pub orientation: Option<Orientation>,
+    /// 数据类型（print_text 为 RAW）
+    pub datatype: PrintDatatype,
+    /// 是否在写入数据前暂停
+    pub hold: bool,
+}
+
+impl PrintCall {
+    /// 单份、未指定纸盒和方向的调用
+    fn new(method: &'static str, printer: &str, data: &[u8], datatype: PrintDatatype) -> Self {
+        Self {
+            method,
+            printer: printer.to_string(),
+            data: data.to_vec(),
+            copies: 1,
+            tray: None,
+            orientation: None,
+            datatype,
+            hold: false,
+        }
+    }
+}
+
+/// 控制任务何时打印完成：等待任务完成的调用一直阻塞到 `open`（模拟出纸时间不确定的打印机，
+/// 测试无需依赖耗时判断执行顺序）
+#[derive(Clone, Default)]
+pub struct JobGate(Arc<(Mutex<bool>, Condvar)>);
+
+impl JobGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 放行所有正在等待和之后等待完成的任务
+    pub fn open(&self) {
+        let (open, changed) = &*self.0;
+        *open.lock().unwrap() = true;
+        changed.notify_all();
+    }
+
+    /// 阻塞到 `open` 被调用
+    fn wait(&self) {
+        let (open, changed) = &*self.0;
+        let mut open = open.lock().unwrap();
+        while !*open {
+            open = changed.wait(open).unwrap();
+        }
+    }
+}
+
+/// 记录打印调用的打印机管理器（克隆后共享调用记录）
+#[derive(Clone, Default)]
+pub struct MockPrinterManager {
+    printers: Vec<PrinterInfo>,
+    default_printer: Option<String>,
+    /// 每次打印调用的耗时（模拟响应缓慢的打印机）
+    delay: Duration,
+    /// 等待任务完成的耗时（模拟出纸较慢的打印机）
+    job_duration: Duration,
+    /// 等待任务完成时阻塞到放行
+    job_gate: Option<JobGate>,
+    /// ESC/POS 实时状态（为空则不支持状态查询）
+    escpos_status: Option<EscposStatus>,
+    calls: Arc<Mutex<Vec<PrintCall>>>,
+    /// 正在进行的打印调用数，以及同时进行的最大数量
+    in_flight: Arc<Mutex<(usize, usize)>>,
+    /// 暂停中的任务 ID
+    paused: Arc<Mutex<HashSet<u32>>>,
+}
+
+impl MockPrinterManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加一台就绪状态的打印机
+    pub fn with_printer(mut self, name: &str) -> Self {
+        self.printers.push(PrinterInfo {
+            name: name.to_string(),
+            id: format!("{}||", name),
+            is_default: false,
+            status: "ready".to_string(),
+            driver: None,
+            port: None,
+            color: None,
+        });
+        self
+    }
+
+    /// 添加一台指定唯一标识的打印机（用于模拟同名打印机）
+    pub fn with_printer_id(mut self, name: &str, id: &str) -> Self {
+        self = self.with_printer(name);
+        if let Some(printer) = self.printers.last_mut() {
+            printer.id = id.to_string();
+        }
+        self
+    }
+
+    /// 设置打印机的驱动名称（需已通过 `with_printer` 添加）
+    pub fn with_driver(mut self, name: &str, driver: &str) -> Self {
+        for printer in &mut self.printers {
+            if printer.name == name {
+                printer.driver = Some(driver.to_string());
+            }
+        }
+        self
+    }
+
+    /// 设置默认打印机（需已通过 `with_printer` 添加）
+    pub fn with_default_printer(mut self, name: &str) -> Self {
+        for printer in &mut self.printers {
+            printer.is_default = printer.name == name;
+        }
+        self.default_printer = Some(name.to_string());
+        self
+    }
+
+    /// 设置每次打印调用的耗时
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// 设置等待每个任务打印完成的耗时
+    pub fn with_job_duration(mut self, duration: Duration) -> Self {
+        self.job_duration = duration;
+        self
+    }
+
+    /// 等待任务完成时阻塞到 `gate` 放行（在 `job_duration` 之后）
+    pub fn with_job_gate(mut self, gate: JobGate) -> Self {
+        self.job_gate = Some(gate);
+        self
+    }
+
+    /// 设置 ESC/POS 实时状态查询的结果
+    pub fn with_escpos_status(mut self, status: EscposStatus) -> Self {
+        self.escpos_status = Some(status);
+        self
+    }
+
+    /// 转换为服务使用的打印机管理器（调用记录仍可通过原实例查看）
+    pub fn shared(&self) -> Arc<Box<dyn PrinterManager>> {
+        Arc::new(Box::new(self.clone()))
+    }
+
+    /// 到目前为止的所有打印调用
+    pub fn calls(&self) -> Vec<PrintCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// 同时进行的打印调用的最大数量（同一打印机的任务依次写入时为 1）
+    pub fn max_concurrent_calls(&self) -> usize {
+        self.in_flight.lock().unwrap().1
+    }
+
+    /// 记录一次调用，返回任务 ID（从 1 开始递增）
+    fn record(&self, call: PrintCall) -> Result<u32, PrintError> {
+        self.check_printer(&call.printer)
+            .map_err(|e| PrintError::new(PRINTER_NOT_FOUND, e))?;
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            in_flight.0 += 1;
+            in_flight.1 = in_flight.1.max(in_flight.0);
+        }
+        std::thread::sleep(self.delay);
+        self.in_flight.lock().unwrap().0 -= 1;
+
+        let mut calls = self.calls.lock().unwrap();
+        let hold = call.hold;
+        calls.push(call);
+        let job_id = calls.len() as u32;
+        if hold {
+            self.paused.lock().unwrap().insert(job_id);
+        }
+        Ok(job_id)
+    }
+
+    /// 打印机不存在时返回错误
+    fn check_printer(&self, printer: &str) -> Result<(), String> {
+        if self.printers.iter().any(|p| p.name == printer) {
+            Ok(())
+        } else {
+            Err(format!("Printer not found: {}", printer))
+        }
+    }
+}
+
+impl PrinterManager for MockPrinterManager {
+    fn list_printers(&self) -> Result<Vec<PrinterInfo>, String> {
+        Ok(self.printers.clone())
+    }
+
+    fn get_default_printer(&self) -> Result<Option<String>, String> {
+        Ok(self.default_printer.clone())
+    }
+
+    fn print_raw(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError> {
+        self.record(PrintCall {
+            hold,
+            ..PrintCall::new("print_raw", printer_name, data, datatype)
+        })
+    }
+
+    fn print_text(&self, printer_name: &str, text: &str) -> Result<(), PrintError> {
+        let raw = PrintDatatype::Raw;
+        let call = PrintCall::new("print_text", printer_name, text.as_bytes(), raw);
+        self.record(call).map(|_| ())
+    }
+
+    fn print_document(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        copies: u32,
+        tray: Option<u16>,
+        orientation: Option<Orientation>,
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError> {
+        self.record(PrintCall {
+            copies,
+            tray,
+            orientation,
+            hold,
+            ..PrintCall::new("print_document", printer_name, data, datatype)
+        })
+    }
+
+    fn get_paper_settings(&self, printer_name: &str) -> Result<PaperSettings, String> {
+        self.check_printer(printer_name)?;
+        Ok(PaperSettings {
+            paper_size: 9,
+            form_name: "A4".to_string(),
+            tray: 7,
+            trays: Vec::new(),
+        })
+    }
+
+    fn wait_for_job(
+        &self,
+        printer_name: &str,
+        job_id: u32,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        self.check_printer(printer_name)?;
+        if self.job_duration > timeout {
+            std::thread::sleep(timeout);
+            return Err(format!("Timed out waiting for job {}", job_id));
+        }
+        std::thread::sleep(self.job_duration);
+        if let Some(gate) = &self.job_gate {
+            gate.wait();
+        }
+        Ok(())
+    }
+
+    fn pause_job(&self, printer_name: &str, job_id: u32) -> Result<(), String> {
+        self.check_printer(printer_name)?;
+        self.paused.lock().unwrap().insert(job_id);
+        Ok(())
+    }
+
+    fn resume_job(&self, printer_name: &str, job_id: u32) -> Result<(), String> {
+        self.check_printer(printer_name)?;
+        self.paused.lock().unwrap().remove(&job_id);
+        Ok(())
+    }
+
+    fn is_job_paused(&self, printer_name: &str, job_id: u32) -> Result<bool, String> {
+        self.check_printer(printer_name)?;
+        Ok(self.paused.lock().unwrap().contains(&job_id))
+    }
+
+    fn set_job_priority(
+        &self,
+        printer_name: &str,
+        _job_id: u32,
+        _priority: u32,
+    ) -> Result<(), String> {
+        self.check_printer(printer_name)
+    }
+
+    fn query_escpos_status(&self, printer_name: &str) -> Result<EscposStatus, PrintError> {
+        self.check_printer(printer_name)
+            .map_err(|e| PrintError::new(PRINTER_NOT_FOUND, e))?;
+        self.escpos_status
+            .clone()
+            .ok_or_else(|| status_not_supported(printer_name))
+    }
+}
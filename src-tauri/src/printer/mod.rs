@@ -3,11 +3,39 @@
 #[cfg(windows)]
 mod windows_printer;
 
+pub mod archive;
+mod detect;
+pub mod escpos;
+#[cfg(any(test, feature = "mock-printer"))]
+pub mod mock;
+mod network;
 pub mod pdf;
+mod selector;
+mod usb;
 
-use crate::protocol::PrinterInfo;
+pub use detect::{detect_printer_type, populate_routing};
+pub use network::NetworkPrinterManager;
+pub use selector::{find_printer, printer_exists, printer_target};
+pub use usb::UsbPrinterManager;
+
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::protocol::{
+    EscposStatus, Orientation, PaperSettings, PrintDatatype, PrintError, PrinterInfo,
+    STATUS_NOT_SUPPORTED,
+};
+
+/// 网络打印机名称前缀（`tcp://host:port`）
+pub const SCHEME_TCP: &str = "tcp://";
+
+/// USB 打印机名称前缀（`usb:VID:PID`）
+pub const SCHEME_USB: &str = "usb:";
 
 /// 打印机管理器 trait
+///
+/// 打印方法返回带错误码的 `PrintError`，客户端可据此区分打印机不存在、无权限等失败原因
 pub trait PrinterManager: Send + Sync {
     /// 获取所有打印机列表
     fn list_printers(&self) -> Result<Vec<PrinterInfo>, String>;
@@ -15,22 +43,231 @@ pub trait PrinterManager: Send + Sync {
     /// 获取默认打印机
     fn get_default_printer(&self) -> Result<Option<String>, String>;
 
-    /// 打印原始数据 (ESC/POS, ZPL)
-    fn print_raw(&self, printer_name: &str, data: &[u8]) -> Result<(), String>;
+    /// 以指定的数据类型打印原始数据 (ESC/POS, ZPL)，返回打印任务 ID
+    ///
+    /// `hold` 时任务在写入数据前暂停，释放（`resume_job`）前不会打印；无法暂停时取消任务并返回 `HOLD_FAILED`
+    fn print_raw(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError>;
 
     /// 打印文本
-    fn print_text(&self, printer_name: &str, text: &str) -> Result<(), String>;
+    fn print_text(&self, printer_name: &str, text: &str) -> Result<(), PrintError>;
+
+    /// 打印文档，由驱动处理份数和逐份打印，可指定纸盒和纸张方向（为空则使用打印机默认设置），返回打印任务 ID
+    ///
+    /// `hold` 与 `print_raw` 相同
+    #[allow(clippy::too_many_arguments)]
+    fn print_document(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        copies: u32,
+        tray: Option<u16>,
+        orientation: Option<Orientation>,
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError>;
+
+    /// 获取打印机的默认纸张、纸盒及可用纸盒列表
+    fn get_paper_settings(&self, printer_name: &str) -> Result<PaperSettings, String>;
+
+    /// 等待任务离开打印队列（已打印），任务出错、被删除或超时时返回错误
+    fn wait_for_job(
+        &self,
+        printer_name: &str,
+        job_id: u32,
+        timeout: Duration,
+    ) -> Result<(), String>;
+
+    /// 暂停打印队列中的任务
+    fn pause_job(&self, printer_name: &str, job_id: u32) -> Result<(), String>;
+
+    /// 恢复已暂停的任务
+    fn resume_job(&self, printer_name: &str, job_id: u32) -> Result<(), String>;
+
+    /// 任务是否仍暂停在打印队列中（已恢复、已删除或已打印时返回 false）
+    fn is_job_paused(&self, printer_name: &str, job_id: u32) -> Result<bool, String>;
+
+    /// 设置任务优先级 (1-99，数值越大越先打印)
+    fn set_job_priority(
+        &self,
+        printer_name: &str,
+        job_id: u32,
+        priority: u32,
+    ) -> Result<(), String>;
+
+    /// 查询 ESC/POS 打印机的实时状态（`DLE EOT`），需要可双向通信的连接
+    ///
+    /// 打印后台无法返回打印机的响应，默认返回 `STATUS_NOT_SUPPORTED`；
+    /// 网络、USB 后端可通过 `escpos::query_status` 实现
+    fn query_escpos_status(&self, printer_name: &str) -> Result<EscposStatus, PrintError> {
+        Err(status_not_supported(printer_name))
+    }
+}
+
+/// 打印机不支持实时状态查询时的错误
+pub fn status_not_supported(printer_name: &str) -> PrintError {
+    PrintError::new(
+        STATUS_NOT_SUPPORTED,
+        format!("Printer '{}' does not support status queries", printer_name),
+    )
 }
 
-/// 创建打印机管理器实例
+/// 排序打印机列表：默认打印机在前，其余按名称排序（不区分大小写），刷新时顺序保持稳定
+pub fn sort_printers(printers: &mut [PrinterInfo]) {
+    printers.sort_by(|a, b| {
+        b.is_default
+            .cmp(&a.is_default)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// 创建打印机管理器实例（系统打印机、`tcp://` 网络打印机，以及返回不支持错误的 `usb:` 打印机）
 pub fn create_printer_manager() -> Box<dyn PrinterManager> {
     #[cfg(windows)]
-    {
-        Box::new(windows_printer::WindowsPrinterManager::new())
-    }
+    let system: Box<dyn PrinterManager> = Box::new(windows_printer::WindowsPrinterManager::new());
     #[cfg(not(windows))]
-    {
-        Box::new(DummyPrinterManager)
+    let system: Box<dyn PrinterManager> = Box::new(DummyPrinterManager);
+
+    Box::new(
+        CompositePrinterManager::new(system)
+            .with_backend(SCHEME_TCP, Box::new(NetworkPrinterManager::new()))
+            .with_backend(SCHEME_USB, Box::new(UsbPrinterManager::new())),
+    )
+}
+
+/// 组合打印机管理器
+///
+/// 按打印机名称的前缀分派到不同后端（如 `tcp://` 网络打印机、`usb:` USB 打印机），
+/// 不带已注册前缀的名称交给系统打印机，客户端无需关心打印机使用哪种连接方式
+pub struct CompositePrinterManager {
+    /// 系统打印机（Windows 打印后台）
+    system: Box<dyn PrinterManager>,
+    /// 按名称前缀注册的后端（按注册顺序匹配）
+    backends: Vec<(String, Box<dyn PrinterManager>)>,
+}
+
+impl CompositePrinterManager {
+    pub fn new(system: Box<dyn PrinterManager>) -> Self {
+        Self {
+            system,
+            backends: Vec::new(),
+        }
+    }
+
+    /// 注册处理指定名称前缀的后端，例如 `with_backend(SCHEME_TCP, network)`
+    pub fn with_backend(mut self, prefix: &str, backend: Box<dyn PrinterManager>) -> Self {
+        self.backends.push((prefix.to_string(), backend));
+        self
+    }
+
+    /// 处理该打印机的后端
+    fn backend(&self, printer_name: &str) -> &dyn PrinterManager {
+        self.backends
+            .iter()
+            .find(|(prefix, _)| printer_name.starts_with(prefix.as_str()))
+            .map_or(self.system.as_ref(), |(_, backend)| backend.as_ref())
+    }
+}
+
+impl PrinterManager for CompositePrinterManager {
+    /// 合并所有后端的打印机（其他后端查询失败时跳过，不影响系统打印机），默认打印机在前，其余按名称排序
+    fn list_printers(&self) -> Result<Vec<PrinterInfo>, String> {
+        let mut printers = self.system.list_printers()?;
+        for (prefix, backend) in &self.backends {
+            match backend.list_printers() {
+                Ok(list) => printers.extend(list),
+                Err(e) => warn!("Failed to list '{}' printers: {}", prefix, e),
+            }
+        }
+        sort_printers(&mut printers);
+        Ok(printers)
+    }
+
+    fn get_default_printer(&self) -> Result<Option<String>, String> {
+        self.system.get_default_printer()
+    }
+
+    fn print_raw(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError> {
+        self.backend(printer_name)
+            .print_raw(printer_name, data, datatype, hold)
+    }
+
+    fn print_text(&self, printer_name: &str, text: &str) -> Result<(), PrintError> {
+        self.backend(printer_name).print_text(printer_name, text)
+    }
+
+    fn print_document(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        copies: u32,
+        tray: Option<u16>,
+        orientation: Option<Orientation>,
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError> {
+        self.backend(printer_name).print_document(
+            printer_name,
+            data,
+            copies,
+            tray,
+            orientation,
+            datatype,
+            hold,
+        )
+    }
+
+    fn get_paper_settings(&self, printer_name: &str) -> Result<PaperSettings, String> {
+        self.backend(printer_name).get_paper_settings(printer_name)
+    }
+
+    fn wait_for_job(
+        &self,
+        printer_name: &str,
+        job_id: u32,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        self.backend(printer_name)
+            .wait_for_job(printer_name, job_id, timeout)
+    }
+
+    fn pause_job(&self, printer_name: &str, job_id: u32) -> Result<(), String> {
+        self.backend(printer_name).pause_job(printer_name, job_id)
+    }
+
+    fn resume_job(&self, printer_name: &str, job_id: u32) -> Result<(), String> {
+        self.backend(printer_name).resume_job(printer_name, job_id)
+    }
+
+    fn is_job_paused(&self, printer_name: &str, job_id: u32) -> Result<bool, String> {
+        self.backend(printer_name)
+            .is_job_paused(printer_name, job_id)
+    }
+
+    fn set_job_priority(
+        &self,
+        printer_name: &str,
+        job_id: u32,
+        priority: u32,
+    ) -> Result<(), String> {
+        self.backend(printer_name)
+            .set_job_priority(printer_name, job_id, priority)
+    }
+
+    fn query_escpos_status(&self, printer_name: &str) -> Result<EscposStatus, PrintError> {
+        self.backend(printer_name).query_escpos_status(printer_name)
     }
 }
 
@@ -48,11 +285,130 @@ impl PrinterManager for DummyPrinterManager {
         Ok(None)
     }
 
-    fn print_raw(&self, _printer_name: &str, _data: &[u8]) -> Result<(), String> {
+    fn print_raw(
+        &self,
+        _printer_name: &str,
+        _data: &[u8],
+        _datatype: PrintDatatype,
+        _hold: bool,
+    ) -> Result<u32, PrintError> {
+        Err("Not supported on this platform".to_string().into())
+    }
+
+    fn print_text(&self, _printer_name: &str, _text: &str) -> Result<(), PrintError> {
+        Err("Not supported on this platform".to_string().into())
+    }
+
+    fn print_document(
+        &self,
+        _printer_name: &str,
+        _data: &[u8],
+        _copies: u32,
+        _tray: Option<u16>,
+        _orientation: Option<Orientation>,
+        _datatype: PrintDatatype,
+        _hold: bool,
+    ) -> Result<u32, PrintError> {
+        Err("Not supported on this platform".to_string().into())
+    }
+
+    fn get_paper_settings(&self, _printer_name: &str) -> Result<PaperSettings, String> {
         Err("Not supported on this platform".to_string())
     }
 
-    fn print_text(&self, _printer_name: &str, _text: &str) -> Result<(), String> {
+    fn wait_for_job(
+        &self,
+        _printer_name: &str,
+        _job_id: u32,
+        _timeout: Duration,
+    ) -> Result<(), String> {
         Err("Not supported on this platform".to_string())
     }
+
+    fn pause_job(&self, _printer_name: &str, _job_id: u32) -> Result<(), String> {
+        Err("Not supported on this platform".to_string())
+    }
+
+    fn resume_job(&self, _printer_name: &str, _job_id: u32) -> Result<(), String> {
+        Err("Not supported on this platform".to_string())
+    }
+
+    fn is_job_paused(&self, _printer_name: &str, _job_id: u32) -> Result<bool, String> {
+        Err("Not supported on this platform".to_string())
+    }
+
+    fn set_job_priority(
+        &self,
+        _printer_name: &str,
+        _job_id: u32,
+        _priority: u32,
+    ) -> Result<(), String> {
+        Err("Not supported on this platform".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockPrinterManager;
+    use super::*;
+
+    #[test]
+    fn test_composite_routes_by_scheme() {
+        let system = MockPrinterManager::new()
+            .with_printer("Office")
+            .with_default_printer("Office");
+        let network = MockPrinterManager::new().with_printer("tcp://10.0.0.5:9100");
+        let usb = MockPrinterManager::new().with_printer("usb:04B8:0202");
+        let composite = CompositePrinterManager::new(Box::new(system.clone()))
+            .with_backend(SCHEME_TCP, Box::new(network.clone()))
+            .with_backend(SCHEME_USB, Box::new(usb.clone()));
+
+        let names: Vec<String> = composite
+            .list_printers()
+            .unwrap()
+            .into_iter()
+            .map(|printer| printer.name)
+            .collect();
+        assert_eq!(names, ["Office", "tcp://10.0.0.5:9100", "usb:04B8:0202"]);
+        assert_eq!(
+            composite.get_default_printer().unwrap().as_deref(),
+            Some("Office")
+        );
+
+        let raw = PrintDatatype::Raw;
+        composite
+            .print_raw("tcp://10.0.0.5:9100", b"net", raw, false)
+            .unwrap();
+        composite
+            .print_raw("usb:04B8:0202", b"usb", raw, false)
+            .unwrap();
+        composite.print_text("Office", "sys").unwrap();
+        assert_eq!(network.calls()[0].data, b"net");
+        assert_eq!(usb.calls()[0].data, b"usb");
+        assert_eq!(system.calls()[0].data, b"sys");
+
+        // 未注册的前缀交给系统打印机
+        assert!(composite.print_raw("lpt:1", b"x", raw, false).is_err());
+        assert_eq!(system.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_printers_sorted_with_default_first() {
+        let system = MockPrinterManager::new()
+            .with_printer("kitchen")
+            .with_printer("Office")
+            .with_printer("Bar")
+            .with_default_printer("Office");
+        let network = MockPrinterManager::new().with_printer("Label");
+        let composite = CompositePrinterManager::new(Box::new(system))
+            .with_backend(SCHEME_TCP, Box::new(network));
+
+        let names: Vec<String> = composite
+            .list_printers()
+            .unwrap()
+            .into_iter()
+            .map(|printer| printer.name)
+            .collect();
+        assert_eq!(names, ["Office", "Bar", "kitchen", "Label"]);
+    }
 }
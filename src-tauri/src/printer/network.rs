@@ -0,0 +1,322 @@
+//! 网络打印机（RAW 9100 端口）
+//!
+//! 名称为 `tcp://host[:port]` 的打印机不经过打印后台，每个任务建立一次 TCP 连接直接发送数据，
+//! 端口默认 9100。连接可双向通信，ESC/POS 打印机支持 `DLE EOT` 实时状态查询。
+//! 数据写入连接后即视为打印完成，没有可暂停或调整优先级的打印队列
+
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use tracing::info;
+
+use super::{escpos, PrinterManager, SCHEME_TCP};
+use crate::protocol::{
+    EscposStatus, Orientation, PaperSettings, PrintDatatype, PrintError, PrinterInfo, HOLD_FAILED,
+    OPTION_NOT_SUPPORTED, PRINTER_NOT_FOUND, PRINT_FAILED,
+};
+
+/// 未指定端口时使用的 RAW 打印端口
+const DEFAULT_RAW_PORT: u16 = 9100;
+
+/// 建立连接的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 发送数据和读取状态响应的超时时间
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 网络打印机管理器（注册到 `CompositePrinterManager` 的 `tcp://` 前缀）
+#[derive(Default)]
+pub struct NetworkPrinterManager {
+    /// 本进程内的任务编号（没有打印队列，仅用于日志和结果中的任务 ID）
+    next_job: AtomicU32,
+}
+
+impl NetworkPrinterManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 连接打印机，设置读写超时
+    fn connect(&self, printer_name: &str) -> Result<TcpStream, PrintError> {
+        let address = parse_address(printer_name)?;
+        let addr = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| {
+                PrintError::new(
+                    PRINTER_NOT_FOUND,
+                    format!("Cannot resolve network printer '{}'", printer_name),
+                )
+            })?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map_err(|e| {
+            PrintError::new(
+                PRINT_FAILED,
+                format!("Failed to connect to '{}': {}", printer_name, e),
+            )
+        })?;
+        stream
+            .set_read_timeout(Some(IO_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)))
+            .map_err(|e| {
+                format!(
+                    "Failed to configure connection to '{}': {}",
+                    printer_name, e
+                )
+            })?;
+        Ok(stream)
+    }
+
+    /// 发送数据（`copies` 份），返回任务 ID
+    fn send(&self, printer_name: &str, data: &[u8], copies: u32) -> Result<u32, PrintError> {
+        let mut stream = self.connect(printer_name)?;
+        for _ in 0..copies {
+            stream.write_all(data).map_err(|e| {
+                PrintError::new(
+                    PRINT_FAILED,
+                    format!("Failed to send data to '{}': {}", printer_name, e),
+                )
+            })?;
+        }
+        stream
+            .flush()
+            .map_err(|e| format!("Failed to send data to '{}': {}", printer_name, e))?;
+
+        let job_id = self.next_job.fetch_add(1, Ordering::Relaxed) + 1;
+        info!(
+            "Sent {} bytes x{} to network printer '{}': job={}",
+            data.len(),
+            copies,
+            printer_name,
+            job_id
+        );
+        Ok(job_id)
+    }
+}
+
+/// 解析 `tcp://host[:port]`，返回 `host:port`
+fn parse_address(printer_name: &str) -> Result<String, PrintError> {
+    let invalid = || {
+        PrintError::new(
+            PRINTER_NOT_FOUND,
+            format!("Invalid network printer address: {}", printer_name),
+        )
+    };
+    let address = printer_name
+        .strip_prefix(SCHEME_TCP)
+        .map(|rest| rest.trim_end_matches('/'))
+        .filter(|rest| !rest.is_empty())
+        .ok_or_else(invalid)?;
+
+    // IPv6 地址需要方括号：tcp://[::1]:9100
+    let has_port = match address.rfind(']') {
+        Some(end) => address[end..].contains(':'),
+        None => address.contains(':'),
+    };
+    if !has_port {
+        return Ok(format!("{}:{}", address, DEFAULT_RAW_PORT));
+    }
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+    if host.is_empty() || port.parse::<u16>().is_err() {
+        return Err(invalid());
+    }
+    Ok(address.to_string())
+}
+
+/// 网络打印机只接受原始数据
+fn check_raw(printer_name: &str, datatype: PrintDatatype, hold: bool) -> Result<(), PrintError> {
+    if hold {
+        return Err(PrintError::new(
+            HOLD_FAILED,
+            format!(
+                "Network printer '{}' has no queue to hold jobs",
+                printer_name
+            ),
+        ));
+    }
+    if datatype != PrintDatatype::Raw {
+        return Err(PrintError::new(
+            OPTION_NOT_SUPPORTED,
+            format!(
+                "Network printer '{}' only accepts RAW data, not {}",
+                printer_name,
+                datatype.as_str()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// 网络打印机没有打印队列时的错误
+fn no_queue(printer_name: &str) -> String {
+    format!("Network printer '{}' has no print queue", printer_name)
+}
+
+impl PrinterManager for NetworkPrinterManager {
+    /// 网络打印机无法枚举，按名称直接使用
+    fn list_printers(&self) -> Result<Vec<PrinterInfo>, String> {
+        Ok(Vec::new())
+    }
+
+    fn get_default_printer(&self) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    fn print_raw(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError> {
+        check_raw(printer_name, datatype, hold)?;
+        self.send(printer_name, data, 1)
+    }
+
+    fn print_text(&self, printer_name: &str, text: &str) -> Result<(), PrintError> {
+        self.send(printer_name, text.as_bytes(), 1).map(|_| ())
+    }
+
+    fn print_document(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        copies: u32,
+        tray: Option<u16>,
+        orientation: Option<Orientation>,
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError> {
+        check_raw(printer_name, datatype, hold)?;
+        if tray.is_some() || orientation.is_some() {
+            return Err(PrintError::new(
+                OPTION_NOT_SUPPORTED,
+                format!(
+                    "Network printer '{}' does not support tray or orientation",
+                    printer_name
+                ),
+            ));
+        }
+        self.send(printer_name, data, copies)
+    }
+
+    fn get_paper_settings(&self, printer_name: &str) -> Result<PaperSettings, String> {
+        Err(format!(
+            "Network printer '{}' does not report paper settings",
+            printer_name
+        ))
+    }
+
+    /// 数据已在打印时写入打印机，无需等待
+    fn wait_for_job(
+        &self,
+        _printer_name: &str,
+        _job_id: u32,
+        _timeout: Duration,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn pause_job(&self, printer_name: &str, _job_id: u32) -> Result<(), String> {
+        Err(no_queue(printer_name))
+    }
+
+    fn resume_job(&self, printer_name: &str, _job_id: u32) -> Result<(), String> {
+        Err(no_queue(printer_name))
+    }
+
+    fn is_job_paused(&self, _printer_name: &str, _job_id: u32) -> Result<bool, String> {
+        Ok(false)
+    }
+
+    fn set_job_priority(
+        &self,
+        printer_name: &str,
+        _job_id: u32,
+        _priority: u32,
+    ) -> Result<(), String> {
+        Err(no_queue(printer_name))
+    }
+
+    fn query_escpos_status(&self, printer_name: &str) -> Result<EscposStatus, PrintError> {
+        let mut stream = self.connect(printer_name)?;
+        escpos::query_status(&mut stream).map_err(|e| format!("{}: {}", printer_name, e).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_parse_address() {
+        let address = |name: &str| parse_address(name).ok();
+        assert_eq!(address("tcp://192.168.1.50").unwrap(), "192.168.1.50:9100");
+        assert_eq!(
+            address("tcp://printer.local:9101/").unwrap(),
+            "printer.local:9101"
+        );
+        assert_eq!(address("tcp://[::1]").unwrap(), "[::1]:9100");
+        assert_eq!(address("tcp://[::1]:9101").unwrap(), "[::1]:9101");
+        assert!(address("tcp://").is_none());
+        assert!(address("tcp://host:port").is_none());
+        assert!(address("usb:04b8:0202").is_none());
+    }
+
+    #[test]
+    fn test_print_raw_and_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let name = format!("tcp://{}", listener.local_addr().unwrap());
+        let printer = thread::spawn(move || {
+            // 第一个连接接收打印数据
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            conn.read_to_end(&mut received).unwrap();
+
+            // 第二个连接响应 DLE EOT 1/2/4（在线，开盖）
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut query = [0u8; 9];
+            conn.read_exact(&mut query[..3]).unwrap();
+            conn.write_all(&[0x12]).unwrap();
+            conn.read_exact(&mut query[3..6]).unwrap();
+            conn.write_all(&[0x16]).unwrap();
+            conn.read_exact(&mut query[6..]).unwrap();
+            conn.write_all(&[0x12]).unwrap();
+            received
+        });
+
+        let manager = NetworkPrinterManager::new();
+        let job = manager
+            .print_document(
+                &name,
+                b"\x1b@hi\n",
+                2,
+                None,
+                None,
+                PrintDatatype::Raw,
+                false,
+            )
+            .unwrap();
+        assert_eq!(job, 1);
+        manager.wait_for_job(&name, job, Duration::ZERO).unwrap();
+
+        let status = manager.query_escpos_status(&name).unwrap();
+        assert!(status.online && status.cover_open);
+        assert_eq!(printer.join().unwrap(), b"\x1b@hi\n\x1b@hi\n");
+
+        // 不支持的选项在连接前报错
+        let error = manager
+            .print_raw(&name, b"x", PrintDatatype::Text, false)
+            .unwrap_err();
+        assert_eq!(error.code, OPTION_NOT_SUPPORTED);
+        let error = manager
+            .print_raw(&name, b"x", PrintDatatype::Raw, true)
+            .unwrap_err();
+        assert_eq!(error.code, HOLD_FAILED);
+    }
+}
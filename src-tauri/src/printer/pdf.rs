@@ -3,9 +3,16 @@
 //! 使用 Tauri WebView 渲染 HTML 后调用系统打印
 
 use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use tauri::{AppHandle, Runtime, WebviewUrl, WebviewWindowBuilder};
-use tracing::{error, info};
+use std::time::Duration;
+use tauri::{AppHandle, Runtime, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::config::PrinterConfig;
+use crate::protocol::{PrintError, ARCHIVE_FAILED, CANCELLED, SILENT_PRINT_NOT_SUPPORTED};
 
 /// 全局打印窗口计数器
 static PRINT_WINDOW_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -20,6 +27,16 @@ pub struct PdfPrintOptions {
     pub paper_size: String,
     /// 是否静默打印 (不显示对话框)
     pub silent: bool,
+    /// 必须静默打印：平台不支持时返回 `SILENT_PRINT_NOT_SUPPORTED`，而不是显示打印对话框
+    pub require_silent: bool,
+    /// 打印窗口大小（逻辑像素，宽 x 高），为空时按纸张大小推算
+    pub window_size: Option<(f64, f64)>,
+    /// 调试模式：显示打印窗口并启用开发者工具，打印后不自动关闭
+    pub debug_visible: bool,
+    /// 打印缩放比例（注入打印样式，抵消显示器缩放对输出尺寸的影响），为空时不缩放
+    pub scale: Option<f64>,
+    /// 打印前将页面保存为 PDF 的归档路径（仅在 `pdf_archive_supported` 时可用），保存失败时不打印
+    pub archive_pdf: Option<PathBuf>,
 }
 
 impl Default for PdfPrintOptions {
@@ -28,46 +45,224 @@ impl Default for PdfPrintOptions {
             copies: 1,
             paper_size: "A4".to_string(),
             silent: false,
+            require_silent: false,
+            window_size: None,
+            debug_visible: false,
+            scale: None,
+            archive_pdf: None,
+        }
+    }
+}
+
+/// 打印缩放比例的允许范围
+const MIN_PRINT_SCALE: f64 = 0.25;
+const MAX_PRINT_SCALE: f64 = 4.0;
+
+/// CSS 像素对应的 DPI
+const CSS_DPI: f64 = 96.0;
+
+/// 确定打印缩放比例：`scale` 直接指定，`dpi` 为内容排版时使用的显示器 DPI（如 125% 缩放为 120），按 96/dpi 缩放
+pub fn print_scale(scale: Option<f64>, dpi: Option<f64>) -> Result<Option<f64>, String> {
+    let scale = match (scale, dpi) {
+        (Some(_), Some(_)) => return Err("Specify either scale or dpi, not both".to_string()),
+        (Some(scale), None) => scale,
+        (None, Some(dpi)) if dpi.is_finite() && dpi > 0.0 => CSS_DPI / dpi,
+        (None, Some(dpi)) => return Err(format!("Invalid print DPI: {}", dpi)),
+        (None, None) => return Ok(None),
+    };
+    check_scale(scale).map(Some)
+}
+
+/// 检查缩放比例是否在允许范围内
+fn check_scale(scale: f64) -> Result<f64, String> {
+    if !(MIN_PRINT_SCALE..=MAX_PRINT_SCALE).contains(&scale) {
+        return Err(format!(
+            "Print scale {} is out of range ({}-{})",
+            scale, MIN_PRINT_SCALE, MAX_PRINT_SCALE
+        ));
+    }
+    Ok(scale)
+}
+
+/// 在 HTML 中注入打印缩放样式（使用 `zoom` 而不是 `transform`，分页仍按缩放后的尺寸计算）
+fn apply_print_scale(html: &str, scale: Option<f64>) -> String {
+    let Some(scale) = scale else {
+        return html.to_string();
+    };
+    let style = format!(
+        "<style>@media print {{ html {{ zoom: {}; }} }}</style>",
+        scale
+    );
+    match html.find("</head>") {
+        Some(index) => format!("{}{}\n{}", &html[..index], style, &html[index..]),
+        None => format!("{}\n{}", style, html),
+    }
+}
+
+/// HTML 打印结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HtmlPrintResult {
+    /// 是否未显示打印对话框
+    pub silent: bool,
+    /// 提示信息（如请求了静默打印但平台不支持）
+    pub warnings: Vec<String>,
+}
+
+/// WebView 的 `window.print()` 能否不显示打印对话框
+///
+/// WebView2（Windows）、WKWebView（macOS）和 WebKitGTK（Linux）都会弹出系统打印对话框，
+/// 目前所有平台都不支持
+pub fn silent_print_supported() -> bool {
+    false
+}
+
+/// 能否将打印页面保存为 PDF 归档
+///
+/// 仅 WebView2（Windows）提供 `PrintToPdf`，其他平台归档渲染后的 HTML
+pub fn pdf_archive_supported() -> bool {
+    cfg!(windows)
+}
+
+/// 检查静默打印请求：平台不支持时按 `require_silent` 报错，否则返回警告并显示打印对话框
+fn check_silent(options: &PdfPrintOptions, supported: bool) -> Result<HtmlPrintResult, String> {
+    if !options.silent || supported {
+        return Ok(HtmlPrintResult {
+            silent: options.silent,
+            warnings: Vec::new(),
+        });
+    }
+
+    let message = "Silent printing is not supported by the WebView on this platform";
+    if options.require_silent {
+        return Err(PrintError::new(SILENT_PRINT_NOT_SUPPORTED, message).to_string());
+    }
+    warn!("{}, showing the print dialog", message);
+    Ok(HtmlPrintResult {
+        silent: false,
+        warnings: vec![format!("{}; the print dialog was shown", message)],
+    })
+}
+
+/// HTML 打印取消信号
+///
+/// `cancel` 会中止调用前已开始的所有打印（关闭打印窗口并返回 `CANCELLED`），之后的打印不受影响
+pub struct PrintCancel {
+    generation: watch::Sender<u64>,
+}
+
+impl Default for PrintCancel {
+    fn default() -> Self {
+        Self {
+            generation: watch::channel(0).0,
+        }
+    }
+}
+
+impl PrintCancel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为一次打印获取取消令牌
+    pub fn token(&self) -> watch::Receiver<u64> {
+        self.generation.subscribe()
+    }
+
+    /// 取消所有进行中的打印
+    pub fn cancel(&self) {
+        self.generation.send_modify(|generation| *generation += 1);
+    }
+}
+
+/// 等待指定时间，期间被取消时返回 `CANCELLED` 错误
+async fn wait_or_cancel(
+    duration: Duration,
+    cancel: &mut watch::Receiver<u64>,
+) -> Result<(), String> {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => Ok(()),
+        Ok(()) = cancel.changed() => {
+            Err(PrintError::new(CANCELLED, "HTML print cancelled").to_string())
         }
     }
 }
 
+/// 无法从纸张大小推算时使用的打印窗口大小
+const DEFAULT_WINDOW_SIZE: (f64, f64) = (800.0, 600.0);
+
 /// 打印 HTML 内容
 ///
-/// 创建一个隐藏的 WebView 窗口，加载 HTML 内容后执行 window.print()
+/// 创建一个隐藏的 WebView 窗口，加载 HTML 内容后执行 window.print()；
+/// 收到取消信号时立即关闭窗口并返回 `CANCELLED`。
+/// `debug_visible` 为 true 时窗口可见且打印后保留，便于检查渲染结果。
+/// 请求静默打印但平台不支持时，结果中带有警告（`require_silent` 时直接报错，不创建窗口）
 pub async fn print_html<R: Runtime>(
     app: &AppHandle<R>,
     html_content: &str,
     options: PdfPrintOptions,
-) -> Result<(), String> {
+    mut cancel: watch::Receiver<u64>,
+) -> Result<HtmlPrintResult, String> {
+    let result = check_silent(&options, silent_print_supported())?;
+    let scale = options.scale.map(check_scale).transpose()?;
+    let html_content = apply_print_scale(html_content, scale);
+
     // 生成唯一的窗口标签
     let window_id = PRINT_WINDOW_COUNTER.fetch_add(1, Ordering::SeqCst);
     let window_label = format!("print-window-{}", window_id);
 
-    info!("Creating print window: {}", window_label);
+    let (width, height) = print_window_size(&options)?;
+    info!(
+        "Creating print window: {} ({}x{})",
+        window_label, width, height
+    );
 
     // 将 HTML 内容编码为 data URI
     let html_base64 = STANDARD.encode(html_content.as_bytes());
     let data_uri = format!("data:text/html;base64,{}", html_base64);
 
-    // 创建隐藏的 WebView 窗口
+    // 创建隐藏的 WebView 窗口（调试模式下可见，并在调试构建或启用 `devtools` 特性时可打开开发者工具）
+    let debug = options.debug_visible;
     let webview_window = WebviewWindowBuilder::new(
         app,
         &window_label,
         WebviewUrl::External(data_uri.parse().map_err(|e| format!("Invalid URL: {}", e))?),
     )
     .title("Print Preview")
-    .inner_size(800.0, 600.0)
-    .visible(false) // 隐藏窗口
-    .skip_taskbar(true)
+    .inner_size(width, height)
+    .visible(debug)
+    .skip_taskbar(!debug)
+    .devtools(debug)
     .build()
     .map_err(|e| format!("Failed to create print window: {}", e))?;
 
     // 等待页面加载完成
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    if let Err(e) = wait_or_cancel(Duration::from_millis(500), &mut cancel).await {
+        info!("Print cancelled, closing window: {}", window_label);
+        let _ = webview_window.close();
+        return Err(e);
+    }
+
+    // 打印前归档 PDF，失败时不打印
+    if let Some(path) = &options.archive_pdf {
+        if let Err(e) = save_pdf(&webview_window, path).await {
+            let _ = webview_window.close();
+            return Err(PrintError::new(
+                ARCHIVE_FAILED,
+                format!("Failed to save archive PDF {:?}: {}", path, e),
+            )
+            .to_string());
+        }
+        info!("Archived HTML print to {:?}", path);
+    }
 
     // 构建打印 JavaScript
-    let print_js = if options.silent {
+    let print_js = if debug {
+        // 调试模式 - 打印后保留窗口
+        r#"
+            window.print();
+        "#
+        .to_string()
+    } else if options.silent {
         // 静默打印 - 直接调用 window.print()
         r#"
             window.onload = function() {
@@ -105,15 +300,76 @@ pub async fn print_html<R: Runtime>(
     }
 
     // 等待打印对话框处理
-    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    let waited = wait_or_cancel(Duration::from_secs(2), &mut cancel).await;
 
-    // 关闭打印窗口
-    if let Err(e) = webview_window.close() {
+    // 关闭打印窗口（调试模式下未取消时保留）
+    if debug && waited.is_ok() {
+        info!("Keeping print window open for debugging: {}", window_label);
+    } else if let Err(e) = webview_window.close() {
         error!("Failed to close print window: {}", e);
     }
+    if let Err(e) = waited {
+        info!("Print cancelled for window: {}", window_label);
+        return Err(e);
+    }
 
     info!("PDF print completed for window: {}", window_label);
-    Ok(())
+    Ok(result)
+}
+
+/// 使用 WebView2 的 `PrintToPdf` 将窗口中的页面保存为 PDF，等待保存完成
+#[cfg(windows)]
+async fn save_pdf<R: Runtime>(window: &WebviewWindow<R>, path: &Path) -> Result<(), String> {
+    use std::sync::{Arc, Mutex};
+    use webview2_com::Microsoft::Web::WebView2::Win32::{
+        ICoreWebView2PrintSettings, ICoreWebView2_7,
+    };
+    use webview2_com::PrintToPdfCompletedHandler;
+    use windows_core::{Interface, HSTRING};
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<Result<(), String>>();
+    // 调用失败时回调不会执行，由调用方直接发送结果
+    let tx = Arc::new(Mutex::new(Some(tx)));
+    let send = move |result: Result<(), String>| {
+        if let Some(tx) = tx.lock().unwrap().take() {
+            let _ = tx.send(result);
+        }
+    };
+    let path = HSTRING::from(path.to_string_lossy().as_ref());
+
+    window
+        .with_webview(move |webview| {
+            let completed = send.clone();
+            let handler = PrintToPdfCompletedHandler::create(Box::new(move |result, saved| {
+                completed(match result {
+                    Ok(()) if saved => Ok(()),
+                    Ok(()) => Err("PrintToPdf did not save the file".to_string()),
+                    Err(e) => Err(e.to_string()),
+                });
+                Ok(())
+            }));
+            let started = unsafe {
+                webview
+                    .controller()
+                    .CoreWebView2()
+                    .and_then(|core| core.cast::<ICoreWebView2_7>())
+                    .and_then(|core| {
+                        core.PrintToPdf(&path, None::<&ICoreWebView2PrintSettings>, &handler)
+                    })
+            };
+            if let Err(e) = started {
+                send(Err(e.to_string()));
+            }
+        })
+        .map_err(|e| e.to_string())?;
+
+    rx.await
+        .map_err(|_| "PrintToPdf was not completed".to_string())?
+}
+
+#[cfg(not(windows))]
+async fn save_pdf<R: Runtime>(_window: &WebviewWindow<R>, _path: &Path) -> Result<(), String> {
+    Err("Saving PDF is not supported by the WebView on this platform".to_string())
 }
 
 /// 规范化 CSS 长度单位
@@ -157,13 +413,16 @@ fn parse_custom_paper_css(paper_size: &str) -> Option<(String, String)> {
     Some((w, h))
 }
 
-/// 将纸张尺寸转换为 CSS @page size 值
+/// A4 纸张的 CSS 尺寸（默认纸张大小也无法识别时使用）
+const A4_CSS: &str = "210mm 297mm";
+
+/// 将纸张尺寸转换为 CSS @page size 值，无法识别时返回 None
 ///
 /// 支持格式：
 /// - 预设：A4, Letter, Legal, A3, A5
 /// - 方向：A4 landscape, Letter portrait
 /// - 自定义：80mm 200mm, 80mmx200mm, 80mm 200mm landscape
-fn paper_size_to_css(paper_size: &str) -> String {
+fn paper_size_to_css(paper_size: &str) -> Option<String> {
     let tokens: Vec<String> = paper_size
         .trim()
         .split_whitespace()
@@ -171,7 +430,7 @@ fn paper_size_to_css(paper_size: &str) -> String {
         .collect();
 
     if tokens.is_empty() {
-        return "210mm 297mm".to_string(); // 默认 A4
+        return None;
     }
 
     // 支持 "A4 landscape" / "80mm 200mm landscape"
@@ -184,17 +443,14 @@ fn paper_size_to_css(paper_size: &str) -> String {
     let base = base_tokens.join(" ");
 
     let mut css = match base.as_str() {
-        "a4" => "210mm 297mm".to_string(),
+        "a4" => A4_CSS.to_string(),
         "letter" => "8.5in 11in".to_string(),
         "legal" => "8.5in 14in".to_string(),
         "a3" => "297mm 420mm".to_string(),
         "a5" => "148mm 210mm".to_string(),
         _ => {
-            if let Some((w, h)) = parse_custom_paper_css(&base) {
-                format!("{} {}", w, h)
-            } else {
-                "210mm 297mm".to_string() // 默认 A4
-            }
+            let (w, h) = parse_custom_paper_css(&base)?;
+            format!("{} {}", w, h)
         }
     };
 
@@ -204,16 +460,126 @@ fn paper_size_to_css(paper_size: &str) -> String {
         }
     }
 
-    css
+    Some(css)
 }
 
-/// 生成打印用的 HTML 包装
+/// 将 CSS 长度（mm、cm、in）转换为像素（96 DPI）
+fn css_length_to_px(length: &str) -> Option<f64> {
+    let (num, px_per_unit) = [("mm", 96.0 / 25.4), ("cm", 96.0 / 2.54), ("in", 96.0)]
+        .into_iter()
+        .find_map(|(unit, ratio)| length.strip_suffix(unit).map(|num| (num, ratio)))?;
+    num.parse::<f64>().ok().map(|num| num * px_per_unit)
+}
+
+/// 将 CSS 长度（mm、cm、in）转换为毫米
+fn css_length_to_mm(length: &str) -> Option<f64> {
+    let (num, mm_per_unit) = [("mm", 1.0), ("cm", 10.0), ("in", 25.4)]
+        .into_iter()
+        .find_map(|(unit, ratio)| length.strip_suffix(unit).map(|num| (num, ratio)))?;
+    num.parse::<f64>().ok().map(|num| num * mm_per_unit)
+}
+
+/// 纸张的实际尺寸（已按方向交换宽高）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PaperDimensions {
+    pub width_mm: f64,
+    pub height_mm: f64,
+    /// 宽度大于高度
+    pub landscape: bool,
+}
+
+/// 解析纸张大小的实际尺寸（与打印时的解析规则相同），为空时使用配置的默认纸张大小，无法识别时报错
+pub fn resolve_paper_size(
+    paper_size: &str,
+    config: &PrinterConfig,
+) -> Result<PaperDimensions, String> {
+    let paper_size = match paper_size.trim() {
+        "" => config.default_paper_size.as_str(),
+        size => size,
+    };
+
+    paper_size_to_css(paper_size)
+        .and_then(|css| {
+            let (w, h) = css.split_once(' ')?;
+            Some((css_length_to_mm(w)?, css_length_to_mm(h)?))
+        })
+        .filter(|&(w, h)| w > 0.0 && h > 0.0)
+        .map(|(width_mm, height_mm)| PaperDimensions {
+            width_mm,
+            height_mm,
+            landscape: width_mm > height_mm,
+        })
+        .ok_or_else(|| format!("Unknown paper size: {}", paper_size))
+}
+
+/// 确定打印窗口大小
 ///
-/// 添加必要的打印样式和页面设置
-pub fn wrap_html_for_print(content: &str, paper_size: &str) -> String {
-    let paper_css = paper_size_to_css(paper_size);
+/// 优先使用 `window_size`，否则按纸张大小推算（窄小票使用窄窗口，避免内容被缩放）；
+/// 纸张大小无法识别时使用默认大小
+fn print_window_size(options: &PdfPrintOptions) -> Result<(f64, f64), String> {
+    let (width, height) = match options.window_size {
+        Some(size) => size,
+        None => paper_size_to_css(&options.paper_size)
+            .and_then(|css| {
+                let (w, h) = css.split_once(' ')?;
+                Some((css_length_to_px(w)?, css_length_to_px(h)?))
+            })
+            .unwrap_or(DEFAULT_WINDOW_SIZE),
+    };
+
+    let valid = |v: f64| v.is_finite() && v > 0.0;
+    if !valid(width) || !valid(height) {
+        return Err(format!("Invalid print window size: {}x{}", width, height));
+    }
+    Ok((width, height))
+}
+
+/// 确定纸张的 CSS 尺寸
+///
+/// 未指定时使用配置的默认纸张大小；无法识别时按 `strict_paper_size` 报错或使用默认纸张大小
+fn resolve_paper_css(paper_size: Option<&str>, config: &PrinterConfig) -> Result<String, String> {
+    let default_css = || {
+        paper_size_to_css(&config.default_paper_size)
+            .ok_or_else(|| format!("Unknown default paper size: {}", config.default_paper_size))
+    };
+
+    let paper_size = match paper_size {
+        Some(size) if !size.trim().is_empty() => size,
+        _ => {
+            return default_css().or_else(|e| {
+                if config.strict_paper_size {
+                    return Err(e);
+                }
+                warn!("{}, using A4", e);
+                Ok(A4_CSS.to_string())
+            })
+        }
+    };
+
+    match paper_size_to_css(paper_size) {
+        Some(css) => Ok(css),
+        None if config.strict_paper_size => Err(format!("Unknown paper size: {}", paper_size)),
+        None => {
+            warn!(
+                "Unknown paper size '{}', using default '{}'",
+                paper_size, config.default_paper_size
+            );
+            Ok(default_css().unwrap_or_else(|_| A4_CSS.to_string()))
+        }
+    }
+}
 
-    format!(
+/// 生成打印用的 HTML 包装
+///
+/// 添加必要的打印样式和页面设置，纸张大小为空时使用配置的默认纸张大小
+pub fn wrap_html_for_print(
+    content: &str,
+    paper_size: Option<&str>,
+    config: &PrinterConfig,
+) -> Result<String, String> {
+    let paper_css = resolve_paper_css(paper_size, config)?;
+
+    Ok(format!(
         r#"<!DOCTYPE html>
 <html>
 <head>
@@ -255,17 +621,20 @@ pub fn wrap_html_for_print(content: &str, paper_size: &str) -> String {
 {content}
 </body>
 </html>"#
-    )
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn wrap(paper_size: &str) -> String {
+        wrap_html_for_print("<h1>Test</h1>", Some(paper_size), &PrinterConfig::default()).unwrap()
+    }
+
     #[test]
     fn test_wrap_html_for_print() {
-        let content = "<h1>Test</h1>";
-        let wrapped = wrap_html_for_print(content, "A4");
+        let wrapped = wrap("A4");
         assert!(wrapped.contains("@page"));
         assert!(wrapped.contains("210mm 297mm"));
         assert!(wrapped.contains("<h1>Test</h1>"));
@@ -273,22 +642,181 @@ mod tests {
 
     #[test]
     fn test_wrap_html_for_print_custom_size_mm() {
-        let content = "<h1>Test</h1>";
-        let wrapped = wrap_html_for_print(content, "80mm 200mm");
+        let wrapped = wrap("80mm 200mm");
         assert!(wrapped.contains("80mm 200mm"));
     }
 
     #[test]
     fn test_wrap_html_for_print_preset_landscape() {
-        let wrapped = wrap_html_for_print("<h1>Test</h1>", "A4 landscape");
+        let wrapped = wrap("A4 landscape");
         assert!(wrapped.contains("297mm 210mm"));
     }
 
+    #[test]
+    fn test_configured_default_paper_size() {
+        let config = PrinterConfig {
+            default_paper_size: "80mm 200mm".to_string(),
+            ..PrinterConfig::default()
+        };
+
+        let wrapped = wrap_html_for_print("", None, &config).unwrap();
+        assert!(wrapped.contains("size: 80mm 200mm;"));
+
+        // 无法识别时使用配置的默认纸张大小
+        assert_eq!(
+            resolve_paper_css(Some("A44"), &config).unwrap(),
+            "80mm 200mm"
+        );
+        assert_eq!(
+            resolve_paper_css(Some("Letter"), &config).unwrap(),
+            "8.5in 11in"
+        );
+    }
+
+    #[test]
+    fn test_strict_paper_size() {
+        let config = PrinterConfig {
+            strict_paper_size: true,
+            ..PrinterConfig::default()
+        };
+
+        let err = wrap_html_for_print("", Some("A44"), &config).unwrap_err();
+        assert!(err.contains("A44"));
+        assert_eq!(resolve_paper_css(None, &config).unwrap(), "210mm 297mm");
+
+        let config = PrinterConfig {
+            default_paper_size: "80mm".to_string(),
+            ..config
+        };
+        assert!(resolve_paper_css(None, &config).is_err());
+    }
+
     #[test]
     fn test_default_options() {
         let options = PdfPrintOptions::default();
         assert_eq!(options.copies, 1);
         assert_eq!(options.paper_size, "A4");
         assert!(!options.silent);
+        assert!(!options.require_silent);
+        assert!(options.window_size.is_none());
+        assert!(!options.debug_visible);
+        assert!(options.scale.is_none());
+    }
+
+    #[test]
+    fn test_print_scale() {
+        assert_eq!(print_scale(None, None).unwrap(), None);
+        assert_eq!(print_scale(Some(0.8), None).unwrap(), Some(0.8));
+        assert_eq!(print_scale(None, Some(120.0)).unwrap(), Some(0.8));
+        assert!(print_scale(Some(0.1), None).is_err());
+        assert!(print_scale(Some(f64::NAN), None).is_err());
+        assert!(print_scale(None, Some(0.0)).is_err());
+        assert!(print_scale(Some(1.0), Some(96.0)).is_err());
+
+        let html = wrap("A4");
+        let scaled = apply_print_scale(&html, Some(0.8));
+        assert!(scaled.contains("zoom: 0.8;"));
+        assert!(scaled.find("zoom").unwrap() < scaled.find("</head>").unwrap());
+        assert_eq!(apply_print_scale(&html, None), html);
+        assert!(apply_print_scale("<p>x</p>", Some(2.0)).ends_with("<p>x</p>"));
+    }
+
+    #[test]
+    fn test_check_silent() {
+        let options = PdfPrintOptions::default();
+        assert_eq!(
+            check_silent(&options, false).unwrap(),
+            HtmlPrintResult::default()
+        );
+
+        let options = PdfPrintOptions {
+            silent: true,
+            ..PdfPrintOptions::default()
+        };
+        assert!(check_silent(&options, true).unwrap().silent);
+        let result = check_silent(&options, false).unwrap();
+        assert!(!result.silent);
+        assert_eq!(result.warnings.len(), 1);
+
+        let options = PdfPrintOptions {
+            require_silent: true,
+            ..options
+        };
+        let err = check_silent(&options, false).unwrap_err();
+        assert!(err.starts_with(SILENT_PRINT_NOT_SUPPORTED));
+    }
+
+    #[tokio::test]
+    async fn test_print_cancel() {
+        let cancel = PrintCancel::new();
+        let mut token = cancel.token();
+        assert!(wait_or_cancel(Duration::from_millis(1), &mut token)
+            .await
+            .is_ok());
+
+        cancel.cancel();
+        let err = wait_or_cancel(Duration::from_secs(60), &mut token)
+            .await
+            .unwrap_err();
+        assert!(err.starts_with(CANCELLED));
+
+        // 取消之后开始的打印不受影响
+        let mut token = cancel.token();
+        assert!(wait_or_cancel(Duration::from_millis(1), &mut token)
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_resolve_paper_size() {
+        let config = PrinterConfig::default();
+        let size = |paper_size: &str| resolve_paper_size(paper_size, &config);
+
+        let a4 = size("A4").unwrap();
+        assert_eq!((a4.width_mm, a4.height_mm), (210.0, 297.0));
+        assert!(!a4.landscape);
+        let landscape = size("a4 landscape").unwrap();
+        assert_eq!((landscape.width_mm, landscape.height_mm), (297.0, 210.0));
+        assert!(landscape.landscape);
+
+        let letter = size("Letter").unwrap();
+        assert!((letter.width_mm - 215.9).abs() < 1e-9 && (letter.height_mm - 279.4).abs() < 1e-9);
+        let receipt = size("80mmx20cm").unwrap();
+        assert_eq!((receipt.width_mm, receipt.height_mm), (80.0, 200.0));
+
+        // 为空时使用默认纸张大小，无法识别时报错
+        assert_eq!(size("  ").unwrap(), a4);
+        assert!(size("A44").is_err());
+        assert!(size("0mm 200mm").is_err());
+    }
+
+    #[test]
+    fn test_print_window_size() {
+        let size = |paper_size: &str| {
+            print_window_size(&PdfPrintOptions {
+                paper_size: paper_size.to_string(),
+                ..PdfPrintOptions::default()
+            })
+        };
+
+        let (w, h) = size("80mm 200mm").unwrap();
+        assert!((w - 302.36).abs() < 0.01 && (h - 755.91).abs() < 0.01);
+        let (w, h) = size("A4 landscape").unwrap();
+        assert!((w - 1122.52).abs() < 0.01 && (h - 793.70).abs() < 0.01);
+        assert_eq!(size("Letter").unwrap(), (816.0, 1056.0));
+        assert_eq!(size("A44").unwrap(), DEFAULT_WINDOW_SIZE);
+        assert!(size("0mm 200mm").is_err());
+        assert!(size("-80mm 200mm").is_err());
+
+        let options = PdfPrintOptions {
+            window_size: Some((400.0, 300.0)),
+            ..PdfPrintOptions::default()
+        };
+        assert_eq!(print_window_size(&options).unwrap(), (400.0, 300.0));
+        let options = PdfPrintOptions {
+            window_size: Some((400.0, 0.0)),
+            ..PdfPrintOptions::default()
+        };
+        assert!(print_window_size(&options).is_err());
     }
 }
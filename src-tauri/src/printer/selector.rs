@@ -0,0 +1,209 @@
+//! 打印机选择
+//!
+//! 按唯一标识、索引或能力条件（类型、彩色）从打印机列表中选出目标打印机
+
+use super::detect_printer_type;
+use crate::protocol::{PrinterInfo, PrinterQuery};
+
+/// 在打印机列表中查找第一台满足所有条件的打印机
+pub fn find_printer<'a>(
+    printers: &'a [PrinterInfo],
+    query: &PrinterQuery,
+) -> Option<&'a PrinterInfo> {
+    let candidates: &[PrinterInfo] = match query.index {
+        Some(index) => std::slice::from_ref(printers.get(index)?),
+        None => printers,
+    };
+
+    candidates.iter().find(|printer| matches(printer, query))
+}
+
+/// 打印机是否存在（按名称或唯一标识匹配，名称不区分大小写，与 Windows 一致）
+pub fn printer_exists(printers: &[PrinterInfo], name: &str) -> bool {
+    printers
+        .iter()
+        .any(|printer| printer.name.eq_ignore_ascii_case(name) || printer.id == name)
+}
+
+/// 选中打印机的打印目标（传给 `print_raw` / `print_document` 的名称）
+///
+/// 名称唯一时直接使用名称；与其他打印机同名时改用共享路径 `\\服务器\名称`，
+/// 没有服务器可以区分时返回 `None`，避免打印到另一台同名打印机
+pub fn printer_target(printers: &[PrinterInfo], printer: &PrinterInfo) -> Option<String> {
+    let duplicated = printers
+        .iter()
+        .any(|other| other.id != printer.id && other.name.eq_ignore_ascii_case(&printer.name));
+    if !duplicated {
+        return Some(printer.name.clone());
+    }
+
+    // 唯一标识的格式为 `名称|端口|服务器`，名称中可能包含 `|`
+    let server = printer.id.rsplit('|').next().unwrap_or_default();
+    (!server.is_empty()).then(|| format!("{}\\{}", server, printer.name))
+}
+
+/// 打印机是否满足查询条件
+fn matches(printer: &PrinterInfo, query: &PrinterQuery) -> bool {
+    if let Some(name) = &query.name {
+        if &printer.name != name {
+            return false;
+        }
+    }
+
+    if let Some(id) = &query.id {
+        if &printer.id != id {
+            return false;
+        }
+    }
+
+    if let Some(printer_type) = &query.printer_type {
+        let detected = detect_printer_type(&printer.name, printer.driver.as_deref());
+        if !detected.eq_ignore_ascii_case(printer_type) {
+            return false;
+        }
+    }
+
+    if let Some(color) = query.color {
+        if printer.color != Some(color) {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printer(name: &str, driver: &str, color: Option<bool>) -> PrinterInfo {
+        PrinterInfo {
+            name: name.to_string(),
+            id: format!("{}||", name),
+            is_default: false,
+            status: "ready".to_string(),
+            driver: Some(driver.to_string()),
+            port: None,
+            color,
+        }
+    }
+
+    fn printers() -> Vec<PrinterInfo> {
+        vec![
+            printer("Office", "HP Color LaserJet", Some(true)),
+            printer("Mono", "HP LaserJet", Some(false)),
+            printer("Label", "ZDesigner ZD420", Some(false)),
+        ]
+    }
+
+    #[test]
+    fn test_find_by_index_and_capability() {
+        let printers = printers();
+
+        let query = PrinterQuery {
+            index: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(find_printer(&printers, &query).unwrap().name, "Mono");
+
+        let query = PrinterQuery {
+            printer_type: Some("ZPL".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find_printer(&printers, &query).unwrap().name, "Label");
+
+        let query = PrinterQuery {
+            color: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(find_printer(&printers, &query).unwrap().name, "Office");
+    }
+
+    #[test]
+    fn test_no_matching_printer() {
+        let printers = printers();
+
+        let query = PrinterQuery {
+            index: Some(5),
+            ..Default::default()
+        };
+        assert!(find_printer(&printers, &query).is_none());
+
+        // 索引与条件同时指定时必须都满足
+        let query = PrinterQuery {
+            index: Some(0),
+            printer_type: Some("zpl".to_string()),
+            ..Default::default()
+        };
+        assert!(find_printer(&printers, &query).is_none());
+
+        let query = PrinterQuery {
+            printer_type: Some("escpos".to_string()),
+            ..Default::default()
+        };
+        assert!(find_printer(&printers, &query).is_none());
+    }
+
+    #[test]
+    fn test_printer_exists() {
+        let printers = printers();
+        assert!(printer_exists(&printers, "Label"));
+        assert!(printer_exists(&printers, "label"));
+        assert!(printer_exists(&printers, "Mono||"));
+        assert!(!printer_exists(&printers, "Removed"));
+        assert!(!printer_exists(&printers, ""));
+    }
+
+    #[test]
+    fn test_find_by_id_with_duplicate_names() {
+        let mut printers = printers();
+        for server in ["SRV1", "SRV2"] {
+            printers.push(PrinterInfo {
+                id: format!("Shared|IP_10.0.0.1|\\\\{}", server),
+                ..printer("Shared", "HP LaserJet", None)
+            });
+        }
+
+        let query = PrinterQuery {
+            id: Some("Shared|IP_10.0.0.1|\\\\SRV2".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find_printer(&printers, &query).unwrap().id, printers[4].id);
+
+        let query = PrinterQuery {
+            id: Some("Shared||".to_string()),
+            ..Default::default()
+        };
+        assert!(find_printer(&printers, &query).is_none());
+    }
+
+    #[test]
+    fn test_printer_target() {
+        let mut printers = printers();
+        assert_eq!(
+            printer_target(&printers, &printers[0]).as_deref(),
+            Some("Office")
+        );
+
+        // 同名的共享打印机通过服务器路径区分
+        for server in ["SRV1", "SRV2"] {
+            printers.push(PrinterInfo {
+                id: format!("Shared|IP_10.0.0.1|\\\\{}", server),
+                ..printer("Shared", "HP LaserJet", None)
+            });
+        }
+        assert_eq!(
+            printer_target(&printers, &printers[4]).as_deref(),
+            Some("\\\\SRV2\\Shared")
+        );
+
+        // 同名的本地打印机无法区分
+        for port in ["USB001", "USB002"] {
+            printers.push(PrinterInfo {
+                id: format!("Dup|{}|", port),
+                ..printer("Dup", "Generic", None)
+            });
+        }
+        assert_eq!(printer_target(&printers, &printers[5]), None);
+    }
+}
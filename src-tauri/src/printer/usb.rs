@@ -0,0 +1,147 @@
+//! USB 直连打印机（`usb:VID:PID`）
+//!
+//! 内置版本没有 USB 驱动，`usb:` 名称的打印机统一返回 `USB_NOT_SUPPORTED`，
+//! 而不是交给打印后台后报出难以理解的“打印机不存在”。需要 USB 直连时，
+//! 使用 `run_with_printer_manager` 注册自定义的 `usb:` 后端
+
+use std::time::Duration;
+
+use super::{PrinterManager, SCHEME_USB};
+use crate::protocol::{
+    EscposStatus, Orientation, PaperSettings, PrintDatatype, PrintError, PrinterInfo,
+    PRINTER_NOT_FOUND, USB_NOT_SUPPORTED,
+};
+
+/// USB 打印机管理器（注册到 `CompositePrinterManager` 的 `usb:` 前缀）
+#[derive(Default)]
+pub struct UsbPrinterManager;
+
+impl UsbPrinterManager {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// 解析 `usb:VID:PID`（十六进制），返回厂商 ID 和产品 ID
+fn parse_device(printer_name: &str) -> Option<(u16, u16)> {
+    let (vid, pid) = printer_name.strip_prefix(SCHEME_USB)?.split_once(':')?;
+    Some((
+        u16::from_str_radix(vid, 16).ok()?,
+        u16::from_str_radix(pid, 16).ok()?,
+    ))
+}
+
+/// USB 打印机无法使用时的错误（名称格式错误时返回 `PRINTER_NOT_FOUND`）
+fn unsupported(printer_name: &str) -> PrintError {
+    match parse_device(printer_name) {
+        Some((vid, pid)) => PrintError::new(
+            USB_NOT_SUPPORTED,
+            format!(
+                "USB printer '{}' (VID {:04X}, PID {:04X}) is not supported by this build",
+                printer_name, vid, pid
+            ),
+        ),
+        None => PrintError::new(
+            PRINTER_NOT_FOUND,
+            format!("Invalid USB printer name: {}", printer_name),
+        ),
+    }
+}
+
+impl PrinterManager for UsbPrinterManager {
+    /// 没有 USB 驱动，不枚举设备
+    fn list_printers(&self) -> Result<Vec<PrinterInfo>, String> {
+        Ok(Vec::new())
+    }
+
+    fn get_default_printer(&self) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    fn print_raw(
+        &self,
+        printer_name: &str,
+        _data: &[u8],
+        _datatype: PrintDatatype,
+        _hold: bool,
+    ) -> Result<u32, PrintError> {
+        Err(unsupported(printer_name))
+    }
+
+    fn print_text(&self, printer_name: &str, _text: &str) -> Result<(), PrintError> {
+        Err(unsupported(printer_name))
+    }
+
+    fn print_document(
+        &self,
+        printer_name: &str,
+        _data: &[u8],
+        _copies: u32,
+        _tray: Option<u16>,
+        _orientation: Option<Orientation>,
+        _datatype: PrintDatatype,
+        _hold: bool,
+    ) -> Result<u32, PrintError> {
+        Err(unsupported(printer_name))
+    }
+
+    fn get_paper_settings(&self, printer_name: &str) -> Result<PaperSettings, String> {
+        Err(unsupported(printer_name).message)
+    }
+
+    fn wait_for_job(
+        &self,
+        printer_name: &str,
+        _job_id: u32,
+        _timeout: Duration,
+    ) -> Result<(), String> {
+        Err(unsupported(printer_name).message)
+    }
+
+    fn pause_job(&self, printer_name: &str, _job_id: u32) -> Result<(), String> {
+        Err(unsupported(printer_name).message)
+    }
+
+    fn resume_job(&self, printer_name: &str, _job_id: u32) -> Result<(), String> {
+        Err(unsupported(printer_name).message)
+    }
+
+    fn is_job_paused(&self, printer_name: &str, _job_id: u32) -> Result<bool, String> {
+        Err(unsupported(printer_name).message)
+    }
+
+    fn set_job_priority(
+        &self,
+        printer_name: &str,
+        _job_id: u32,
+        _priority: u32,
+    ) -> Result<(), String> {
+        Err(unsupported(printer_name).message)
+    }
+
+    fn query_escpos_status(&self, printer_name: &str) -> Result<EscposStatus, PrintError> {
+        Err(unsupported(printer_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usb_not_supported() {
+        assert_eq!(parse_device("usb:04b8:0202"), Some((0x04B8, 0x0202)));
+        assert_eq!(parse_device("usb:04b8"), None);
+        assert_eq!(parse_device("tcp://10.0.0.5"), None);
+
+        let manager = UsbPrinterManager::new();
+        let error = manager
+            .print_raw("usb:04B8:0202", b"x", PrintDatatype::Raw, false)
+            .unwrap_err();
+        assert_eq!(error.code, USB_NOT_SUPPORTED);
+        assert!(error.message.contains("VID 04B8, PID 0202"));
+        let error = manager.print_text("usb:printer", "x").unwrap_err();
+        assert_eq!(error.code, PRINTER_NOT_FOUND);
+        assert!(manager.list_printers().unwrap().is_empty());
+    }
+}
@@ -1,29 +1,94 @@
 //! Windows 打印机 API 封装
 
-use std::ffi::OsString;
-use std::os::windows::ffi::OsStringExt;
-use tracing::{debug, error, info};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, error, info, warn};
 use windows::{
-    core::{HSTRING, PCWSTR, PWSTR},
+    core::{HRESULT, HSTRING, PCWSTR, PWSTR},
     Win32::{
-        Foundation::HANDLE,
-        Graphics::Printing::{
-            ClosePrinter, EndDocPrinter, EndPagePrinter, EnumPrintersW, GetDefaultPrinterW,
-            OpenPrinterW, StartDocPrinterW, StartPagePrinter, WritePrinter, DOC_INFO_1W,
-            PRINTER_ENUM_CONNECTIONS, PRINTER_ENUM_LOCAL, PRINTER_INFO_2W,
+        Foundation::{
+            GetLastError, ERROR_ACCESS_DENIED, ERROR_INSUFFICIENT_BUFFER, ERROR_INVALID_DATATYPE,
+            ERROR_INVALID_PARAMETER, ERROR_INVALID_PRINTER_NAME, ERROR_NOT_ENOUGH_MEMORY,
+            ERROR_OUT_OF_PAPER, ERROR_PRINTER_DELETED, ERROR_PRINTER_NOT_FOUND,
+            ERROR_PRINT_CANCELLED, HANDLE, HWND, WIN32_ERROR,
+        },
+        Graphics::{
+            Gdi::{
+                DEVMODEW, DMCOLLATE_TRUE, DMORIENT_LANDSCAPE, DMORIENT_PORTRAIT, DM_COLLATE,
+                DM_COPIES, DM_DEFAULTSOURCE, DM_IN_BUFFER, DM_ORIENTATION, DM_OUT_BUFFER,
+            },
+            Printing::{
+                AbortPrinter, ClosePrinter, DocumentPropertiesW, EndDocPrinter, EndPagePrinter,
+                EnumPrintersW, GetDefaultPrinterW, GetJobW, OpenPrinterW, SetJobW,
+                StartDocPrinterW, StartPagePrinter, WritePrinter, DOC_INFO_1W, JOB_CONTROL_PAUSE,
+                JOB_CONTROL_RESUME, JOB_INFO_1W, JOB_STATUS_COMPLETE, JOB_STATUS_DELETED,
+                JOB_STATUS_DELETING, JOB_STATUS_ERROR, JOB_STATUS_PAUSED, JOB_STATUS_PRINTED,
+                MAX_PRIORITY, MIN_PRIORITY, PRINTER_ACCESS_USE, PRINTER_DEFAULTSW,
+                PRINTER_ENUM_CONNECTIONS, PRINTER_ENUM_LOCAL, PRINTER_INFO_2W,
+            },
         },
+        Storage::Xps::{DeviceCapabilitiesW, DC_BINNAMES, DC_BINS, DC_COLORDEVICE},
     },
 };
 
-use super::PrinterManager;
-use crate::protocol::PrinterInfo;
+use super::{sort_printers, PrinterManager};
+use crate::protocol::{
+    Orientation, PaperSettings, PrintDatatype, PrintError, PrinterInfo, TrayInfo, ACCESS_DENIED,
+    CANCELLED, HOLD_FAILED, PRINTER_NOT_FOUND, PRINT_FAILED, SPOOLER_UNAVAILABLE,
+};
+
+/// 枚举打印机的最大尝试次数（缓冲区不足时重试）
+const ENUM_PRINTERS_MAX_ATTEMPTS: u32 = 3;
+
+/// 等待任务完成时查询任务状态的间隔
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// DC_BINNAMES 返回的每个纸盒名称的长度（UTF-16 字符）
+const TRAY_NAME_LEN: usize = 24;
+
+/// 打印后台处理程序未运行时 OpenPrinter 等返回的 RPC_S_SERVER_UNAVAILABLE
+const RPC_S_SERVER_UNAVAILABLE: WIN32_ERROR = WIN32_ERROR(1722);
+
+/// 彩色能力查询失败后重新查询的间隔
+const COLOR_RETRY_INTERVAL: Duration = Duration::from_secs(300);
 
 /// Windows 打印机管理器
-pub struct WindowsPrinterManager;
+pub struct WindowsPrinterManager {
+    /// 按驱动缓存的彩色能力
+    colors: ColorCache,
+}
 
 impl WindowsPrinterManager {
     pub fn new() -> Self {
-        Self
+        Self {
+            colors: ColorCache::default(),
+        }
+    }
+}
+
+/// 按驱动名称缓存的彩色能力
+///
+/// 每次枚举打印机都会用到，而 `DeviceCapabilities` 查询较慢（网络和脱机打印机尤其明显）；
+/// 同一驱动的打印机能力相同，查询成功后不再重复查询，失败时间隔一段时间再重试
+#[derive(Default)]
+struct ColorCache {
+    drivers: Mutex<HashMap<String, (Option<bool>, Instant)>>,
+}
+
+impl ColorCache {
+    /// 获取驱动的彩色能力，未缓存或上次查询失败已超过重试间隔时调用 `query`
+    fn color(&self, driver: &str, query: impl FnOnce() -> Option<bool>) -> Option<bool> {
+        let mut drivers = self.drivers.lock().unwrap();
+        if let Some((color, checked)) = drivers.get(driver) {
+            if color.is_some() || checked.elapsed() < COLOR_RETRY_INTERVAL {
+                return *color;
+            }
+        }
+        let color = query();
+        drivers.insert(driver.to_string(), (color, Instant::now()));
+        color
     }
 }
 
@@ -35,71 +100,110 @@ impl Default for WindowsPrinterManager {
 
 impl PrinterManager for WindowsPrinterManager {
     fn list_printers(&self) -> Result<Vec<PrinterInfo>, String> {
-        list_windows_printers()
+        list_windows_printers(&self.colors)
     }
 
     fn get_default_printer(&self) -> Result<Option<String>, String> {
         get_default_printer_name()
     }
 
-    fn print_raw(&self, printer_name: &str, data: &[u8]) -> Result<(), String> {
-        print_raw_data(printer_name, data)
+    fn print_raw(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError> {
+        print_raw_data(printer_name, data, datatype, hold)
     }
 
-    fn print_text(&self, printer_name: &str, text: &str) -> Result<(), String> {
+    fn print_text(&self, printer_name: &str, text: &str) -> Result<(), PrintError> {
         // 文本转换为字节后打印
-        print_raw_data(printer_name, text.as_bytes())
+        print_raw_data(printer_name, text.as_bytes(), PrintDatatype::Raw, false).map(|_| ())
     }
-}
 
-/// 获取 Windows 打印机列表
-fn list_windows_printers() -> Result<Vec<PrinterInfo>, String> {
-    let default_printer = get_default_printer_name().unwrap_or(None);
+    fn print_document(
+        &self,
+        printer_name: &str,
+        data: &[u8],
+        copies: u32,
+        tray: Option<u16>,
+        orientation: Option<Orientation>,
+        datatype: PrintDatatype,
+        hold: bool,
+    ) -> Result<u32, PrintError> {
+        print_document_data(
+            printer_name,
+            data,
+            copies,
+            tray,
+            orientation,
+            datatype,
+            hold,
+        )
+    }
 
-    unsafe {
-        // 第一次调用获取需要的缓冲区大小
-        let mut bytes_needed: u32 = 0;
-        let mut count: u32 = 0;
-        let flags = PRINTER_ENUM_LOCAL | PRINTER_ENUM_CONNECTIONS;
+    fn get_paper_settings(&self, printer_name: &str) -> Result<PaperSettings, String> {
+        paper_settings(printer_name)
+    }
 
-        let _ = EnumPrintersW(
-            flags,
-            PCWSTR::null(),
-            2, // PRINTER_INFO_2
-            None,
-            &mut bytes_needed,
-            &mut count,
-        );
+    fn wait_for_job(
+        &self,
+        printer_name: &str,
+        job_id: u32,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        wait_for_job(printer_name, job_id, timeout)
+    }
 
-        if bytes_needed == 0 {
-            debug!("No printers found");
-            return Ok(vec![]);
+    fn pause_job(&self, printer_name: &str, job_id: u32) -> Result<(), String> {
+        control_job(printer_name, job_id, JOB_CONTROL_PAUSE)
+    }
+
+    fn resume_job(&self, printer_name: &str, job_id: u32) -> Result<(), String> {
+        control_job(printer_name, job_id, JOB_CONTROL_RESUME)
+    }
+
+    fn is_job_paused(&self, printer_name: &str, job_id: u32) -> Result<bool, String> {
+        unsafe {
+            let handle = open_printer(printer_name).map_err(|e| e.message)?;
+            let status = job_status(handle, job_id);
+            let _ = ClosePrinter(handle);
+            Ok(status
+                .map_err(|e| e.message)?
+                .is_some_and(|status| status & JOB_STATUS_PAUSED != 0))
         }
+    }
 
-        // 分配缓冲区
-        let mut buffer: Vec<u8> = vec![0u8; bytes_needed as usize];
+    fn set_job_priority(
+        &self,
+        printer_name: &str,
+        job_id: u32,
+        priority: u32,
+    ) -> Result<(), String> {
+        set_job_priority(printer_name, job_id, priority)
+    }
+}
 
-        let result = EnumPrintersW(
-            flags,
-            PCWSTR::null(),
-            2,
-            Some(&mut buffer),
-            &mut bytes_needed,
-            &mut count,
-        );
+/// 获取 Windows 打印机列表（彩色能力按驱动缓存）
+fn list_windows_printers(colors: &ColorCache) -> Result<Vec<PrinterInfo>, String> {
+    let default_printer = get_default_printer_name().unwrap_or(None);
 
-        if result.is_err() {
-            error!("EnumPrintersW failed");
-            return Err("Failed to enumerate printers".to_string());
+    unsafe {
+        let (buffer, count) = enum_printers_info2()?;
+        if count == 0 {
+            debug!("No printers found");
+            return Ok(vec![]);
         }
 
         let printer_info_ptr = buffer.as_ptr() as *const PRINTER_INFO_2W;
         let printer_infos = std::slice::from_raw_parts(printer_info_ptr, count as usize);
+        let bytes = as_bytes(&buffer);
 
-        let printers: Vec<PrinterInfo> = printer_infos
+        let mut printers: Vec<PrinterInfo> = printer_infos
             .iter()
             .filter_map(|info| {
-                let name = pwstr_to_string(info.pPrinterName);
+                let name = pwstr_to_string(info.pPrinterName, bytes);
                 if name.is_empty() {
                     return None;
                 }
@@ -109,19 +213,88 @@ fn list_windows_printers() -> Result<Vec<PrinterInfo>, String> {
                 // 判断状态
                 let status = if info.Status == 0 { "ready" } else { "busy" };
 
+                let port = non_empty(pwstr_to_string(info.pPortName, bytes));
+                let server = non_empty(pwstr_to_string(info.pServerName, bytes));
+                let driver = non_empty(pwstr_to_string(info.pDriverName, bytes));
+                let query_color = || is_color_device(info.pPrinterName, info.pPortName);
+                let color = match &driver {
+                    Some(driver) => colors.color(driver, query_color),
+                    None => query_color(),
+                };
+
                 Some(PrinterInfo {
+                    id: printer_id(&name, port.as_deref(), server.as_deref()),
                     name,
                     is_default,
                     status: status.to_string(),
+                    driver,
+                    port,
+                    color,
                 })
             })
             .collect();
+        // 枚举顺序不稳定，排序后界面刷新时不会变动
+        sort_printers(&mut printers);
 
         info!("Found {} printers", printers.len());
         Ok(printers)
     }
 }
 
+/// 枚举打印机（PRINTER_INFO_2），返回对齐的缓冲区及打印机数量
+///
+/// 两次调用之间新增打印机会导致缓冲区不足，此时按新的大小重新分配并重试
+unsafe fn enum_printers_info2() -> Result<(Vec<u64>, u32), String> {
+    let flags = PRINTER_ENUM_LOCAL | PRINTER_ENUM_CONNECTIONS;
+    let mut bytes_needed: u32 = 0;
+    let mut count: u32 = 0;
+
+    // 第一次调用获取需要的缓冲区大小
+    let _ = EnumPrintersW(
+        flags,
+        PCWSTR::null(),
+        2, // PRINTER_INFO_2
+        None,
+        &mut bytes_needed,
+        &mut count,
+    );
+
+    let mut attempt = 1;
+    loop {
+        if bytes_needed == 0 {
+            return Ok((Vec::new(), 0));
+        }
+
+        let mut buffer = aligned_buffer(bytes_needed);
+        let result = EnumPrintersW(
+            flags,
+            PCWSTR::null(),
+            2,
+            Some(as_bytes_mut(&mut buffer)),
+            &mut bytes_needed,
+            &mut count,
+        );
+
+        match result {
+            Ok(()) => return Ok((buffer, count)),
+            Err(e)
+                if e.code() == ERROR_INSUFFICIENT_BUFFER.to_hresult()
+                    && attempt < ENUM_PRINTERS_MAX_ATTEMPTS =>
+            {
+                warn!(
+                    "EnumPrintersW buffer too small (attempt {}), retrying with {} bytes",
+                    attempt, bytes_needed
+                );
+                attempt += 1;
+            }
+            Err(e) => {
+                error!("EnumPrintersW failed: {}", e);
+                return Err(format!("Failed to enumerate printers: {}", e));
+            }
+        }
+    }
+}
+
 /// 获取默认打印机名称
 fn get_default_printer_name() -> Result<Option<String>, String> {
     unsafe {
@@ -140,94 +313,822 @@ fn get_default_printer_name() -> Result<Option<String>, String> {
             return Ok(None);
         }
 
-        // 找到 null 终止符
-        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
-        let name = OsString::from_wide(&buffer[..len])
-            .to_string_lossy()
-            .to_string();
+        let name = wide_to_string(&buffer);
 
         Ok(Some(name))
     }
 }
 
-/// 打印原始数据 (RAW)
-fn print_raw_data(printer_name: &str, data: &[u8]) -> Result<(), String> {
-    info!("Printing {} bytes to '{}'", data.len(), printer_name);
+/// 以指定的数据类型打印原始数据，返回任务 ID
+fn print_raw_data(
+    printer_name: &str,
+    data: &[u8],
+    datatype: PrintDatatype,
+    hold: bool,
+) -> Result<u32, PrintError> {
+    info!(
+        "Printing {} bytes to '{}' ({})",
+        data.len(),
+        printer_name,
+        datatype.as_str()
+    );
 
     unsafe {
-        let printer_name_wide = HSTRING::from(printer_name);
-        let mut handle: HANDLE = HANDLE::default();
-
-        // 打开打印机
-        let result = OpenPrinterW(
-            PCWSTR(printer_name_wide.as_ptr()),
-            &mut handle,
-            None,
-        );
+        let handle = open_printer(printer_name)?;
+        write_document(handle, printer_name, data, datatype, hold)
+    }
+}
+
+/// 打印文档，份数通过 DEVMODE.dmCopies 交给驱动处理（支持逐份打印），
+/// 指定纸盒时设置 DEVMODE.dmDefaultSource，返回任务 ID
+///
+/// 份数为 0 时不提交任务，返回的任务 ID 为 0
+fn print_document_data(
+    printer_name: &str,
+    data: &[u8],
+    copies: u32,
+    tray: Option<u16>,
+    orientation: Option<Orientation>,
+    datatype: PrintDatatype,
+    hold: bool,
+) -> Result<u32, PrintError> {
+    if copies == 0 {
+        return Ok(0);
+    }
+    let copies = i16::try_from(copies)
+        .map_err(|_| PrintError::from(format!("Too many copies: {}", copies)))?;
+
+    info!(
+        "Printing {} bytes to '{}', copies={}, tray={:?}, orientation={:?}",
+        data.len(),
+        printer_name,
+        copies,
+        tray,
+        orientation
+    );
+
+    unsafe {
+        let mut devmode = document_devmode(printer_name, copies, tray, orientation)?;
+        let defaults = PRINTER_DEFAULTSW {
+            pDatatype: PWSTR::null(),
+            pDevMode: devmode.as_mut_ptr() as *mut DEVMODEW,
+            DesiredAccess: PRINTER_ACCESS_USE,
+        };
+        let handle = open_printer_with_defaults(printer_name, Some(&defaults))?;
+        write_document(handle, printer_name, data, datatype, hold)
+    }
+}
+
+/// 获取打印机的 DEVMODE 并设置份数、纸盒和纸张方向，返回包含驱动私有数据的完整缓冲区
+unsafe fn document_devmode(
+    printer_name: &str,
+    copies: i16,
+    tray: Option<u16>,
+    orientation: Option<Orientation>,
+) -> Result<Vec<u64>, PrintError> {
+    let handle = open_printer(printer_name)?;
+    let mut buffer = match default_devmode(handle, printer_name) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            let _ = ClosePrinter(handle);
+            return Err(e);
+        }
+    };
+
+    let devmode = buffer.as_mut_ptr() as *mut DEVMODEW;
+    (*devmode).Anonymous1.Anonymous1.dmCopies = copies;
+    (*devmode).dmCollate = DMCOLLATE_TRUE;
+    (*devmode).dmFields |= DM_COPIES | DM_COLLATE;
+    if let Some(tray) = tray {
+        (*devmode).Anonymous1.Anonymous1.dmDefaultSource = tray as i16;
+        (*devmode).dmFields |= DM_DEFAULTSOURCE;
+    }
+    if let Some(orientation) = orientation {
+        let value = match orientation {
+            Orientation::Portrait => DMORIENT_PORTRAIT,
+            Orientation::Landscape => DMORIENT_LANDSCAPE,
+        };
+        (*devmode).Anonymous1.Anonymous1.dmOrientation = value as i16;
+        (*devmode).dmFields |= DM_ORIENTATION;
+    }
+
+    // 交给驱动校验合并（不支持的字段由驱动调整）
+    let device_name_wide = HSTRING::from(printer_name);
+    let result = DocumentPropertiesW(
+        HWND::default(),
+        handle,
+        PCWSTR(device_name_wide.as_ptr()),
+        Some(devmode),
+        Some(devmode as *const DEVMODEW),
+        (DM_IN_BUFFER | DM_OUT_BUFFER).0,
+    );
+    if result < 0 {
+        let e = last_error(format!(
+            "Failed to set document properties on '{}'",
+            printer_name
+        ));
+        let _ = ClosePrinter(handle);
+        return Err(e);
+    }
+    let _ = ClosePrinter(handle);
+
+    Ok(buffer)
+}
+
+/// 在已打开的打印机上获取驱动的默认 DEVMODE（不关闭句柄），返回包含驱动私有数据的完整缓冲区（按 DEVMODEW 对齐）
+unsafe fn default_devmode(handle: HANDLE, printer_name: &str) -> Result<Vec<u64>, PrintError> {
+    let device_name_wide = HSTRING::from(printer_name);
+    let device_name = PCWSTR(device_name_wide.as_ptr());
+
+    // fMode 为 0 时返回所需的缓冲区大小
+    let size = DocumentPropertiesW(HWND::default(), handle, device_name, None, None, 0);
+    if size <= 0 {
+        return Err(last_error(format!(
+            "Failed to get document properties of '{}'",
+            printer_name
+        )));
+    }
+
+    let mut buffer = aligned_buffer(size as u32);
+    let result = DocumentPropertiesW(
+        HWND::default(),
+        handle,
+        device_name,
+        Some(buffer.as_mut_ptr() as *mut DEVMODEW),
+        None,
+        DM_OUT_BUFFER.0,
+    );
+    if result < 0 {
+        return Err(last_error(format!(
+            "Failed to get document properties of '{}'",
+            printer_name
+        )));
+    }
+
+    Ok(buffer)
+}
+
+/// 读取打印机默认的纸张和纸盒设置，以及可用纸盒列表
+fn paper_settings(printer_name: &str) -> Result<PaperSettings, String> {
+    unsafe {
+        let handle = open_printer(printer_name).map_err(|e| e.message)?;
+        let buffer = default_devmode(handle, printer_name);
+        let _ = ClosePrinter(handle);
+        let buffer = buffer.map_err(|e| e.message)?;
+
+        let devmode = &*(buffer.as_ptr() as *const DEVMODEW);
+        let fields = devmode.Anonymous1.Anonymous1;
+        Ok(PaperSettings {
+            paper_size: fields.dmPaperSize,
+            form_name: wide_to_string(&devmode.dmFormName),
+            tray: fields.dmDefaultSource as u16,
+            trays: printer_trays(printer_name),
+        })
+    }
+}
+
+/// 查询打印机的可用纸盒（查询失败时返回空列表）
+unsafe fn printer_trays(printer_name: &str) -> Vec<TrayInfo> {
+    let device_name_wide = HSTRING::from(printer_name);
+    let device_name = PCWSTR(device_name_wide.as_ptr());
+
+    // 输出缓冲区为空时返回纸盒数量
+    let count = DeviceCapabilitiesW(device_name, PCWSTR::null(), DC_BINS, PWSTR::null(), None);
+    if count <= 0 {
+        return Vec::new();
+    }
+
+    let mut ids = vec![0u16; count as usize];
+    let result = DeviceCapabilitiesW(
+        device_name,
+        PCWSTR::null(),
+        DC_BINS,
+        PWSTR(ids.as_mut_ptr()),
+        None,
+    );
+    if result <= 0 {
+        warn!("Failed to query trays of '{}'", printer_name);
+        return Vec::new();
+    }
+
+    let mut names = vec![0u16; ids.len() * TRAY_NAME_LEN];
+    let result = DeviceCapabilitiesW(
+        device_name,
+        PCWSTR::null(),
+        DC_BINNAMES,
+        PWSTR(names.as_mut_ptr()),
+        None,
+    );
+    if result <= 0 {
+        names.clear();
+    }
+
+    tray_list(&ids, &names)
+}
+
+/// 将 DC_BINS 的纸盒编号与 DC_BINNAMES 的定长名称对应起来（缺少名称时为空字符串）
+fn tray_list(ids: &[u16], names: &[u16]) -> Vec<TrayInfo> {
+    let mut names = names.chunks(TRAY_NAME_LEN).map(wide_to_string);
+    ids.iter()
+        .map(|&id| TrayInfo {
+            id,
+            name: names.next().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// 在已打开的打印机上以指定的数据类型写入一个文档，完成后关闭打印机句柄，返回任务 ID
+///
+/// `hold` 时在写入数据前暂停任务，无法暂停时取消任务，避免未经确认就打印
+unsafe fn write_document(
+    handle: HANDLE,
+    printer_name: &str,
+    data: &[u8],
+    datatype: PrintDatatype,
+    hold: bool,
+) -> Result<u32, PrintError> {
+    // 设置文档信息
+    let doc_name = HSTRING::from("rprint document");
+    let data_type = HSTRING::from(datatype.as_str());
+
+    let doc_info = DOC_INFO_1W {
+        pDocName: PWSTR(doc_name.as_ptr() as *mut u16),
+        pOutputFile: PWSTR::null(),
+        pDatatype: PWSTR(data_type.as_ptr() as *mut u16),
+    };
+
+    // 开始文档
+    let job_id = StartDocPrinterW(handle, 1, &doc_info as *const DOC_INFO_1W);
+    // 错误码需在 ClosePrinter 等清理调用之前读取
+    if job_id == 0 {
+        let e = last_error(format!("Failed to start document on '{}'", printer_name));
+        let _ = ClosePrinter(handle);
+        return Err(e);
+    }
+
+    // 暂停任务（数据尚未写入，打印后台不会开始打印）
+    if hold && !SetJobW(handle, job_id, 0, None, JOB_CONTROL_PAUSE).as_bool() {
+        let e = last_error(format!(
+            "Failed to hold job {} on '{}'",
+            job_id, printer_name
+        ));
+        let _ = AbortPrinter(handle);
+        let _ = ClosePrinter(handle);
+        return Err(PrintError::new(HOLD_FAILED, e.message));
+    }
+
+    // 开始页面
+    if !StartPagePrinter(handle).as_bool() {
+        let e = last_error(format!("Failed to start page on '{}'", printer_name));
+        let _ = EndDocPrinter(handle);
+        let _ = ClosePrinter(handle);
+        return Err(e);
+    }
+
+    // 写入数据
+    let mut bytes_written: u32 = 0;
+    let write_result = WritePrinter(
+        handle,
+        data.as_ptr() as *const _,
+        data.len() as u32,
+        &mut bytes_written,
+    );
+
+    if !write_result.as_bool() {
+        let e = last_error(format!("Failed to write to printer '{}'", printer_name));
+        let _ = EndPagePrinter(handle);
+        let _ = EndDocPrinter(handle);
+        let _ = ClosePrinter(handle);
+        return Err(e);
+    }
+
+    // 结束页面和文档
+    let _ = EndPagePrinter(handle);
+    let _ = EndDocPrinter(handle);
+    let _ = ClosePrinter(handle);
+
+    info!(
+        "Successfully printed {} bytes, job={}",
+        bytes_written, job_id
+    );
+    Ok(job_id)
+}
+
+/// 打印任务在队列中的状态
+#[derive(Debug, PartialEq)]
+enum JobState {
+    /// 等待或正在打印
+    Pending,
+    /// 打印机报告错误（如缺纸、脱机），通常会自行恢复，继续等待直到超时
+    Error,
+    /// 已打印
+    Completed,
+    /// 未打印就被删除
+    Failed(&'static str),
+}
+
+/// 根据 JOB_INFO_1.Status 判断任务状态
+///
+/// 先检查是否已打印：打印完成的任务离开队列时也会带有 `JOB_STATUS_DELETING`
+fn job_state(status: u32) -> JobState {
+    if status & (JOB_STATUS_PRINTED | JOB_STATUS_COMPLETE) != 0 {
+        JobState::Completed
+    } else if status & (JOB_STATUS_DELETED | JOB_STATUS_DELETING) != 0 {
+        JobState::Failed("deleted")
+    } else if status & JOB_STATUS_ERROR != 0 {
+        JobState::Error
+    } else {
+        JobState::Pending
+    }
+}
 
-        if result.is_err() || handle.is_invalid() {
-            return Err(format!("Failed to open printer: {}", printer_name));
+/// 分配按 8 字节对齐的缓冲区（`JOB_INFO_1W` 等结构体直接在缓冲区上读写，`Vec<u8>` 不保证对齐）
+fn aligned_buffer(bytes: u32) -> Vec<u64> {
+    vec![0u64; (bytes as usize).div_ceil(std::mem::size_of::<u64>())]
+}
+
+/// 以只读字节切片访问对齐的缓冲区
+fn as_bytes(buffer: &[u64]) -> &[u8] {
+    let len = std::mem::size_of_val(buffer);
+    // SAFETY: u64 切片的内存可以按字节访问，长度与生命周期不变
+    unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, len) }
+}
+
+/// 以字节切片访问对齐的缓冲区
+fn as_bytes_mut(buffer: &mut [u64]) -> &mut [u8] {
+    let len = std::mem::size_of_val(buffer);
+    // SAFETY: u64 切片的内存可以按字节访问，长度与生命周期不变
+    unsafe { std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, len) }
+}
+
+/// 查询任务的 JOB_INFO_1.Status，任务已不在队列中（`ERROR_INVALID_PARAMETER`）时返回 None，
+/// 其他查询失败（如打印后台服务异常）返回错误，不能当作任务已完成
+unsafe fn job_status(handle: HANDLE, job_id: u32) -> Result<Option<u32>, PrintError> {
+    let mut bytes_needed: u32 = 0;
+    if !GetJobW(handle, job_id, 1, None, &mut bytes_needed).as_bool() {
+        match GetLastError() {
+            ERROR_INVALID_PARAMETER => return Ok(None),
+            ERROR_INSUFFICIENT_BUFFER if bytes_needed > 0 => {}
+            error => {
+                return Err(win32_error(
+                    error,
+                    format!("Failed to query job {}", job_id),
+                ))
+            }
         }
+    }
+
+    let mut buffer = aligned_buffer(bytes_needed);
+    if !GetJobW(
+        handle,
+        job_id,
+        1,
+        Some(as_bytes_mut(&mut buffer)),
+        &mut bytes_needed,
+    )
+    .as_bool()
+    {
+        return match GetLastError() {
+            ERROR_INVALID_PARAMETER => Ok(None),
+            error => Err(win32_error(
+                error,
+                format!("Failed to query job {}", job_id),
+            )),
+        };
+    }
+    Ok(Some((*(buffer.as_ptr() as *const JOB_INFO_1W)).Status))
+}
 
-        // 设置文档信息
-        let doc_name = HSTRING::from("rprint document");
-        let data_type = HSTRING::from("RAW");
+/// 轮询任务状态，直到任务离开打印队列（视为已打印）、被删除、查询失败或超时
+///
+/// 打印机报告错误时继续等待，超时后在错误信息中注明
+fn wait_for_job(printer_name: &str, job_id: u32, timeout: Duration) -> Result<(), String> {
+    debug!(
+        "Waiting for job {} on '{}' (timeout {:?})",
+        job_id, printer_name, timeout
+    );
+    let deadline = Instant::now() + timeout;
 
-        let doc_info = DOC_INFO_1W {
-            pDocName: PWSTR(doc_name.as_ptr() as *mut u16),
-            pOutputFile: PWSTR::null(),
-            pDatatype: PWSTR(data_type.as_ptr() as *mut u16),
+    unsafe {
+        let handle = open_printer(printer_name).map_err(|e| e.message)?;
+        let result = loop {
+            let state = match job_status(handle, job_id) {
+                Ok(status) => status.map_or(JobState::Completed, job_state),
+                Err(e) => break Err(e.message),
+            };
+            match state {
+                JobState::Completed => break Ok(()),
+                JobState::Failed(reason) => {
+                    break Err(format!(
+                        "Job {} on '{}' failed: {}",
+                        job_id, printer_name, reason
+                    ))
+                }
+                JobState::Pending if Instant::now() >= deadline => {
+                    break Err(format!(
+                        "Timed out waiting for job {} on '{}'",
+                        job_id, printer_name
+                    ))
+                }
+                JobState::Error if Instant::now() >= deadline => {
+                    break Err(format!(
+                        "Timed out waiting for job {} on '{}': printer reported an error",
+                        job_id, printer_name
+                    ))
+                }
+                JobState::Pending | JobState::Error => std::thread::sleep(JOB_POLL_INTERVAL),
+            }
         };
+        let _ = ClosePrinter(handle);
 
-        // 开始文档
-        let job_id = StartDocPrinterW(handle, 1, &doc_info as *const DOC_INFO_1W);
-        if job_id == 0 {
+        if result.is_ok() {
+            info!("Job {} on '{}' completed", job_id, printer_name);
+        }
+        result
+    }
+}
+
+/// 查询打印机是否支持彩色（查询失败时返回 None）
+unsafe fn is_color_device(name: PWSTR, port: PWSTR) -> Option<bool> {
+    match DeviceCapabilitiesW(
+        PCWSTR(name.0),
+        PCWSTR(port.0),
+        DC_COLORDEVICE,
+        PWSTR::null(),
+        None,
+    ) {
+        1 => Some(true),
+        0 => Some(false),
+        _ => None,
+    }
+}
+
+/// 打开打印机，返回打印机句柄
+unsafe fn open_printer(printer_name: &str) -> Result<HANDLE, PrintError> {
+    open_printer_with_defaults(printer_name, None)
+}
+
+/// 使用指定的默认设置（数据类型、DEVMODE）打开打印机
+unsafe fn open_printer_with_defaults(
+    printer_name: &str,
+    defaults: Option<&PRINTER_DEFAULTSW>,
+) -> Result<HANDLE, PrintError> {
+    let printer_name_wide = HSTRING::from(printer_name);
+    let mut handle: HANDLE = HANDLE::default();
+
+    let result = OpenPrinterW(
+        PCWSTR(printer_name_wide.as_ptr()),
+        &mut handle,
+        defaults.map(|d| d as *const PRINTER_DEFAULTSW),
+    );
+    if result.is_err() || handle.is_invalid() {
+        return Err(last_error(format!(
+            "Failed to open printer '{}'",
+            printer_name
+        )));
+    }
+
+    Ok(handle)
+}
+
+/// 读取 `GetLastError()` 构造失败的 Win32 调用的错误（需在失败后立即调用，清理句柄之前）
+unsafe fn last_error(action: String) -> PrintError {
+    win32_error(GetLastError(), action)
+}
+
+/// 将 Win32 错误码映射为带错误码的打印错误，消息包含失败的操作、可读说明和原始错误码
+fn win32_error(error: WIN32_ERROR, action: String) -> PrintError {
+    let (code, name, description) = match error {
+        ERROR_ACCESS_DENIED => (ACCESS_DENIED, "ERROR_ACCESS_DENIED", "access denied"),
+        ERROR_INVALID_PRINTER_NAME => (
+            PRINTER_NOT_FOUND,
+            "ERROR_INVALID_PRINTER_NAME",
+            "the printer name is invalid",
+        ),
+        ERROR_PRINTER_NOT_FOUND => (
+            PRINTER_NOT_FOUND,
+            "ERROR_PRINTER_NOT_FOUND",
+            "the printer driver is not installed",
+        ),
+        ERROR_PRINTER_DELETED => (
+            PRINTER_NOT_FOUND,
+            "ERROR_PRINTER_DELETED",
+            "the printer has been deleted",
+        ),
+        RPC_S_SERVER_UNAVAILABLE => (
+            SPOOLER_UNAVAILABLE,
+            "RPC_S_SERVER_UNAVAILABLE",
+            "the print spooler is not running",
+        ),
+        ERROR_PRINT_CANCELLED => (
+            CANCELLED,
+            "ERROR_PRINT_CANCELLED",
+            "the print job was cancelled",
+        ),
+        ERROR_OUT_OF_PAPER => (
+            PRINT_FAILED,
+            "ERROR_OUT_OF_PAPER",
+            "the printer is out of paper",
+        ),
+        ERROR_INVALID_DATATYPE => (
+            PRINT_FAILED,
+            "ERROR_INVALID_DATATYPE",
+            "the printer does not support the data type",
+        ),
+        ERROR_NOT_ENOUGH_MEMORY => (PRINT_FAILED, "ERROR_NOT_ENOUGH_MEMORY", "not enough memory"),
+        _ => {
+            // 其他错误使用系统提供的错误说明
+            let description = HRESULT::from_win32(error.0).message();
+            return PrintError::new(
+                PRINT_FAILED,
+                format!(
+                    "{}: {} (Win32 error {})",
+                    action,
+                    description.trim(),
+                    error.0
+                ),
+            );
+        }
+    };
+
+    PrintError::new(
+        code,
+        format!("{}: {} ({}, {})", action, description, name, error.0),
+    )
+}
+
+/// 控制打印任务（暂停、恢复）
+fn control_job(printer_name: &str, job_id: u32, command: u32) -> Result<(), String> {
+    info!(
+        "Job control: printer='{}', job={}, command={}",
+        printer_name, job_id, command
+    );
+
+    unsafe {
+        let handle = open_printer(printer_name).map_err(|e| e.message)?;
+        let result = SetJobW(handle, job_id, 0, None, command);
+        if !result.as_bool() {
+            let e = last_error(format!(
+                "Failed to control job {} on '{}'",
+                job_id, printer_name
+            ));
             let _ = ClosePrinter(handle);
-            return Err("Failed to start document".to_string());
+            return Err(e.message);
         }
+        let _ = ClosePrinter(handle);
+    }
+
+    Ok(())
+}
 
-        // 开始页面
-        if !StartPagePrinter(handle).as_bool() {
-            let _ = EndDocPrinter(handle);
+/// 设置打印任务优先级
+fn set_job_priority(printer_name: &str, job_id: u32, priority: u32) -> Result<(), String> {
+    if !(MIN_PRIORITY..=MAX_PRIORITY).contains(&priority) {
+        return Err(format!(
+            "Invalid job priority: {} (expected {}-{})",
+            priority, MIN_PRIORITY, MAX_PRIORITY
+        ));
+    }
+
+    info!(
+        "Setting job priority: printer='{}', job={}, priority={}",
+        printer_name, job_id, priority
+    );
+
+    unsafe {
+        let handle = open_printer(printer_name).map_err(|e| e.message)?;
+
+        // 读取当前的 JOB_INFO_1，仅修改优先级
+        let mut bytes_needed: u32 = 0;
+        let _ = GetJobW(handle, job_id, 1, None, &mut bytes_needed);
+        if bytes_needed == 0 {
             let _ = ClosePrinter(handle);
-            return Err("Failed to start page".to_string());
+            return Err(format!("Job {} not found on '{}'", job_id, printer_name));
         }
 
-        // 写入数据
-        let mut bytes_written: u32 = 0;
-        let write_result = WritePrinter(
+        let mut buffer = aligned_buffer(bytes_needed);
+        if !GetJobW(
             handle,
-            data.as_ptr() as *const _,
-            data.len() as u32,
-            &mut bytes_written,
-        );
-
-        if !write_result.as_bool() {
-            let _ = EndPagePrinter(handle);
-            let _ = EndDocPrinter(handle);
+            job_id,
+            1,
+            Some(as_bytes_mut(&mut buffer)),
+            &mut bytes_needed,
+        )
+        .as_bool()
+        {
+            let e = last_error(format!(
+                "Failed to get job {} on '{}'",
+                job_id, printer_name
+            ));
             let _ = ClosePrinter(handle);
-            return Err("Failed to write to printer".to_string());
+            return Err(e.message);
         }
 
-        // 结束页面和文档
-        let _ = EndPagePrinter(handle);
-        let _ = EndDocPrinter(handle);
-        let _ = ClosePrinter(handle);
+        let info = &mut *(buffer.as_mut_ptr() as *mut JOB_INFO_1W);
+        info.Priority = priority;
+        // JOB_POSITION_UNSPECIFIED：不改变任务在队列中的位置
+        info.Position = 0;
 
-        info!("Successfully printed {} bytes", bytes_written);
-        Ok(())
+        let result = SetJobW(handle, job_id, 1, Some(buffer.as_ptr() as *const u8), 0);
+        if !result.as_bool() {
+            let e = last_error(format!(
+                "Failed to set priority of job {} on '{}'",
+                job_id, printer_name
+            ));
+            let _ = ClosePrinter(handle);
+            return Err(e.message);
+        }
+        let _ = ClosePrinter(handle);
     }
+
+    Ok(())
 }
 
-/// 将 PWSTR 转换为 String
-fn pwstr_to_string(pwstr: PWSTR) -> String {
+/// 将指向 buffer 内部的 PWSTR 转换为 String
+///
+/// 查找终止符时不会越过 buffer 末尾，指针不在 buffer 内时返回空字符串
+unsafe fn pwstr_to_string(pwstr: PWSTR, buffer: &[u8]) -> String {
     if pwstr.is_null() {
         return String::new();
     }
 
-    unsafe {
-        let len = (0..).take_while(|&i| *pwstr.0.add(i) != 0).count();
-        let slice = std::slice::from_raw_parts(pwstr.0, len);
-        OsString::from_wide(slice).to_string_lossy().to_string()
+    let start = buffer.as_ptr() as usize;
+    let end = start + buffer.len();
+    let ptr = pwstr.0 as usize;
+    if ptr < start || ptr >= end {
+        return String::new();
+    }
+
+    let max_len = (end - ptr) / std::mem::size_of::<u16>();
+    wide_to_string(std::slice::from_raw_parts(pwstr.0, max_len))
+}
+
+/// 将 UTF-16 缓冲区转换为 String
+///
+/// 截止到第一个 0 或缓冲区末尾；正确处理代理对，孤立的代理项替换为 U+FFFD
+fn wide_to_string(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    char::decode_utf16(buf[..len].iter().copied())
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// 生成打印机唯一标识：`名称|端口|服务器`
+///
+/// 网络中可能存在多台同名的共享打印机，只按名称无法区分
+fn printer_id(name: &str, port: Option<&str>, server: Option<&str>) -> String {
+    format!(
+        "{}|{}|{}",
+        name,
+        port.unwrap_or_default(),
+        server.unwrap_or_default()
+    )
+}
+
+/// 空字符串转换为 None
+fn non_empty(s: String) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn test_color_cached_per_driver() {
+        let cache = ColorCache::default();
+        assert_eq!(cache.color("HP Color", || Some(true)), Some(true));
+        // 同一驱动不再查询
+        assert_eq!(cache.color("HP Color", || unreachable!()), Some(true));
+
+        // 查询失败后在重试间隔内不再查询
+        assert_eq!(cache.color("Offline", || None), None);
+        assert_eq!(cache.color("Offline", || unreachable!()), None);
+    }
+
+    #[test]
+    fn test_wide_to_string_surrogate_pair() {
+        let mut buf = wide("Label 🖨️ 打印机");
+        buf.push(0);
+        buf.extend(wide("garbage"));
+        assert_eq!(wide_to_string(&buf), "Label 🖨️ 打印机");
+
+        // 没有终止符时截止到缓冲区末尾
+        assert_eq!(wide_to_string(&wide("𠀀POS")), "𠀀POS");
+
+        // 孤立的代理项
+        assert_eq!(wide_to_string(&[0xD83D, 0x0041, 0]), "\u{FFFD}A");
+    }
+
+    #[test]
+    fn test_pwstr_to_string_bounds() {
+        let mut chars = wide("🖨 Printer");
+        chars.push(0);
+        let len = std::mem::size_of_val(chars.as_slice());
+        // 与实际的 Win32 缓冲区一样使用对齐的缓冲区，保证 PWSTR 按 u16 对齐
+        let mut storage = aligned_buffer(len as u32);
+        for (bytes, c) in as_bytes_mut(&mut storage).chunks_exact_mut(2).zip(&chars) {
+            bytes.copy_from_slice(&c.to_ne_bytes());
+        }
+        let buffer = &as_bytes(&storage)[..len];
+
+        unsafe {
+            let ptr = PWSTR(buffer.as_ptr() as *mut u16);
+            assert_eq!(pwstr_to_string(ptr, buffer), "🖨 Printer");
+
+            // 缓冲区内没有终止符时不越界
+            let truncated = &buffer[..buffer.len() - 2];
+            assert_eq!(pwstr_to_string(ptr, truncated), "🖨 Printer");
+
+            // 指针不在缓冲区内
+            assert_eq!(pwstr_to_string(ptr, &buffer[4..]), "");
+            assert_eq!(pwstr_to_string(PWSTR::null(), buffer), "");
+        }
+    }
+
+    #[test]
+    fn test_job_state() {
+        assert_eq!(job_state(0), JobState::Pending);
+        assert_eq!(job_state(JOB_STATUS_PRINTED), JobState::Completed);
+        assert_eq!(job_state(JOB_STATUS_ERROR), JobState::Error);
+        assert_eq!(
+            job_state(JOB_STATUS_PRINTED | JOB_STATUS_DELETING),
+            JobState::Completed
+        );
+        assert_eq!(
+            job_state(JOB_STATUS_COMPLETE | JOB_STATUS_ERROR),
+            JobState::Completed
+        );
+        assert_eq!(job_state(JOB_STATUS_DELETING), JobState::Failed("deleted"));
+    }
+
+    #[test]
+    fn test_win32_error() {
+        let e = win32_error(
+            ERROR_ACCESS_DENIED,
+            "Failed to open printer 'Office'".to_string(),
+        );
+        assert_eq!(e.code, ACCESS_DENIED);
+        assert_eq!(
+            e.message,
+            "Failed to open printer 'Office': access denied (ERROR_ACCESS_DENIED, 5)"
+        );
+
+        let e = win32_error(ERROR_INVALID_PRINTER_NAME, "open".to_string());
+        assert_eq!(e.code, PRINTER_NOT_FOUND);
+        assert!(e.message.contains("ERROR_INVALID_PRINTER_NAME, 1801"));
+
+        assert_eq!(
+            win32_error(RPC_S_SERVER_UNAVAILABLE, "open".to_string()).code,
+            SPOOLER_UNAVAILABLE
+        );
+
+        // 未映射的错误使用通用错误码，保留原始错误码
+        let e = win32_error(WIN32_ERROR(87), "write".to_string());
+        assert_eq!(e.code, PRINT_FAILED);
+        assert!(e.message.ends_with("(Win32 error 87)"));
+    }
+
+    #[test]
+    fn test_tray_list() {
+        let mut names = vec![0u16; TRAY_NAME_LEN * 2];
+        for (i, name) in ["Tray 1", "信笺纸盒"].iter().enumerate() {
+            let name = wide(name);
+            names[i * TRAY_NAME_LEN..i * TRAY_NAME_LEN + name.len()].copy_from_slice(&name);
+        }
+
+        assert_eq!(
+            tray_list(&[7, 258], &names),
+            vec![
+                TrayInfo {
+                    id: 7,
+                    name: "Tray 1".to_string()
+                },
+                TrayInfo {
+                    id: 258,
+                    name: "信笺纸盒".to_string()
+                },
+            ]
+        );
+
+        // 没有名称时为空字符串
+        assert_eq!(tray_list(&[1], &[])[0].name, "");
+    }
+
+    #[test]
+    fn test_printer_id_distinguishes_servers() {
+        let a = printer_id("Shared", Some("IP_10.0.0.1"), Some("\\\\SRV1"));
+        let b = printer_id("Shared", Some("IP_10.0.0.1"), Some("\\\\SRV2"));
+        assert_ne!(a, b);
+        assert_eq!(a, "Shared|IP_10.0.0.1|\\\\SRV1");
+        assert_eq!(printer_id("Local", None, None), "Local||");
     }
 }
@@ -1,5 +1,7 @@
 //! WebSocket 协议消息定义
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 /// 客户端发送的消息类型
@@ -7,11 +9,75 @@ use serde::{Deserialize, Serialize};
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
     /// 打印请求
-    Print(PrintRequest),
+    Print(Box<PrintRequest>),
+    /// 分块打印请求（data 为打印请求 JSON 的片段，seq 从 0 开始，全部到齐后执行）
+    PrintChunk {
+        id: String,
+        seq: u32,
+        total: u32,
+        data: String,
+    },
+    /// 暂停打印队列中的任务（printer 为空则使用默认打印机）
+    PauseJob {
+        #[serde(default)]
+        printer: Option<String>,
+        job_id: u32,
+    },
+    /// 恢复已暂停的任务
+    ResumeJob {
+        #[serde(default)]
+        printer: Option<String>,
+        job_id: u32,
+    },
+    /// 释放 `options.hold` 暂停的任务（printer 为空时使用提交任务的打印机）
+    ReleaseJob {
+        #[serde(default)]
+        printer: Option<String>,
+        job_id: u32,
+    },
+    /// 设置任务优先级 (1-99)
+    SetJobPriority {
+        #[serde(default)]
+        printer: Option<String>,
+        job_id: u32,
+        priority: u32,
+    },
+    /// 设置当前连接的默认打印机（为空则清除，断开连接后失效）
+    SetDefaultPrinter {
+        #[serde(default)]
+        printer: Option<String>,
+    },
+    /// 重新发送打印机最近一次打印的数据（printer 为空则使用默认打印机）
+    Reprint {
+        #[serde(default)]
+        printer: Option<String>,
+    },
+    /// 预览渲染结果（不打印）
+    Preview(Box<PrintRequest>),
     /// 获取打印机列表
     GetPrinters,
+    /// 检查打印机是否存在（name 可以是打印机名称或唯一标识）
+    PrinterExists { name: String },
     /// 获取服务状态
     GetStatus,
+    /// 获取监控用的详细状态（运行时间、任务统计、打印机）
+    GetDetailedStatus,
+    /// 获取最近失败的打印任务
+    GetFailedJobs,
+    /// 取消尚未执行的定时打印任务
+    CancelScheduled { id: String },
+    /// 查询 ESC/POS 打印机的实时状态（printer 为空则使用默认打印机）
+    GetEscposStatus {
+        #[serde(default)]
+        printer: Option<String>,
+    },
+    /// 查询最近打印请求的结果（断线重连后使用，结果保留 `server.result_retention_secs` 秒）
+    GetResult { id: String },
+    /// 只接收指定类型的广播消息（如 `print_result`，为空则恢复接收全部），断开连接后失效
+    Subscribe {
+        #[serde(default)]
+        events: Vec<String>,
+    },
     /// 心跳
     Ping,
 }
@@ -22,14 +88,45 @@ pub enum ClientMessage {
 pub enum ServerMessage {
     /// 打印结果
     PrintResult(PrintResult),
+    /// 分块已接收（尚未到齐）
+    ChunkAck {
+        id: String,
+        received: usize,
+        total: u32,
+    },
+    /// 任务控制操作已完成
+    JobUpdated { job_id: u32 },
+    /// 当前连接的默认打印机
+    DefaultPrinter { printer: Option<String> },
+    /// 补打已完成
+    Reprinted { printer: String },
+    /// 预览结果
+    Preview(PreviewResponse),
     /// 打印机列表
     Printers(PrintersResponse),
+    /// 打印机是否存在
+    PrinterExists { name: String, exists: bool },
     /// 服务状态
     Status(StatusResponse),
+    /// 详细状态
+    DetailedStatus(DetailedStatusResponse),
+    /// 失败任务列表
+    FailedJobs(FailedJobsResponse),
+    /// 定时打印任务已取消
+    ScheduledCancelled { id: String },
+    /// ESC/POS 打印机的实时状态
+    EscposStatus {
+        printer: String,
+        status: EscposStatus,
+    },
+    /// 当前连接订阅的广播消息类型（为空表示全部）
+    Subscribed { events: Vec<String> },
     /// 心跳响应
     Pong,
     /// 错误
     Error(ErrorResponse),
+    /// 服务即将停止，客户端应在 retry_after_ms 毫秒后再重连
+    Shutdown { retry_after_ms: u64 },
 }
 
 /// 打印请求
@@ -40,19 +137,81 @@ pub struct PrintRequest {
     /// 模板类型: pdf, escpos, zpl
     pub template_type: String,
     /// 模板内容 (HTML/ESC-POS/ZPL)
+    #[serde(default)]
     pub template: String,
+    /// 模板名称 (可选，从模板目录加载，优先于 template)
+    #[serde(default)]
+    pub template_name: Option<String>,
     /// 模板数据 (变量替换)
     pub data: serde_json::Value,
-    /// 目标打印机名称 (可选，为空则使用默认)
+    /// 模板数据的 JSON Schema (可选，渲染前校验 data)
+    #[serde(default)]
+    pub schema: Option<serde_json::Value>,
+    /// 目标打印机 (可选，名称或选择条件，为空则使用默认)
     #[serde(default)]
-    pub printer: Option<String>,
+    pub printer: Option<PrinterSelector>,
     /// 打印选项
     #[serde(default)]
     pub options: PrintOptions,
+    /// 定时打印时间（Unix 毫秒，为空或已过去则立即打印）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_at: Option<u64>,
+    /// 附加文档（如厨房单后接顾客小票，依次打印在主文档之后，`options.single_job` 控制是否合并为一个任务）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<PrintDocument>,
 }
 
-/// 打印选项
+/// 附加文档（使用主请求的模板类型、打印机和打印选项）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintDocument {
+    /// 模板内容
+    #[serde(default)]
+    pub template: String,
+    /// 模板名称 (可选，从模板目录加载，优先于 template)
+    #[serde(default)]
+    pub template_name: Option<String>,
+    /// 模板数据
+    #[serde(default)]
+    pub data: serde_json::Value,
+    /// 模板数据的 JSON Schema (可选)
+    #[serde(default)]
+    pub schema: Option<serde_json::Value>,
+}
+
+/// 打印机选择器
+///
+/// 可以是打印机名称字符串，也可以是选择条件对象，例如 `{"index": 0}`、`{"type": "zpl"}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PrinterSelector {
+    /// 打印机名称
+    Name(String),
+    /// 选择条件
+    Query(PrinterQuery),
+}
+
+/// 打印机选择条件（所有条件同时满足，取第一台匹配的打印机）
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PrinterQuery {
+    /// 打印机名称
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 打印机唯一标识（`list_printers` 返回的 `id`）
+    #[serde(default)]
+    pub id: Option<String>,
+    /// 在打印机列表中的索引
+    #[serde(default)]
+    pub index: Option<usize>,
+    /// 打印机类型: zpl, epl, escpos, normal
+    #[serde(default, rename = "type")]
+    pub printer_type: Option<String>,
+    /// 是否支持彩色
+    #[serde(default)]
+    pub color: Option<bool>,
+}
+
+/// 打印选项
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrintOptions {
     /// 打印份数
     #[serde(default = "default_copies")]
@@ -60,6 +219,116 @@ pub struct PrintOptions {
     /// 纸张大小
     #[serde(default)]
     pub paper_size: Option<String>,
+    /// 是否归档本次打印内容（为空则使用全局配置）
+    #[serde(default)]
+    pub archive: Option<bool>,
+    /// 换行符: lf, crlf, cr（仅文本任务，ESC/POS 等原始数据不转换，为空则保持原样）
+    #[serde(default)]
+    pub line_ending: Option<String>,
+    /// ZPL 存储格式（仅 ZPL 任务，首次使用时下载格式，之后只发送字段数据）
+    #[serde(default)]
+    pub zpl_format: Option<ZplFormat>,
+    /// 等待打印机完成任务（离开打印队列）后再返回结果，而不是写入打印队列后立即返回
+    #[serde(default)]
+    pub wait_completion: bool,
+    /// 纸盒（DMBIN 值，可从打印机纸张设置的 `trays` 中选择；仅文本任务，为空则使用打印机默认纸盒）
+    #[serde(default)]
+    pub tray: Option<u16>,
+    /// 纸张方向（仅文本任务，为空则使用打印机默认方向；HTML/PDF 通过 `paper_size` 的 `landscape` 指定）
+    #[serde(default)]
+    pub orientation: Option<Orientation>,
+    /// Windows 打印数据类型（RAW / TEXT / XPS_PASS，仅 ESC/POS、ZPL 和文本任务，为空则使用打印机配置，默认 RAW）
+    #[serde(default)]
+    pub datatype: Option<PrintDatatype>,
+    /// 渲染和打印的超时（毫秒，为空则使用服务配置的 `print_timeout_secs`），超时返回 `PRINT_TIMEOUT`
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// 打印前去掉 ANSI 转义序列和控制字符（保留换行和制表符；仅文本任务），防止数据中的字符触发打印机指令
+    #[serde(default)]
+    pub sanitize_text: bool,
+    /// 主文档和附加文档合并为一个打印任务（一次 StartDocPrinter/EndDocPrinter，中间不会因任务结束而切纸），否则每个文档单独提交
+    #[serde(default)]
+    pub single_job: bool,
+    /// 提交到打印队列后立即暂停，操作员确认后通过 `release_job` 释放（用于敏感单据，需要打印后台支持暂停任务）
+    #[serde(default)]
+    pub hold: bool,
+}
+
+/// 与反序列化时的默认值一致：请求省略 `options` 时打印一份（派生的 `Default` 为 0 份，不会打印任何内容）
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            copies: default_copies(),
+            paper_size: None,
+            archive: None,
+            line_ending: None,
+            zpl_format: None,
+            wait_completion: false,
+            tray: None,
+            orientation: None,
+            datatype: None,
+            timeout_ms: None,
+            sanitize_text: false,
+            single_job: false,
+            hold: false,
+        }
+    }
+}
+
+/// 纸张方向（文档打印时设置到 DEVMODE 的 `dmOrientation`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    /// 纵向
+    Portrait,
+    /// 横向
+    Landscape,
+}
+
+/// Windows 打印数据类型（提交打印任务时的 `pDatatype`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrintDatatype {
+    /// 原始数据，不经驱动处理直接发送到打印机
+    #[default]
+    #[serde(rename = "RAW", alias = "raw")]
+    Raw,
+    /// 纯文本，由驱动排版（部分打印机以 RAW 打印文本时格式错乱）
+    #[serde(rename = "TEXT", alias = "text")]
+    Text,
+    /// XPS 直通（部分仅支持 XPS 的驱动需要）
+    #[serde(rename = "XPS_PASS", alias = "xps_pass")]
+    XpsPass,
+}
+
+impl PrintDatatype {
+    /// 传给打印后台处理程序的数据类型名称
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Raw => "RAW",
+            Self::Text => "TEXT",
+            Self::XpsPass => "XPS_PASS",
+        }
+    }
+
+    /// 打印后台是否按 DEVMODE 处理份数、纸盒和纸张方向（RAW、XPS 直通的数据不经驱动排版，忽略这些设置）
+    pub fn applies_devmode(self) -> bool {
+        matches!(self, Self::Text)
+    }
+}
+
+/// ZPL 存储格式（^DF 下载 / ^XF 调用）
+///
+/// 模板内容作为格式主体，用 `^FN<n>` 声明字段；`fields` 按字段号给出本次打印的字段数据
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ZplFormat {
+    /// 格式名称，例如 `PRICE` 或 `E:PRICE.ZPL`
+    pub name: String,
+    /// 字段数据（字段号 -> 内容）
+    #[serde(default)]
+    pub fields: BTreeMap<u32, String>,
+    /// 强制重新下载格式（打印机重启后内存中的格式会丢失）
+    #[serde(default)]
+    pub force_download: bool,
 }
 
 fn default_copies() -> u32 {
@@ -71,11 +340,23 @@ fn default_copies() -> u32 {
 pub struct PrintResult {
     /// 请求 ID
     pub id: String,
-    /// 状态: success, error
+    /// 状态: success, error, held（已暂停，等待释放）
     pub status: String,
     /// 消息
     #[serde(default)]
     pub message: Option<String>,
+    /// 发送到打印机的字节数（单份，仅打印成功时返回）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_sent: Option<usize>,
+    /// 发送数据的 CRC32 校验值（8 位小写十六进制，仅打印成功时返回），可与客户端自行计算的值比对
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// 已暂停等待释放的打印任务 ID（仅 `options.hold`）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub held_jobs: Vec<u32>,
+    /// 提交到打印队列的任务 ID（打印成功或已暂停时返回），可用于调整优先级、暂停或恢复任务
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub jobs: Vec<u32>,
 }
 
 /// 打印机信息
@@ -83,10 +364,59 @@ pub struct PrintResult {
 pub struct PrinterInfo {
     /// 打印机名称
     pub name: String,
+    /// 唯一标识（由名称、端口和服务器组成，同名的共享打印机也可以区分）
+    #[serde(default)]
+    pub id: String,
     /// 是否为默认打印机
     pub is_default: bool,
     /// 状态: ready, busy, error, offline
     pub status: String,
+    /// 驱动名称
+    #[serde(default)]
+    pub driver: Option<String>,
+    /// 端口名称
+    #[serde(default)]
+    pub port: Option<String>,
+    /// 是否支持彩色（无法获取时为空）
+    #[serde(default)]
+    pub color: Option<bool>,
+}
+
+/// 打印机的默认纸张和纸盒设置（来自驱动的默认 DEVMODE）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperSettings {
+    /// 默认纸张（DMPAPER 值，如 9 为 A4）
+    pub paper_size: i16,
+    /// 默认纸张名称
+    pub form_name: String,
+    /// 默认纸盒（DMBIN 值）
+    pub tray: u16,
+    /// 可用纸盒
+    pub trays: Vec<TrayInfo>,
+}
+
+/// 纸盒信息
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrayInfo {
+    /// 纸盒编号（DMBIN 值），用于打印选项 `tray`
+    pub id: u16,
+    /// 纸盒名称（驱动提供，如 "Tray 1"）
+    pub name: String,
+}
+
+/// ESC/POS 打印机的实时状态（DLE EOT 查询结果）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EscposStatus {
+    /// 是否联机
+    pub online: bool,
+    /// 纸仓盖是否打开
+    pub cover_open: bool,
+    /// 纸将用尽
+    pub paper_near_end: bool,
+    /// 缺纸
+    pub paper_end: bool,
+    /// 发生错误（如切刀错误、过热）
+    pub error: bool,
 }
 
 /// 打印机列表响应
@@ -106,6 +436,80 @@ pub struct StatusResponse {
     pub version: String,
 }
 
+/// 详细状态响应（按服务、任务、打印机分节）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetailedStatusResponse {
+    pub server: ServerSection,
+    pub jobs: JobsSection,
+    pub printers: PrintersSection,
+}
+
+/// 详细状态：服务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerSection {
+    /// 服务状态: online, draining（停机中，不再接受新任务）
+    pub status: String,
+    /// 版本
+    pub version: String,
+    /// 连接数
+    pub connections: usize,
+    /// 应用启动以来的运行时间（秒）
+    pub uptime_secs: u64,
+}
+
+/// 详细状态：打印任务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobsSection {
+    /// 已完成的打印任务总数
+    pub total: u64,
+    /// 其中失败的任务数
+    pub failed: u64,
+    /// 进行中的任务数（包括等待同一打印机上其他任务的）
+    pub active: usize,
+    /// 等待执行的定时任务数
+    pub scheduled: usize,
+}
+
+/// 详细状态：打印机
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintersSection {
+    /// 默认打印机
+    pub default_printer: Option<String>,
+    /// 已安装的打印机数
+    pub count: usize,
+    /// 查询打印机失败的原因（如打印后台未运行）
+    pub error: Option<String>,
+}
+
+/// 失败的打印任务记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedJob {
+    /// 原始请求（列出时敏感字段已脱敏）
+    pub request: PrintRequest,
+    /// 错误信息
+    pub error: String,
+    /// 失败时间（Unix 毫秒）
+    pub failed_at: u64,
+}
+
+/// 预览响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewResponse {
+    /// 请求 ID
+    pub id: String,
+    /// 渲染结果
+    pub content: String,
+    /// 渲染耗时（微秒）
+    pub render_time_us: u64,
+}
+
+/// 失败任务列表响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedJobsResponse {
+    /// 最近失败的任务（最新的在最前）
+    pub jobs: Vec<FailedJob>,
+}
+
 /// 错误响应
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorResponse {
@@ -113,4 +517,102 @@ pub struct ErrorResponse {
     pub code: String,
     /// 错误消息
     pub message: String,
+    /// 关联的请求 ID
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+/// 打印失败的通用错误码
+pub const PRINT_FAILED: &str = "PRINT_FAILED";
+
+/// 系统中没有安装任何打印机
+pub const NO_PRINTERS_INSTALLED: &str = "NO_PRINTERS_INSTALLED";
+
+/// 打印机名称无效或打印机已被删除
+pub const PRINTER_NOT_FOUND: &str = "PRINTER_NOT_FOUND";
+
+/// 选中的打印机与其他打印机同名，无法确定打印目标
+pub const AMBIGUOUS_PRINTER: &str = "AMBIGUOUS_PRINTER";
+
+/// 没有访问打印机的权限
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const ACCESS_DENIED: &str = "ACCESS_DENIED";
+
+/// 打印后台处理程序（Print Spooler）未运行或无法连接
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const SPOOLER_UNAVAILABLE: &str = "SPOOLER_UNAVAILABLE";
+
+/// 打印被取消
+pub const CANCELLED: &str = "CANCELLED";
+
+/// 渲染和打印未在超时时间内完成
+pub const PRINT_TIMEOUT: &str = "PRINT_TIMEOUT";
+
+/// 等待打印任务完成时任务出错、被删除或超时
+pub const JOB_NOT_COMPLETED: &str = "JOB_NOT_COMPLETED";
+
+/// 打印机不支持实时状态查询（如通过打印后台连接的打印机）
+pub const STATUS_NOT_SUPPORTED: &str = "STATUS_NOT_SUPPORTED";
+
+/// 服务正在停止，不再接受新的打印任务
+pub const SHUTTING_DOWN: &str = "SHUTTING_DOWN";
+
+/// 要求静默打印，但平台的 WebView 无法不显示打印对话框
+pub const SILENT_PRINT_NOT_SUPPORTED: &str = "SILENT_PRINT_NOT_SUPPORTED";
+
+/// 磁盘空间不足，配置、归档或审计日志写入失败
+pub const DISK_FULL: &str = "DISK_FULL";
+
+/// 打印前归档打印数据失败（任务不会打印）
+pub const ARCHIVE_FAILED: &str = "ARCHIVE_FAILED";
+
+/// 要求暂停的任务无法暂停（任务已取消，不会打印）
+pub const HOLD_FAILED: &str = "HOLD_FAILED";
+
+/// 打印选项不适用于该任务（如以 RAW 数据类型提交的任务指定纸盒或纸张方向，打印后台会忽略）
+pub const OPTION_NOT_SUPPORTED: &str = "OPTION_NOT_SUPPORTED";
+
+/// USB 直连打印机（`usb:VID:PID`）没有可用的后端
+pub const USB_NOT_SUPPORTED: &str = "USB_NOT_SUPPORTED";
+
+/// 服务端口被占用（重试绑定后仍失败）
+pub const PORT_IN_USE: &str = "PORT_IN_USE";
+
+/// 没有与打印机选择条件匹配的打印机
+pub const NO_MATCHING_PRINTER: &str = "NO_MATCHING_PRINTER";
+
+/// 查询、取消或重新执行的任务不存在（或结果已过期）
+pub const JOB_NOT_FOUND: &str = "JOB_NOT_FOUND";
+
+/// 打印机没有可重新发送的最近一次打印数据
+pub const NOTHING_TO_REPRINT: &str = "NOTHING_TO_REPRINT";
+
+/// 打印错误（错误码 + 可读消息）
+#[derive(Debug, Clone)]
+pub struct PrintError {
+    /// 错误码
+    pub code: String,
+    /// 错误消息
+    pub message: String,
+}
+
+impl PrintError {
+    pub fn new(code: &str, message: impl Into<String>) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl From<String> for PrintError {
+    fn from(message: String) -> Self {
+        Self::new(PRINT_FAILED, message)
+    }
+}
+
+impl std::fmt::Display for PrintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
 }
@@ -5,73 +5,234 @@
 //! - ESC/POS (热敏小票打印机)
 //! - ZPL (标签打印机)
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use handlebars::{
     handlebars_helper, Context, Handlebars, Helper, HelperResult, Output, RenderContext,
+    RenderErrorReason,
 };
+use std::cell::RefCell;
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tracing::debug;
+use tracing::{debug, warn};
+
+use crate::protocol::PrintError;
+
+/// 默认小票宽度（字符数，58mm 纸）
+pub const DEFAULT_RECEIPT_WIDTH: usize = 32;
+
+/// 默认的渲染结果大小上限（字节）
+pub const DEFAULT_MAX_RENDER_SIZE: usize = 10 * 1024 * 1024;
+
+/// 渲染结果超过大小上限的错误码
+pub const RENDER_TOO_LARGE: &str = "RENDER_TOO_LARGE";
+
+/// Helper 配置
+///
+/// 不同部署可在配置中选择不同的 profile，使同名 helper（如 `currency`、`date_format`）按各自的规则输出
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HelperProfile {
+    /// `currency` 的货币符号
+    #[serde(default = "default_currency_symbol")]
+    pub currency_symbol: String,
+
+    /// `currency` 保留的小数位（最多 9 位）
+    #[serde(default = "default_currency_decimals")]
+    pub currency_decimals: u32,
+
+    /// `currency` 的舍入方式（为空时直接按小数位格式化）
+    #[serde(default)]
+    pub currency_rounding: Option<Rounding>,
+
+    /// `date_format` 使用的时区偏移（分钟，如 UTC+8 为 480）
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+impl Default for HelperProfile {
+    fn default() -> Self {
+        Self {
+            currency_symbol: default_currency_symbol(),
+            currency_decimals: default_currency_decimals(),
+            currency_rounding: None,
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+impl HelperProfile {
+    /// 按货币符号、小数位和舍入方式格式化金额
+    pub fn format_currency(&self, value: f64) -> String {
+        let decimals = self.currency_decimals.min(MAX_ROUND_DECIMALS);
+        let value = match self.currency_rounding {
+            Some(rounding) => round_decimals(value, decimals, rounding),
+            None => value,
+        };
+        format!("{}{:.2$}", self.currency_symbol, value, decimals as usize)
+    }
+}
+
+fn default_currency_symbol() -> String {
+    "¥".to_string()
+}
+
+fn default_currency_decimals() -> u32 {
+    2
+}
 
-/// 创建配置好的 Handlebars 实例
-fn create_handlebars<'a>() -> Handlebars<'a> {
+/// 创建配置好的 Handlebars 实例（`receipt_width` 为分隔线等 helper 的默认宽度）
+fn create_handlebars<'a>(
+    receipt_width: usize,
+    max_size: usize,
+    profile: &HelperProfile,
+) -> Handlebars<'a> {
     let mut hbs = Handlebars::new();
 
     // 禁用 HTML 转义（对于 ESC/POS 和 ZPL 很重要）
     hbs.set_strict_mode(false);
 
     // 注册常用 helpers
-    register_helpers(&mut hbs);
+    register_helpers(&mut hbs, receipt_width, max_size, profile);
 
     hbs
 }
 
+thread_local! {
+    /// 固定的 `uuid` / `now` 输出（UUID、Unix 毫秒），仅对当前线程生效
+    static FIXED_GENERATED: RefCell<Option<(String, i64)>> = const { RefCell::new(None) };
+}
+
+/// 固定 `uuid` / `now` 的输出，使渲染结果可复现（测试用，None 恢复随机值和当前时间）
+#[cfg(test)]
+pub(crate) fn fix_generated(values: Option<(&str, i64)>) {
+    FIXED_GENERATED.with(|fixed| {
+        *fixed.borrow_mut() = values.map(|(uuid, now)| (uuid.to_string(), now));
+    });
+}
+
+fn generated_uuid() -> String {
+    FIXED_GENERATED
+        .with(|fixed| fixed.borrow().as_ref().map(|(uuid, _)| uuid.clone()))
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+fn generated_now_millis() -> i64 {
+    FIXED_GENERATED
+        .with(|fixed| fixed.borrow().as_ref().map(|(_, now)| *now))
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0)
+        })
+}
+
 /// 注册自定义 helpers
-fn register_helpers(hbs: &mut Handlebars) {
+///
+/// 按参数生成长内容的 helper（repeat、separator、填充、replace）逐段写出，且最多生成略超过 `max_size` 的内容，
+/// 作为子表达式时（输出不受渲染大小上限约束）也不会构造超大的字符串
+fn register_helpers(
+    hbs: &mut Handlebars,
+    receipt_width: usize,
+    max_size: usize,
+    profile: &HelperProfile,
+) {
+    // 数值类 helper 同时接受数字字符串（如 "1,234.50"），无法解析的值按 0 计算
+
+    // 转换为数字（{{to_number "1,234.50"}}，无法解析时为 null）
+    handlebars_helper!(to_number: |v: Json| as_number(v));
+    hbs.register_helper("to_number", Box::new(to_number));
+
     // 格式化数字（保留小数位）
-    handlebars_helper!(format_number: |v: f64, decimals: u64| {
-        format!("{:.1$}", v, decimals as usize)
+    handlebars_helper!(format_number: |v: Json, decimals: u64| {
+        format!("{:.1$}", number_or_zero(v), decimals as usize)
     });
     hbs.register_helper("format_number", Box::new(format_number));
 
-    // 格式化货币
-    handlebars_helper!(currency: |v: f64| {
-        format!("¥{:.2}", v)
-    });
-    hbs.register_helper("currency", Box::new(currency));
-
-    // 日期格式化（简单实现）
-    handlebars_helper!(date_format: |timestamp: i64, _format: str| {
-        // 简化实现：直接返回时间戳的字符串表示
-        // 生产环境应使用 chrono 库
-        let secs = timestamp / 1000;
-        let naive = chrono_lite_format(secs);
-        naive
-    });
-    hbs.register_helper("date_format", Box::new(date_format));
-
-    // 字符串填充（左填充）
-    handlebars_helper!(pad_left: |s: str, width: u64, ch: str| {
-        let ch = ch.chars().next().unwrap_or(' ');
-        format!("{:>width$}", s, width = width as usize).replace(' ', &ch.to_string())
-    });
+    // 格式化货币（货币符号、小数位等由 profile 决定）
+    let currency_profile = profile.clone();
+    hbs.register_helper(
+        "currency",
+        Box::new(
+            move |h: &Helper,
+                  _: &Handlebars,
+                  _: &Context,
+                  _: &mut RenderContext,
+                  out: &mut dyn Output|
+                  -> HelperResult {
+                let v = h
+                    .param(0)
+                    .ok_or(RenderErrorReason::ParamNotFoundForIndex("currency", 0))?;
+                out.write(&currency_profile.format_currency(number_or_zero(v.value())))?;
+                Ok(())
+            },
+        ),
+    );
+
+    // 日期格式化（简单实现，按 profile 的时区偏移输出 YYYY-MM-DD HH:MM:SS）
+    // 生产环境应使用 chrono 库
+    let offset_secs = i64::from(profile.utc_offset_minutes) * 60;
+    hbs.register_helper(
+        "date_format",
+        Box::new(
+            move |h: &Helper,
+                  _: &Handlebars,
+                  _: &Context,
+                  _: &mut RenderContext,
+                  out: &mut dyn Output|
+                  -> HelperResult {
+                let timestamp = h
+                    .param(0)
+                    .and_then(|v| v.value().as_i64())
+                    .ok_or(RenderErrorReason::ParamNotFoundForIndex("date_format", 0))?;
+                out.write(&chrono_lite_format(timestamp / 1000 + offset_secs))?;
+                Ok(())
+            },
+        ),
+    );
+
+    // 当前时间（{{now}} 输出 YYYY-MM-DD HH:MM:SS，{{now "%Y%m%d"}} 按格式输出，时区同 date_format）
+    hbs.register_helper(
+        "now",
+        Box::new(
+            move |h: &Helper,
+                  _: &Handlebars,
+                  _: &Context,
+                  _: &mut RenderContext,
+                  out: &mut dyn Output|
+                  -> HelperResult {
+                let secs = generated_now_millis() / 1000 + offset_secs;
+                match h.param(0).and_then(|v| v.value().as_str()) {
+                    Some(format) => out.write(&format_time(secs, format))?,
+                    None => out.write(&chrono_lite_format(secs))?,
+                }
+                Ok(())
+            },
+        ),
+    );
+
+    // 随机 UUID（v4，如交易号）
+    handlebars_helper!(uuid: | | generated_uuid());
+    hbs.register_helper("uuid", Box::new(uuid));
+
+    // 字符串填充（左填充 / 右填充，填充部分逐段写出）
+    let pad_left = helper_pad("pad_left", true, max_size);
     hbs.register_helper("pad_left", Box::new(pad_left));
-
-    // 字符串填充（右填充）
-    handlebars_helper!(pad_right: |s: str, width: u64, ch: str| {
-        let ch = ch.chars().next().unwrap_or(' ');
-        let s_len = s.chars().count();
-        if s_len >= width as usize {
-            s.to_string()
-        } else {
-            format!("{}{}", s, ch.to_string().repeat(width as usize - s_len))
-        }
-    });
+    let pad_right = helper_pad("pad_right", false, max_size);
     hbs.register_helper("pad_right", Box::new(pad_right));
 
-    // 重复字符
-    handlebars_helper!(repeat: |s: str, times: u64| {
-        s.repeat(times as usize)
-    });
-    hbs.register_helper("repeat", Box::new(repeat));
+    // 重复字符（逐段写出，超过渲染大小上限时及时中止）
+    hbs.register_helper("repeat", Box::new(helper_repeat(max_size)));
+
+    // 分隔线（{{hr}}、{{separator "=" 48}}，未指定宽度时使用小票宽度）
+    let separator = helper_separator(receipt_width, max_size);
+    hbs.register_helper("hr", Box::new(separator));
+    hbs.register_helper("separator", Box::new(separator));
+
+    // 按列宽自动换行（中日韩字符按 2 列计算，续行缩进，未指定列宽时使用小票宽度）
+    hbs.register_helper("wrap", Box::new(helper_wrap(receipt_width)));
 
     // 截断字符串
     handlebars_helper!(truncate: |s: str, max_len: u64| {
@@ -92,6 +253,35 @@ fn register_helpers(hbs: &mut Handlebars) {
     handlebars_helper!(lowercase: |s: str| s.to_lowercase());
     hbs.register_helper("lowercase", Box::new(lowercase));
 
+    // 字符串判断（返回布尔值，可用于 {{#if (starts_with sku "FR")}}；数字按字符串比较，null 视为空字符串）
+    handlebars_helper!(starts_with: |s: Json, prefix: Json| {
+        text_value(s).starts_with(&text_value(prefix))
+    });
+    hbs.register_helper("starts_with", Box::new(starts_with));
+    handlebars_helper!(ends_with: |s: Json, suffix: Json| {
+        text_value(s).ends_with(&text_value(suffix))
+    });
+    hbs.register_helper("ends_with", Box::new(ends_with));
+    handlebars_helper!(contains: |s: Json, part: Json| {
+        text_value(s).contains(&text_value(part))
+    });
+    hbs.register_helper("contains", Box::new(contains));
+
+    // 替换所有匹配的子串（{{replace phone "-" ""}}，from 为空时原样返回，结果逐段写出）
+    hbs.register_helper("replace", Box::new(helper_replace(max_size)));
+
+    // 电话号码格式化（{{format_phone phone "###-####-####"}}，# 依次填入号码中的数字，数字个数不符时原样输出）
+    handlebars_helper!(format_phone_helper: |number: Json, pattern: str| {
+        format_phone(&text_value(number), pattern)
+    });
+    hbs.register_helper("format_phone", Box::new(format_phone_helper));
+
+    // 脱敏（{{mask card_no 4}}，只保留末尾 N 个字符，其余替换为 *）
+    handlebars_helper!(mask_helper: |value: Json, visible_tail: u64| {
+        mask(&text_value(value), visible_tail as usize)
+    });
+    hbs.register_helper("mask", Box::new(mask_helper));
+
     // 条件相等
     hbs.register_helper("eq", Box::new(helper_eq));
 
@@ -105,20 +295,704 @@ fn register_helpers(hbs: &mut Handlebars) {
     hbs.register_helper("lt", Box::new(helper_lt));
 
     // 数学运算：加法
-    handlebars_helper!(add: |a: f64, b: f64| a + b);
+    handlebars_helper!(add: |a: Json, b: Json| number_or_zero(a) + number_or_zero(b));
     hbs.register_helper("add", Box::new(add));
 
     // 数学运算：减法
-    handlebars_helper!(sub: |a: f64, b: f64| a - b);
+    handlebars_helper!(sub: |a: Json, b: Json| number_or_zero(a) - number_or_zero(b));
     hbs.register_helper("sub", Box::new(sub));
 
     // 数学运算：乘法
-    handlebars_helper!(mul: |a: f64, b: f64| a * b);
+    handlebars_helper!(mul: |a: Json, b: Json| number_or_zero(a) * number_or_zero(b));
     hbs.register_helper("mul", Box::new(mul));
 
     // 数学运算：除法
-    handlebars_helper!(div: |a: f64, b: f64| if b != 0.0 { a / b } else { 0.0 });
+    handlebars_helper!(div: |a: Json, b: Json| {
+        let (a, b) = (number_or_zero(a), number_or_zero(b));
+        if b != 0.0 { a / b } else { 0.0 }
+    });
     hbs.register_helper("div", Box::new(div));
+
+    // 数学运算：最小值 / 最大值（缺少的参数忽略）
+    handlebars_helper!(min: |*args| {
+        args.iter().filter_map(|v| as_number(v)).reduce(f64::min).unwrap_or(0.0)
+    });
+    hbs.register_helper("min", Box::new(min));
+
+    handlebars_helper!(max: |*args| {
+        args.iter().filter_map(|v| as_number(v)).reduce(f64::max).unwrap_or(0.0)
+    });
+    hbs.register_helper("max", Box::new(max));
+
+    // 数学运算：限制范围（{{clamp value lo hi}}，缺少的边界视为不限制）
+    handlebars_helper!(clamp: |*args| {
+        let arg = |i: usize| args.get(i).and_then(|v| as_number(v));
+        clamp_value(arg(0).unwrap_or(0.0), arg(1), arg(2))
+    });
+    hbs.register_helper("clamp", Box::new(clamp));
+
+    // 舍入（{{round total 2}}，省略小数位时取整；默认四舍五入，mode="half_even" 为银行家舍入）
+    handlebars_helper!(round: |v: Json, {mode: str = "half_up"}, *args| {
+        let rounding = match mode {
+            "half_even" | "bankers" => Rounding::HalfEven,
+            _ => Rounding::HalfUp,
+        };
+        round_decimals(number_or_zero(v), decimals_arg(args.get(1).copied()), rounding)
+    });
+    hbs.register_helper("round", Box::new(round));
+
+    // 向上 / 向下取整（{{ceil total 1}}、{{floor total}}）
+    handlebars_helper!(ceil: |v: Json, *args| {
+        round_decimals(number_or_zero(v), decimals_arg(args.get(1).copied()), Rounding::Ceil)
+    });
+    hbs.register_helper("ceil", Box::new(ceil));
+    handlebars_helper!(floor: |v: Json, *args| {
+        round_decimals(number_or_zero(v), decimals_arg(args.get(1).copied()), Rounding::Floor)
+    });
+    hbs.register_helper("floor", Box::new(floor));
+
+    // 舍入到指定单位的整数倍（{{round_to total 0.05}}，用于现金找零）
+    handlebars_helper!(round_to: |v: Json, nearest: Json| {
+        round_to_nearest(number_or_zero(v), number_or_zero(nearest))
+    });
+    hbs.register_helper("round_to", Box::new(round_to));
+
+    // 数组聚合：求和（{{sum items "price"}}，省略字段名时对元素本身求和）
+    handlebars_helper!(sum: |*args| {
+        sum_items(args.first().copied(), args.get(1).and_then(|v| v.as_str()))
+    });
+    hbs.register_helper("sum", Box::new(sum));
+
+    // 数组聚合：计数（{{count items}}）
+    handlebars_helper!(count: |*args| {
+        args.first().and_then(|v| v.as_array()).map_or(0, |items| items.len())
+    });
+    hbs.register_helper("count", Box::new(count));
+
+    // 可选字段默认值：返回第一个非空参数（{{default nickname "顾客"}}、{{coalesce a b "无"}}）
+    handlebars_helper!(default_helper: |*args| first_present(args.iter().copied()));
+    hbs.register_helper("default", Box::new(default_helper));
+    hbs.register_helper("coalesce", Box::new(default_helper));
+
+    // 按位置取数组元素（{{index items 0}}，负数从末尾计数，越界时为 null）
+    // 按键取值可使用内置的 lookup：{{lookup obj key}}
+    handlebars_helper!(index: |items: Json, i: i64| array_index(items, i));
+    hbs.register_helper("index", Box::new(index));
+
+    // 对象键值对（{{#each (entries tax)}}{{key}}: {{value}}{{/each}}，按键排序）
+    handlebars_helper!(entries: |v: Json| object_entries(v));
+    hbs.register_helper("entries", Box::new(entries));
+
+    // 转换为可用作文件名的字符串
+    handlebars_helper!(slugify_helper: |s: str| slugify(s));
+    hbs.register_helper("slugify", Box::new(slugify_helper));
+
+    // base64 / 十六进制编解码（{{base64_decode token}}、{{hex_encode s}}）
+    // 解码结果须为 UTF-8 文本，输入无效时渲染失败
+    handlebars_helper!(base64_encode: |s: str| STANDARD.encode(s));
+    hbs.register_helper("base64_encode", Box::new(base64_encode));
+    hbs.register_helper(
+        "base64_decode",
+        Box::new(helper_decode("base64_decode", base64_decode)),
+    );
+    handlebars_helper!(hex_encode_helper: |s: str| hex_encode(s.as_bytes()));
+    hbs.register_helper("hex_encode", Box::new(hex_encode_helper));
+    hbs.register_helper(
+        "hex_decode",
+        Box::new(helper_decode("hex_decode", hex_decode)),
+    );
+
+    // 金额转大写 / 英文单词（{{number_to_words total lang="zh"}}）
+    hbs.register_helper("number_to_words", Box::new(helper_number_to_words));
+
+    // 序列化为 JSON（{{json order}}、{{json_pretty order}}，可作为二维码数据）
+    hbs.register_helper("json", Box::new(helper_json("json", false)));
+    hbs.register_helper("json_pretty", Box::new(helper_json("json_pretty", true)));
+
+    // ZPL 旋转文本
+    hbs.register_helper("rotate", Box::new(helper_rotate));
+
+    // ZPL 多行文本块（{{text_block description 400 lines=3 justify="center" x=50 y=100}}）
+    hbs.register_helper("text_block", Box::new(helper_text_block));
+
+    // ZPL 图片（{{zpl_image logo name="LOGO" x=50 y=20}}，logo 为 base64 编码的 PNG/BMP）
+    hbs.register_helper("zpl_image", Box::new(helper_zpl_image));
+}
+
+/// 对数组求和（指定字段时累加各元素的该字段）
+///
+/// 非数组视为空数组；缺失或非数字的值按 0 计算，数字字符串会被解析
+fn sum_items(items: Option<&Value>, field: Option<&str>) -> f64 {
+    let Some(items) = items.and_then(|v| v.as_array()) else {
+        return 0.0;
+    };
+
+    items
+        .iter()
+        .filter_map(|item| match field {
+            Some(field) => item.get(field),
+            None => Some(item),
+        })
+        .filter_map(as_number)
+        .sum()
+}
+
+/// 将字符串、数字和布尔值转换为文本，null、数组和对象视为空字符串
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// 返回第一个不为 null 且不是空字符串的值，都为空时返回 null
+fn first_present<'a>(values: impl IntoIterator<Item = &'a Value>) -> Value {
+    values
+        .into_iter()
+        .find(|v| !matches!(v, Value::Null) && v.as_str() != Some(""))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+/// 取数组中指定位置的元素（负数从末尾计数），不是数组或越界时返回 null
+fn array_index(items: &Value, index: i64) -> Value {
+    let Some(items) = items.as_array() else {
+        return Value::Null;
+    };
+
+    let position = if index < 0 {
+        items.len().checked_sub(index.unsigned_abs() as usize)
+    } else {
+        Some(index as usize)
+    };
+    position
+        .and_then(|i| items.get(i))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+/// 将对象转换为 `[{"key": ..., "value": ...}]` 数组，非对象返回空数组
+///
+/// 便于在 `#each` 中同时使用键和值，或与 `count` 等数组 helper 组合
+fn object_entries(value: &Value) -> Value {
+    let entries = value
+        .as_object()
+        .map(|map| {
+            map.iter()
+                .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+                .collect()
+        })
+        .unwrap_or_default();
+    Value::Array(entries)
+}
+
+/// 数字字符串开头允许出现的货币符号
+const CURRENCY_SYMBOLS: &[char] = &['¥', '￥', '$', '€', '£'];
+
+/// 将 JSON 值转换为数字（支持数字字符串）
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => parse_number(s),
+        _ => None,
+    }
+}
+
+/// 将 JSON 值转换为数字，无法解析时为 0
+fn number_or_zero(value: &Value) -> f64 {
+    as_number(value).unwrap_or(0.0)
+}
+
+/// 解析数字字符串，忽略千位分隔符和开头的货币符号（如 "1,234.50"、"-¥1,234.50"）
+fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let digits: String = s
+        .trim_start_matches(CURRENCY_SYMBOLS)
+        .trim_start()
+        .chars()
+        .filter(|&c| c != ',')
+        .collect();
+
+    let n = digits.parse::<f64>().ok().filter(|n| n.is_finite())?;
+    Some(if negative { -n } else { n })
+}
+
+/// 将数值限制在 [lo, hi] 范围内（lo 大于 hi 时以 hi 为准）
+fn clamp_value(value: f64, lo: Option<f64>, hi: Option<f64>) -> f64 {
+    let value = lo.map_or(value, |lo| value.max(lo));
+    hi.map_or(value, |hi| value.min(hi))
+}
+
+/// 舍入允许的最大小数位
+const MAX_ROUND_DECIMALS: u32 = 9;
+
+/// 舍入方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// 四舍五入（.5 远离零）
+    HalfUp,
+    /// 银行家舍入（.5 舍入到偶数）
+    HalfEven,
+    /// 向上取整
+    Ceil,
+    /// 向下取整
+    Floor,
+}
+
+impl Rounding {
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            Self::HalfUp => value.round(),
+            Self::HalfEven => value.round_ties_even(),
+            Self::Ceil => value.ceil(),
+            Self::Floor => value.floor(),
+        }
+    }
+}
+
+/// 按小数位舍入（最多 9 位）
+///
+/// 舍入前先消除浮点误差，例如 2.675 实际存储为 2.67499…，仍按 2.675 舍入为 2.68
+pub fn round_decimals(value: f64, decimals: u32, rounding: Rounding) -> f64 {
+    let factor = 10f64.powi(decimals.min(MAX_ROUND_DECIMALS) as i32);
+    rounding.apply(remove_float_error(value * factor)) / factor
+}
+
+/// 四舍五入到 nearest 的整数倍（如现金找零的 0.05），nearest 不是正数时原样返回
+pub fn round_to_nearest(value: f64, nearest: f64) -> f64 {
+    if nearest <= 0.0 || nearest.is_nan() {
+        return value;
+    }
+    let multiple = Rounding::HalfUp.apply(remove_float_error(value / nearest));
+    remove_float_error(multiple * nearest)
+}
+
+/// 去掉小数点后第 9 位以后的浮点误差
+fn remove_float_error(value: f64) -> f64 {
+    (value * 1e9).round() / 1e9
+}
+
+/// helper 的小数位参数（缺省或无法解析时为 0）
+fn decimals_arg(value: Option<&Value>) -> u32 {
+    value.and_then(as_number).map_or(0, |d| d.max(0.0) as u32)
+}
+
+// 换行 helper: wrap
+//
+// 用法：{{wrap name}}、{{wrap name 20 indent=2}}
+// 列宽默认为打印机配置的小票宽度，续行默认缩进 2 列
+fn helper_wrap(
+    receipt_width: usize,
+) -> impl Fn(&Helper, &Handlebars, &Context, &mut RenderContext, &mut dyn Output) -> HelperResult
+       + Copy
+       + Send
+       + Sync {
+    move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| {
+        let text = h
+            .param(0)
+            .map(|v| text_value(v.value()))
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("wrap", 0))?;
+        let width = h
+            .param(1)
+            .and_then(|v| v.value().as_u64())
+            .map_or(receipt_width, |w| w as usize);
+        let indent = h
+            .hash_get("indent")
+            .and_then(|v| v.value().as_u64())
+            .unwrap_or(2);
+        out.write(&wrap_text(&text, width, indent as usize))?;
+        Ok(())
+    }
+}
+
+// 分隔线 helper: hr / separator
+//
+// 用法：{{hr}}、{{hr "="}}、{{separator "=" 48}}
+// 字符默认为 `-`，宽度默认为打印机配置的小票宽度
+fn helper_separator(
+    receipt_width: usize,
+    max_size: usize,
+) -> impl Fn(&Helper, &Handlebars, &Context, &mut RenderContext, &mut dyn Output) -> HelperResult
+       + Copy
+       + Send
+       + Sync {
+    move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| {
+        let pattern = h
+            .param(0)
+            .and_then(|v| v.value().as_str())
+            .filter(|s| !s.is_empty())
+            .unwrap_or("-");
+        let width = h
+            .param(1)
+            .and_then(|v| v.value().as_u64())
+            .map_or(receipt_width, |w| w as usize);
+        let times = width / display_width(pattern).max(1);
+        write_repeated(out, pattern, times, max_size)?;
+        Ok(())
+    }
+}
+
+// 填充 helper: pad_left / pad_right
+//
+// 用法：{{pad_left num 5 "0"}}，字符数不足 width 时用填充字符（省略时为空格）补足
+fn helper_pad(
+    name: &'static str,
+    left: bool,
+    max_size: usize,
+) -> impl Fn(&Helper, &Handlebars, &Context, &mut RenderContext, &mut dyn Output) -> HelperResult
+       + Copy
+       + Send
+       + Sync {
+    move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| {
+        let s = h
+            .param(0)
+            .and_then(|v| v.value().as_str())
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(name, 0))?;
+        let width = h
+            .param(1)
+            .and_then(|v| v.value().as_u64())
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(name, 1))?;
+        let ch = h
+            .param(2)
+            .and_then(|v| v.value().as_str())
+            .and_then(|ch| ch.chars().next())
+            .unwrap_or(' ');
+
+        let padding = (width as usize).saturating_sub(s.chars().count());
+        if !left {
+            out.write(s)?;
+        }
+        write_repeated(out, ch.encode_utf8(&mut [0; 4]), padding, max_size)?;
+        if left {
+            out.write(s)?;
+        }
+        Ok(())
+    }
+}
+
+// 替换 helper: replace
+//
+// 用法：{{replace phone "-" ""}}，逐段写出替换结果，不先构造完整字符串
+fn helper_replace(
+    max_size: usize,
+) -> impl Fn(&Helper, &Handlebars, &Context, &mut RenderContext, &mut dyn Output) -> HelperResult
+       + Copy
+       + Send
+       + Sync {
+    move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| {
+        let param = |i: usize| {
+            h.param(i)
+                .map(|v| text_value(v.value()))
+                .ok_or(RenderErrorReason::ParamNotFoundForIndex("replace", i))
+        };
+        let (s, from, to) = (param(0)?, param(1)?, param(2)?);
+        if from.is_empty() {
+            out.write(&s)?;
+            return Ok(());
+        }
+
+        let mut written = 0;
+        for (i, part) in s.split(from.as_str()).enumerate() {
+            if written > max_size {
+                break;
+            }
+            if i > 0 {
+                out.write(&to)?;
+                written += to.len();
+            }
+            out.write(part)?;
+            written += part.len();
+        }
+        Ok(())
+    }
+}
+
+// 解码 helper: base64_decode / hex_decode
+//
+// 用法：{{base64_decode token}}，解码后不是 UTF-8 文本或输入无效时返回渲染错误
+fn helper_decode(
+    name: &'static str,
+    decode: fn(&str) -> Result<Vec<u8>, String>,
+) -> impl Fn(&Helper, &Handlebars, &Context, &mut RenderContext, &mut dyn Output) -> HelperResult
+       + Copy
+       + Send
+       + Sync {
+    move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(name, 0))?;
+        let input = value.value().as_str().ok_or_else(|| {
+            RenderErrorReason::Other(format!("{}: not a string: {}", name, value.value()))
+        })?;
+        let bytes =
+            decode(input).map_err(|e| RenderErrorReason::Other(format!("{}: {}", name, e)))?;
+        let text = String::from_utf8(bytes).map_err(|_| {
+            RenderErrorReason::Other(format!("{}: decoded data is not valid UTF-8", name))
+        })?;
+        out.write(&text)?;
+        Ok(())
+    }
+}
+
+// JSON helper: json / json_pretty
+//
+// 用法：{{json order}}，输出不做 HTML 转义；`^` `~` `<` `>` `&` 以 \uXXXX 转义，
+// 结果仍是等价的合法 JSON，可直接放入 ZPL 的 ^FD 或 HTML 中
+fn helper_json(
+    name: &'static str,
+    pretty: bool,
+) -> impl Fn(&Helper, &Handlebars, &Context, &mut RenderContext, &mut dyn Output) -> HelperResult
+       + Copy
+       + Send
+       + Sync {
+    move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(name, 0))?;
+        out.write(&to_safe_json(value.value(), pretty))?;
+        Ok(())
+    }
+}
+
+/// 序列化为 JSON，并转义打印指令和 HTML 中有特殊含义的字符（这些字符只会出现在字符串中）
+pub fn to_safe_json(value: &Value, pretty: bool) -> String {
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+    .unwrap_or_default();
+
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '^' | '~' | '<' | '>' | '&' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 重复 helper: repeat
+//
+// 用法：{{repeat "=" 32}}
+fn helper_repeat(
+    max_size: usize,
+) -> impl Fn(&Helper, &Handlebars, &Context, &mut RenderContext, &mut dyn Output) -> HelperResult
+       + Copy
+       + Send
+       + Sync {
+    move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| {
+        let s = h
+            .param(0)
+            .and_then(|v| v.value().as_str())
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("repeat", 0))?;
+        let times = h
+            .param(1)
+            .and_then(|v| v.value().as_u64())
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("repeat", 1))?;
+        write_repeated(out, s, times as usize, max_size)?;
+        Ok(())
+    }
+}
+
+/// 重复内容每次写出的大小（逐段写出，超过渲染大小上限时及时中止）
+const REPEAT_CHUNK_SIZE: usize = 4096;
+
+/// 将 `s` 重复写出 `times` 次，按段写入而不先构造完整字符串
+///
+/// 最多写出略超过 `max_size` 的内容：直接输出时由渲染大小上限中止，作为子表达式时截断
+fn write_repeated(
+    out: &mut dyn Output,
+    s: &str,
+    times: usize,
+    max_size: usize,
+) -> std::io::Result<()> {
+    if s.is_empty() {
+        return Ok(());
+    }
+    let times = times.min(max_size / s.len() + 1);
+    if times == 0 {
+        return Ok(());
+    }
+    let per_chunk = (REPEAT_CHUNK_SIZE / s.len()).clamp(1, times);
+    let chunk = s.repeat(per_chunk);
+    let mut remaining = times;
+    while remaining >= per_chunk {
+        out.write(&chunk)?;
+        remaining -= per_chunk;
+    }
+    out.write(&s.repeat(remaining))
+}
+
+// 金额 helper: number_to_words
+//
+// 用法：{{number_to_words total lang="zh"}}，lang 为 en（默认）或 zh
+fn helper_number_to_words(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = h.param(0).ok_or(RenderErrorReason::ParamNotFoundForIndex(
+        "number_to_words",
+        0,
+    ))?;
+    let amount = as_number(value.value()).ok_or_else(|| {
+        RenderErrorReason::Other(format!("number_to_words: not a number: {}", value.value()))
+    })?;
+    let lang = h
+        .hash_get("lang")
+        .and_then(|v| v.value().as_str())
+        .unwrap_or("en");
+
+    let words = number_to_words(amount, lang)
+        .map_err(|e| RenderErrorReason::Other(format!("number_to_words: {}", e)))?;
+    out.write(&words)?;
+    Ok(())
+}
+
+// ZPL helper: text_block
+//
+// 用法：{{text_block description 400 lines=3 spacing=0 justify="center" x=50 y=100 size=30}}
+// 输出在指定宽度（点）内自动换行的多行文本字段（^FB），文本中的换行符转换为强制换行
+fn helper_text_block(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let text = h
+        .param(0)
+        .map(|v| text_value(v.value()))
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("text_block", 0))?;
+    let width = h
+        .param(1)
+        .and_then(|v| v.value().as_u64())
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("text_block", 1))?;
+
+    let hash_u32 = |name: &str, default: u32| {
+        h.hash_get(name)
+            .and_then(|v| v.value().as_u64())
+            .map_or(default, |v| v as u32)
+    };
+    let justify = match h.hash_get("justify").and_then(|v| v.value().as_str()) {
+        Some(name) => zpl::Justify::from_name(name).ok_or_else(|| {
+            RenderErrorReason::Other(format!("text_block: unsupported justify '{}'", name))
+        })?,
+        None => zpl::Justify::Left,
+    };
+    let spacing = h
+        .hash_get("spacing")
+        .and_then(|v| v.value().as_i64())
+        .unwrap_or(0);
+    let size = hash_u32("size", 30);
+    let font = h
+        .hash_get("font")
+        .and_then(|v| v.value().as_str())
+        .and_then(|s| s.chars().next())
+        .unwrap_or('0');
+
+    out.write(&zpl::field_origin(hash_u32("x", 0), hash_u32("y", 0)))?;
+    out.write(&zpl::font(font, size, size))?;
+    out.write(&zpl::field_block(
+        width as u32,
+        hash_u32("lines", 1),
+        spacing as i32,
+        justify,
+    ))?;
+    let text = text.replace("\r\n", "\\&").replace('\n', "\\&");
+    out.write(&zpl::field_data(&text))?;
+    Ok(())
+}
+
+// ZPL helper: zpl_image
+//
+// 用法：{{zpl_image logo name="LOGO" x=50 y=20}}
+// logo 为 base64 编码的 PNG/BMP 图片（可带 `data:image/png;base64,` 前缀），
+// 输出下载图形（~DG）和在指定位置打印该图形（^XG）的指令
+fn helper_zpl_image(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let data = h
+        .param(0)
+        .and_then(|v| v.value().as_str())
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("zpl_image", 0))?;
+    let data = data.split_once("base64,").map_or(data, |(_, data)| data);
+    let name = h
+        .hash_get("name")
+        .and_then(|v| v.value().as_str())
+        .unwrap_or("LOGO");
+    let hash_u32 = |name: &str| {
+        h.hash_get(name)
+            .and_then(|v| v.value().as_u64())
+            .map_or(0, |v| v as u32)
+    };
+
+    let download = base64_decode(data)
+        .and_then(|image| zpl::download_graphic(name, &image))
+        .map_err(|e| RenderErrorReason::Other(format!("zpl_image: {}", e)))?;
+    out.write(&download)?;
+    out.write("\n")?;
+    out.write(&zpl::recall_graphic(hash_u32("x"), hash_u32("y"), name))?;
+    Ok(())
+}
+
+// ZPL helper: rotate
+//
+// 用法：{{rotate text 90 x=50 y=50 size=30}}
+// 输出旋转指定角度（0/90/180/270）的文本字段
+fn helper_rotate(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let text = h
+        .param(0)
+        .map(|v| match v.value() {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("rotate", 0))?;
+    let degrees = h.param(1).and_then(|v| v.value().as_u64()).unwrap_or(0);
+    let rotation = zpl::Rotation::from_degrees(degrees as u32).ok_or_else(|| {
+        RenderErrorReason::Other(format!(
+            "rotate: unsupported rotation {} (expected 0, 90, 180 or 270)",
+            degrees
+        ))
+    })?;
+
+    let hash_u32 = |name: &str, default: u32| {
+        h.hash_get(name)
+            .and_then(|v| v.value().as_u64())
+            .map_or(default, |v| v as u32)
+    };
+    let x = hash_u32("x", 0);
+    let y = hash_u32("y", 0);
+    let size = hash_u32("size", 30);
+    let font = h
+        .hash_get("font")
+        .and_then(|v| v.value().as_str())
+        .and_then(|s| s.chars().next())
+        .unwrap_or('0');
+
+    out.write(&zpl::rotated_text(x, y, font, rotation, size, size, &text))?;
+    Ok(())
 }
 
 // 条件 helper: eq
@@ -161,8 +1035,8 @@ fn helper_gt(
     _: &mut RenderContext,
     out: &mut dyn Output,
 ) -> HelperResult {
-    let a = h.param(0).and_then(|v| v.value().as_f64()).unwrap_or(0.0);
-    let b = h.param(1).and_then(|v| v.value().as_f64()).unwrap_or(0.0);
+    let a = h.param(0).map_or(0.0, |v| number_or_zero(v.value()));
+    let b = h.param(1).map_or(0.0, |v| number_or_zero(v.value()));
     if a > b {
         out.write("true")?;
     }
@@ -177,77 +1051,623 @@ fn helper_lt(
     _: &mut RenderContext,
     out: &mut dyn Output,
 ) -> HelperResult {
-    let a = h.param(0).and_then(|v| v.value().as_f64()).unwrap_or(0.0);
-    let b = h.param(1).and_then(|v| v.value().as_f64()).unwrap_or(0.0);
+    let a = h.param(0).map_or(0.0, |v| number_or_zero(v.value()));
+    let b = h.param(1).map_or(0.0, |v| number_or_zero(v.value()));
     if a < b {
         out.write("true")?;
     }
-    Ok(())
+    Ok(())
+}
+
+/// 简化的时间格式化（不引入 chrono 依赖）
+pub fn chrono_lite_format(secs: i64) -> String {
+    // 简单的时间格式化：YYYY-MM-DD HH:MM:SS
+    // 生产环境应该使用 chrono 库
+    let [year, month, day, hours, minutes, seconds] = civil_time(secs);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day, hours, minutes, seconds
+    )
+}
+
+/// 按格式输出时间（支持 `%Y` `%m` `%d` `%H` `%M` `%S` `%%`，其他字符原样输出）
+pub fn format_time(secs: i64, format: &str) -> String {
+    let [year, month, day, hours, minutes, seconds] = civil_time(secs);
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", year)),
+            Some('m') => out.push_str(&format!("{:02}", month)),
+            Some('d') => out.push_str(&format!("{:02}", day)),
+            Some('H') => out.push_str(&format!("{:02}", hours)),
+            Some('M') => out.push_str(&format!("{:02}", minutes)),
+            Some('S') => out.push_str(&format!("{:02}", seconds)),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// 秒数转换为 [年, 月, 日, 时, 分, 秒]（从 1970-01-01 开始）
+fn civil_time(secs: i64) -> [i64; 6] {
+    let days_since_epoch = secs / 86400;
+    let time_of_day = secs % 86400;
+
+    let hours = time_of_day / 3600;
+    let minutes = (time_of_day % 3600) / 60;
+    let seconds = time_of_day % 60;
+
+    // 简化的日期计算（从 1970-01-01 开始）
+    let mut year = 1970i64;
+    let mut remaining_days = days_since_epoch;
+
+    loop {
+        let days_in_year = if is_leap_year(year) { 366 } else { 365 };
+        if remaining_days < days_in_year {
+            break;
+        }
+        remaining_days -= days_in_year;
+        year += 1;
+    }
+
+    let days_in_months: [i64; 12] = if is_leap_year(year) {
+        [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+    } else {
+        [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+    };
+
+    let mut month = 1;
+    for days in days_in_months {
+        if remaining_days < days {
+            break;
+        }
+        remaining_days -= days;
+        month += 1;
+    }
+
+    let day = remaining_days + 1;
+    [year, month, day, hours, minutes, seconds]
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0)
+}
+
+/// 字符显示宽度（中日韩等全角字符占 2 列）
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// 字符串显示宽度
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// 按列宽换行
+///
+/// 英文按单词换行，中日韩字符可在任意位置断行，超长单词强制截断；
+/// 第二行起缩进 `indent` 列
+pub fn wrap_text(text: &str, width: usize, indent: usize) -> String {
+    let width = width.max(1);
+    let indent = indent.min(width - 1);
+
+    // 拆分为 (词, 前面是否有空白)；中日韩字符单独成词
+    let mut tokens: Vec<(String, bool)> = Vec::new();
+    let mut word = String::new();
+    let mut space_before = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !word.is_empty() {
+                tokens.push((std::mem::take(&mut word), space_before));
+            }
+            space_before = true;
+        } else if char_width(c) == 2 {
+            if !word.is_empty() {
+                tokens.push((std::mem::take(&mut word), space_before));
+                space_before = false;
+            }
+            tokens.push((c.to_string(), space_before));
+            space_before = false;
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push((word, space_before));
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+
+    for (token, space_before) in tokens {
+        let limit = if lines.is_empty() { width } else { width - indent };
+        let token_width = display_width(&token);
+        let sep = usize::from(space_before && !current.is_empty());
+
+        if current_width + sep + token_width <= limit {
+            if sep == 1 {
+                current.push(' ');
+            }
+            current.push_str(&token);
+            current_width += sep + token_width;
+            continue;
+        }
+
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+            current_width = 0;
+        }
+
+        // 单词本身超过一行宽度时强制截断
+        for c in token.chars() {
+            let limit = if lines.is_empty() { width } else { width - indent };
+            let w = char_width(c);
+            if current_width + w > limit && !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+                current_width = 0;
+            }
+            current.push(c);
+            current_width += w;
+        }
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+
+    let separator = format!("\n{}", " ".repeat(indent));
+    lines.join(&separator)
+}
+
+/// 文件名中不允许的字符（Windows 规则，包含路径分隔符）
+const FILENAME_UNSAFE: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Windows 保留的设备名（不能作为文件名）
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// 文件名片段的最大字符数
+const MAX_SLUG_CHARS: usize = 64;
+
+/// 转换为可安全用作文件名的字符串
+///
+/// 空白、控制字符和文件名不允许的字符替换为 `_`（连续的合并为一个），
+/// 去掉首尾的 `_` 和 `.`，中日韩等字符保持不变；结果可能为空
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    let mut separator = false;
+    for c in text.chars() {
+        if c == '_' || c.is_whitespace() || c.is_control() || FILENAME_UNSAFE.contains(&c) {
+            separator = true;
+            continue;
+        }
+        if separator && !slug.is_empty() {
+            slug.push('_');
+        }
+        separator = false;
+        slug.push(c);
+    }
+
+    let slug: String = slug.chars().take(MAX_SLUG_CHARS).collect();
+    let slug = slug.trim_matches(|c| c == '_' || c == '.');
+
+    let stem = slug.split('.').next().unwrap_or_default();
+    let reserved = RESERVED_NAMES.iter().any(|n| stem.eq_ignore_ascii_case(n));
+    if reserved {
+        format!("_{}", slug)
+    } else {
+        slug.to_string()
+    }
+}
+
+/// 按模式格式化电话号码：模式中的 `#` 依次替换为号码中的数字，其他字符原样保留
+///
+/// 号码中的数字个数与 `#` 的个数不同时返回原号码，避免输出错位的号码
+pub fn format_phone(number: &str, pattern: &str) -> String {
+    let digits: Vec<char> = number.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.len() != pattern.matches('#').count() {
+        return number.to_string();
+    }
+
+    let mut digits = digits.into_iter();
+    pattern
+        .chars()
+        .map(|c| match c {
+            '#' => digits.next().unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// 脱敏：只保留末尾 `visible_tail` 个字符，其余替换为 `*`
+///
+/// 最多保留一半的字符（向下取整），过短的值不会因此完整显示
+pub fn mask(value: &str, visible_tail: usize) -> String {
+    let len = value.chars().count();
+    let hidden = len - visible_tail.min(len / 2);
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i < hidden { '*' } else { c })
+        .collect()
+}
+
+/// 解码 base64（标准字母表，忽略首尾空白）
+pub fn base64_decode(s: &str) -> Result<Vec<u8>, String> {
+    STANDARD
+        .decode(s.trim())
+        .map_err(|e| format!("invalid base64: {}", e))
+}
+
+/// 编码为小写十六进制
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 解码十六进制（不区分大小写，忽略空白，如 `1B 40`）
+pub fn hex_decode(s: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if let Some(&c) = digits.iter().find(|b| !b.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex digit: {:?}", c as char));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err("invalid hex: odd number of digits".to_string());
+    }
+
+    Ok(digits
+        .chunks(2)
+        .map(|pair| (hex_value(pair[0]) << 4) | hex_value(pair[1]))
+        .collect())
+}
+
+/// 单个十六进制数字的值（调用前已校验）
+fn hex_value(digit: u8) -> u8 {
+    (digit as char).to_digit(16).unwrap_or(0) as u8
+}
+
+/// 中文大写数字
+const ZH_DIGITS: [&str; 10] = ["零", "壹", "贰", "叁", "肆", "伍", "陆", "柒", "捌", "玖"];
+
+/// 中文大写金额支持的最大值（不含万亿）
+const ZH_MAX_AMOUNT: f64 = 1e12;
+
+/// 英文金额支持的最大值（不含 quadrillion）
+const EN_MAX_AMOUNT: f64 = 1e15;
+
+/// 将金额转换为文字，小数部分按分（两位）四舍五入
+///
+/// - `zh`：中文大写金额，如 `壹仟贰佰叁拾肆元伍角陆分`
+/// - `en`：英文单词，如 `one thousand two hundred thirty-four and fifty-six cents`
+pub fn number_to_words(amount: f64, lang: &str) -> Result<String, String> {
+    let chinese = match lang.to_lowercase().as_str() {
+        "zh" => true,
+        "en" => false,
+        _ => return Err(format!("unsupported language: {}", lang)),
+    };
+    let max = if chinese {
+        ZH_MAX_AMOUNT
+    } else {
+        EN_MAX_AMOUNT
+    };
+    if !amount.is_finite() || amount.abs() >= max {
+        return Err(format!("amount out of range: {}", amount));
+    }
+
+    let cents = (amount.abs() * 100.0).round() as u64;
+    let (integer, fraction) = (cents / 100, cents % 100);
+    let negative = amount < 0.0 && cents > 0;
+
+    Ok(if chinese {
+        let words = zh_amount(integer, fraction);
+        if negative {
+            format!("负{}", words)
+        } else {
+            words
+        }
+    } else {
+        let words = en_amount(integer, fraction);
+        if negative {
+            format!("minus {}", words)
+        } else {
+            words
+        }
+    })
+}
+
+/// 中文大写金额（元、角、分）
+fn zh_amount(integer: u64, fraction: u64) -> String {
+    const UNITS: [&str; 4] = ["", "拾", "佰", "仟"];
+    const SECTIONS: [&str; 3] = ["", "万", "亿"];
+
+    let (jiao, fen) = ((fraction / 10) as usize, (fraction % 10) as usize);
+    let mut out = String::new();
+
+    if integer > 0 {
+        let digits: Vec<usize> = integer
+            .to_string()
+            .bytes()
+            .map(|b| (b - b'0') as usize)
+            .collect();
+        let mut zero = false;
+        let mut section_has_digit = false;
+
+        for (i, &d) in digits.iter().enumerate() {
+            let pos = digits.len() - 1 - i;
+            let (section, unit) = (pos / 4, pos % 4);
+            if d == 0 {
+                zero = true;
+            } else {
+                if zero {
+                    out.push_str(ZH_DIGITS[0]);
+                    zero = false;
+                }
+                out.push_str(ZH_DIGITS[d]);
+                out.push_str(UNITS[unit]);
+                section_has_digit = true;
+            }
+            // 每四位一节，节内有非零数字时加上万、亿
+            if unit == 0 && section > 0 {
+                if section_has_digit {
+                    out.push_str(SECTIONS[section]);
+                }
+                section_has_digit = false;
+            }
+        }
+        out.push('元');
+    }
+
+    match (jiao, fen) {
+        (0, 0) if integer == 0 => "零元整".to_string(),
+        (0, 0) => out + "整",
+        _ => {
+            if jiao > 0 {
+                out.push_str(ZH_DIGITS[jiao]);
+                out.push('角');
+            } else if integer > 0 {
+                out.push_str(ZH_DIGITS[0]);
+            }
+            if fen > 0 {
+                out.push_str(ZH_DIGITS[fen]);
+                out.push('分');
+            }
+            out
+        }
+    }
 }
 
-/// 简化的时间格式化（不引入 chrono 依赖）
-fn chrono_lite_format(secs: i64) -> String {
-    // 简单的时间格式化：YYYY-MM-DD HH:MM:SS
-    // 生产环境应该使用 chrono 库
-    let days_since_epoch = secs / 86400;
-    let time_of_day = secs % 86400;
+/// 英文金额（整数部分 and 分）
+fn en_amount(integer: u64, fraction: u64) -> String {
+    let words = en_number(integer);
+    match fraction {
+        0 => words,
+        1 => format!("{} and one cent", words),
+        _ => format!("{} and {} cents", words, en_number(fraction)),
+    }
+}
 
-    let hours = time_of_day / 3600;
-    let minutes = (time_of_day % 3600) / 60;
-    let seconds = time_of_day % 60;
+/// 英文数字（小于 10^15）
+fn en_number(n: u64) -> String {
+    const ONES: [&str; 20] = [
+        "zero",
+        "one",
+        "two",
+        "three",
+        "four",
+        "five",
+        "six",
+        "seven",
+        "eight",
+        "nine",
+        "ten",
+        "eleven",
+        "twelve",
+        "thirteen",
+        "fourteen",
+        "fifteen",
+        "sixteen",
+        "seventeen",
+        "eighteen",
+        "nineteen",
+    ];
+    const TENS: [&str; 10] = [
+        "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+    ];
+    const SCALES: [&str; 5] = ["", "thousand", "million", "billion", "trillion"];
+
+    // 1000 以内
+    let below_thousand = |n: u64| {
+        let mut parts = Vec::new();
+        if n >= 100 {
+            parts.push(format!("{} hundred", ONES[(n / 100) as usize]));
+        }
+        match n % 100 {
+            0 => {}
+            r @ 1..=19 => parts.push(ONES[r as usize].to_string()),
+            r => {
+                let (tens, ones) = ((r / 10) as usize, (r % 10) as usize);
+                parts.push(match ones {
+                    0 => TENS[tens].to_string(),
+                    _ => format!("{}-{}", TENS[tens], ONES[ones]),
+                });
+            }
+        }
+        parts.join(" ")
+    };
 
-    // 简化的日期计算（从 1970-01-01 开始）
-    let mut year = 1970i64;
-    let mut remaining_days = days_since_epoch;
+    if n == 0 {
+        return ONES[0].to_string();
+    }
 
-    loop {
-        let days_in_year = if is_leap_year(year) { 366 } else { 365 };
-        if remaining_days < days_in_year {
+    let mut groups = Vec::new();
+    let mut rest = n;
+    for scale in SCALES {
+        let group = rest % 1000;
+        if group > 0 {
+            let words = below_thousand(group);
+            groups.push(if scale.is_empty() {
+                words
+            } else {
+                format!("{} {}", words, scale)
+            });
+        }
+        rest /= 1000;
+        if rest == 0 {
             break;
         }
-        remaining_days -= days_in_year;
-        year += 1;
     }
+    groups.reverse();
+    groups.join(" ")
+}
 
-    let days_in_months: [i64; 12] = if is_leap_year(year) {
-        [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
-    } else {
-        [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+/// 统一换行符（lf / crlf / cr）
+pub fn normalize_line_endings(text: &str, line_ending: &str) -> Result<String, String> {
+    let newline = match line_ending.to_lowercase().as_str() {
+        "lf" => "\n",
+        "crlf" => "\r\n",
+        "cr" => "\r",
+        _ => return Err(format!("Unknown line ending: {}", line_ending)),
     };
 
-    let mut month = 1;
-    for days in days_in_months {
-        if remaining_days < days {
-            break;
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    Ok(if newline == "\n" {
+        text
+    } else {
+        text.replace('\n', newline)
+    })
+}
+
+/// 去掉 ANSI 转义序列（ESC [ ... 结束字节）和控制字符，保留换行、回车和制表符
+pub fn sanitize_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            // CSI 序列：参数和中间字节直到 0x40-0x7E 的结束字节
+            chars.next();
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        } else if !c.is_control() || matches!(c, '\n' | '\r' | '\t') {
+            result.push(c);
         }
-        remaining_days -= days;
-        month += 1;
     }
+    result
+}
 
-    let day = remaining_days + 1;
-
-    format!(
-        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
-        year, month, day, hours, minutes, seconds
-    )
+/// 限制总大小的渲染输出，超过上限时写入失败，渲染随即中止（无需渲染完整结果后再检查）
+struct LimitedOutput {
+    buffer: Vec<u8>,
+    max_size: usize,
+    exceeded: bool,
 }
 
-fn is_leap_year(year: i64) -> bool {
-    (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0)
+impl Write for LimitedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.buffer.len() + buf.len() > self.max_size {
+            self.exceeded = true;
+            return Err(std::io::Error::other("render size limit exceeded"));
+        }
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 
-/// 渲染模板
+/// 渲染模板（使用默认的 helper profile、小票宽度和渲染大小上限）
+#[cfg(test)]
 pub fn render_template(template: &str, data: &Value) -> Result<String, String> {
-    let hbs = create_handlebars();
+    render_template_with_profile(template, data, &HelperProfile::default())
+}
+
+/// 使用指定的 helper profile 渲染模板（使用默认的小票宽度和渲染大小上限）
+#[cfg(test)]
+pub fn render_template_with_profile(
+    template: &str,
+    data: &Value,
+    profile: &HelperProfile,
+) -> Result<String, String> {
+    render_template_limited(
+        template,
+        data,
+        DEFAULT_RECEIPT_WIDTH,
+        DEFAULT_MAX_RENDER_SIZE,
+        profile,
+    )
+    .map_err(|e| e.message)
+}
+
+/// 渲染模板，结果超过 `max_size` 字节时中止并返回 `RENDER_TOO_LARGE`
+pub fn render_template_limited(
+    template: &str,
+    data: &Value,
+    receipt_width: usize,
+    max_size: usize,
+    profile: &HelperProfile,
+) -> Result<String, PrintError> {
+    let hbs = create_handlebars(receipt_width, max_size, profile);
+    let mut output = LimitedOutput {
+        buffer: Vec::new(),
+        max_size,
+        exceeded: false,
+    };
 
-    let result = hbs
-        .render_template(template, data)
-        .map_err(|e| format!("Template render error: {}", e))?;
+    if let Err(e) = hbs.render_template_to_write(template, data, &mut output) {
+        if output.exceeded {
+            warn!("Rendered output exceeds {} bytes, aborted", max_size);
+            return Err(PrintError::new(
+                RENDER_TOO_LARGE,
+                format!("Rendered output exceeds the limit of {} bytes", max_size),
+            ));
+        }
+        return Err(format!("Template render error: {}", e).into());
+    }
 
+    // handlebars 按字符串片段写入，结果总是有效的 UTF-8
+    let result = String::from_utf8(output.buffer).map_err(|e| e.to_string())?;
     debug!("Rendered template: {} bytes", result.len());
     Ok(result)
 }
 
+/// 渲染模板并返回耗时（用于性能调优）
+pub fn render_template_timed(
+    template: &str,
+    data: &Value,
+    receipt_width: usize,
+    max_size: usize,
+    profile: &HelperProfile,
+) -> Result<(String, Duration), PrintError> {
+    let start = Instant::now();
+    let result = render_template_limited(template, data, receipt_width, max_size, profile)?;
+    Ok((result, start.elapsed()))
+}
+
 /// ESC/POS 命令构建器
 #[allow(dead_code)]
 pub mod escpos {
@@ -297,8 +1717,21 @@ pub mod escpos {
         vec![0x1B, b'B', times, duration]
     }
 
-    /// 构建简单的小票
+    /// 构建简单的小票（58mm 纸，每行 32 个字符）
     pub fn build_receipt(title: &str, items: &[(String, f64)], total: f64) -> Vec<u8> {
+        build_receipt_with_width(title, items, total, super::DEFAULT_RECEIPT_WIDTH)
+    }
+
+    /// 按小票宽度（每行字符数）构建简单的小票，价格右对齐到行尾
+    pub fn build_receipt_with_width(
+        title: &str,
+        items: &[(String, f64)],
+        total: f64,
+        width: usize,
+    ) -> Vec<u8> {
+        // 价格占 10 列，与名称之间空一列，行尾留一列避免打印机自动换行
+        let name_width = width.saturating_sub(12).max(1);
+        let separator = format!("{}\n", "-".repeat(width));
         let mut data = Vec::new();
 
         // 初始化
@@ -313,20 +1746,29 @@ pub mod escpos {
 
         // 分隔线
         data.extend_from_slice(ALIGN_LEFT);
-        data.extend_from_slice(b"--------------------------------\n");
+        data.extend_from_slice(separator.as_bytes());
 
-        // 商品列表
+        // 商品列表（名称超过名称列宽时换行，价格保持在首行对齐）
         for (name, price) in items {
-            let line = format!("{:<20} {:>10.2}\n", name, price);
+            let wrapped = super::wrap_text(name, name_width, 2);
+            let mut lines = wrapped.lines();
+            let first = lines.next().unwrap_or_default();
+            let padding = " ".repeat(name_width.saturating_sub(super::display_width(first)));
+            let line = format!("{}{} {:>10.2}\n", first, padding, price);
             data.extend_from_slice(line.as_bytes());
+            for rest in lines {
+                data.extend_from_slice(rest.as_bytes());
+                data.push(b'\n');
+            }
         }
 
         // 分隔线
-        data.extend_from_slice(b"--------------------------------\n");
+        data.extend_from_slice(separator.as_bytes());
 
         // 合计
         data.extend_from_slice(BOLD_ON);
-        let total_line = format!("{:<20} {:>10.2}\n", "合计", total);
+        let padding = " ".repeat(name_width.saturating_sub(super::display_width("合计")));
+        let total_line = format!("合计{} {:>10.2}\n", padding, total);
         data.extend_from_slice(total_line.as_bytes());
         data.extend_from_slice(BOLD_OFF);
 
@@ -340,6 +1782,10 @@ pub mod escpos {
 /// ZPL 命令构建器
 #[allow(dead_code)]
 pub mod zpl {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
     /// 标签开始
     pub const LABEL_START: &str = "^XA";
 
@@ -356,16 +1802,215 @@ pub mod zpl {
         format!("^FO{},{}", x, y)
     }
 
+    /// 字段旋转方向（^A 的方向参数）
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Rotation {
+        /// 正常 (N)
+        Normal,
+        /// 顺时针旋转 90 度 (R)
+        Rotated90,
+        /// 旋转 180 度 (I)
+        Inverted180,
+        /// 旋转 270 度，自下而上 (B)
+        Bottom270,
+    }
+
+    impl Rotation {
+        /// 按角度创建（仅支持 0/90/180/270）
+        pub fn from_degrees(degrees: u32) -> Option<Self> {
+            match degrees % 360 {
+                0 => Some(Self::Normal),
+                90 => Some(Self::Rotated90),
+                180 => Some(Self::Inverted180),
+                270 => Some(Self::Bottom270),
+                _ => None,
+            }
+        }
+
+        /// ZPL 方向代码
+        pub fn code(self) -> char {
+            match self {
+                Self::Normal => 'N',
+                Self::Rotated90 => 'R',
+                Self::Inverted180 => 'I',
+                Self::Bottom270 => 'B',
+            }
+        }
+    }
+
     /// 字体设置（字体名，高度，宽度）
     pub fn font(name: char, height: u32, width: u32) -> String {
         format!("^A{},{},{}", name, height, width)
     }
 
+    /// 打印机内置字体
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct ZplFont {
+        /// 字体名（用于 ^A）
+        pub name: char,
+        /// 字体说明
+        pub description: &'static str,
+        /// 基准高度（点，8 点/毫米）
+        pub height: u32,
+        /// 基准宽度（点，8 点/毫米）
+        pub width: u32,
+        /// 是否可任意缩放（点阵字体只能按基准尺寸的整数倍放大）
+        pub scalable: bool,
+    }
+
+    /// Zebra ZPL 打印机的标准内置字体（A-H 点阵字体及可缩放字体 0）
+    pub const RESIDENT_FONTS: &[ZplFont] = &[
+        resident_font('A', "Bitmap", 9, 5),
+        resident_font('B', "Bitmap, uppercase only", 11, 7),
+        resident_font('C', "Bitmap", 18, 10),
+        resident_font('D', "Bitmap", 18, 10),
+        resident_font('E', "OCR-B", 28, 15),
+        resident_font('F', "Bitmap", 26, 13),
+        resident_font('G', "Bitmap", 60, 40),
+        resident_font('H', "OCR-A, uppercase only", 21, 13),
+        ZplFont {
+            name: '0',
+            description: "Scalable (CG Triumvirate Bold Condensed)",
+            height: 15,
+            width: 12,
+            scalable: true,
+        },
+    ];
+
+    const fn resident_font(
+        name: char,
+        description: &'static str,
+        height: u32,
+        width: u32,
+    ) -> ZplFont {
+        ZplFont {
+            name,
+            description,
+            height,
+            width,
+            scalable: false,
+        }
+    }
+
+    /// 带旋转方向的字体设置（字体名，方向，高度，宽度）
+    pub fn font_rotated(name: char, rotation: Rotation, height: u32, width: u32) -> String {
+        format!("^A{}{},{},{}", name, rotation.code(), height, width)
+    }
+
     /// 字段数据
     pub fn field_data(text: &str) -> String {
         format!("^FD{}^FS", text)
     }
 
+    /// 字段数据（^FD ... ^FS），内容含指令前缀 `^`、`~` 时使用 ^FH 十六进制转义，避免数据被当作指令执行
+    pub fn escaped_field_data(text: &str) -> String {
+        if !text.contains(['^', '~']) {
+            return field_data(text);
+        }
+        let mut escaped = String::with_capacity(text.len() + 8);
+        for c in text.chars() {
+            match c {
+                // ^FH 默认以 `_` 作为转义字符，本身也需要转义
+                '^' | '~' | '_' => escaped.push_str(&format!("_{:02X}", c as u32)),
+                _ => escaped.push(c),
+            }
+        }
+        format!("^FH{}", field_data(&escaped))
+    }
+
+    /// 统一 ZPL 的换行符：指令之间的 CRLF、CR 转换为 LF，字段数据（`^FD`、`^FV` 到下一个指令）中的 CR 直接去掉
+    ///
+    /// 在 Windows 上编辑的模板经串口发送时，字段数据中残留的 `\r` 会被打印成乱码；`^FS` 等指令保持不变
+    pub fn normalize_line_endings(zpl: &str) -> String {
+        let mut result = String::with_capacity(zpl.len());
+        let mut in_field = false;
+        let mut chars = zpl.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '^' => {
+                    let command = zpl[i + 1..].get(..2).unwrap_or_default();
+                    in_field = matches!(command.to_ascii_uppercase().as_str(), "FD" | "FV");
+                    result.push(c);
+                }
+                '\r' if in_field => {}
+                '\r' => {
+                    if chars.peek().map(|&(_, next)| next) != Some('\n') {
+                        result.push('\n');
+                    }
+                }
+                _ => result.push(c),
+            }
+        }
+        result
+    }
+
+    /// 字段块中文本的对齐方式（^FB 的对齐参数）
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Justify {
+        /// 左对齐 (L)
+        Left,
+        /// 居中 (C)
+        Center,
+        /// 右对齐 (R)
+        Right,
+        /// 两端对齐 (J)
+        Justified,
+    }
+
+    impl Justify {
+        /// 按名称创建（left/center/right/justified，不区分大小写，也接受 ZPL 代码 L/C/R/J）
+        pub fn from_name(name: &str) -> Option<Self> {
+            match name.to_ascii_lowercase().as_str() {
+                "left" | "l" => Some(Self::Left),
+                "center" | "c" => Some(Self::Center),
+                "right" | "r" => Some(Self::Right),
+                "justified" | "justify" | "j" => Some(Self::Justified),
+                _ => None,
+            }
+        }
+
+        /// ZPL 对齐代码
+        pub fn code(self) -> char {
+            match self {
+                Self::Left => 'L',
+                Self::Center => 'C',
+                Self::Right => 'R',
+                Self::Justified => 'J',
+            }
+        }
+    }
+
+    /// 字段块（宽度，最多行数，行间距，对齐方式），文本在宽度内自动换行，超过最多行数的部分会覆盖最后一行
+    ///
+    /// 放在 ^FD 之前；字段数据中的 `\&` 为强制换行
+    pub fn field_block(width: u32, max_lines: u32, line_spacing: i32, justify: Justify) -> String {
+        format!(
+            "^FB{},{},{},{}",
+            width,
+            max_lines.max(1),
+            line_spacing,
+            justify.code()
+        )
+    }
+
+    /// 旋转文本字段（原点 + 旋转字体 + 字段数据）
+    pub fn rotated_text(
+        x: u32,
+        y: u32,
+        font_name: char,
+        rotation: Rotation,
+        height: u32,
+        width: u32,
+        text: &str,
+    ) -> String {
+        format!(
+            "{}{}{}",
+            field_origin(x, y),
+            font_rotated(font_name, rotation, height, width),
+            field_data(text)
+        )
+    }
+
     /// 条形码 Code 128
     pub fn barcode_128(x: u32, y: u32, height: u32, data: &str) -> String {
         format!("^FO{},{}^BY2^BCN,{},Y,N,N^FD{}^FS", x, y, height, data)
@@ -379,6 +2024,81 @@ pub mod zpl {
         )
     }
 
+    /// 存储格式名称是否合法（不能为空，且不能包含 ZPL 控制字符）
+    pub fn is_valid_format_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+    }
+
+    /// 存储格式路径：未指定存储设备时使用 `R:`（内存），未指定扩展名时使用 `.ZPL`
+    pub fn format_path(name: &str) -> String {
+        object_path(name, ".ZPL")
+    }
+
+    /// 图形路径：未指定存储设备时使用 `R:`（内存），未指定扩展名时使用 `.GRF`
+    pub fn graphic_path(name: &str) -> String {
+        object_path(name, ".GRF")
+    }
+
+    /// 打印机存储对象的路径，补全默认的存储设备和扩展名
+    fn object_path(name: &str, extension: &str) -> String {
+        let mut path = if name.contains(':') {
+            name.to_string()
+        } else {
+            format!("R:{}", name)
+        };
+        if !name.contains('.') {
+            path.push_str(extension);
+        }
+        path
+    }
+
+    /// 下载存储格式（^DF），body 中用 `^FN<n>` 声明可变字段
+    pub fn download_format(name: &str, body: &str) -> String {
+        let body = body.trim();
+        let body = body.strip_prefix(LABEL_START).unwrap_or(body);
+        let body = body.strip_suffix(LABEL_END).unwrap_or(body);
+        format!(
+            "{}^DF{}^FS\n{}\n{}",
+            LABEL_START,
+            format_path(name),
+            body.trim(),
+            LABEL_END
+        )
+    }
+
+    /// 调用存储格式（^XF），按字段号填充字段数据（含 `^`、`~` 的内容以 ^FH 转义）
+    pub fn recall_format(name: &str, fields: &BTreeMap<u32, String>) -> String {
+        let mut zpl = format!("{}^XF{}^FS\n", LABEL_START, format_path(name));
+        for (number, value) in fields {
+            zpl.push_str(&format!("^FN{}{}\n", number, escaped_field_data(value)));
+        }
+        zpl.push_str(LABEL_END);
+        zpl
+    }
+
+    /// 下载图形（~DG）：PNG/BMP 图片转换为单色点阵，灰度和彩色图片自动抖动
+    pub fn download_graphic(name: &str, image: &[u8]) -> Result<String, String> {
+        if !is_valid_format_name(name) {
+            return Err(format!("Invalid graphic name: {}", name));
+        }
+        let bitmap = crate::graphics::to_monochrome(image)?;
+        Ok(format!(
+            "~DG{},{},{},{}",
+            graphic_path(name),
+            bitmap.data.len(),
+            bitmap.bytes_per_row(),
+            super::hex_encode(&bitmap.data).to_uppercase()
+        ))
+    }
+
+    /// 在指定位置打印已下载的图形（^XG，原始大小）
+    pub fn recall_graphic(x: u32, y: u32, name: &str) -> String {
+        format!("{}^XG{},1,1^FS", field_origin(x, y), graphic_path(name))
+    }
+
     /// 构建简单的标签
     pub fn build_label(
         product_name: &str,
@@ -422,6 +2142,7 @@ pub mod zpl {
 mod tests {
     use super::*;
     use serde_json::json;
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_render_simple() {
@@ -439,6 +2160,44 @@ mod tests {
         assert!(result.contains("Order: 12345"));
     }
 
+    #[test]
+    fn test_render_template_timed() {
+        let data = json!({"name": "World"});
+        let (width, max_size) = (DEFAULT_RECEIPT_WIDTH, DEFAULT_MAX_RENDER_SIZE);
+        let profile = HelperProfile::default();
+        let (result, elapsed) =
+            render_template_timed("Hello, {{name}}!", &data, width, max_size, &profile).unwrap();
+        assert_eq!(result, "Hello, World!");
+        assert!(elapsed > Duration::ZERO);
+        assert!(render_template_timed("{{#if}}", &data, width, max_size, &profile).is_err());
+    }
+
+    #[test]
+    fn test_separator_helper() {
+        let data = json!({});
+
+        let result = render_template("{{hr}}", &data).unwrap();
+        assert_eq!(result, "-".repeat(32));
+
+        let profile = HelperProfile::default();
+        let render_with_width = |template: &str, width: usize| {
+            render_template_limited(template, &data, width, DEFAULT_MAX_RENDER_SIZE, &profile)
+                .unwrap()
+        };
+
+        let result = render_with_width("{{hr}}|{{hr \"=\"}}", 48);
+        assert_eq!(result, format!("{}|{}", "-".repeat(48), "=".repeat(48)));
+
+        // 显式宽度优先于小票宽度
+        let result = render_with_width("{{separator \"*\" 32}}", 48);
+        assert_eq!(result, "*".repeat(32));
+
+        // 宽字符按 2 列计算
+        let result = render_with_width("{{separator \"＝\"}}", 48);
+        assert_eq!(result, "＝".repeat(24));
+        assert_eq!(render_with_width("{{separator \"-=\" 5}}", 48), "-=-=");
+    }
+
     #[test]
     fn test_currency_helper() {
         let template = "Total: {{currency total}}";
@@ -447,6 +2206,73 @@ mod tests {
         assert_eq!(result, "Total: ¥128.50");
     }
 
+    #[test]
+    fn test_helper_profile() {
+        let profile = HelperProfile {
+            currency_symbol: "$".to_string(),
+            currency_decimals: 1,
+            currency_rounding: Some(Rounding::HalfEven),
+            utc_offset_minutes: 480,
+        };
+        let data = json!({"total": 0.25, "ts": 0});
+        let template = "{{currency total}}|{{date_format ts \"\"}}";
+        let result = render_template_with_profile(template, &data, &profile).unwrap();
+        assert_eq!(result, "$0.2|1970-01-01 08:00:00");
+
+        let profile: HelperProfile = serde_json::from_value(json!({
+            "currency_symbol": "HK$",
+            "currency_rounding": "half_up"
+        }))
+        .unwrap();
+        assert_eq!(profile.format_currency(2.675), "HK$2.68");
+        assert_eq!(HelperProfile::default().format_currency(-3.0), "¥-3.00");
+    }
+
+    #[test]
+    fn test_uuid_and_now_helpers() {
+        let template = "{{uuid}}|{{now}}|{{now \"%Y%m%d-%H%M%S %%\"}}";
+        fix_generated(Some((
+            "00000000-0000-4000-8000-000000000000",
+            86_400_000 + 3_723_000,
+        )));
+        let result = render_template(template, &json!({})).unwrap();
+        assert_eq!(
+            result,
+            "00000000-0000-4000-8000-000000000000|1970-01-02 01:02:03|19700102-010203 %"
+        );
+
+        // 未固定时每次生成不同的 UUID
+        fix_generated(None);
+        let first = render_template("{{uuid}}", &json!({})).unwrap();
+        assert_eq!(uuid::Uuid::parse_str(&first).unwrap().get_version_num(), 4);
+        assert_ne!(first, render_template("{{uuid}}", &json!({})).unwrap());
+        let year: i32 = render_template("{{now \"%Y\"}}", &json!({}))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((2024..10000).contains(&year));
+    }
+
+    #[test]
+    fn test_json_helpers() {
+        let data = json!({"order": {"no": "A^1~", "note": "<b>&</b>", "qty": 2}});
+        let result = render_template("^FDQA,{{json order}}^FS", &data).unwrap();
+        assert_eq!(
+            result,
+            r#"^FDQA,{"no":"A\u005e1\u007e","note":"\u003cb\u003e\u0026\u003c/b\u003e","qty":2}^FS"#
+        );
+
+        // 转义后仍是等价的 JSON
+        let json = result.trim_start_matches("^FDQA,").trim_end_matches("^FS");
+        assert_eq!(serde_json::from_str::<Value>(json).unwrap(), data["order"]);
+
+        let data = json!({"items": [{"a": 1}]});
+        let result = render_template("{{json_pretty (index items 0)}}", &data).unwrap();
+        assert_eq!(result, "{\n  \"a\": 1\n}");
+        let result = render_template("{{json missing}}", &json!({})).unwrap();
+        assert_eq!(result, "null");
+    }
+
     #[test]
     fn test_format_number_helper() {
         let template = "Value: {{format_number value 2}}";
@@ -471,6 +2297,229 @@ mod tests {
         assert_eq!(result, "----------");
     }
 
+    #[test]
+    fn test_sanitize_text() {
+        assert_eq!(sanitize_text("Total:\t9.90\r\n"), "Total:\t9.90\r\n");
+        assert_eq!(sanitize_text("\x1b[31mRed\x1b[0m text"), "Red text");
+        // ESC/POS 指令的控制字节被去掉，剩余的可打印字符原样保留
+        assert_eq!(sanitize_text("A\x1b@B\x1dV\x00C\x07"), "A@BVC");
+        assert_eq!(sanitize_text("删除\x7f\u{85}中文"), "删除中文");
+    }
+
+    #[test]
+    fn test_render_size_limit() {
+        let data = json!({"items": [1, 2, 3]});
+        let profile = HelperProfile::default();
+        let render = |template: &str, max_size: usize| {
+            render_template_limited(template, &data, 32, max_size, &profile)
+        };
+        let result = render("{{#each items}}ab{{/each}}", 6).unwrap();
+        assert_eq!(result, "ababab");
+
+        let error = render("{{#each items}}ab{{/each}}", 5).unwrap_err();
+        assert_eq!(error.code, RENDER_TOO_LARGE);
+
+        // 超大的 repeat 在达到上限时即中止，不会先生成完整结果
+        let error = render("{{repeat \"x\" 1000000000000}}", 1024).unwrap_err();
+        assert_eq!(error.code, RENDER_TOO_LARGE);
+        for template in [
+            "{{separator \"=\" 1000000000000}}",
+            "{{pad_left \"1\" 1000000000000 \"0\"}}",
+            "{{pad_right \"1\" 1000000000000 \" \"}}",
+            "{{replace (repeat \"a\" 100) \"a\" (repeat \"b\" 100)}}",
+        ] {
+            let error = render(template, 1024).unwrap_err();
+            assert_eq!(error.code, RENDER_TOO_LARGE, "{}", template);
+        }
+
+        // 子表达式的输出不经过大小上限，helper 自身限制生成的长度
+        let result = render("{{truncate (repeat \"x\" 1000000000000) 3}}", 1024).unwrap();
+        assert_eq!(result, "xxx");
+
+        // 其他渲染错误不受影响
+        let error = render("{{#each}}", 1024).unwrap_err();
+        assert_ne!(error.code, RENDER_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(
+            wrap_text("Organic whole milk 1L", 10, 2),
+            "Organic\n  whole\n  milk 1L"
+        );
+        // 中文按 2 列计算
+        assert_eq!(wrap_text("有机全脂牛奶", 8, 0), "有机全脂\n牛奶");
+        // 超长单词强制截断
+        assert_eq!(wrap_text("ABCDEFGHIJ", 4, 1), "ABCD\n EFG\n HIJ");
+        // 不需要换行
+        assert_eq!(wrap_text("Tea", 10, 2), "Tea");
+    }
+
+    #[test]
+    fn test_string_helpers() {
+        let data = json!({"sku": "FR-1001", "order": 20240101, "note": null});
+        let render = |template: &str| render_template(template, &data).unwrap();
+
+        assert_eq!(
+            render("{{#if (starts_with sku \"FR\")}}frozen{{else}}other{{/if}}"),
+            "frozen"
+        );
+        assert_eq!(render("{{#if (starts_with sku \"fr\")}}frozen{{/if}}"), "");
+        assert_eq!(render("{{starts_with order 2024}}"), "true");
+        assert_eq!(render("{{#if (ends_with sku \"1001\")}}yes{{/if}}"), "yes");
+        assert_eq!(render("{{ends_with note \"x\"}}"), "false");
+        assert_eq!(render("{{#if (contains sku \"-\")}}yes{{/if}}"), "yes");
+        assert_eq!(render("{{#unless (contains x \"-\")}}no{{/unless}}"), "no");
+
+        assert_eq!(render("{{replace sku \"-\" \"\"}}"), "FR1001");
+        assert_eq!(render("{{replace \"a.b.c\" \".\" \"/\"}}"), "a/b/c");
+        assert_eq!(render("{{replace order 0 9}}"), "29249191");
+        assert_eq!(render("{{replace sku \"\" \"x\"}}"), "FR-1001");
+        assert_eq!(render("[{{replace note \"a\" \"b\"}}]"), "[]");
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Order #1024"), "Order_#1024");
+        assert_eq!(slugify("  a \t b  "), "a_b");
+        assert_eq!(slugify("张三 / 订单"), "张三_订单");
+        assert_eq!(slugify("..\\..\\etc/passwd"), "etc_passwd");
+        assert_eq!(slugify("a:b*c?\"d<e>f|g"), "a_b_c_d_e_f_g");
+        assert_eq!(slugify("report."), "report");
+        assert_eq!(slugify("con.txt"), "_con.txt");
+        assert_eq!(slugify("///"), "");
+        assert_eq!(slugify(&"长".repeat(100)).chars().count(), MAX_SLUG_CHARS);
+
+        let result = render_template("{{slugify name}}.pdf", &json!({"name": "李四 A/B"}));
+        assert_eq!(result.unwrap(), "李四_A_B.pdf");
+    }
+
+    #[test]
+    fn test_format_phone() {
+        assert_eq!(
+            format_phone("13812345678", "###-####-####"),
+            "138-1234-5678"
+        );
+        assert_eq!(
+            format_phone("(021) 6123-4567", "### ########"),
+            "021 61234567"
+        );
+        // 数字个数不符时原样返回
+        assert_eq!(format_phone("12345", "###-####-####"), "12345");
+        assert_eq!(format_phone("", "###"), "");
+
+        let data = json!({"phone": 13812345678u64});
+        let result = render_template("{{format_phone phone \"### #### ####\"}}", &data);
+        assert_eq!(result.unwrap(), "138 1234 5678");
+    }
+
+    #[test]
+    fn test_mask() {
+        assert_eq!(mask("4111111111111111", 4), "************1111");
+        assert_eq!(mask("6222020200112233", 0), "****************");
+        assert_eq!(mask("张三丰", 1), "**丰");
+        // 过短的值最多显示一半，不会完整显示
+        assert_eq!(mask("1234", 4), "**34");
+        assert_eq!(mask("123", 4), "**3");
+        assert_eq!(mask("1", 4), "*");
+        assert_eq!(mask("", 4), "");
+
+        let data = json!({"card": "4111111111111111", "cvv": 123});
+        let result = render_template("{{mask card 4}} {{mask cvv 4}}", &data);
+        assert_eq!(result.unwrap(), "************1111 **3");
+    }
+
+    #[test]
+    fn test_encoding_helpers() {
+        let data = json!({"text": "取餐码 A1", "b64": "5Y+W6aSQ56CBIEEx", "hex": "1B 40 6f6B"});
+        let render = |template: &str| render_template(template, &data);
+
+        assert_eq!(
+            render("{{base64_encode text}}").unwrap(),
+            "5Y+W6aSQ56CBIEEx"
+        );
+        assert_eq!(render("{{base64_decode b64}}").unwrap(), "取餐码 A1");
+        assert_eq!(render("{{hex_decode hex}}").unwrap(), "\x1B@ok");
+        assert_eq!(
+            render("{{hex_encode (hex_decode hex)}}").unwrap(),
+            "1b406f6b"
+        );
+
+        assert_eq!(hex_decode("1B40").unwrap(), [0x1B, 0x40]);
+        assert!(hex_decode("1B4").is_err());
+        assert!(hex_decode("+1").is_err());
+
+        // 无效输入时渲染失败，而不是输出空字符串
+        assert!(render("{{base64_decode \"not base64!\"}}").is_err());
+        assert!(render("{{hex_decode \"zz\"}}").is_err());
+        assert!(render("{{hex_decode \"ff\"}}").is_err());
+        assert!(render("{{base64_decode missing}}").is_err());
+    }
+
+    #[test]
+    fn test_number_to_words_zh() {
+        let zh = |v: f64| number_to_words(v, "zh").unwrap();
+        assert_eq!(zh(1234.56), "壹仟贰佰叁拾肆元伍角陆分");
+        assert_eq!(zh(0.0), "零元整");
+        assert_eq!(zh(100.0), "壹佰元整");
+        assert_eq!(zh(1005.0), "壹仟零伍元整");
+        assert_eq!(zh(10.05), "壹拾元零伍分");
+        assert_eq!(zh(0.5), "伍角");
+        assert_eq!(zh(0.05), "伍分");
+        assert_eq!(zh(100000.0), "壹拾万元整");
+        assert_eq!(zh(100010000.0), "壹亿零壹万元整");
+        assert_eq!(zh(100000001.0), "壹亿零壹元整");
+        assert_eq!(zh(-12.3), "负壹拾贰元叁角");
+        assert!(number_to_words(1e12, "zh").is_err());
+    }
+
+    #[test]
+    fn test_number_to_words_en() {
+        let en = |v: f64| number_to_words(v, "en").unwrap();
+        assert_eq!(
+            en(1234.56),
+            "one thousand two hundred thirty-four and fifty-six cents"
+        );
+        assert_eq!(en(0.0), "zero");
+        assert_eq!(en(0.01), "zero and one cent");
+        assert_eq!(en(2_000_015.0), "two million fifteen");
+        assert_eq!(en(-40.0), "minus forty");
+        assert!(number_to_words(1.0, "fr").is_err());
+        assert!(number_to_words(f64::NAN, "en").is_err());
+
+        let result = render_template(
+            "{{number_to_words total lang=\"zh\"}}",
+            &json!({"total": "88.8"}),
+        );
+        assert_eq!(result.unwrap(), "捌拾捌元捌角");
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        let text = "a\nb\r\nc\rd";
+        assert_eq!(normalize_line_endings(text, "lf").unwrap(), "a\nb\nc\nd");
+        assert_eq!(
+            normalize_line_endings(text, "CRLF").unwrap(),
+            "a\r\nb\r\nc\r\nd"
+        );
+        assert_eq!(normalize_line_endings(text, "cr").unwrap(), "a\rb\rc\rd");
+        assert!(normalize_line_endings(text, "crcr").is_err());
+    }
+
+    #[test]
+    fn test_wrap_helper() {
+        let template = "{{#each items}}{{wrap name 12 indent=1}}|{{/each}}";
+        let data = json!({"items": [{"name": "Green tea 500ml bottle"}]});
+        let result = render_template(template, &data).unwrap();
+        assert_eq!(result, "Green tea\n 500ml\n bottle|");
+
+        // 未指定列宽时使用小票宽度
+        let data = json!({"name": "Green tea 500ml bottle"});
+        let profile = HelperProfile::default();
+        let result = render_template_limited("{{wrap name}}", &data, 16, 1024, &profile).unwrap();
+        assert_eq!(result, "Green tea 500ml\n  bottle");
+    }
+
     #[test]
     fn test_math_helpers() {
         let template = "Sum: {{add a b}}, Product: {{mul a b}}";
@@ -480,6 +2529,196 @@ mod tests {
         assert!(result.contains("Sum: 7") && result.contains("Product: 12"));
     }
 
+    #[test]
+    fn test_rounding_helpers() {
+        // 四舍五入与银行家舍入
+        assert_eq!(round_decimals(0.125, 2, Rounding::HalfUp), 0.13);
+        assert_eq!(round_decimals(0.125, 2, Rounding::HalfEven), 0.12);
+        assert_eq!(round_decimals(0.135, 2, Rounding::HalfEven), 0.14);
+        assert_eq!(round_decimals(2.5, 0, Rounding::HalfUp), 3.0);
+        assert_eq!(round_decimals(2.5, 0, Rounding::HalfEven), 2.0);
+        assert_eq!(round_decimals(-2.5, 0, Rounding::HalfUp), -3.0);
+        // 浮点误差不影响舍入
+        assert_eq!(round_decimals(2.675, 2, Rounding::HalfUp), 2.68);
+        assert_eq!(round_decimals(1.005, 2, Rounding::HalfUp), 1.01);
+        assert_eq!(round_decimals(0.29, 2, Rounding::Floor), 0.29);
+        assert_eq!(round_decimals(1.21, 1, Rounding::Ceil), 1.3);
+        assert_eq!(round_decimals(-1.21, 1, Rounding::Floor), -1.3);
+
+        // 现金找零舍入到 0.05
+        assert_eq!(round_to_nearest(1.23, 0.05), 1.25);
+        assert_eq!(round_to_nearest(1.22, 0.05), 1.2);
+        assert_eq!(round_to_nearest(1.225, 0.05), 1.25);
+        assert_eq!(round_to_nearest(17.0, 5.0), 15.0);
+        assert_eq!(round_to_nearest(1.23, 0.0), 1.23);
+
+        let data = json!({"total": "12.345", "cash": 9.97});
+        let template = "{{round total 2}}|{{round total 2 mode=\"half_even\"}}|{{round cash}}";
+        let result = render_template(template, &data).unwrap();
+        assert_eq!(result, "12.35|12.34|10.0");
+        let template = "{{ceil total 1}}|{{floor total}}|{{round_to cash 0.05}}";
+        let result = render_template(template, &data).unwrap();
+        assert_eq!(result, "12.4|12.0|9.95");
+    }
+
+    #[test]
+    fn test_numeric_string_inputs() {
+        let data = json!({
+            "total": "1,234.50",
+            "price": "¥9.90",
+            "refund": "-$1,000",
+            "qty": " 3 ",
+            "note": "n/a"
+        });
+
+        let result =
+            render_template("{{currency total}}|{{format_number price 1}}", &data).unwrap();
+        assert_eq!(result, "¥1234.50|9.9");
+        let result = render_template("{{add total refund}}|{{mul qty 2}}", &data).unwrap();
+        assert_eq!(result, "234.5|6.0");
+        let result = render_template("{{#if (gt total 1000)}}big{{/if}}", &data).unwrap();
+        assert_eq!(result, "big");
+        let result = render_template("{{to_number total}}|{{to_number note}}", &data).unwrap();
+        assert_eq!(result, "1234.5|");
+        let result = render_template("{{currency note}}|{{div total 0}}", &data).unwrap();
+        assert_eq!(result, "¥0.00|0.0");
+
+        assert_eq!(parse_number("1,234.50"), Some(1234.5));
+        assert_eq!(parse_number("-¥ 12"), Some(-12.0));
+        assert_eq!(parse_number("inf"), None);
+        assert_eq!(parse_number(""), None);
+    }
+
+    #[test]
+    fn test_index_and_lookup() {
+        let data = json!({
+            "taxes": [{"rate": "6%"}, {"rate": "13%"}],
+            "codes": ["A", "B", "C"],
+            "totals": {"net": 100, "tax": 13},
+            "field": "tax"
+        });
+
+        let result = render_template(
+            "{{index codes 0}}|{{index codes -1}}|[{{index codes 3}}]|[{{index codes -4}}]",
+            &data,
+        )
+        .unwrap();
+        assert_eq!(result, "A|C|[]|[]");
+
+        let result = render_template(
+            "{{#with (index taxes 0)}}{{rate}}{{/with}}|{{lookup (index taxes 1) \"rate\"}}",
+            &data,
+        )
+        .unwrap();
+        assert_eq!(result, "6%|13%");
+
+        // 不是数组时为空
+        let result = render_template("[{{index totals 0}}][{{index missing 0}}]", &data).unwrap();
+        assert_eq!(result, "[][]");
+
+        // 内置 lookup 按键（或变量中的键）取值，不存在时为空
+        let result = render_template(
+            "{{lookup totals \"net\"}}|{{lookup totals field}}|[{{lookup totals \"none\"}}]|[{{lookup codes 5}}]",
+            &data,
+        )
+        .unwrap();
+        assert_eq!(result, "100|13|[]|[]");
+    }
+
+    #[test]
+    fn test_object_entries() {
+        let data = json!({
+            "tax": {"6%": "1.20", "13%": "3.90"},
+            "empty": {},
+            "name": "Alice"
+        });
+
+        let result = render_template(
+            "{{#each (entries tax)}}{{key}}={{currency value}};{{/each}}",
+            &data,
+        )
+        .unwrap();
+        assert_eq!(result, "13%=¥3.90;6%=¥1.20;");
+
+        // 内置 #each 同样可以通过 @key 访问键
+        let result = render_template("{{#each tax}}{{@key}}={{this}};{{/each}}", &data).unwrap();
+        assert_eq!(result, "13%=3.90;6%=1.20;");
+
+        let result = render_template(
+            "{{count (entries tax)}}|{{count (entries empty)}}|{{count (entries name)}}",
+            &data,
+        )
+        .unwrap();
+        assert_eq!(result, "2|0|0");
+    }
+
+    #[test]
+    fn test_min_max_clamp_helpers() {
+        let data = json!({"price": 80, "discount": 100, "qty": 12});
+
+        let result =
+            render_template("{{min price discount}}|{{max price discount}}", &data).unwrap();
+        assert_eq!(result, "80.0|100.0");
+        let result = render_template("{{min price missing}}|{{max}}", &data).unwrap();
+        assert_eq!(result, "80.0|0.0");
+
+        // 两端边界
+        let result = render_template("{{clamp qty 1 10}}|{{clamp qty 20 50}}", &data).unwrap();
+        assert_eq!(result, "10.0|20.0");
+        let result = render_template("{{clamp qty 1 99}}|{{clamp qty 1}}", &data).unwrap();
+        assert_eq!(result, "12.0|12.0");
+        assert_eq!(clamp_value(5.0, Some(10.0), Some(1.0)), 1.0);
+    }
+
+    #[test]
+    fn test_sum_count_helpers() {
+        let data = json!({
+            "items": [
+                {"price": 10.5},
+                {"price": "2.5"},
+                {"price": "n/a"},
+                {"name": "no price"}
+            ],
+            "amounts": [1, 2, 3]
+        });
+
+        let result = render_template("{{sum items \"price\"}}", &data).unwrap();
+        assert_eq!(result, "13.0");
+        let result = render_template("{{currency (sum items \"price\")}}", &data).unwrap();
+        assert_eq!(result, "¥13.00");
+        let result = render_template("{{sum amounts}}", &data).unwrap();
+        assert_eq!(result, "6.0");
+        let result = render_template("{{count items}}", &data).unwrap();
+        assert_eq!(result, "4");
+        let result =
+            render_template("{{count missing}}|{{sum missing \"price\"}}", &data).unwrap();
+        assert_eq!(result, "0|0.0");
+    }
+
+    #[test]
+    fn test_default_coalesce_helpers() {
+        let data = json!({"name": "Alice", "empty": "", "none": null, "qty": 0});
+
+        let result = render_template("{{default name \"顾客\"}}", &data).unwrap();
+        assert_eq!(result, "Alice");
+        let result = render_template("{{default empty \"顾客\"}}", &data).unwrap();
+        assert_eq!(result, "顾客");
+        let result = render_template("{{default none \"顾客\"}}", &data).unwrap();
+        assert_eq!(result, "顾客");
+        let result = render_template("{{default missing \"顾客\"}}", &data).unwrap();
+        assert_eq!(result, "顾客");
+        // 0 不视为空值
+        let result = render_template("{{default qty 1}}", &data).unwrap();
+        assert_eq!(result, "0");
+
+        let result = render_template("{{coalesce none empty missing name \"-\"}}", &data).unwrap();
+        assert_eq!(result, "Alice");
+        let result = render_template("[{{coalesce none empty}}]", &data).unwrap();
+        assert_eq!(result, "[]");
+        let result = render_template("{{uppercase (default empty \"n/a\")}}", &data).unwrap();
+        assert_eq!(result, "N/A");
+    }
+
     #[test]
     fn test_escpos_builder() {
         let items = vec![
@@ -489,6 +2728,139 @@ mod tests {
         let receipt = escpos::build_receipt("测试小票", &items, 43.50);
         assert!(!receipt.is_empty());
         assert!(receipt.starts_with(escpos::INIT));
+
+        // 长名称换行后价格仍在首行第 21 列之后
+        let items = vec![("Organic whole milk family size".to_string(), 12.00)];
+        let receipt = escpos::build_receipt("T", &items, 12.00);
+        let text = String::from_utf8_lossy(&receipt);
+        assert!(text.contains("Organic whole milk        12.00\n  family size\n"));
+
+        // 80mm 纸名称列加宽，分隔线和合计随宽度调整
+        let receipt = escpos::build_receipt_with_width("T", &items, 12.00, 48);
+        let text = String::from_utf8_lossy(&receipt);
+        assert!(text.contains(&format!(
+            "Organic whole milk family size{}12.00\n",
+            " ".repeat(12)
+        )));
+        assert!(text.contains(&format!("{}\n", "-".repeat(48))));
+        assert!(text.contains(&format!("合计{}12.00\n", " ".repeat(38))));
+    }
+
+    #[test]
+    fn test_zpl_rotation() {
+        use zpl::Rotation;
+
+        assert_eq!(Rotation::from_degrees(90), Some(Rotation::Rotated90));
+        assert_eq!(Rotation::from_degrees(45), None);
+        assert_eq!(
+            zpl::rotated_text(10, 20, '0', Rotation::Bottom270, 30, 30, "SKU"),
+            "^FO10,20^A0B,30,30^FDSKU^FS"
+        );
+    }
+
+    #[test]
+    fn test_zpl_stored_format() {
+        assert_eq!(zpl::format_path("LABEL"), "R:LABEL.ZPL");
+        assert_eq!(zpl::format_path("E:LABEL.ZPL"), "E:LABEL.ZPL");
+        assert!(zpl::is_valid_format_name("E:PRICE_1"));
+        assert!(!zpl::is_valid_format_name("A^FS"));
+        assert!(!zpl::is_valid_format_name(""));
+
+        assert_eq!(
+            zpl::download_format("PRICE", "^XA^FO50,50^A0N,30,30^FN1^FS^XZ"),
+            "^XA^DFR:PRICE.ZPL^FS\n^FO50,50^A0N,30,30^FN1^FS\n^XZ"
+        );
+
+        let fields = BTreeMap::from([(2, "9.90".to_string()), (1, "Milk".to_string())]);
+        assert_eq!(
+            zpl::recall_format("PRICE", &fields),
+            "^XA^XFR:PRICE.ZPL^FS\n^FN1^FDMilk^FS\n^FN2^FD9.90^FS\n^XZ"
+        );
+
+        // 字段数据中的指令前缀被转义，不会结束字段或执行指令
+        let fields = BTreeMap::from([(1, "A^XZ~JA_1".to_string()), (2, "a_b".to_string())]);
+        assert_eq!(
+            zpl::recall_format("PRICE", &fields),
+            "^XA^XFR:PRICE.ZPL^FS\n^FN1^FH^FDA_5EXZ_7EJA_5F1^FS\n^FN2^FDa_b^FS\n^XZ"
+        );
+    }
+
+    #[test]
+    fn test_zpl_normalize_line_endings() {
+        let crlf = "^XA\r\n^FO50,50^FDHello\r^FS\r\n^FO50,100^fdWorld\r\n^FS\r^XZ\r\n";
+        assert_eq!(
+            zpl::normalize_line_endings(crlf),
+            "^XA\n^FO50,50^FDHello^FS\n^FO50,100^fdWorld\n^FS\n^XZ\n"
+        );
+        // 与 LF 编辑的模板结果相同
+        let lf = "^XA\n^FO50,50^FV牛奶\n^FS\n^XZ";
+        assert_eq!(zpl::normalize_line_endings(&lf.replace('\n', "\r\n")), lf);
+        assert_eq!(zpl::normalize_line_endings("^FD"), "^FD");
+        assert_eq!(zpl::normalize_line_endings("^\r"), "^\n");
+    }
+
+    #[test]
+    fn test_zpl_graphic() {
+        // 2x2 的 24 位 BMP：左上、右下为黑点
+        let mut bmp = b"BM\x46\0\0\0\0\0\0\0\x36\0\0\0\x28\0\0\0".to_vec();
+        bmp.extend_from_slice(&[2, 0, 0, 0, 2, 0, 0, 0, 1, 0, 24, 0]);
+        bmp.extend_from_slice(&[0; 24]);
+        // 自下而上存储，每行补齐到 4 字节
+        bmp.extend_from_slice(&[255, 255, 255, 0, 0, 0, 0, 0]);
+        bmp.extend_from_slice(&[0, 0, 0, 255, 255, 255, 0, 0]);
+
+        assert_eq!(zpl::graphic_path("LOGO"), "R:LOGO.GRF");
+        assert_eq!(
+            zpl::download_graphic("LOGO", &bmp).unwrap(),
+            "~DGR:LOGO.GRF,2,1,8040"
+        );
+        assert!(zpl::download_graphic("A^FS", &bmp).is_err());
+        assert!(zpl::download_graphic("LOGO", b"not an image").is_err());
+        assert_eq!(
+            zpl::recall_graphic(50, 20, "LOGO"),
+            "^FO50,20^XGR:LOGO.GRF,1,1^FS"
+        );
+
+        let data = json!({"logo": format!("data:image/bmp;base64,{}", STANDARD.encode(&bmp))});
+        let result = render_template("{{zpl_image logo x=50 y=20}}", &data).unwrap();
+        assert_eq!(
+            result,
+            "~DGR:LOGO.GRF,2,1,8040\n^FO50,20^XGR:LOGO.GRF,1,1^FS"
+        );
+        assert!(render_template("{{zpl_image missing}}", &data).is_err());
+    }
+
+    #[test]
+    fn test_zpl_field_block() {
+        use zpl::Justify;
+
+        assert_eq!(zpl::field_block(400, 3, 5, Justify::Center), "^FB400,3,5,C");
+        assert_eq!(zpl::field_block(400, 0, -2, Justify::Left), "^FB400,1,-2,L");
+        assert_eq!(Justify::from_name("Justified"), Some(Justify::Justified));
+        assert_eq!(Justify::from_name("r"), Some(Justify::Right));
+        assert_eq!(Justify::from_name("middle"), None);
+
+        let template = "{{text_block desc 400 lines=3 spacing=4 justify=\"center\" x=50 y=100}}";
+        let data = json!({"desc": "Organic whole milk\nfamily size"});
+        let result = render_template(template, &data).unwrap();
+        assert_eq!(
+            result,
+            "^FO50,100^A0,30,30^FB400,3,4,C^FDOrganic whole milk\\&family size^FS"
+        );
+
+        assert!(render_template("{{text_block desc}}", &data).is_err());
+        assert!(render_template("{{text_block desc 400 justify=\"middle\"}}", &data).is_err());
+    }
+
+    #[test]
+    fn test_rotate_helper() {
+        let template = "{{rotate name 90 x=50 y=60 size=25}}";
+        let data = json!({"name": "Vertical"});
+        let result = render_template(template, &data).unwrap();
+        assert_eq!(result, "^FO50,60^A0R,25,25^FDVertical^FS");
+
+        let result = render_template("{{rotate name 45}}", &data);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -498,4 +2870,14 @@ mod tests {
         assert!(label.contains("^XZ"));
         assert!(label.contains("1234567890123"));
     }
+
+    #[test]
+    fn test_zpl_resident_fonts() {
+        let names: String = zpl::RESIDENT_FONTS.iter().map(|font| font.name).collect();
+        assert_eq!(names, "ABCDEFGH0");
+
+        let font = serde_json::to_value(&zpl::RESIDENT_FONTS[0]).unwrap();
+        assert_eq!(font["name"], "A");
+        assert_eq!(font["scalable"], false);
+    }
 }
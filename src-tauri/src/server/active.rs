@@ -0,0 +1,135 @@
+//! 进行中的打印任务
+//!
+//! 停机时先停止接受新任务，再等待已接受的任务（包括等待写入锁的任务）完成，
+//! 避免停机或重启前刚接受的小票丢失
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// 进行中的打印任务（按请求 ID 计数，同一 ID 可能同时执行多次）
+#[derive(Default)]
+pub struct ActiveJobs {
+    jobs: Mutex<HashMap<String, usize>>,
+    draining: AtomicBool,
+    idle: Notify,
+}
+
+impl ActiveJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记一个打印任务，停机中不再接受新任务时返回 None；返回的守卫释放时任务结束
+    pub fn begin(self: &Arc<Self>, id: &str) -> Option<ActiveJob> {
+        let mut jobs = self.jobs.lock().unwrap();
+        // 在锁内检查并登记，drain 检查任务列表时不会漏掉正在登记的任务
+        if self.draining.load(Ordering::SeqCst) {
+            return None;
+        }
+        *jobs.entry(id.to_string()).or_default() += 1;
+        Some(ActiveJob {
+            jobs: self.clone(),
+            id: id.to_string(),
+        })
+    }
+
+    /// 停止接受新任务，等待进行中的任务完成，超时时返回仍未完成的任务 ID
+    pub async fn drain(&self, timeout: Duration) -> Vec<String> {
+        self.draining.store(true, Ordering::SeqCst);
+
+        let wait = async {
+            loop {
+                // 先注册通知再检查，避免错过检查期间结束的任务
+                let idle = self.idle.notified();
+                if self.jobs.lock().unwrap().is_empty() {
+                    return;
+                }
+                idle.await;
+            }
+        };
+
+        if tokio::time::timeout(timeout, wait).await.is_ok() {
+            return Vec::new();
+        }
+        let mut ids: Vec<String> = self.jobs.lock().unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// 任务是否正在执行
+    pub fn contains(&self, id: &str) -> bool {
+        self.jobs.lock().unwrap().contains_key(id)
+    }
+
+    /// 进行中的任务数（包括等待写入锁的任务）
+    pub fn count(&self) -> usize {
+        self.jobs.lock().unwrap().values().sum()
+    }
+
+    /// 是否已停止接受新任务
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// 重新接受新任务（服务重新启动时）
+    pub fn resume(&self) {
+        self.draining.store(false, Ordering::SeqCst);
+    }
+
+    fn finish(&self, id: &str) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(count) = jobs.get_mut(id) {
+            *count -= 1;
+            if *count == 0 {
+                jobs.remove(id);
+            }
+        }
+        if jobs.is_empty() {
+            self.idle.notify_waiters();
+        }
+    }
+}
+
+/// 进行中的打印任务守卫（释放时从进行中的任务中移除）
+pub struct ActiveJob {
+    jobs: Arc<ActiveJobs>,
+    id: String,
+}
+
+impl Drop for ActiveJob {
+    fn drop(&mut self) {
+        self.jobs.finish(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_active_jobs() {
+        let jobs = Arc::new(ActiveJobs::new());
+        let job = jobs.begin("1").unwrap();
+        let other = jobs.begin("2").unwrap();
+
+        // 超时后返回仍未完成的任务，之后不再接受新任务
+        drop(job);
+        assert_eq!(jobs.drain(Duration::from_millis(20)).await, ["2"]);
+        assert!(jobs.begin("3").is_none());
+
+        jobs.resume();
+        let job = jobs.begin("3").unwrap();
+        let drained = tokio::spawn({
+            let jobs = jobs.clone();
+            async move { jobs.drain(Duration::from_secs(5)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(job);
+        drop(other);
+        assert!(drained.await.unwrap().is_empty());
+    }
+}
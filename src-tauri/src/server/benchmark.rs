@@ -0,0 +1,211 @@
+//! 打印延迟测试
+//!
+//! 用很小的文本任务重复打印，统计每次请求的耗时，便于比较各门店打印机和驱动的性能，
+//! 在打印机彻底故障前发现变慢的趋势。试运行模式只渲染并查询打印后台，不消耗纸张。
+//! 测试任务与打印请求一样依次写入打印机（遵守任务间隔），不归档，不记录为补打内容，
+//! 不计入打印统计、失败任务和审计日志，也不触发通知和 webhook
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::info;
+
+use super::{execute_print, receipt_width, resolve_printer, ServerState};
+use crate::printer::printer_exists;
+use crate::protocol::{PrintError, PrintOptions, PrintRequest, PrinterSelector, PRINTER_NOT_FOUND};
+use crate::renderer::render_template_limited;
+
+/// 单次测试允许的最大打印次数
+pub const MAX_BENCHMARK_ITERATIONS: usize = 100;
+
+/// 测试打印使用的模板
+const BENCHMARK_TEMPLATE: &str = "rprint benchmark {{iteration}}/{{iterations}}\n";
+
+/// 打印延迟统计（毫秒）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PrintBenchmark {
+    /// 打印机名称
+    pub printer: String,
+    /// 打印次数
+    pub iterations: usize,
+    /// 是否为试运行（未实际打印）
+    pub dry_run: bool,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// 重复打印测试任务并统计耗时（printer 为空则使用默认打印机）
+///
+/// 实际打印与打印请求使用相同的渲染和发送流程；试运行时渲染测试模板并查询打印后台中的打印机
+pub async fn benchmark_print(
+    state: &Arc<ServerState>,
+    printer: Option<&str>,
+    iterations: usize,
+    dry_run: bool,
+) -> Result<PrintBenchmark, PrintError> {
+    if !(1..=MAX_BENCHMARK_ITERATIONS).contains(&iterations) {
+        return Err(format!(
+            "Iterations must be between 1 and {}",
+            MAX_BENCHMARK_ITERATIONS
+        )
+        .into());
+    }
+
+    let printer_name = resolve_printer(state, printer)?;
+    info!(
+        "Benchmarking '{}' with {} prints (dry run: {})",
+        printer_name, iterations, dry_run
+    );
+
+    let mut samples = Vec::with_capacity(iterations);
+    for iteration in 1..=iterations {
+        let data = serde_json::json!({ "iteration": iteration, "iterations": iterations });
+        let start = Instant::now();
+        if dry_run {
+            dry_run_print(state, &printer_name, &data).await?;
+        } else {
+            let req = PrintRequest {
+                id: format!("benchmark-{}", iteration),
+                template_type: "text".to_string(),
+                template: BENCHMARK_TEMPLATE.to_string(),
+                template_name: None,
+                data,
+                schema: None,
+                printer: Some(PrinterSelector::Name(printer_name.clone())),
+                options: PrintOptions {
+                    archive: Some(false),
+                    ..PrintOptions::default()
+                },
+                schedule_at: None,
+                documents: Vec::new(),
+            };
+            send_print(state, req, &printer_name).await?;
+        }
+        samples.push(start.elapsed());
+    }
+
+    Ok(summarize(printer_name, dry_run, &samples))
+}
+
+/// 持有打印机写入锁发送测试任务（不等待打印完成）
+async fn send_print(
+    state: &Arc<ServerState>,
+    req: PrintRequest,
+    printer: &str,
+) -> Result<(), PrintError> {
+    let config = state.config.read().await.clone();
+    let guard = state
+        .printer_locks
+        .lock_job(printer, config.printer.min_job_interval_for(printer))
+        .await;
+    let (state, printer) = (state.clone(), printer.to_string());
+    tokio::task::spawn_blocking(move || {
+        let _guard = guard;
+        execute_print(&req, &state, &config, &printer, false)
+    })
+    .await
+    .map_err(|e| format!("Print task failed: {}", e))?
+    .map(|_| ())
+}
+
+/// 试运行：渲染测试模板并确认打印机存在，不发送数据
+async fn dry_run_print(
+    state: &Arc<ServerState>,
+    printer: &str,
+    data: &serde_json::Value,
+) -> Result<(), PrintError> {
+    let config = state.config.read().await.clone();
+    render_template_limited(
+        BENCHMARK_TEMPLATE,
+        data,
+        receipt_width(state, &config.printer, Some(printer)),
+        config.server.max_render_bytes,
+        &config.printer.active_helper_profile(),
+    )?;
+
+    let printers = state.printer_manager.list_printers()?;
+    if !printer_exists(&printers, printer) {
+        return Err(PrintError::new(
+            PRINTER_NOT_FOUND,
+            format!("Printer not found: {}", printer),
+        ));
+    }
+    Ok(())
+}
+
+/// 计算耗时统计（samples 不为空）
+fn summarize(printer: String, dry_run: bool, samples: &[Duration]) -> PrintBenchmark {
+    let mut ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+    ms.sort_by(f64::total_cmp);
+
+    // 最近秩法：不小于 95% 样本的最小值
+    let p95_index = (ms.len() * 95).div_ceil(100).max(1) - 1;
+    PrintBenchmark {
+        printer,
+        iterations: ms.len(),
+        dry_run,
+        min_ms: ms[0],
+        avg_ms: ms.iter().sum::<f64>() / ms.len() as f64,
+        max_ms: ms[ms.len() - 1],
+        p95_ms: ms[p95_index],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::printer::mock::MockPrinterManager;
+    use crate::server::mock_state;
+
+    #[test]
+    fn test_summarize() {
+        let samples: Vec<Duration> = (1..=20).rev().map(Duration::from_millis).collect();
+        let result = summarize("Office".to_string(), true, &samples);
+        assert_eq!(result.iterations, 20);
+        assert_eq!((result.min_ms, result.max_ms), (1.0, 20.0));
+        assert_eq!(result.avg_ms, 10.5);
+        assert_eq!(result.p95_ms, 19.0);
+
+        let result = summarize("Office".to_string(), true, &[Duration::from_millis(7)]);
+        assert_eq!(
+            (result.min_ms, result.p95_ms, result.max_ms),
+            (7.0, 7.0, 7.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_print() {
+        let printers = MockPrinterManager::new().with_printer("Office");
+        let state = mock_state(&printers, AppConfig::default());
+
+        // 试运行不发送数据
+        let result = benchmark_print(&state, Some("Office"), 3, true)
+            .await
+            .unwrap();
+        assert_eq!((result.iterations, result.dry_run), (3, true));
+        assert!(printers.calls().is_empty());
+
+        let result = benchmark_print(&state, Some("Office"), 2, false)
+            .await
+            .unwrap();
+        assert_eq!(result.printer, "Office");
+        let calls = printers.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].data, b"rprint benchmark 2/2\n");
+        // 测试任务不计入打印统计，也不作为补打内容
+        assert_eq!(state.stats.total(), 0);
+        assert!(state.last_printed.get("Office").is_none());
+
+        let error = benchmark_print(&state, Some("Missing"), 1, true)
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, PRINTER_NOT_FOUND);
+        assert!(benchmark_print(&state, Some("Office"), 0, true)
+            .await
+            .is_err());
+    }
+}
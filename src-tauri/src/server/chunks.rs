@@ -0,0 +1,258 @@
+//! 分块打印请求组装
+//!
+//! 大任务可拆分为多个 `print_chunk` 消息发送，按连接和 `id` 汇总后拼接为完整的打印请求 JSON。
+//! 不同连接使用相同的 `id` 互不影响；单个任务和所有未完成任务占用的字节数都有上限
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+/// 单个任务允许的最大分块数
+const MAX_CHUNKS: u32 = 10_000;
+
+/// 单个任务已收到分块的最大字节数
+const MAX_UPLOAD_BYTES: usize = 32 * 1024 * 1024;
+
+/// 所有未完成任务已收到分块的最大字节数
+const MAX_PENDING_BYTES: usize = 128 * 1024 * 1024;
+
+/// 任务键（连接 ID，任务 ID）
+type UploadKey = (u64, String);
+
+/// 正在组装的任务
+struct PendingJob {
+    /// 分块总数
+    total: u32,
+    /// 已收到的分块（按序号）
+    chunks: HashMap<u32, String>,
+    /// 已收到分块的字节数
+    bytes: usize,
+    /// 第一个分块到达时间
+    started: Instant,
+}
+
+/// 分块组装器
+#[derive(Default)]
+pub struct ChunkAssembler {
+    pending: Mutex<HashMap<UploadKey, PendingJob>>,
+}
+
+impl ChunkAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加连接 `conn` 的一个分块（seq 从 0 开始，可乱序到达）
+    ///
+    /// 所有分块到齐后返回拼接后的完整内容；超过 `timeout` 未完成的任务会被丢弃。
+    /// 超过单个任务或所有未完成任务的字节数上限时丢弃该任务并返回错误
+    pub fn add(
+        &self,
+        conn: u64,
+        id: &str,
+        seq: u32,
+        total: u32,
+        data: String,
+        timeout: Duration,
+    ) -> Result<Option<String>, String> {
+        if total == 0 || total > MAX_CHUNKS {
+            return Err(format!("Invalid chunk total: {}", total));
+        }
+        if seq >= total {
+            return Err(format!("Chunk seq {} out of range (total {})", seq, total));
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        purge_expired(&mut pending, timeout);
+
+        let key = (conn, id.to_string());
+        let pending_bytes: usize = pending
+            .iter()
+            .filter(|(k, _)| **k != key)
+            .map(|(_, job)| job.bytes)
+            .sum();
+        let job = pending.entry(key.clone()).or_insert_with(|| PendingJob {
+            total,
+            chunks: HashMap::new(),
+            bytes: 0,
+            started: Instant::now(),
+        });
+
+        if job.total != total {
+            let expected = job.total;
+            pending.remove(&key);
+            return Err(format!(
+                "Chunk total mismatch for '{}': expected {}, got {}",
+                id, expected, total
+            ));
+        }
+
+        // 重复发送的分块替换旧内容
+        let replaced = job.chunks.get(&seq).map_or(0, String::len);
+        let bytes = job.bytes - replaced + data.len();
+        if bytes > MAX_UPLOAD_BYTES {
+            pending.remove(&key);
+            return Err(format!(
+                "Chunked job '{}' exceeds {} bytes",
+                id, MAX_UPLOAD_BYTES
+            ));
+        }
+        if pending_bytes + bytes > MAX_PENDING_BYTES {
+            pending.remove(&key);
+            return Err(format!(
+                "Too much pending chunked data (limit {} bytes), job '{}' discarded",
+                MAX_PENDING_BYTES, id
+            ));
+        }
+        job.bytes = bytes;
+        job.chunks.insert(seq, data);
+        if job.chunks.len() < total as usize {
+            return Ok(None);
+        }
+
+        // 全部到齐，按序号拼接
+        let mut job = pending.remove(&key).unwrap();
+        let content = (0..total)
+            .filter_map(|seq| job.chunks.remove(&seq))
+            .collect::<String>();
+        Ok(Some(content))
+    }
+
+    /// 连接 `conn` 的任务已收到的分块数量
+    pub fn received(&self, conn: u64, id: &str) -> usize {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(&(conn, id.to_string()))
+            .map_or(0, |job| job.chunks.len())
+    }
+
+    /// 丢弃连接未完成的任务（连接断开时调用）
+    pub fn discard(&self, conn: u64) {
+        self.pending.lock().unwrap().retain(|(c, _), _| *c != conn);
+    }
+}
+
+/// 丢弃超时未完成的任务
+fn purge_expired(pending: &mut HashMap<UploadKey, PendingJob>, timeout: Duration) {
+    pending.retain(|(_, id), job| {
+        let alive = job.started.elapsed() < timeout;
+        if !alive {
+            warn!(
+                "Discarding incomplete chunked job '{}' ({}/{} chunks received)",
+                id,
+                job.chunks.len(),
+                job.total
+            );
+        }
+        alive
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_assemble_out_of_order() {
+        let assembler = ChunkAssembler::new();
+        assert_eq!(
+            assembler.add(1, "job", 2, 3, "c".into(), TIMEOUT).unwrap(),
+            None
+        );
+        assert_eq!(
+            assembler.add(1, "job", 0, 3, "a".into(), TIMEOUT).unwrap(),
+            None
+        );
+        assert_eq!(assembler.received(1, "job"), 2);
+        assert_eq!(
+            assembler.add(1, "job", 1, 3, "b".into(), TIMEOUT).unwrap(),
+            Some("abc".to_string())
+        );
+        assert_eq!(assembler.received(1, "job"), 0);
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        let assembler = ChunkAssembler::new();
+        assert!(assembler.add(1, "job", 3, 3, "x".into(), TIMEOUT).is_err());
+        assert!(assembler.add(1, "job", 0, 0, "x".into(), TIMEOUT).is_err());
+
+        assembler.add(1, "job", 0, 3, "a".into(), TIMEOUT).unwrap();
+        assert!(assembler.add(1, "job", 1, 4, "b".into(), TIMEOUT).is_err());
+        assert_eq!(assembler.received(1, "job"), 0);
+    }
+
+    #[test]
+    fn test_expired_jobs_discarded() {
+        let assembler = ChunkAssembler::new();
+        assembler.add(1, "old", 0, 2, "a".into(), TIMEOUT).unwrap();
+        // 超时为 0 时，下一次添加会清理未完成的任务
+        assembler
+            .add(1, "new", 0, 2, "a".into(), Duration::ZERO)
+            .unwrap();
+        assert_eq!(assembler.received(1, "old"), 0);
+    }
+
+    #[test]
+    fn test_jobs_scoped_by_connection() {
+        let assembler = ChunkAssembler::new();
+        assembler.add(1, "job", 0, 2, "a".into(), TIMEOUT).unwrap();
+        // 其他连接使用相同 id 不会混入或打断该任务
+        assert!(assembler.add(2, "job", 0, 3, "x".into(), TIMEOUT).is_ok());
+        assert_eq!(assembler.received(1, "job"), 1);
+        assert_eq!(
+            assembler.add(1, "job", 1, 2, "b".into(), TIMEOUT).unwrap(),
+            Some("ab".to_string())
+        );
+
+        // 连接断开时丢弃其未完成的任务
+        assembler.discard(2);
+        assert_eq!(assembler.received(2, "job"), 0);
+    }
+
+    #[test]
+    fn test_byte_limits() {
+        let assembler = ChunkAssembler::new();
+        let chunk = "x".repeat(MAX_UPLOAD_BYTES / 2);
+
+        // 单个任务超过上限时丢弃
+        assembler
+            .add(1, "big", 0, 3, chunk.clone(), TIMEOUT)
+            .unwrap();
+        assembler
+            .add(1, "big", 1, 3, chunk.clone(), TIMEOUT)
+            .unwrap();
+        assert!(assembler.add(1, "big", 2, 3, "y".into(), TIMEOUT).is_err());
+        assert_eq!(assembler.received(1, "big"), 0);
+
+        // 重复发送的分块不重复计数
+        assembler
+            .add(1, "big", 0, 3, chunk.clone(), TIMEOUT)
+            .unwrap();
+        assembler
+            .add(1, "big", 0, 3, chunk.clone(), TIMEOUT)
+            .unwrap();
+        assembler
+            .add(1, "big", 1, 3, chunk.clone(), TIMEOUT)
+            .unwrap();
+        assembler.discard(1);
+
+        // 所有未完成任务的总量超过上限时丢弃新分块所属的任务
+        let count = MAX_PENDING_BYTES / MAX_UPLOAD_BYTES;
+        for conn in 0..count as u64 {
+            for seq in 0..2 {
+                assembler
+                    .add(conn, "job", seq, 3, chunk.clone(), TIMEOUT)
+                    .unwrap();
+            }
+        }
+        assert!(assembler.add(99, "job", 0, 2, "z".into(), TIMEOUT).is_err());
+        assert_eq!(assembler.received(99, "job"), 0);
+        assert_eq!(assembler.received(0, "job"), 2);
+    }
+}
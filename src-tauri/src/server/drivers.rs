@@ -0,0 +1,102 @@
+//! 打印机驱动名称缓存
+//!
+//! 按型号匹配小票宽度需要打印机的驱动名称，而枚举打印机较慢（网络打印机更明显），
+//! 枚举结果缓存一段时间，每次打印和预览不再重新枚举
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::protocol::PrinterInfo;
+
+/// 枚举结果的有效期（期间新安装或更换驱动的打印机仍按旧结果匹配）
+const DRIVER_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// 最近一次枚举的打印机列表
+struct Snapshot {
+    printers: Vec<PrinterInfo>,
+    fetched: Instant,
+}
+
+/// 驱动名称缓存
+#[derive(Default)]
+pub struct DriverCache {
+    snapshot: Mutex<Option<Snapshot>>,
+}
+
+impl DriverCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取打印机（名称或 ID）的驱动名称，缓存过期时调用 `list` 重新枚举（失败时不缓存）
+    pub fn driver(
+        &self,
+        printer: &str,
+        list: impl FnOnce() -> Result<Vec<PrinterInfo>, String>,
+    ) -> Option<String> {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        if snapshot
+            .as_ref()
+            .is_none_or(|s| s.fetched.elapsed() >= DRIVER_CACHE_TTL)
+        {
+            match list() {
+                Ok(printers) => {
+                    *snapshot = Some(Snapshot {
+                        printers,
+                        fetched: Instant::now(),
+                    })
+                }
+                Err(e) => {
+                    debug!("Failed to list printers for driver lookup: {}", e);
+                    return None;
+                }
+            }
+        }
+
+        snapshot
+            .as_ref()?
+            .printers
+            .iter()
+            .find(|info| info.name == printer || info.id == printer)?
+            .driver
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printer(name: &str, driver: &str) -> PrinterInfo {
+        PrinterInfo {
+            name: name.to_string(),
+            id: format!("{}||", name),
+            is_default: false,
+            status: "ready".to_string(),
+            driver: Some(driver.to_string()),
+            port: None,
+            color: None,
+        }
+    }
+
+    #[test]
+    fn test_driver_cached() {
+        let cache = DriverCache::new();
+        assert_eq!(cache.driver("Bar", || Err("spooler".to_string())), None);
+
+        let driver = cache.driver("Bar", || Ok(vec![printer("Bar", "EPSON TM-T20")]));
+        assert_eq!(driver.as_deref(), Some("EPSON TM-T20"));
+
+        // 有效期内不再枚举
+        let listed = std::cell::Cell::new(false);
+        let driver = cache.driver("Bar||", || {
+            listed.set(true);
+            Ok(Vec::new())
+        });
+        assert_eq!(driver.as_deref(), Some("EPSON TM-T20"));
+        assert!(!listed.get());
+        assert_eq!(cache.driver("Kitchen", || Ok(Vec::new())), None);
+    }
+}
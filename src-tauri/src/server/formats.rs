@@ -0,0 +1,57 @@
+//! ZPL 存储格式跟踪
+//!
+//! 记录每台打印机已下载（^DF）的格式，之后的任务只需调用（^XF）并发送字段数据
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::renderer::zpl::format_path;
+
+/// 各打印机已下载的存储格式
+#[derive(Default)]
+pub struct ZplFormats {
+    downloaded: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl ZplFormats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 格式是否已下载到该打印机
+    pub fn is_downloaded(&self, printer: &str, name: &str) -> bool {
+        self.downloaded
+            .lock()
+            .unwrap()
+            .get(printer)
+            .is_some_and(|formats| formats.contains(&format_path(name)))
+    }
+
+    /// 记录格式已下载到该打印机
+    pub fn mark_downloaded(&self, printer: &str, name: &str) {
+        self.downloaded
+            .lock()
+            .unwrap()
+            .entry(printer.to_string())
+            .or_default()
+            .insert(format_path(name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_per_printer() {
+        let formats = ZplFormats::new();
+        assert!(!formats.is_downloaded("Label", "PRICE"));
+
+        formats.mark_downloaded("Label", "PRICE");
+        assert!(formats.is_downloaded("Label", "PRICE"));
+        // 名称按完整路径比较
+        assert!(formats.is_downloaded("Label", "R:PRICE.ZPL"));
+        assert!(!formats.is_downloaded("Label", "E:PRICE.ZPL"));
+        assert!(!formats.is_downloaded("Label 2", "PRICE"));
+    }
+}
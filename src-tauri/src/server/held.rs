@@ -0,0 +1,90 @@
+//! 暂停待释放的打印任务
+//!
+//! `options.hold` 的任务提交到打印队列后立即暂停，操作员到打印机旁确认后再通过 `release_job` 释放；
+//! 记录任务所在的打印机，释放时只需提供任务 ID；在打印机旁释放或取消的任务在下次暂停新任务时清理
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// 已暂停等待释放的任务（任务 ID -> 打印机名称）
+#[derive(Default)]
+pub struct HeldJobs {
+    jobs: Mutex<HashMap<u32, String>>,
+}
+
+impl HeldJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录已暂停的任务
+    pub fn hold(&self, job_id: u32, printer: &str) {
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(job_id, printer.to_string());
+    }
+
+    /// 任务所在的打印机
+    pub fn printer(&self, job_id: u32) -> Option<String> {
+        self.jobs.lock().unwrap().get(&job_id).cloned()
+    }
+
+    /// 任务已释放
+    pub fn release(&self, job_id: u32) {
+        self.jobs.lock().unwrap().remove(&job_id);
+    }
+
+    /// 清理不再暂停的任务（在打印机旁释放、取消或已打印），`still_held` 查询打印队列
+    ///
+    /// 查询期间不持有锁，查询失败的任务由调用方决定是否保留
+    pub fn prune(&self, still_held: impl Fn(u32, &str) -> bool) {
+        let jobs: Vec<(u32, String)> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(job_id, printer)| (*job_id, printer.clone()))
+            .collect();
+        let gone: Vec<u32> = jobs
+            .into_iter()
+            .filter(|(job_id, printer)| !still_held(*job_id, printer))
+            .map(|(job_id, _)| job_id)
+            .collect();
+
+        let mut held = self.jobs.lock().unwrap();
+        for job_id in gone {
+            held.remove(&job_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_held_jobs() {
+        let held = HeldJobs::new();
+        held.hold(7, "Office");
+        assert_eq!(held.printer(7).as_deref(), Some("Office"));
+        assert_eq!(held.printer(8), None);
+
+        held.release(7);
+        assert_eq!(held.printer(7), None);
+    }
+
+    #[test]
+    fn test_prune_released_jobs() {
+        let held = HeldJobs::new();
+        held.hold(1, "Office");
+        held.hold(2, "Office");
+        held.hold(3, "Label");
+
+        // 任务 2 已在打印机旁释放
+        held.prune(|job_id, _| job_id != 2);
+        assert_eq!(held.printer(1).as_deref(), Some("Office"));
+        assert_eq!(held.printer(2), None);
+        assert_eq!(held.printer(3).as_deref(), Some("Label"));
+    }
+}
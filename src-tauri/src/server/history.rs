@@ -0,0 +1,284 @@
+//! 打印任务历史记录
+//!
+//! 保存最近失败的打印请求，便于排查和重新执行；
+//! 各打印机最近一次成功打印的原始数据，用于补打；
+//! 以及最近的打印结果，客户端断线重连后可按请求 ID 查询
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::protocol::{FailedJob, PrintDatatype, PrintRequest, ServerMessage};
+
+/// 需要脱敏的字段名关键字（不区分大小写）
+const SENSITIVE_KEYS: &[&str] = &[
+    "token",
+    "password",
+    "secret",
+    "authorization",
+    "api_key",
+    "apikey",
+    "webhook_url",
+];
+
+/// 脱敏后的占位值
+const REDACTED: &str = "***";
+
+/// 可补打任务的最大字节数（超过则不保存）
+const MAX_REPRINT_BYTES: usize = 4 * 1024 * 1024;
+
+/// 失败任务列表（最新的在最前）
+///
+/// 内存中保留原始请求用于重新执行，只在列出时脱敏
+#[derive(Default)]
+pub struct FailedJobs {
+    jobs: Mutex<VecDeque<FailedJob>>,
+}
+
+impl FailedJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一次失败，超过 limit 时丢弃最旧的记录
+    pub fn record(&self, request: &PrintRequest, error: &str, limit: usize) {
+        if limit == 0 {
+            return;
+        }
+
+        let job = FailedJob {
+            request: request.clone(),
+            error: error.to_string(),
+            failed_at: now_millis(),
+        };
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.push_front(job);
+        jobs.truncate(limit);
+    }
+
+    /// 获取所有失败记录（敏感字段已脱敏）
+    pub fn list(&self) -> Vec<FailedJob> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .map(|mut job| {
+                redact(&mut job.request.data);
+                job
+            })
+            .collect()
+    }
+
+    /// 按索引获取原始失败记录（0 为最近一次，用于重新执行，不对外返回）
+    pub fn get(&self, index: usize) -> Option<FailedJob> {
+        self.jobs.lock().unwrap().get(index).cloned()
+    }
+}
+
+/// 各打印机最近一次成功打印的原始数据及其数据类型
+#[derive(Default)]
+pub struct LastPrinted {
+    jobs: Mutex<HashMap<String, (Vec<u8>, PrintDatatype)>>,
+}
+
+impl LastPrinted {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录打印机最近一次打印的数据（超过大小限制时清除旧记录）
+    pub fn record(&self, printer: &str, data: &[u8], datatype: PrintDatatype) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if data.len() > MAX_REPRINT_BYTES {
+            jobs.remove(printer);
+            return;
+        }
+        jobs.insert(printer.to_string(), (data.to_vec(), datatype));
+    }
+
+    /// 获取打印机最近一次打印的数据及其数据类型
+    pub fn get(&self, printer: &str) -> Option<(Vec<u8>, PrintDatatype)> {
+        self.jobs.lock().unwrap().get(printer).cloned()
+    }
+}
+
+/// 最近的打印结果（最新的在最前，按请求 ID 查询）
+#[derive(Default)]
+pub struct RecentResults {
+    results: Mutex<VecDeque<(String, ServerMessage, u64)>>,
+}
+
+impl RecentResults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录请求的结果（同一 ID 只保留最新的结果），超过 limit 时丢弃最旧的记录
+    pub fn record(&self, id: &str, result: &ServerMessage, limit: usize, ttl_secs: u64) {
+        if limit == 0 || ttl_secs == 0 {
+            return;
+        }
+
+        let now = now_millis();
+        let mut results = self.results.lock().unwrap();
+        results.retain(|(existing, _, stored_at)| {
+            existing != id && !expired(*stored_at, now, ttl_secs)
+        });
+        results.push_front((id.to_string(), result.clone(), now));
+        results.truncate(limit);
+    }
+
+    /// 获取请求的结果（不存在或已过期时返回 None）
+    pub fn get(&self, id: &str, ttl_secs: u64) -> Option<ServerMessage> {
+        let now = now_millis();
+        let results = self.results.lock().unwrap();
+        results
+            .iter()
+            .find(|(existing, _, stored_at)| existing == id && !expired(*stored_at, now, ttl_secs))
+            .map(|(_, result, _)| result.clone())
+    }
+}
+
+/// 结果是否已超过保留时长
+fn expired(stored_at: u64, now: u64, ttl_secs: u64) -> bool {
+    now.saturating_sub(stored_at) >= ttl_secs.saturating_mul(1000)
+}
+
+/// 当前时间（Unix 毫秒）
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 递归替换敏感字段的值
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if SENSITIVE_KEYS.iter().any(|k| key.contains(k)) {
+                    *v = Value::String(REDACTED.to_string());
+                } else {
+                    redact(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::PrintResult;
+    use serde_json::json;
+
+    fn request(id: &str, data: Value) -> PrintRequest {
+        serde_json::from_value(json!({
+            "id": id,
+            "template_type": "escpos",
+            "template": "{{order_no}}",
+            "data": data,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_record_keeps_latest_first_and_bounded() {
+        let jobs = FailedJobs::new();
+        for i in 0..5 {
+            jobs.record(&request(&i.to_string(), json!({})), "failed", 3);
+        }
+
+        let list = jobs.list();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].request.id, "4");
+        assert_eq!(jobs.get(2).unwrap().request.id, "2");
+        assert!(jobs.get(3).is_none());
+    }
+
+    #[test]
+    fn test_last_printed_per_printer() {
+        let last = LastPrinted::new();
+        assert!(last.get("A").is_none());
+
+        let raw = PrintDatatype::Raw;
+        last.record("A", b"first", raw);
+        last.record("B", b"other", PrintDatatype::Text);
+        last.record("A", b"second", raw);
+        assert_eq!(last.get("A").unwrap(), (b"second".to_vec(), raw));
+        assert_eq!(
+            last.get("B").unwrap(),
+            (b"other".to_vec(), PrintDatatype::Text)
+        );
+
+        // 超过大小限制时不保存，也不保留旧数据
+        last.record("A", &vec![0u8; MAX_REPRINT_BYTES + 1], raw);
+        assert!(last.get("A").is_none());
+    }
+
+    #[test]
+    fn test_record_redacts_secrets() {
+        let jobs = FailedJobs::new();
+        let data = json!({
+            "order_no": "A001",
+            "auth_token": "abc",
+            "customer": {"Password": "p", "name": "Li"},
+            "items": [{"api_key": "k"}]
+        });
+        jobs.record(&request("1", data), "failed", 10);
+
+        let data = &jobs.list()[0].request.data;
+        assert_eq!(data["order_no"], "A001");
+        assert_eq!(data["auth_token"], REDACTED);
+        assert_eq!(data["customer"]["Password"], REDACTED);
+        assert_eq!(data["customer"]["name"], "Li");
+        assert_eq!(data["items"][0]["api_key"], REDACTED);
+
+        // 重新执行使用原始请求
+        let data = &jobs.get(0).unwrap().request.data;
+        assert_eq!(data["auth_token"], "abc");
+        assert_eq!(data["items"][0]["api_key"], "k");
+    }
+
+    fn result(id: &str, status: &str) -> ServerMessage {
+        ServerMessage::PrintResult(PrintResult {
+            id: id.to_string(),
+            status: status.to_string(),
+            message: None,
+            bytes_sent: None,
+            checksum: None,
+            held_jobs: Vec::new(),
+            jobs: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_recent_results_bounded_and_expired() {
+        let results = RecentResults::new();
+        results.record("1", &result("1", "scheduled"), 2, 60);
+        results.record("1", &result("1", "success"), 2, 60);
+        let ServerMessage::PrintResult(stored) = results.get("1", 60).unwrap() else {
+            panic!("expected print result");
+        };
+        assert_eq!(stored.status, "success");
+
+        // 同一 ID 只占一条记录，超过上限时丢弃最旧的
+        results.record("2", &result("2", "success"), 2, 60);
+        results.record("3", &result("3", "error"), 2, 60);
+        assert!(results.get("1", 60).is_none());
+        assert!(results.get("2", 60).is_some());
+
+        // 超过保留时长的结果不再返回
+        assert!(results.get("3", 0).is_none());
+        assert!(expired(1_000, 61_000, 60));
+        assert!(!expired(1_000, 60_999, 60));
+    }
+}
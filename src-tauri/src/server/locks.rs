@@ -0,0 +1,104 @@
+//! 打印机写入锁
+//!
+//! 同一打印机的打印任务依次写入，避免并发的 RAW 数据交错导致 ESC/POS 指令错乱；
+//! 不同打印机之间互不影响。锁中记录上一个任务的结束时间，可按打印机限制相邻任务的最小间隔
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use tokio::time::Instant;
+
+/// 按打印机名称区分的写入锁（保存该打印机上一个任务的结束时间）
+#[derive(Default)]
+pub struct PrinterLocks {
+    locks: Mutex<HashMap<String, Arc<AsyncMutex<Option<Instant>>>>>,
+}
+
+/// 打印任务持有的写入锁，释放时记录任务结束时间
+pub struct JobGuard {
+    guard: OwnedMutexGuard<Option<Instant>>,
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        *self.guard = Some(Instant::now());
+    }
+}
+
+impl PrinterLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 等待获取打印机的写入锁，持有期间其他任务不能写入该打印机
+    pub async fn lock(&self, printer: &str) -> OwnedMutexGuard<Option<Instant>> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(printer.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
+    /// 获取写入锁并等待到距上一个任务结束至少 `min_interval`，用于保护连续出纸易过热或卡纸的打印机
+    pub async fn lock_job(&self, printer: &str, min_interval: Duration) -> JobGuard {
+        let guard = self.lock(printer).await;
+        if let Some(last) = *guard {
+            let ready = last + min_interval;
+            if ready > Instant::now() {
+                tokio::time::sleep_until(ready).await;
+            }
+        }
+        JobGuard { guard }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lock_per_printer() {
+        let locks = PrinterLocks::new();
+        let guard = locks.lock("Kitchen").await;
+
+        // 同一打印机需要等待，其他打印机可以立即写入
+        let waiting =
+            tokio::time::timeout(std::time::Duration::from_millis(20), locks.lock("Kitchen")).await;
+        assert!(waiting.is_err());
+        let _other = locks.lock("Bar").await;
+
+        drop(guard);
+        let _guard = locks.lock("Kitchen").await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_job_interval() {
+        let locks = PrinterLocks::new();
+        let interval = Duration::from_millis(100);
+
+        // 第一个任务无需等待
+        drop(locks.lock_job("Kitchen", interval).await);
+
+        // 同一打印机的下一个任务等待间隔，其他打印机不受影响
+        let next = locks.lock_job("Kitchen", interval);
+        tokio::pin!(next);
+        assert!(futures::poll!(&mut next).is_pending());
+        drop(locks.lock_job("Bar", interval).await);
+
+        tokio::time::advance(interval - Duration::from_millis(1)).await;
+        assert!(futures::poll!(&mut next).is_pending());
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert!(futures::poll!(&mut next).is_ready());
+
+        // 状态查询等非打印任务不记录结束时间
+        drop(locks.lock("Counter").await);
+        let next = locks.lock_job("Counter", interval);
+        tokio::pin!(next);
+        assert!(futures::poll!(&mut next).is_ready());
+    }
+}
@@ -1,27 +1,84 @@
 //! WebSocket 服务模块
+//!
+//! 同时提供只读的 SSE 事件流（`GET /events`），推送与 WebSocket 相同的广播消息
 
-use std::net::SocketAddr;
-use std::sync::Arc;
+mod active;
+mod benchmark;
+mod chunks;
+mod drivers;
+mod formats;
+mod held;
+mod history;
+mod locks;
+mod schedule;
+mod stats;
+#[cfg(unix)]
+mod unix;
+mod webhook;
+
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Path as RoutePath, Query, Request, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Json, Response,
     },
-    response::IntoResponse,
     routing::get,
     Router,
 };
-use futures::{SinkExt, StreamExt};
-use tokio::sync::{broadcast, RwLock};
+use futures::{FutureExt, SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, RwLock};
 use tower_http::cors::CorsLayer;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
-use crate::printer::{create_printer_manager, PrinterManager};
+use crate::audit::{self, AuditEntry, AuditLog};
+use crate::config::{
+    get_archive_dir, get_scheduled_jobs_path, AppConfig, PrinterConfig, ServerConfig,
+};
+use crate::printer::{archive, find_printer, printer_exists, printer_target, PrinterManager};
 use crate::protocol::{
-    ClientMessage, ErrorResponse, PrintResult, PrintersResponse, ServerMessage, StatusResponse,
+    ClientMessage, DetailedStatusResponse, ErrorResponse, EscposStatus, FailedJobsResponse,
+    JobsSection, PreviewResponse, PrintDatatype, PrintDocument, PrintError, PrintOptions,
+    PrintRequest, PrintResult, PrinterSelector, PrintersResponse, PrintersSection, ServerMessage,
+    ServerSection, StatusResponse, ZplFormat, AMBIGUOUS_PRINTER, ARCHIVE_FAILED, JOB_NOT_COMPLETED,
+    JOB_NOT_FOUND, NOTHING_TO_REPRINT, NO_MATCHING_PRINTER, NO_PRINTERS_INSTALLED,
+    OPTION_NOT_SUPPORTED, PORT_IN_USE, PRINT_FAILED, PRINT_TIMEOUT, SHUTTING_DOWN,
+};
+use crate::renderer::{
+    normalize_line_endings, render_template_limited, render_template_timed, sanitize_text, zpl,
+    HelperProfile,
 };
-use crate::renderer::render_template;
+use crate::storage::write_print_error;
+use crate::templates::{check_data, TemplateStore};
+use active::{ActiveJob, ActiveJobs};
+use chunks::ChunkAssembler;
+use drivers::DriverCache;
+use formats::ZplFormats;
+use held::HeldJobs;
+use history::{now_millis, FailedJobs, LastPrinted, RecentResults};
+use locks::PrinterLocks;
+use schedule::ScheduledJobs;
+use stats::JobStats;
+use webhook::{WebhookPayload, Webhooks};
+
+pub use benchmark::{benchmark_print, PrintBenchmark};
+pub use history::redact;
 
 /// 服务状态
 #[derive(Clone)]
@@ -29,45 +86,543 @@ pub struct ServerState {
     /// 连接计数
     pub connection_count: Arc<RwLock<usize>>,
     /// 广播通道（用于通知所有连接）
-    pub broadcast_tx: broadcast::Sender<String>,
+    pub broadcast_tx: broadcast::Sender<Broadcast>,
     /// 打印机管理器
     pub printer_manager: Arc<Box<dyn PrinterManager>>,
+    /// 模板库
+    pub templates: Arc<TemplateStore>,
+    /// 应用配置
+    pub config: Arc<RwLock<AppConfig>>,
+    /// 停机通知（置为 true 后各连接发送完剩余消息并关闭）
+    pub shutdown_tx: watch::Sender<bool>,
+    /// 最近失败的打印任务
+    pub failed_jobs: Arc<FailedJobs>,
+    /// 各打印机最近一次成功打印的数据（用于补打）
+    pub last_printed: Arc<LastPrinted>,
+    /// 最近的打印结果（客户端重连后按请求 ID 查询）
+    pub recent_results: Arc<RecentResults>,
+    /// 正在组装的分块打印任务
+    pub chunks: Arc<ChunkAssembler>,
+    /// 各打印机已下载的 ZPL 存储格式
+    pub zpl_formats: Arc<ZplFormats>,
+    /// 各打印机的写入锁（同一打印机的任务依次写入）
+    pub printer_locks: Arc<PrinterLocks>,
+    /// 打印审计日志
+    pub audit: Arc<AuditLog>,
+    /// 等待执行的定时打印任务
+    pub scheduled: Arc<ScheduledJobs>,
+    /// 打印完成通知（应用据此向前端推送 `print-complete` 事件）
+    pub print_complete_tx: broadcast::Sender<PrintComplete>,
+    /// 进行中的打印任务（停机时等待其完成）
+    pub active_jobs: Arc<ActiveJobs>,
+    /// 打印完成 Webhook
+    pub webhooks: Arc<Webhooks>,
+    /// 打印任务统计
+    pub stats: Arc<JobStats>,
+    /// 暂停等待释放的任务
+    pub held_jobs: Arc<HeldJobs>,
+    /// 打印机驱动名称（按型号匹配小票宽度）
+    pub drivers: Arc<DriverCache>,
 }
 
 impl ServerState {
-    pub fn new() -> Self {
+    /// 使用指定的打印机管理器创建服务状态（与应用共用，也可以是测试用的模拟打印机）
+    pub fn with_printer_manager(
+        config: Arc<RwLock<AppConfig>>,
+        templates: Arc<TemplateStore>,
+        printer_manager: Arc<Box<dyn PrinterManager>>,
+    ) -> Self {
         let (broadcast_tx, _) = broadcast::channel(100);
+        let (shutdown_tx, _) = watch::channel(false);
+        let (print_complete_tx, _) = broadcast::channel(100);
         Self {
             connection_count: Arc::new(RwLock::new(0)),
             broadcast_tx,
-            printer_manager: Arc::new(create_printer_manager()),
+            printer_manager,
+            templates,
+            config,
+            shutdown_tx,
+            failed_jobs: Arc::new(FailedJobs::new()),
+            last_printed: Arc::new(LastPrinted::new()),
+            recent_results: Arc::new(RecentResults::new()),
+            chunks: Arc::new(ChunkAssembler::new()),
+            zpl_formats: Arc::new(ZplFormats::new()),
+            printer_locks: Arc::new(PrinterLocks::new()),
+            audit: Arc::new(AuditLog::new()),
+            scheduled: Arc::new(ScheduledJobs::new()),
+            print_complete_tx,
+            active_jobs: Arc::new(ActiveJobs::new()),
+            webhooks: Arc::new(Webhooks::new()),
+            stats: Arc::new(JobStats::new()),
+            held_jobs: Arc::new(HeldJobs::new()),
+            drivers: Arc::new(DriverCache::new()),
+        }
+    }
+
+    /// 向所有连接广播服务主动推送的消息（没有连接时忽略）
+    pub fn broadcast(&self, text: String) {
+        let _ = self.broadcast_tx.send(Broadcast { origin: None, text });
+    }
+
+    /// 通知打印任务已完成（没有订阅者时忽略）
+    pub fn notify_print_complete<T, E: std::fmt::Display>(
+        &self,
+        id: Option<&str>,
+        printer: Option<&str>,
+        result: &Result<T, E>,
+    ) {
+        let _ = self.print_complete_tx.send(PrintComplete {
+            id: id.map(str::to_string),
+            printer: printer.map(str::to_string),
+            status: if result.is_ok() { "success" } else { "error" }.to_string(),
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+    }
+
+    /// 写入一条打印审计记录（未配置 `audit_log_path` 时忽略，未开启 `audit_include_data` 时不记录模板数据）
+    pub fn record_audit(&self, config: &ServerConfig, mut entry: AuditEntry) {
+        let Some(path) = config.audit_log_path.as_deref() else {
+            return;
+        };
+        if !config.audit_include_data {
+            entry.data = None;
+        }
+        if let Err(e) = self.audit.record(Path::new(path), &entry) {
+            warn!("Failed to write audit log: {}", e);
+        }
+    }
+
+    /// 通知任务已提交但暂停等待释放（尚未打印，`success` 为 false）
+    pub fn notify_print_held(&self, id: Option<&str>, printer: Option<&str>) {
+        let _ = self.print_complete_tx.send(PrintComplete {
+            id: id.map(str::to_string),
+            printer: printer.map(str::to_string),
+            status: "held".to_string(),
+            success: false,
+            error: None,
+        });
+    }
+}
+
+/// 广播消息
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    /// 发出该响应的连接（该连接已直接收到响应，不再重复发送；服务主动推送的消息为空）
+    pub origin: Option<u64>,
+    /// 消息 JSON
+    pub text: String,
+}
+
+/// 打印完成通知
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PrintComplete {
+    /// 请求 ID（Tauri 命令发起的打印为空）
+    pub id: Option<String>,
+    /// 打印机名称（未能确定打印机时为空）
+    pub printer: Option<String>,
+    /// 打印结果: success, held（已暂停等待释放）, error
+    pub status: String,
+    /// 是否打印成功
+    pub success: bool,
+    /// 失败原因
+    pub error: Option<String>,
+}
+
+/// 下一个连接的 ID（用于识别广播消息的来源连接）
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 不受订阅过滤的广播消息类型（停机通知和订阅确认总是发送）
+const UNFILTERED_EVENTS: &[&str] = &["shutdown", "subscribed"];
+
+/// 连接订阅的广播消息类型（未订阅时接收全部），由接收任务设置、发送任务据此过滤
+///
+/// 只过滤其他连接的响应和服务主动推送的消息，连接自己请求的响应总是直接发送
+#[derive(Default)]
+struct Subscription(Mutex<Option<HashSet<String>>>);
+
+/// 广播消息中用于过滤的类型字段
+#[derive(Deserialize)]
+struct EventType {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl Subscription {
+    /// 设置订阅的消息类型，为空时恢复接收全部
+    fn set(&self, events: &[String]) {
+        *self.0.lock().unwrap() = (!events.is_empty()).then(|| events.iter().cloned().collect());
+    }
+
+    /// 是否向该连接发送这条广播消息
+    fn accepts(&self, msg: &str) -> bool {
+        let events = self.0.lock().unwrap();
+        let Some(events) = events.as_ref() else {
+            return true;
+        };
+        match serde_json::from_str::<EventType>(msg) {
+            Ok(event) => {
+                events.contains(&event.kind) || UNFILTERED_EVENTS.contains(&event.kind.as_str())
+            }
+            Err(_) => false,
         }
     }
 }
 
-impl Default for ServerState {
-    fn default() -> Self {
-        Self::new()
+/// 单个连接的状态（断开后丢弃）
+#[derive(Default)]
+struct ConnectionState {
+    /// 连接 ID（分块任务按连接区分）
+    id: u64,
+    /// 连接级默认打印机（优先于系统默认打印机）
+    default_printer: Option<String>,
+    /// 客户端 IP（用于审计日志）
+    client_ip: Option<IpAddr>,
+    /// 订阅的广播消息类型
+    subscription: Arc<Subscription>,
+}
+
+impl ConnectionState {
+    /// 请求未指定打印机时使用连接级默认打印机
+    fn apply_default_printer(&self, req: &mut PrintRequest) {
+        let is_empty = match &req.printer {
+            None => true,
+            Some(PrinterSelector::Name(name)) => name.is_empty(),
+            Some(PrinterSelector::Query(_)) => false,
+        };
+        if is_empty {
+            if let Some(printer) = &self.default_printer {
+                req.printer = Some(PrinterSelector::Name(printer.clone()));
+            }
+        }
+    }
+
+    /// 未指定打印机时返回连接级默认打印机
+    fn printer_or_default(&self, printer: Option<String>) -> Option<String> {
+        printer
+            .filter(|name| !name.is_empty())
+            .or_else(|| self.default_printer.clone())
     }
 }
 
+/// 服务运行结果
+type ServeResult<T = ()> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// 端口被占用时重试绑定的次数（重启服务时旧的监听可能尚未释放端口）
+const BIND_RETRIES: u32 = 5;
+
+/// 重试绑定的间隔
+const BIND_RETRY_DELAY: Duration = Duration::from_millis(500);
+
 /// 启动 WebSocket 服务
-pub async fn start_server(port: u16) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let state = Arc::new(ServerState::new());
+///
+/// 默认监听 TCP 端口；配置了 `unix_socket` 时同时（或在关闭 TCP 时仅）监听 Unix domain socket。
+/// 收到 `shutdown` 信号后优雅停止：先广播 `Shutdown` 消息告知客户端重连延迟，
+/// 停止接受新的打印任务并等待已接受的任务完成，再关闭所有连接
+pub async fn start_server(
+    port: u16,
+    state: Arc<ServerState>,
+    shutdown: oneshot::Receiver<()>,
+) -> ServeResult {
+    let app = router(state.clone());
+
+    let server_config = state.config.read().await.server.clone();
+    let unix_socket = server_config.unix_socket.filter(|path| !path.is_empty());
+    if !server_config.tcp_enabled && unix_socket.is_none() {
+        return Err("No listener configured: enable TCP or set unix_socket".into());
+    }
+
+    // 恢复上次保存的定时打印任务
+    state.scheduled.load(&get_scheduled_jobs_path());
+    // 重新启动时恢复接受打印任务
+    state.active_jobs.resume();
+    let scheduler_state = state.clone();
+
+    // 停止信号由 TCP、Unix socket 监听和定时任务共享
+    let shutdown = async move {
+        let _ = shutdown.await;
+        notify_shutdown(&state).await;
+    }
+    .shared();
+
+    let tcp = async {
+        if !server_config.tcp_enabled {
+            return Ok(());
+        }
+
+        let ip = listen_ip(&server_config.host)
+            .ok_or_else(|| format!("Invalid server.host: {}", server_config.host))?;
+        let addr = SocketAddr::new(ip, port);
+        info!("WebSocket server starting on ws://{}", addr);
+
+        let keepalive = Some(server_config.tcp_keepalive_secs)
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+        let listener = bind_tcp_with_retry(addr, keepalive, BIND_RETRIES, BIND_RETRY_DELAY).await?;
+        axum::serve(
+            listener,
+            app.clone()
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .tcp_nodelay(server_config.tcp_nodelay)
+        .with_graceful_shutdown(shutdown.clone())
+        .await?;
+        ServeResult::Ok(())
+    };
+
+    let unix = async {
+        match &unix_socket {
+            #[cfg(unix)]
+            Some(path) => {
+                let mode = &server_config.unix_socket_mode;
+                unix::serve(path, mode, app.clone(), shutdown.clone()).await
+            }
+            #[cfg(not(unix))]
+            Some(_) => Err("Unix domain sockets are not supported on this platform".into()),
+            None => Ok(()),
+        }
+    };
+
+    let scheduler = async {
+        run_scheduled_jobs(&scheduler_state, shutdown.clone()).await;
+        ServeResult::Ok(())
+    };
+
+    tokio::try_join!(tcp, unix, scheduler)?;
+
+    info!("WebSocket server stopped");
+    Ok(())
+}
+
+/// 解析 `server.host` 监听地址，`localhost` 视为 127.0.0.1
+fn listen_ip(host: &str) -> Option<IpAddr> {
+    if host.eq_ignore_ascii_case("localhost") {
+        return Some(IpAddr::from([127, 0, 0, 1]));
+    }
+    host.parse().ok()
+}
+
+/// 服务是否只能从本机访问：TCP 只监听回环地址，或只开启了 Unix socket
+fn local_only(server: &ServerConfig) -> bool {
+    !server.tcp_enabled || listen_ip(&server.host).is_some_and(|ip| ip.is_loopback())
+}
+
+/// 创建 TCP 监听，按需开启 keepalive（接受的连接继承监听 socket 的 keepalive 设置）
+fn bind_tcp(
+    addr: SocketAddr,
+    keepalive: Option<Duration>,
+) -> std::io::Result<tokio::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    // 与 tokio 的 TcpListener::bind 一致：非 Windows 平台允许重启后立即重新绑定端口
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    if let Some(interval) = keepalive {
+        let params = TcpKeepalive::new()
+            .with_time(interval)
+            .with_interval(interval);
+        socket.set_tcp_keepalive(&params)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+/// 创建 TCP 监听，端口被占用时每隔 `delay` 重试，`retries` 次后仍失败则返回 `PORT_IN_USE`
+async fn bind_tcp_with_retry(
+    addr: SocketAddr,
+    keepalive: Option<Duration>,
+    retries: u32,
+    delay: Duration,
+) -> ServeResult<tokio::net::TcpListener> {
+    let mut attempt = 0;
+    loop {
+        match bind_tcp(addr, keepalive) {
+            Ok(listener) => return Ok(listener),
+            Err(e) if e.kind() != std::io::ErrorKind::AddrInUse => return Err(e.into()),
+            Err(_) if attempt >= retries => {
+                let message = format!(
+                    "Port {} is already in use (tried {} times)",
+                    addr.port(),
+                    attempt + 1
+                );
+                return Err(PrintError::new(PORT_IN_USE, message).to_string().into());
+            }
+            Err(_) => {
+                warn!(
+                    "Port {} is in use, retrying in {:?} ({}/{})",
+                    addr.port(),
+                    delay,
+                    attempt + 1,
+                    retries
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// 执行到期的定时打印任务，结果广播给所有连接，直到服务停止
+async fn run_scheduled_jobs(state: &Arc<ServerState>, shutdown: impl Future<Output = ()>) {
+    tokio::pin!(shutdown);
+    loop {
+        // 停机中不再执行到期任务，保留到下次启动
+        if state.active_jobs.is_draining() {
+            break;
+        }
+
+        // 先注册通知再检查任务，避免错过检查期间新增的任务
+        let changed = state.scheduled.changed();
+
+        for req in state.scheduled.take_due(now_millis()) {
+            info!("Running scheduled print: id={}", req.id);
+            let id = req.id.clone();
+            let response = print_response(req, state, None).await;
+            state.scheduled.finish(&id);
+            if let Ok(text) = serde_json::to_string(&response) {
+                state.broadcast(text);
+            }
+        }
 
-    let app = Router::new()
+        // 每次按当前时间重新计算等待时长，不会累积误差
+        let next_due = state.scheduled.next_due();
+        let wait = async {
+            match next_due {
+                Some(at) => {
+                    let delay = at.saturating_sub(now_millis());
+                    tokio::time::sleep(Duration::from_millis(delay)).await
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = wait => {}
+            _ = changed => {}
+            _ = &mut shutdown => break,
+        }
+    }
+}
+
+/// 构造 HTTP 路由：REST 端点和 SSE 事件流经过认证中间件，WebSocket、测试页和健康检查不受影响
+fn router(state: Arc<ServerState>) -> Router {
+    let auth = middleware::from_fn_with_state(state.clone(), require_auth);
+    // REST 端点不允许跨域调用，在 CORS 层之后合并
+    let rest = Router::new()
+        .route("/print/:template_name", get(get_print_handler))
+        .route("/config", get(config_handler))
+        .route_layer(auth.clone());
+    // 监控页面可跨域订阅事件流（EventSource 无法设置请求头，通过 `_key` 查询参数认证）
+    let events = Router::new()
+        .route("/events", get(events_handler))
+        .route_layer(auth);
+
+    Router::new()
+        .route("/", get(index_handler))
         .route("/ws", get(ws_handler))
         .route("/health", get(health_handler))
+        .merge(events)
         .layer(CorsLayer::permissive())
-        .with_state(state);
+        .merge(rest)
+        .with_state(state)
+}
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    info!("WebSocket server starting on ws://0.0.0.0:{}", port);
+/// REST 端点的统一认证：配置了 `server.api_token` 或 `server.api_key` 时，须携带匹配的
+/// `Authorization: Bearer <token>`、`server.auth_header` 请求头或 `_key` 查询参数（任一匹配即可），
+/// 否则返回 401。服务没有 `/metrics` 端点，受保护的只有经过该中间件的路由（`/print`、`/config`、`/events`）
+async fn require_auth(
+    State(state): State<Arc<ServerState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (authorized, bearer) = {
+        let config = state.config.read().await;
+        let token = config.server.api_token.as_deref().filter(|t| !t.is_empty());
+        let key = config.server.api_key.as_deref().filter(|k| !k.is_empty());
+        let headers = request.headers();
+        let token_ok = token.is_some_and(|token| {
+            bearer_token(headers).is_some_and(|provided| constant_time_eq(provided, token))
+        });
+        let key_ok = key.is_some_and(|key| {
+            headers
+                .get(config.server.auth_header.as_str())
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|provided| constant_time_eq(provided, key))
+        });
+        // 无法设置请求头的设备（如扫码枪固件）通过查询参数携带凭据
+        let query_ok = Query::<BTreeMap<String, String>>::try_from_uri(request.uri())
+            .ok()
+            .and_then(|Query(mut params)| params.remove(QUERY_KEY))
+            .is_some_and(|provided| {
+                [token, key]
+                    .into_iter()
+                    .flatten()
+                    .any(|expected| constant_time_eq(&provided, expected))
+            });
+        let open = token.is_none() && key.is_none();
+        (open || token_ok || key_ok || query_ok, token.is_some())
+    };
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    if !authorized {
+        warn!("Unauthorized request: {}", request.uri().path());
+        if bearer {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                "Unauthorized",
+            )
+                .into_response();
+        }
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+    next.run(request).await
+}
 
-    Ok(())
+/// 按常数时间比较凭据，耗时不随首个不匹配字符的位置变化
+fn constant_time_eq(provided: &str, expected: &str) -> bool {
+    let (a, b) = (provided.as_bytes(), expected.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// 广播停机消息，等待进行中的打印任务完成后通知所有连接关闭
+async fn notify_shutdown(state: &Arc<ServerState>) {
+    let (retry_after_ms, drain_timeout_secs) = {
+        let config = state.config.read().await;
+        (
+            config.server.shutdown_retry_after_ms,
+            config.server.shutdown_drain_timeout_secs,
+        )
+    };
+    info!(
+        "Shutting down WebSocket server, clients advised to retry after {} ms",
+        retry_after_ms
+    );
+
+    let msg = ServerMessage::Shutdown { retry_after_ms };
+    if let Ok(text) = serde_json::to_string(&msg) {
+        state.broadcast(text);
+    }
+
+    // 不再接受新任务，已接受的任务打印完成（结果仍发送给客户端）后再关闭连接
+    let dropped = state
+        .active_jobs
+        .drain(Duration::from_secs(drain_timeout_secs))
+        .await;
+    if !dropped.is_empty() {
+        warn!(
+            "Print jobs not finished within {} s before shutdown: {}",
+            drain_timeout_secs,
+            dropped.join(", ")
+        );
+    }
+
+    let _ = state.shutdown_tx.send(true);
+}
+
+/// 内置测试页（列出打印机、查看状态、发送测试打印）
+async fn index_handler() -> Html<&'static str> {
+    Html(include_str!("index.html"))
 }
 
 /// 健康检查端点
@@ -75,92 +630,415 @@ async fn health_handler() -> impl IntoResponse {
     "OK"
 }
 
+/// 只读配置端点：`GET /config`，返回当前生效的配置（敏感字段已脱敏），供批量部署工具核对设置
+///
+/// 认证由 [`require_auth`] 完成；监听非回环地址且未配置任何凭据时拒绝返回配置
+async fn config_handler(State(state): State<Arc<ServerState>>) -> Response {
+    let config = state.config.read().await;
+    if !has_credentials(&config.server) && !local_only(&config.server) {
+        warn!("Config request refused: no credentials configured on a non-loopback listener");
+        return (
+            StatusCode::FORBIDDEN,
+            "GET /config requires server.api_token or server.api_key when server.host is not a loopback address",
+        )
+            .into_response();
+    }
+
+    match serde_json::to_value(&*config) {
+        Ok(mut value) => {
+            redact(&mut value);
+            Json(value).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// 是否配置了 REST 端点的凭据（`api_token` 或 `api_key`）
+fn has_credentials(server: &ServerConfig) -> bool {
+    [&server.api_token, &server.api_key]
+        .into_iter()
+        .any(|c| c.as_deref().is_some_and(|c| !c.is_empty()))
+}
+
+/// 读取 `Authorization: Bearer <token>` 请求头中的令牌
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// 简易打印端点：`GET /print/:template_name?order_no=123`
+///
+/// 用于只能发送 GET 请求的设备（如扫码枪固件）：查询参数作为模板数据，打印指定的模板库模板，
+/// 返回纯文本 `OK` 或 `ERROR: ...`。GET 请求可被任意网页跨站触发，未配置凭据时拒绝打印
+async fn get_print_handler(
+    RoutePath(template_name): RoutePath<String>,
+    Query(params): Query<BTreeMap<String, String>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    State(state): State<Arc<ServerState>>,
+) -> (StatusCode, String) {
+    let (printer_config, has_credentials) = {
+        let config = state.config.read().await;
+        (config.printer.clone(), has_credentials(&config.server))
+    };
+    if !has_credentials {
+        warn!("GET print refused: no credentials configured");
+        return (
+            StatusCode::FORBIDDEN,
+            "ERROR: GET /print requires server.api_key or server.api_token".to_string(),
+        );
+    }
+    let req = query_print_request(template_name, params, &printer_config);
+    info!(
+        "GET print request: id={}, template={:?}",
+        req.id, req.template_name
+    );
+
+    let client_ip = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    match run_print(&req, &state, client_ip).await {
+        Ok(_) => (StatusCode::OK, "OK".to_string()),
+        Err(e) => {
+            error!("GET print failed: id={}, error={}", req.id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("ERROR: {}", e))
+        }
+    }
+}
+
+/// 查询参数中的模板类型（默认 escpos）
+const QUERY_TYPE: &str = "_type";
+
+/// 查询参数中的目标打印机（默认使用该类型配置的打印机，再回退到默认打印机）
+const QUERY_PRINTER: &str = "_printer";
+
+/// 查询参数中的 API key（认证中间件检查，不作为模板数据）
+const QUERY_KEY: &str = "_key";
+
+/// 由 GET 打印端点的查询参数构造打印请求（`_type`、`_printer`、`_key` 以外的参数作为字符串数据）
+fn query_print_request(
+    template_name: String,
+    mut params: BTreeMap<String, String>,
+    printer_config: &PrinterConfig,
+) -> PrintRequest {
+    params.remove(QUERY_KEY);
+    let template_type = params
+        .remove(QUERY_TYPE)
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| "escpos".to_string());
+    let configured = match template_type.as_str() {
+        "escpos" => &printer_config.escpos_printer,
+        "zpl" => &printer_config.zpl_printer,
+        _ => &None,
+    };
+    let printer = params
+        .remove(QUERY_PRINTER)
+        .or_else(|| configured.clone())
+        .or_else(|| printer_config.default_printer.clone())
+        .filter(|p| !p.is_empty())
+        .map(PrinterSelector::Name);
+
+    PrintRequest {
+        id: format!("get-{}", uuid::Uuid::new_v4()),
+        template_type,
+        template: String::new(),
+        template_name: Some(template_name),
+        data: serde_json::to_value(params).unwrap_or_default(),
+        schema: None,
+        printer,
+        options: Default::default(),
+        schedule_at: None,
+        documents: Vec::new(),
+    }
+}
+
+/// SSE 事件流处理器（只读监控，每条广播消息作为一个事件推送）
+async fn events_handler(
+    State(state): State<Arc<ServerState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    debug!("New SSE subscriber");
+    let events = broadcast_messages(&state).map(|msg| Ok(Event::default().data(msg)));
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// 订阅广播消息，停机时发送完已排队的消息（包括 Shutdown 通知）后结束
+///
+/// 订阅者处理过慢时跳过丢失的消息，而不是断开连接
+fn broadcast_messages(state: &ServerState) -> impl Stream<Item = String> {
+    let broadcast_rx = state.broadcast_tx.subscribe();
+    let shutdown_rx = state.shutdown_tx.subscribe();
+
+    futures::stream::unfold(
+        (broadcast_rx, shutdown_rx, false),
+        |(mut broadcast_rx, mut shutdown_rx, mut stopping)| async move {
+            loop {
+                let msg = if stopping {
+                    match broadcast_rx.try_recv() {
+                        Ok(msg) => msg.text,
+                        Err(TryRecvError::Lagged(_)) => continue,
+                        Err(_) => return None,
+                    }
+                } else {
+                    tokio::select! {
+                        msg = broadcast_rx.recv() => match msg {
+                            Ok(msg) => msg.text,
+                            Err(RecvError::Lagged(skipped)) => {
+                                warn!("SSE subscriber lagged, skipped {} messages", skipped);
+                                continue;
+                            }
+                            Err(RecvError::Closed) => return None,
+                        },
+                        _ = shutdown_rx.changed() => {
+                            stopping = true;
+                            continue;
+                        }
+                    }
+                };
+                return Some((msg, (broadcast_rx, shutdown_rx, stopping)));
+            }
+        },
+    )
+}
+
 /// WebSocket 处理器
 async fn ws_handler(
     ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     State(state): State<Arc<ServerState>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    // 通过 Unix socket 连接时没有客户端地址
+    let addr = connect_info.map(|ConnectInfo(addr)| addr);
+    ws.on_upgrade(move |socket| handle_socket(socket, state, addr))
+}
+
+/// 记录连接生命周期日志：默认为 debug，开启 `log_connections` 时为 info
+fn log_connection(log_connections: bool, message: std::fmt::Arguments) {
+    if log_connections {
+        info!("{}", message);
+    } else {
+        debug!("{}", message);
+    }
 }
 
 /// 处理单个 WebSocket 连接
-async fn handle_socket(socket: WebSocket, state: Arc<ServerState>) {
+async fn handle_socket(socket: WebSocket, state: Arc<ServerState>, addr: Option<SocketAddr>) {
+    let log_connections = state.config.read().await.server.log_connections;
+
     // 增加连接计数
     {
         let mut count = state.connection_count.write().await;
         *count += 1;
-        info!("New WebSocket connection. Total: {}", *count);
+        log_connection(
+            log_connections,
+            format_args!(
+                "New WebSocket connection from {:?}. Total: {}",
+                addr, *count
+            ),
+        );
     }
 
     let (mut sender, mut receiver) = socket.split();
+    let id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
     let mut broadcast_rx = state.broadcast_tx.subscribe();
+    let subscription = Arc::new(Subscription::default());
+    let send_subscription = subscription.clone();
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    // 本连接请求的响应由接收任务交给发送任务直接发送，不经过广播和订阅过滤
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<String>();
+    // 客户端主动关闭时，由接收任务通知发送任务回复关闭帧
+    let (close_tx, mut close_rx) = oneshot::channel::<Option<CloseFrame<'static>>>();
 
-    // 发送任务：处理广播消息
-    let send_task = tokio::spawn(async move {
-        while let Ok(msg) = broadcast_rx.recv().await {
-            if sender.send(Message::Text(msg.into())).await.is_err() {
-                break;
+    // 发送任务：发送本连接的响应和广播消息
+    let mut send_task = tokio::spawn(async move {
+        // 本连接的响应已直接发送，订阅过滤只作用于其他来源的广播
+        let accepts =
+            |msg: &Broadcast| msg.origin != Some(id) && send_subscription.accepts(&msg.text);
+        loop {
+            tokio::select! {
+                Some(reply) = reply_rx.recv() => {
+                    if sender.send(Message::Text(reply)).await.is_err() {
+                        break;
+                    }
+                }
+                msg = broadcast_rx.recv() => {
+                    let Ok(msg) = msg else { break };
+                    if !accepts(&msg) {
+                        continue;
+                    }
+                    if sender.send(Message::Text(msg.text)).await.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    // 停机：先发送已排队的响应和消息（包括 Shutdown 通知），再关闭连接
+                    let replies = std::iter::from_fn(|| reply_rx.try_recv().ok());
+                    let broadcasts = std::iter::from_fn(|| broadcast_rx.try_recv().ok())
+                        .filter(accepts)
+                        .map(|msg| msg.text);
+                    for msg in replies.chain(broadcasts) {
+                        if sender.send(Message::Text(msg)).await.is_err() {
+                            break;
+                        }
+                    }
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
+                frame = &mut close_rx => {
+                    // 回复关闭帧（接收任务异常退出时 frame 为 Err，同样关闭连接）
+                    let _ = sender.send(Message::Close(frame.ok().flatten())).await;
+                    break;
+                }
             }
         }
     });
 
     // 接收任务：处理客户端消息
     let state_clone = state.clone();
-    let recv_task = tokio::spawn(async move {
+    let mut recv_task = tokio::spawn(async move {
+        let mut conn = ConnectionState {
+            id,
+            client_ip: addr.map(|addr| addr.ip()),
+            subscription,
+            ..Default::default()
+        };
         while let Some(Ok(msg)) = receiver.next().await {
-            if let Message::Text(text) = msg {
-                let response = handle_message(&text, &state_clone).await;
-                if let Err(e) = state_clone.broadcast_tx.send(response) {
-                    warn!("Failed to broadcast: {}", e);
+            match msg {
+                Message::Text(text) => {
+                    let response = handle_message(&text, &state_clone, &mut conn).await;
+                    let _ = reply_tx.send(response.clone());
+                    // 其他连接（及 SSE 订阅者）仍可看到该响应；没有其他订阅者时忽略
+                    let _ = state_clone.broadcast_tx.send(Broadcast {
+                        origin: Some(id),
+                        text: response,
+                    });
+                }
+                Message::Close(frame) => {
+                    log_connection(
+                        log_connections,
+                        format_args!("Client closed WebSocket connection: {:?}", frame),
+                    );
+                    let _ = close_tx.send(frame);
+                    break;
                 }
+                _ => {}
             }
         }
     });
 
     // 等待任一任务完成
     tokio::select! {
-        _ = send_task => {},
-        _ = recv_task => {},
+        // 发送任务结束（停机或发送失败）时不再处理客户端消息
+        _ = &mut send_task => recv_task.abort(),
+        // 接收任务结束时发送任务会回复关闭帧后自行退出
+        _ = &mut recv_task => {},
     }
+    state.chunks.discard(id);
 
     // 减少连接计数
     {
         let mut count = state.connection_count.write().await;
         *count = count.saturating_sub(1);
-        info!("WebSocket disconnected. Total: {}", *count);
+        log_connection(
+            log_connections,
+            format_args!("WebSocket disconnected. Total: {}", *count),
+        );
     }
 }
 
 /// 处理客户端消息
-async fn handle_message(text: &str, state: &Arc<ServerState>) -> String {
+async fn handle_message(
+    text: &str,
+    state: &Arc<ServerState>,
+    conn: &mut ConnectionState,
+) -> String {
     let msg: Result<ClientMessage, _> = serde_json::from_str(text);
 
     let response = match msg {
-        Ok(ClientMessage::Print(req)) => {
+        Ok(ClientMessage::Print(mut req)) => {
+            conn.apply_default_printer(&mut req);
+            print_response(*req, state, conn.client_ip).await
+        }
+        Ok(ClientMessage::PrintChunk {
+            id,
+            seq,
+            total,
+            data,
+        }) => handle_chunk(id, seq, total, data, state, conn).await,
+        Ok(ClientMessage::PauseJob { printer, job_id }) => {
+            control_job(state, printer, job_id, |pm, name| {
+                pm.pause_job(name, job_id)
+            })
+        }
+        Ok(ClientMessage::ResumeJob { printer, job_id }) => {
+            control_job(state, printer, job_id, |pm, name| {
+                pm.resume_job(name, job_id)
+            })
+        }
+        Ok(ClientMessage::ReleaseJob { printer, job_id }) => {
+            let printer = printer.or_else(|| state.held_jobs.printer(job_id));
+            let response = control_job(state, printer, job_id, |pm, name| {
+                pm.resume_job(name, job_id)
+            });
+            if matches!(response, ServerMessage::JobUpdated { .. }) {
+                info!("Held job released: job={}", job_id);
+                state.held_jobs.release(job_id);
+            }
+            response
+        }
+        Ok(ClientMessage::SetJobPriority {
+            printer,
+            job_id,
+            priority,
+        }) => control_job(state, printer, job_id, |pm, name| {
+            pm.set_job_priority(name, job_id, priority)
+        }),
+        Ok(ClientMessage::SetDefaultPrinter { printer }) => {
+            conn.default_printer = printer.filter(|name| !name.is_empty());
             info!(
-                "Print request: id={}, type={}",
-                req.id, req.template_type
+                "Connection default printer set to {:?}",
+                conn.default_printer
             );
-
-            // 执行打印
-            let print_result = execute_print(&req, state);
-
-            match print_result {
-                Ok(_) => ServerMessage::PrintResult(PrintResult {
-                    id: req.id,
-                    status: "success".to_string(),
-                    message: Some("打印任务已完成".to_string()),
-                }),
+            ServerMessage::DefaultPrinter {
+                printer: conn.default_printer.clone(),
+            }
+        }
+        Ok(ClientMessage::Reprint { printer }) => {
+            let printer = conn.printer_or_default(printer);
+            match reprint_last(state, printer.as_deref()).await {
+                Ok(printer) => ServerMessage::Reprinted { printer },
                 Err(e) => {
-                    error!("Print failed: {}", e);
-                    ServerMessage::PrintResult(PrintResult {
-                        id: req.id,
-                        status: "error".to_string(),
-                        message: Some(e),
+                    error!("Reprint failed: {}", e);
+                    ServerMessage::Error(ErrorResponse {
+                        code: e.code,
+                        message: e.message,
+                        id: None,
                     })
                 }
             }
         }
+        Ok(ClientMessage::GetEscposStatus { printer }) => {
+            let printer = conn.printer_or_default(printer);
+            match escpos_status(state, printer.as_deref()).await {
+                Ok((printer, status)) => ServerMessage::EscposStatus { printer, status },
+                Err(e) => ServerMessage::Error(ErrorResponse {
+                    code: e.code,
+                    message: e.message,
+                    id: None,
+                }),
+            }
+        }
+        Ok(ClientMessage::Preview(req)) => match preview(&req, state).await {
+            Ok(preview) => ServerMessage::Preview(preview),
+            Err(e) => {
+                error!("Preview failed: {}", e);
+                ServerMessage::Error(ErrorResponse {
+                    code: e.code,
+                    message: e.message,
+                    id: Some(req.id),
+                })
+            }
+        },
         Ok(ClientMessage::GetPrinters) => {
             // 从 Windows API 获取真实打印机列表
             match state.printer_manager.list_printers() {
@@ -170,10 +1048,25 @@ async fn handle_message(text: &str, state: &Arc<ServerState>) -> String {
                     ServerMessage::Error(ErrorResponse {
                         code: "PRINTER_ERROR".to_string(),
                         message: e,
+                        id: None,
                     })
                 }
             }
         }
+        Ok(ClientMessage::PrinterExists { name }) => match state.printer_manager.list_printers() {
+            Ok(printers) => ServerMessage::PrinterExists {
+                exists: printer_exists(&printers, &name),
+                name,
+            },
+            Err(e) => {
+                error!("Failed to list printers: {}", e);
+                ServerMessage::Error(ErrorResponse {
+                    code: "PRINTER_ERROR".to_string(),
+                    message: e,
+                    id: None,
+                })
+            }
+        },
         Ok(ClientMessage::GetStatus) => {
             let count = *state.connection_count.read().await;
             ServerMessage::Status(StatusResponse {
@@ -182,12 +1075,58 @@ async fn handle_message(text: &str, state: &Arc<ServerState>) -> String {
                 version: "0.1.0".to_string(),
             })
         }
+        Ok(ClientMessage::GetDetailedStatus) => {
+            ServerMessage::DetailedStatus(detailed_status(state).await)
+        }
+        Ok(ClientMessage::GetFailedJobs) => ServerMessage::FailedJobs(FailedJobsResponse {
+            jobs: state.failed_jobs.list(),
+        }),
+        Ok(ClientMessage::CancelScheduled { id }) => {
+            if state.scheduled.cancel(&id) {
+                info!("Scheduled print cancelled: id={}", id);
+                ServerMessage::ScheduledCancelled { id }
+            } else {
+                ServerMessage::Error(ErrorResponse {
+                    code: JOB_NOT_FOUND.to_string(),
+                    message: format!("No scheduled job with id '{}'", id),
+                    id: Some(id),
+                })
+            }
+        }
+        Ok(ClientMessage::GetResult { id }) => {
+            let ttl = state.config.read().await.server.result_retention_secs;
+            if let Some(result) = state.recent_results.get(&id, ttl) {
+                result
+            } else if state.active_jobs.contains(&id) {
+                ServerMessage::PrintResult(PrintResult {
+                    id,
+                    status: "printing".to_string(),
+                    message: Some("打印任务进行中".to_string()),
+                    bytes_sent: None,
+                    checksum: None,
+                    held_jobs: Vec::new(),
+                    jobs: Vec::new(),
+                })
+            } else {
+                ServerMessage::Error(ErrorResponse {
+                    code: JOB_NOT_FOUND.to_string(),
+                    message: format!("No recent result for id '{}'", id),
+                    id: Some(id),
+                })
+            }
+        }
+        Ok(ClientMessage::Subscribe { events }) => {
+            conn.subscription.set(&events);
+            info!("Connection subscribed to {:?}", events);
+            ServerMessage::Subscribed { events }
+        }
         Ok(ClientMessage::Ping) => ServerMessage::Pong,
         Err(e) => {
             error!("Failed to parse message: {}", e);
             ServerMessage::Error(ErrorResponse {
                 code: "INVALID_MESSAGE".to_string(),
                 message: format!("Invalid message format: {}", e),
+                id: None,
             })
         }
     };
@@ -195,57 +1134,2015 @@ async fn handle_message(text: &str, state: &Arc<ServerState>) -> String {
     serde_json::to_string(&response).unwrap_or_else(|_| "{}".to_string())
 }
 
-/// 执行打印任务
-fn execute_print(
-    req: &crate::protocol::PrintRequest,
+/// 执行任务控制操作（暂停、恢复、调整优先级）
+fn control_job(
     state: &Arc<ServerState>,
-) -> Result<(), String> {
-    // 确定目标打印机
-    let printer_name = match &req.printer {
-        Some(name) if !name.is_empty() => name.clone(),
-        _ => state
-            .printer_manager
-            .get_default_printer()?
-            .ok_or_else(|| "No default printer available".to_string())?,
+    printer: Option<String>,
+    job_id: u32,
+    action: impl FnOnce(&dyn PrinterManager, &str) -> Result<(), String>,
+) -> ServerMessage {
+    let result = resolve_printer(state, printer.as_deref())
+        .map_err(|e| e.message)
+        .and_then(|name| action(state.printer_manager.as_ref().as_ref(), &name));
+
+    match result {
+        Ok(_) => ServerMessage::JobUpdated { job_id },
+        Err(e) => {
+            error!("Job control failed: job={}, {}", job_id, e);
+            ServerMessage::Error(ErrorResponse {
+                code: "JOB_CONTROL_FAILED".to_string(),
+                message: e,
+                id: None,
+            })
+        }
+    }
+}
+
+/// 汇总监控用的详细状态（查询打印机失败时仍返回其他部分）
+async fn detailed_status(state: &Arc<ServerState>) -> DetailedStatusResponse {
+    let status = if state.active_jobs.is_draining() {
+        "draining"
+    } else {
+        "online"
+    };
+    let server = ServerSection {
+        status: status.to_string(),
+        version: "0.1.0".to_string(),
+        connections: *state.connection_count.read().await,
+        uptime_secs: state.stats.uptime_secs(),
+    };
+    let jobs = JobsSection {
+        total: state.stats.total(),
+        failed: state.stats.failed(),
+        active: state.active_jobs.count(),
+        scheduled: state.scheduled.count(),
     };
 
-    // 渲染模板
-    let rendered = render_template(&req.template, &req.data)?;
+    let printers = state.printer_manager.list_printers().and_then(|list| {
+        let default_printer = state.printer_manager.get_default_printer()?;
+        Ok((default_printer, list.len()))
+    });
+    let printers = match printers {
+        Ok((default_printer, count)) => PrintersSection {
+            default_printer,
+            count,
+            error: None,
+        },
+        Err(e) => PrintersSection {
+            default_printer: None,
+            count: 0,
+            error: Some(e),
+        },
+    };
 
-    // 根据模板类型执行打印
-    match req.template_type.as_str() {
-        "escpos" | "zpl" => {
-            // 原始打印（ESC/POS 或 ZPL）
-            let data = rendered.as_bytes().to_vec();
+    DetailedStatusResponse {
+        server,
+        jobs,
+        printers,
+    }
+}
 
-            // 根据 copies 打印多份
-            for _ in 0..req.options.copies {
-                state.printer_manager.print_raw(&printer_name, &data)?;
+/// 确定目标打印机（未指定时使用默认打印机）
+fn resolve_printer(state: &Arc<ServerState>, printer: Option<&str>) -> Result<String, PrintError> {
+    match printer {
+        Some(name) if !name.is_empty() => Ok(name.to_string()),
+        _ => match state.printer_manager.get_default_printer()? {
+            Some(name) => Ok(name),
+            None if printers_installed(state)? => {
+                Err("No default printer available".to_string().into())
             }
+            None => Err(no_printers_installed()),
+        },
+    }
+}
+
+/// 打印机的小票宽度，未按名称配置且启用了型号匹配时查询打印机的驱动名称（结果缓存）以匹配型号
+fn receipt_width(state: &ServerState, config: &PrinterConfig, printer: Option<&str>) -> usize {
+    let driver = printer
+        .filter(|name| !config.receipt_widths.contains_key(*name))
+        .filter(|_| config.matches_models())
+        .and_then(|name| {
+            state
+                .drivers
+                .driver(name, || state.printer_manager.list_printers())
+        });
+    config.receipt_width_for(printer, driver.as_deref())
+}
+
+/// 等待打印机可用时的轮询间隔
+const PRINTER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 等待至少一台打印机可用（开机时打印后台可能尚未就绪），超时仍没有打印机时返回 false
+pub async fn wait_for_printers(state: &ServerState, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        match state.printer_manager.list_printers() {
+            Ok(printers) if !printers.is_empty() => return true,
+            Ok(_) => debug!("No printers available yet"),
+            Err(e) => debug!("Failed to list printers: {}", e),
         }
-        "text" => {
-            // 文本打印
-            for _ in 0..req.options.copies {
-                state.printer_manager.print_text(&printer_name, &rendered)?;
-            }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
         }
-        "pdf" | "html" => {
-            // PDF/HTML 打印 - 通过 WebSocket 不支持，需要通过 Tauri 命令调用
-            // 这里返回提示信息，客户端应使用 print_pdf 或 print_template_as_pdf 命令
-            return Err(
-                "PDF printing via WebSocket is not supported. Please use the Tauri command 'print_pdf' or 'print_template_as_pdf' instead."
-                    .to_string(),
-            );
+        tokio::time::sleep_until(
+            (tokio::time::Instant::now() + PRINTER_POLL_INTERVAL).min(deadline),
+        )
+        .await;
+    }
+}
+
+/// 启动预热：渲染一个简单模板、枚举一次打印机并载入模板缓存，避免第一个打印请求承担初始化开销
+///
+/// 会调用打印后台，应在阻塞线程中执行
+pub fn warm_up(state: &ServerState, config: &AppConfig) {
+    let start = Instant::now();
+    let profile = config.printer.active_helper_profile();
+    let data = serde_json::json!({});
+    if let Err(e) = render_template_limited(
+        "{{separator}}",
+        &data,
+        receipt_width(state, &config.printer, None),
+        config.server.max_render_bytes,
+        &profile,
+    ) {
+        warn!("Warm-up render failed: {}", e);
+    }
+    match state.printer_manager.list_printers() {
+        Ok(printers) => debug!("Warm-up found {} printers", printers.len()),
+        Err(e) => warn!("Warm-up failed to list printers: {}", e),
+    }
+    match state.templates.preload() {
+        Ok(count) => debug!("Warm-up loaded {} templates", count),
+        Err(e) => warn!("Warm-up failed to load templates: {}", e),
+    }
+    info!("Warm-up finished in {:?}", start.elapsed());
+}
+
+/// 系统中是否安装了打印机
+fn printers_installed(state: &Arc<ServerState>) -> Result<bool, String> {
+    Ok(!state.printer_manager.list_printers()?.is_empty())
+}
+
+/// 没有安装任何打印机时的错误，界面可据此引导用户安装打印机
+fn no_printers_installed() -> PrintError {
+    PrintError::new(NO_PRINTERS_INSTALLED, "No printers installed")
+}
+
+/// 根据选择器确定目标打印机，没有匹配的打印机时返回 `NO_MATCHING_PRINTER`，
+/// 匹配的打印机与其他打印机同名且无法区分时返回 `AMBIGUOUS_PRINTER`
+fn select_printer(
+    state: &Arc<ServerState>,
+    selector: Option<&PrinterSelector>,
+) -> Result<String, PrintError> {
+    let query = match selector {
+        Some(PrinterSelector::Query(query)) => query,
+        Some(PrinterSelector::Name(name)) => return resolve_printer(state, Some(name)),
+        None => return resolve_printer(state, None),
+    };
+
+    // 只指定了名称时直接使用，无需查询打印机列表
+    if query.id.is_none()
+        && query.index.is_none()
+        && query.printer_type.is_none()
+        && query.color.is_none()
+    {
+        return resolve_printer(state, query.name.as_deref());
+    }
+
+    let printers = state.printer_manager.list_printers()?;
+    if printers.is_empty() {
+        return Err(no_printers_installed());
+    }
+    let printer = find_printer(&printers, query).ok_or_else(|| {
+        PrintError::new(
+            NO_MATCHING_PRINTER,
+            format!("No printer matches selector: {:?}", query),
+        )
+    })?;
+    printer_target(&printers, printer).ok_or_else(|| {
+        PrintError::new(
+            AMBIGUOUS_PRINTER,
+            format!(
+                "Printer '{}' shares its name with another printer and cannot be told apart",
+                printer.name
+            ),
+        )
+    })
+}
+
+/// 执行打印请求并生成响应消息
+async fn print_response(
+    req: PrintRequest,
+    state: &Arc<ServerState>,
+    client_ip: Option<IpAddr>,
+) -> ServerMessage {
+    let id = req.id.clone();
+    let response = print_outcome(req, state, client_ip).await;
+
+    let config = state.config.read().await;
+    state.recent_results.record(
+        &id,
+        &response,
+        config.server.result_retention_limit,
+        config.server.result_retention_secs,
+    );
+    response
+}
+
+/// 执行（或排程）打印请求并生成响应
+async fn print_outcome(
+    req: PrintRequest,
+    state: &Arc<ServerState>,
+    client_ip: Option<IpAddr>,
+) -> ServerMessage {
+    if let Some(at) = req.schedule_at.filter(|&at| at > now_millis()) {
+        info!("Print request scheduled: id={}, at={}", req.id, at);
+        let id = req.id.clone();
+        state.scheduled.schedule(req);
+        return ServerMessage::PrintResult(PrintResult {
+            id,
+            status: "scheduled".to_string(),
+            message: Some("打印任务已排程".to_string()),
+            bytes_sent: None,
+            checksum: None,
+            held_jobs: Vec::new(),
+            jobs: Vec::new(),
+        });
+    }
+
+    info!("Print request: id={}, type={}", req.id, req.template_type);
+
+    // 执行打印
+    let print_result = run_print(&req, state, client_ip).await;
+
+    match print_result {
+        Ok(sent) if !sent.held_jobs.is_empty() => ServerMessage::PrintResult(PrintResult {
+            id: req.id,
+            status: "held".to_string(),
+            message: Some("打印任务已暂停，等待释放".to_string()),
+            bytes_sent: Some(sent.bytes),
+            checksum: Some(sent.checksum),
+            held_jobs: sent.held_jobs,
+            jobs: sent.jobs,
+        }),
+        Ok(sent) => ServerMessage::PrintResult(PrintResult {
+            id: req.id,
+            status: "success".to_string(),
+            message: Some("打印任务已完成".to_string()),
+            bytes_sent: Some(sent.bytes),
+            checksum: Some(sent.checksum),
+            held_jobs: Vec::new(),
+            jobs: sent.jobs,
+        }),
+        Err(e) if e.code == PRINT_FAILED => {
+            error!("Print failed: {}", e.message);
+            ServerMessage::PrintResult(PrintResult {
+                id: req.id,
+                status: "error".to_string(),
+                message: Some(e.message),
+                bytes_sent: None,
+                checksum: None,
+                held_jobs: Vec::new(),
+                jobs: Vec::new(),
+            })
         }
-        _ => {
-            return Err(format!("Unknown template type: {}", req.template_type));
+        Err(e) => {
+            // 请求本身不合法（如数据校验失败），返回带错误码的错误响应
+            error!("Print rejected: {}", e);
+            ServerMessage::Error(ErrorResponse {
+                code: e.code,
+                message: e.message,
+                id: Some(req.id),
+            })
         }
     }
+}
 
-    info!(
-        "Print completed: printer={}, type={}, copies={}",
-        printer_name, req.template_type, req.options.copies
-    );
+/// 处理分块打印请求，全部分块到齐后解析并执行完整的打印请求
+async fn handle_chunk(
+    id: String,
+    seq: u32,
+    total: u32,
+    data: String,
+    state: &Arc<ServerState>,
+    conn: &ConnectionState,
+) -> ServerMessage {
+    let timeout = Duration::from_secs(state.config.read().await.server.chunk_timeout_secs);
 
-    Ok(())
+    let content = match state.chunks.add(conn.id, &id, seq, total, data, timeout) {
+        Ok(Some(content)) => content,
+        Ok(None) => {
+            return ServerMessage::ChunkAck {
+                received: state.chunks.received(conn.id, &id),
+                id,
+                total,
+            }
+        }
+        Err(e) => {
+            error!("Invalid chunk: {}", e);
+            return ServerMessage::Error(ErrorResponse {
+                code: "INVALID_CHUNK".to_string(),
+                message: e,
+                id: Some(id),
+            });
+        }
+    };
+
+    info!(
+        "Chunked print request assembled: id={}, chunks={}, bytes={}",
+        id,
+        total,
+        content.len()
+    );
+
+    match serde_json::from_str::<PrintRequest>(&content) {
+        Ok(mut req) => {
+            // 以分块消息的 id 为准
+            req.id = id;
+            conn.apply_default_printer(&mut req);
+            print_response(req, state, conn.client_ip).await
+        }
+        Err(e) => {
+            error!("Failed to parse chunked print request: {}", e);
+            ServerMessage::Error(ErrorResponse {
+                code: "INVALID_MESSAGE".to_string(),
+                message: format!("Invalid chunked print request: {}", e),
+                id: Some(id),
+            })
+        }
+    }
+}
+
+/// 发送到打印机的数据（单份）
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentData {
+    /// 字节数
+    pub bytes: usize,
+    /// CRC32 校验值（8 位小写十六进制）
+    pub checksum: String,
+    /// 已暂停等待释放的打印任务 ID（`options.hold`）
+    pub held_jobs: Vec<u32>,
+    /// 提交到打印队列的任务 ID
+    pub jobs: Vec<u32>,
+}
+
+impl SentData {
+    fn new(data: &[u8]) -> Self {
+        Self {
+            bytes: data.len(),
+            checksum: format!("{:08x}", crc32fast::hash(data)),
+            held_jobs: Vec::new(),
+            jobs: Vec::new(),
+        }
+    }
+}
+
+/// 执行打印请求，失败时记录到失败任务列表，并按配置写入审计日志，返回发送到打印机的数据摘要
+pub async fn run_print(
+    req: &PrintRequest,
+    state: &Arc<ServerState>,
+    client_ip: Option<IpAddr>,
+) -> Result<SentData, PrintError> {
+    // 停机中不再接受新任务，已接受的任务在关闭连接前完成
+    let Some(job) = state.active_jobs.begin(&req.id) else {
+        warn!("Print request rejected during shutdown: id={}", req.id);
+        return Err(PrintError::new(
+            SHUTTING_DOWN,
+            "Server is shutting down, retry later",
+        ));
+    };
+
+    let config = state.config.read().await.clone();
+    // 路由规则优先于请求指定的打印机
+    let selected = match config.printer.route(&req.data) {
+        Some(printer) => {
+            debug!("Print request routed to '{}': id={}", printer, req.id);
+            Ok(printer.to_string())
+        }
+        None => select_printer(state, req.printer.as_ref()),
+    };
+    // 超时返回后阻塞线程可能仍在写入打印队列，由双方共同持有任务守卫
+    let job = Arc::new(job);
+    let (printer, result) = match selected {
+        Ok(printer) => {
+            let result = execute_print_with_timeout(req, state, &config, &printer, &job).await;
+            (Some(printer), result)
+        }
+        Err(e) => (None, Err(e)),
+    };
+
+    state.stats.record(result.is_ok());
+    if let Err(e) = &result {
+        let limit = config.server.failed_jobs_limit;
+        state.failed_jobs.record(req, &e.to_string(), limit);
+    }
+    // 暂停的任务尚未打印，不报告为成功
+    let status = match &result {
+        Ok(sent) if !sent.held_jobs.is_empty() => "held",
+        Ok(_) => "success",
+        Err(_) => "error",
+    };
+    if status == "held" {
+        state.notify_print_held(Some(&req.id), printer.as_deref());
+    } else {
+        state.notify_print_complete(Some(&req.id), printer.as_deref(), &result);
+    }
+    state.webhooks.notify(
+        &config.server,
+        WebhookPayload {
+            id: req.id.clone(),
+            printer: printer.clone(),
+            status: status.to_string(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            timestamp: now_millis(),
+        },
+    );
+
+    state.record_audit(
+        &config.server,
+        AuditEntry {
+            timestamp: audit::timestamp(),
+            id: &req.id,
+            printer: printer.as_deref(),
+            template_type: &req.template_type,
+            status,
+            bytes: result.as_ref().map_or(0, |sent| sent.bytes),
+            client_ip,
+            error: result.as_ref().err().map(|e| e.to_string()),
+            data: Some(&req.data),
+        },
+    );
+
+    result
+}
+
+/// 重新执行记录的失败任务（index 0 为最近一次失败）
+pub async fn replay_failed_job(state: &Arc<ServerState>, index: usize) -> Result<(), PrintError> {
+    let job = state.failed_jobs.get(index).ok_or_else(|| {
+        PrintError::new(JOB_NOT_FOUND, format!("No failed job at index {}", index))
+    })?;
+
+    info!("Replaying failed job: id={}", job.request.id);
+    run_print(&job.request, state, None).await.map(|_| ())
+}
+
+/// 重新发送打印机最近一次打印的数据（不重新渲染），返回打印机名称
+pub async fn reprint_last(
+    state: &Arc<ServerState>,
+    printer: Option<&str>,
+) -> Result<String, PrintError> {
+    let printer_name = resolve_printer(state, printer)?;
+    let (data, datatype) = state.last_printed.get(&printer_name).ok_or_else(|| {
+        PrintError::new(
+            NOTHING_TO_REPRINT,
+            format!("No print job to reprint on '{}'", printer_name),
+        )
+    })?;
+
+    info!("Reprinting {} bytes to '{}'", data.len(), printer_name);
+    let min_interval = state
+        .config
+        .read()
+        .await
+        .printer
+        .min_job_interval_for(&printer_name);
+    let guard = state
+        .printer_locks
+        .lock_job(&printer_name, min_interval)
+        .await;
+    let printer_manager = state.printer_manager.clone();
+    let name = printer_name.clone();
+    tokio::task::spawn_blocking(move || {
+        let _guard = guard;
+        printer_manager.print_raw(&name, &data, datatype, false)
+    })
+    .await
+    .map_err(|e| PrintError::from(format!("Print task failed: {}", e)))??;
+    Ok(printer_name)
+}
+
+/// 查询 ESC/POS 打印机的实时状态，返回打印机名称及状态
+///
+/// 查询与打印共用同一连接，等待该打印机正在进行的打印完成后再查询
+async fn escpos_status(
+    state: &Arc<ServerState>,
+    printer: Option<&str>,
+) -> Result<(String, EscposStatus), PrintError> {
+    let printer_name = resolve_printer(state, printer)?;
+    let _guard = state.printer_locks.lock(&printer_name).await;
+    let status = state.printer_manager.query_escpos_status(&printer_name)?;
+    Ok((printer_name, status))
+}
+
+/// 确定模板内容（指定了模板名称时从模板目录加载），并在渲染前用 Schema 校验数据
+fn prepare_template(req: &PrintRequest, state: &Arc<ServerState>) -> Result<String, PrintError> {
+    let (template, schema) = match &req.template_name {
+        Some(name) if !name.is_empty() => {
+            let template = state.templates.get(name)?;
+            let schema = match &req.schema {
+                Some(schema) => Some(schema.clone()),
+                None => state.templates.get_schema(name)?,
+            };
+            (template, schema)
+        }
+        _ => (req.template.clone(), req.schema.clone()),
+    };
+
+    if let Some(schema) = &schema {
+        check_data(schema, &req.data)?;
+    }
+
+    Ok(template)
+}
+
+/// 渲染预览（不打印），返回渲染结果及耗时
+async fn preview(
+    req: &PrintRequest,
+    state: &Arc<ServerState>,
+) -> Result<PreviewResponse, PrintError> {
+    // 按名称指定了打印机时使用该打印机的小票宽度
+    let printer = match &req.printer {
+        Some(PrinterSelector::Name(name)) => Some(name.as_str()),
+        _ => None,
+    };
+    let (printer_config, max_size) = {
+        let config = state.config.read().await;
+        (config.printer.clone(), config.server.max_render_bytes)
+    };
+    let receipt_width = receipt_width(state, &printer_config, printer);
+    let profile = printer_config.active_helper_profile();
+
+    let template = prepare_template(req, state)?;
+    let (content, elapsed) =
+        render_template_timed(&template, &req.data, receipt_width, max_size, &profile)?;
+    debug!("Preview rendered: id={}, {:?}", req.id, elapsed);
+
+    Ok(PreviewResponse {
+        id: req.id.clone(),
+        content,
+        render_time_us: elapsed.as_micros() as u64,
+    })
+}
+
+/// 按请求选项或全局配置归档打印内容（在实际打印前执行，归档失败则不打印）
+fn archive_print(
+    req: &PrintRequest,
+    config: &AppConfig,
+    extension: &str,
+    data: &[u8],
+) -> Result<(), PrintError> {
+    if !req.options.archive.unwrap_or(config.printer.archive) {
+        return Ok(());
+    }
+
+    archive::archive_job(&get_archive_dir(config), &req.id, extension, data)
+        .map(|_| ())
+        .map_err(|e| write_print_error(e, ARCHIVE_FAILED))
+}
+
+/// 在阻塞线程中执行打印任务，超过请求的 `timeout_ms`（为空则使用服务配置）时返回 `PRINT_TIMEOUT`
+///
+/// 同一打印机的任务依次执行，等待其他任务（及 `min_job_interval_ms` 间隔）的时间也计入超时；
+/// 超时后不再等待，已写入打印队列的数据不会撤回。阻塞线程持有 `job` 直到结束，
+/// 停机时仍会等待超时后还在写入打印队列的任务
+async fn execute_print_with_timeout(
+    req: &PrintRequest,
+    state: &Arc<ServerState>,
+    config: &AppConfig,
+    printer_name: &str,
+    job: &Arc<ActiveJob>,
+) -> Result<SentData, PrintError> {
+    let timeout = req.options.timeout_ms.map_or(
+        Duration::from_secs(config.server.print_timeout_secs),
+        Duration::from_millis,
+    );
+
+    let task = async {
+        let min_interval = config.printer.min_job_interval_for(printer_name);
+        let guard = state
+            .printer_locks
+            .lock_job(printer_name, min_interval)
+            .await;
+        let (req, state, config) = (req.clone(), state.clone(), config.clone());
+        let (printer_name, job) = (printer_name.to_string(), job.clone());
+        tokio::task::spawn_blocking(move || {
+            let _job = job;
+            // 只在写入打印队列时持有写入锁（即使已超时返回），等待打印完成时不阻塞同一打印机的后续任务
+            let sent = {
+                let _guard = guard;
+                execute_print(&req, &state, &config, &printer_name, true)?
+            };
+            if req.options.wait_completion && !req.options.hold {
+                let timeout = Duration::from_secs(config.server.job_wait_timeout_secs);
+                wait_for_jobs(&state, &printer_name, &sent.jobs, timeout)?;
+                info!(
+                    "Print jobs finished: printer={}, jobs={:?}",
+                    printer_name, sent.jobs
+                );
+            }
+            Ok(sent)
+        })
+        .await
+    };
+
+    match tokio::time::timeout(timeout, task).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(format!("Print task failed: {}", e).into()),
+        Err(_) => {
+            warn!(
+                "Print timed out after {:?}: id={}, printer='{}'",
+                timeout, req.id, printer_name
+            );
+            Err(PrintError::new(
+                PRINT_TIMEOUT,
+                format!(
+                    "Print on '{}' did not finish within {} ms",
+                    printer_name,
+                    timeout.as_millis()
+                ),
+            ))
+        }
+    }
+}
+
+/// 执行打印任务（写入打印队列，不等待打印完成），返回发送到打印机的数据摘要（单份）
+///
+/// `record_last` 为 false 时不把数据记录为该打印机最近一次打印的内容（如延迟测试任务，补打时不应重打）
+fn execute_print(
+    req: &PrintRequest,
+    state: &Arc<ServerState>,
+    config: &AppConfig,
+    printer_name: &str,
+    record_last: bool,
+) -> Result<SentData, PrintError> {
+    let zpl_format = stored_zpl_format(req);
+    let documents = render_documents(req, state, config, printer_name)?;
+
+    if req.options.hold {
+        // 清理已在打印机旁释放或取消的任务（查询失败时保留）
+        state.held_jobs.prune(|job_id, printer| {
+            state
+                .printer_manager
+                .is_job_paused(printer, job_id)
+                .unwrap_or(true)
+        });
+    }
+
+    // 根据模板类型执行打印
+    let datatype = config
+        .printer
+        .datatype_for(printer_name, req.options.datatype);
+    check_devmode_options(req, datatype)?;
+    let mut jobs = Vec::new();
+    let sent = match req.template_type.as_str() {
+        "escpos" | "zpl" => {
+            // 原始打印（ESC/POS 或 ZPL）
+            let mut payloads = Vec::with_capacity(documents.len());
+            for rendered in &documents {
+                let mut data = rendered.as_bytes().to_vec();
+
+                // ESC/POS 任务加上该打印机配置的前置/后置指令
+                if req.template_type == "escpos" {
+                    if let Some(commands) = config.printer.commands.get(printer_name) {
+                        data = commands
+                            .wrap(&data)
+                            .map_err(|e| format!("Printer '{}': {}", printer_name, e))?;
+                    }
+                }
+                payloads.push(data);
+            }
+            let data = payloads.concat();
+
+            archive_print(req, config, archive::EXT_RAW, &data)?;
+
+            // 根据 copies 打印多份（每份依次打印所有文档）
+            for _ in 0..req.options.copies {
+                for payload in &payloads {
+                    let job_id = state
+                        .printer_manager
+                        .print_raw(printer_name, payload, datatype, req.options.hold)
+                        .map_err(|e| with_held_jobs(e, req, &jobs))?;
+                    if req.options.hold {
+                        state.held_jobs.hold(job_id, printer_name);
+                    }
+                    jobs.push(job_id);
+                }
+            }
+
+            if record_last {
+                state.last_printed.record(printer_name, &data, datatype);
+            }
+            if let Some(format) = zpl_format {
+                state
+                    .zpl_formats
+                    .mark_downloaded(printer_name, &format.name);
+            }
+            SentData::new(&data)
+        }
+        "text" => {
+            // 文本打印
+            let text = documents.concat();
+            archive_print(req, config, archive::EXT_TEXT, text.as_bytes())?;
+            // TEXT 由驱动按 DEVMODE 处理份数，每个文档只提交一个任务；
+            // RAW 数据不经驱动处理、忽略 DEVMODE 份数，每份依次提交所有文档
+            let (rounds, copies) = if datatype.applies_devmode() {
+                (1, req.options.copies)
+            } else {
+                (req.options.copies, 1)
+            };
+            for _ in 0..rounds {
+                for rendered in &documents {
+                    let job_id = state
+                        .printer_manager
+                        .print_document(
+                            printer_name,
+                            rendered.as_bytes(),
+                            copies,
+                            req.options.tray,
+                            req.options.orientation,
+                            datatype,
+                            req.options.hold,
+                        )
+                        .map_err(|e| with_held_jobs(e, req, &jobs))?;
+                    if req.options.hold {
+                        state.held_jobs.hold(job_id, printer_name);
+                    }
+                    jobs.push(job_id);
+                }
+            }
+            SentData::new(text.as_bytes())
+        }
+        "pdf" | "html" => {
+            // PDF/HTML 打印 - 通过 WebSocket 不支持，需要通过 Tauri 命令调用
+            // 这里返回提示信息，客户端应使用 print_pdf 或 print_template_as_pdf 命令
+            return Err(
+                "PDF printing via WebSocket is not supported. Please use the Tauri command 'print_pdf' or 'print_template_as_pdf' instead."
+                    .to_string()
+                    .into(),
+            );
+        }
+        _ => {
+            return Err(format!("Unknown template type: {}", req.template_type).into());
+        }
+    };
+
+    // 暂停的任务在释放前不会离开打印队列，无需等待
+    if req.options.hold {
+        info!(
+            "Print held: printer={}, type={}, jobs={:?}",
+            printer_name, req.template_type, jobs
+        );
+        return Ok(SentData {
+            held_jobs: jobs.clone(),
+            jobs,
+            ..sent
+        });
+    }
+
+    info!(
+        "Print completed: printer={}, type={}, copies={}",
+        printer_name, req.template_type, req.options.copies
+    );
+
+    Ok(SentData { jobs, ..sent })
+}
+
+/// 检查依赖 DEVMODE 的打印选项：只有以 TEXT 数据类型提交的文本任务由驱动处理纸盒和纸张方向，
+/// 其他任务的设置会被打印后台忽略，直接拒绝而不是按默认设置打印
+fn check_devmode_options(req: &PrintRequest, datatype: PrintDatatype) -> Result<(), PrintError> {
+    if req.template_type == "text" && datatype.applies_devmode() {
+        return Ok(());
+    }
+    let option = if req.options.tray.is_some() {
+        "tray"
+    } else if req.options.orientation.is_some() {
+        "orientation"
+    } else {
+        return Ok(());
+    };
+    Err(PrintError::new(
+        OPTION_NOT_SUPPORTED,
+        format!(
+            "options.{} requires a text job with the TEXT datatype, got {} job with {}",
+            option,
+            req.template_type,
+            datatype.as_str()
+        ),
+    ))
+}
+
+/// 暂停任务部分提交后失败时，在错误消息中列出已暂停的任务，操作员可释放或在打印机旁取消
+fn with_held_jobs(e: PrintError, req: &PrintRequest, held: &[u32]) -> PrintError {
+    if !req.options.hold || held.is_empty() {
+        return e;
+    }
+    PrintError::new(
+        &e.code,
+        format!("{} (jobs already held: {:?})", e.message, held),
+    )
+}
+
+/// 渲染主文档及附加文档，`single_job` 时合并为一个文档
+fn render_documents(
+    req: &PrintRequest,
+    state: &Arc<ServerState>,
+    config: &AppConfig,
+    printer_name: &str,
+) -> Result<Vec<String>, PrintError> {
+    let mut documents = vec![render_document(req, state, config, printer_name)?];
+    for document in &req.documents {
+        let req = document_request(req, document);
+        documents.push(render_document(&req, state, config, printer_name)?);
+    }
+
+    if req.options.single_job && documents.len() > 1 {
+        debug!(
+            "Combining {} documents into one job: id={}",
+            documents.len(),
+            req.id
+        );
+        return Ok(vec![documents.concat()]);
+    }
+    Ok(documents)
+}
+
+/// 附加文档对应的请求（沿用主请求的模板类型、打印机和打印选项，ZPL 存储格式只用于主文档）
+fn document_request(req: &PrintRequest, document: &PrintDocument) -> PrintRequest {
+    PrintRequest {
+        template: document.template.clone(),
+        template_name: document.template_name.clone(),
+        data: document.data.clone(),
+        schema: document.schema.clone(),
+        options: PrintOptions {
+            zpl_format: None,
+            ..req.options.clone()
+        },
+        documents: Vec::new(),
+        ..req.clone()
+    }
+}
+
+/// 校验数据并渲染单个文档（ZPL 存储格式已下载时只发送字段数据）
+fn render_document(
+    req: &PrintRequest,
+    state: &Arc<ServerState>,
+    config: &AppConfig,
+    printer_name: &str,
+) -> Result<String, PrintError> {
+    let profile = config.printer.active_helper_profile();
+    let mut rendered = match stored_zpl_format(req) {
+        Some(format) => zpl_format_job(req, state, config, printer_name, format, &profile)?,
+        None => {
+            let template = prepare_template(req, state)?;
+            let receipt_width = receipt_width(state, &config.printer, Some(printer_name));
+            let max_size = config.server.max_render_bytes;
+            render_template_limited(&template, &req.data, receipt_width, max_size, &profile)?
+        }
+    };
+
+    // 文本任务按需去掉控制字符，避免不可信的数据触发打印机指令
+    if req.options.sanitize_text && req.template_type == "text" {
+        rendered = sanitize_text(&rendered);
+    }
+
+    // ZPL 字段数据中不能残留 CR（在 Windows 上编辑的模板）
+    if req.template_type == "zpl" {
+        rendered = zpl::normalize_line_endings(&rendered);
+    }
+
+    // 文本任务按需统一换行符（ESC/POS 指令参数中可能有 0x0A、0x0D 字节，不做转换）
+    if let Some(line_ending) = &req.options.line_ending {
+        if req.template_type == "text" {
+            rendered = normalize_line_endings(&rendered, line_ending)?;
+        }
+    }
+
+    Ok(rendered)
+}
+
+/// 等待所有任务离开打印队列，超时时间为所有任务共用
+fn wait_for_jobs(
+    state: &Arc<ServerState>,
+    printer_name: &str,
+    jobs: &[u32],
+    timeout: Duration,
+) -> Result<(), PrintError> {
+    let deadline = Instant::now() + timeout;
+    for &job_id in jobs {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        state
+            .printer_manager
+            .wait_for_job(printer_name, job_id, remaining)
+            .map_err(|e| PrintError::new(JOB_NOT_COMPLETED, e))?;
+    }
+    Ok(())
+}
+
+/// ZPL 任务使用的存储格式
+fn stored_zpl_format(req: &PrintRequest) -> Option<&ZplFormat> {
+    req.options
+        .zpl_format
+        .as_ref()
+        .filter(|_| req.template_type == "zpl")
+}
+
+/// 生成存储格式任务：格式未下载到该打印机时先下载（^DF），再调用格式填充字段（^XF）
+fn zpl_format_job(
+    req: &PrintRequest,
+    state: &Arc<ServerState>,
+    config: &AppConfig,
+    printer_name: &str,
+    format: &ZplFormat,
+    profile: &HelperProfile,
+) -> Result<String, PrintError> {
+    if !zpl::is_valid_format_name(&format.name) {
+        return Err(format!("Invalid ZPL format name: '{}'", format.name).into());
+    }
+
+    // 格式已下载时也按 Schema 校验数据
+    let template = prepare_template(req, state)?;
+    let recall = zpl::recall_format(&format.name, &format.fields);
+    if !format.force_download && state.zpl_formats.is_downloaded(printer_name, &format.name) {
+        return Ok(recall);
+    }
+
+    let receipt_width = receipt_width(state, &config.printer, Some(printer_name));
+    let body = render_template_limited(
+        &template,
+        &req.data,
+        receipt_width,
+        config.server.max_render_bytes,
+        profile,
+    )?;
+    debug!(
+        "Downloading ZPL format '{}' to printer '{}'",
+        format.name, printer_name
+    );
+    Ok(format!(
+        "{}\n{}",
+        zpl::download_format(&format.name, &body),
+        recall
+    ))
+}
+
+/// 使用模拟打印机的服务状态（测试用）
+///
+/// 模板目录是每个状态独有、尚未创建的临时路径，不会读到系统临时目录或其他测试中的文件
+#[cfg(test)]
+pub(crate) fn mock_state(
+    printers: &crate::printer::mock::MockPrinterManager,
+    config: AppConfig,
+) -> Arc<ServerState> {
+    let templates_dir =
+        std::env::temp_dir().join(format!("rprint-templates-{}", uuid::Uuid::new_v4()));
+    Arc::new(ServerState::with_printer_manager(
+        Arc::new(RwLock::new(config)),
+        Arc::new(TemplateStore::new(templates_dir)),
+        printers.shared(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PrinterRoute;
+    use crate::printer::mock::{JobGate, MockPrinterManager};
+    use crate::protocol::{Orientation, PrintDatatype, PrintOptions, STATUS_NOT_SUPPORTED};
+    use serde_json::json;
+
+    fn request(printer: serde_json::Value) -> PrintRequest {
+        serde_json::from_value(json!({
+            "id": "1",
+            "template_type": "escpos",
+            "template": "",
+            "data": {},
+            "printer": printer,
+        }))
+        .unwrap()
+    }
+
+    fn printer_name(req: &PrintRequest) -> Option<&str> {
+        match &req.printer {
+            Some(PrinterSelector::Name(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tcp_keepalive() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        for keepalive in [Some(Duration::from_secs(5)), None] {
+            let listener = bind_tcp(addr, keepalive).unwrap();
+            let _client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            let enabled = socket2::SockRef::from(&stream).keepalive().unwrap();
+            assert_eq!(enabled, keepalive.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_retry_port_in_use() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let delay = Duration::from_millis(50);
+        let listener = bind_tcp(addr, None).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // 端口一直被占用时重试后返回 PORT_IN_USE
+        let error = bind_tcp_with_retry(addr, None, 1, delay).await.unwrap_err();
+        assert!(error.to_string().starts_with(PORT_IN_USE));
+
+        // 重试期间端口被释放则绑定成功
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            drop(listener);
+        });
+        bind_tcp_with_retry(addr, None, 5, delay).await.unwrap();
+        release.await.unwrap();
+    }
+
+    type WsClient = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn ws_send(ws: &mut WsClient, msg: &str) {
+        ws.send(tokio_tungstenite::tungstenite::Message::Text(
+            msg.to_string(),
+        ))
+        .await
+        .unwrap();
+    }
+
+    async fn ws_next(ws: &mut WsClient) -> serde_json::Value {
+        loop {
+            let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            if let tokio_tungstenite::tungstenite::Message::Text(text) = msg {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_subscription_filter() {
+        let subscription = Subscription::default();
+        let pong = r#"{"type":"pong"}"#;
+        assert!(subscription.accepts(pong));
+
+        subscription.set(&["print_result".to_string()]);
+        assert!(!subscription.accepts(pong));
+        assert!(subscription.accepts(r#"{"type":"print_result","id":"1","success":true}"#));
+        assert!(subscription.accepts(r#"{"type":"shutdown","retry_after_ms":3000}"#));
+
+        // 空列表恢复接收全部
+        subscription.set(&[]);
+        assert!(subscription.accepts(pong));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let printers = MockPrinterManager::new().with_printer("Office");
+        let state = mock_state(&printers, AppConfig::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+
+        // 只订阅打印结果的连接仍然收到自己请求的响应
+        let (mut dashboard, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        ws_send(
+            &mut dashboard,
+            r#"{"type": "subscribe", "events": ["print_result"]}"#,
+        )
+        .await;
+        assert_eq!(ws_next(&mut dashboard).await["type"], "subscribed");
+        ws_send(&mut dashboard, r#"{"type": "ping"}"#).await;
+        assert_eq!(ws_next(&mut dashboard).await["type"], "pong");
+
+        // 其他连接的响应只直接发送一次
+        let (mut other, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        ws_send(&mut other, r#"{"type": "ping"}"#).await;
+        assert_eq!(ws_next(&mut other).await["type"], "pong");
+        let print = r#"{"type": "print", "id": "order-1", "template_type": "text",
+            "template": "hi", "data": {}, "printer": "Office"}"#;
+        ws_send(&mut other, print).await;
+        assert_eq!(ws_next(&mut other).await["type"], "print_result");
+
+        // 其他连接的 pong 被订阅过滤，打印结果照常推送
+        let result = ws_next(&mut dashboard).await;
+        assert_eq!(result["type"], "print_result");
+        assert_eq!(result["id"], "order-1");
+    }
+
+    #[tokio::test]
+    async fn test_get_result_after_reconnect() {
+        let printers = MockPrinterManager::new().with_printer("Office");
+        let state = mock_state(&printers, AppConfig::default());
+        let print = r#"{"type": "print", "id": "order-1", "template_type": "text",
+            "template": "hi", "data": {}, "printer": "Office"}"#;
+        handle_message(print, &state, &mut ConnectionState::default()).await;
+
+        // 新连接查询之前的结果
+        let mut conn = ConnectionState::default();
+        let query = r#"{"type": "get_result", "id": "order-1"}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_message(query, &state, &mut conn).await).unwrap();
+        assert_eq!(response["type"], "print_result");
+        assert_eq!(response["status"], "success");
+
+        let query = r#"{"type": "get_result", "id": "unknown"}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_message(query, &state, &mut conn).await).unwrap();
+        assert_eq!(response["code"], JOB_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_connection_default_printer() {
+        let conn = ConnectionState {
+            default_printer: Some("Local".to_string()),
+            ..Default::default()
+        };
+
+        for printer in [json!(null), json!("")] {
+            let mut req = request(printer);
+            conn.apply_default_printer(&mut req);
+            assert_eq!(printer_name(&req), Some("Local"));
+        }
+
+        // 显式指定的打印机不受影响
+        let mut req = request(json!("Other"));
+        conn.apply_default_printer(&mut req);
+        assert_eq!(printer_name(&req), Some("Other"));
+
+        let mut req = request(json!({"type": "zpl"}));
+        conn.apply_default_printer(&mut req);
+        assert!(matches!(req.printer, Some(PrinterSelector::Query(_))));
+
+        assert_eq!(conn.printer_or_default(None).as_deref(), Some("Local"));
+        assert_eq!(
+            conn.printer_or_default(Some("Other".to_string()))
+                .as_deref(),
+            Some("Other")
+        );
+    }
+
+    #[test]
+    fn test_query_print_request() {
+        let printer_config = PrinterConfig {
+            default_printer: Some("Office".to_string()),
+            zpl_printer: Some("Label".to_string()),
+            ..Default::default()
+        };
+        let params = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let req = query_print_request(
+            "receipt".to_string(),
+            params(&[("order_no", "123"), ("total", "9.90")]),
+            &printer_config,
+        );
+        assert_eq!(req.template_type, "escpos");
+        assert_eq!(req.template_name.as_deref(), Some("receipt"));
+        assert_eq!(req.data, json!({"order_no": "123", "total": "9.90"}));
+        assert_eq!(printer_name(&req), Some("Office"));
+
+        let req = query_print_request(
+            "label".to_string(),
+            params(&[("_type", "zpl"), ("sku", "A1"), ("_key", "k3y")]),
+            &printer_config,
+        );
+        assert_eq!(req.template_type, "zpl");
+        assert_eq!(req.data, json!({"sku": "A1"}));
+        assert_eq!(printer_name(&req), Some("Label"));
+
+        let req = query_print_request(
+            "label".to_string(),
+            params(&[("_type", "zpl"), ("_printer", "Other")]),
+            &printer_config,
+        );
+        assert_eq!(printer_name(&req), Some("Other"));
+        assert!(
+            query_print_request("x".to_string(), params(&[]), &PrinterConfig::default())
+                .printer
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_no_printers_installed() {
+        let state = mock_state(&MockPrinterManager::new(), AppConfig::default());
+
+        let req = request(json!(null));
+        let error = select_printer(&state, req.printer.as_ref()).unwrap_err();
+        assert_eq!(error.code, NO_PRINTERS_INSTALLED);
+
+        let req = request(json!({"type": "zpl"}));
+        let error = select_printer(&state, req.printer.as_ref()).unwrap_err();
+        assert_eq!(error.code, NO_PRINTERS_INSTALLED);
+
+        // 显式指定名称时不查询打印机列表
+        let req = request(json!("Label"));
+        assert_eq!(
+            select_printer(&state, req.printer.as_ref()).unwrap(),
+            "Label"
+        );
+    }
+
+    #[test]
+    fn test_select_printer_with_duplicate_names() {
+        let printers = MockPrinterManager::new()
+            .with_printer_id("Shared", "Shared|IP_10.0.0.1|\\\\SRV1")
+            .with_printer_id("Shared", "Shared|IP_10.0.0.1|\\\\SRV2")
+            .with_printer_id("Dup", "Dup|USB001|")
+            .with_printer_id("Dup", "Dup|USB002|");
+        let state = mock_state(&printers, AppConfig::default());
+
+        // 按唯一标识选择时打印到对应服务器上的打印机，而不是第一台同名打印机
+        let req = request(json!({"id": "Shared|IP_10.0.0.1|\\\\SRV2"}));
+        assert_eq!(
+            select_printer(&state, req.printer.as_ref()).unwrap(),
+            "\\\\SRV2\\Shared"
+        );
+
+        // 无法区分的同名打印机拒绝打印
+        let req = request(json!({"id": "Dup|USB002|"}));
+        let error = select_printer(&state, req.printer.as_ref()).unwrap_err();
+        assert_eq!(error.code, AMBIGUOUS_PRINTER);
+    }
+
+    #[tokio::test]
+    async fn test_run_print_with_mock_printer() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Receipt")
+            .with_printer("Kitchen")
+            .with_default_printer("Receipt");
+        let mut config = AppConfig::default();
+        config.printer.routes.push(PrinterRoute {
+            field: "department".to_string(),
+            equals: json!("kitchen"),
+            printer: "Kitchen".to_string(),
+        });
+        let state = mock_state(&printers, config);
+
+        let mut req = request(json!(null));
+        req.template = "Order {{no}}".to_string();
+        req.data = json!({"no": 1});
+        // 省略 options 时打印一份
+        let sent = run_print(&req, &state, None).await.unwrap();
+        assert_eq!(sent.bytes, 7);
+        assert_eq!(
+            sent.checksum,
+            format!("{:08x}", crc32fast::hash(b"Order 1"))
+        );
+
+        // 路由规则优先于请求指定的打印机
+        let mut req = request(json!("Receipt"));
+        req.template_type = "text".to_string();
+        req.template = "Order {{no}}".to_string();
+        req.data = json!({"no": 2, "department": "kitchen"});
+        req.options.copies = 2;
+        req.options.tray = Some(258);
+        req.options.orientation = Some(Orientation::Landscape);
+        req.options.datatype = Some(PrintDatatype::Text);
+        req.options.sanitize_text = true;
+        req.data["no"] = json!("2\u{7}");
+        run_print(&req, &state, None).await.unwrap();
+
+        let calls = printers.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].method, "print_raw");
+        assert_eq!(calls[0].printer, "Receipt");
+        assert_eq!(calls[0].data, b"Order 1");
+        assert_eq!(calls[1].method, "print_document");
+        assert_eq!(calls[1].printer, "Kitchen");
+        assert_eq!(calls[1].data, b"Order 2");
+        assert_eq!((calls[1].copies, calls[1].tray), (2, Some(258)));
+        assert_eq!(calls[1].orientation, Some(Orientation::Landscape));
+
+        // 打印失败的请求记录到失败任务中，便于重新执行
+        let req = request(json!("Missing"));
+        assert!(run_print(&req, &state, None).await.is_err());
+        assert_eq!(state.failed_jobs.get(0).unwrap().request.id, "1");
+    }
+
+    #[test]
+    fn test_line_ending_text_only() {
+        let printers = MockPrinterManager::new().with_printer("Kitchen");
+        let state = mock_state(&printers, AppConfig::default());
+        let config = AppConfig::default();
+
+        let mut req = request(json!("Kitchen"));
+        req.template_type = "text".to_string();
+        req.template = "a\nb\r\n".to_string();
+        req.options.line_ending = Some("crlf".to_string());
+        let documents = render_documents(&req, &state, &config, "Kitchen").unwrap();
+        assert_eq!(documents, vec!["a\r\nb\r\n"]);
+
+        // ESC/POS 的二进制指令（ESC d 10 走纸 10 行）保持不变
+        req.template_type = "escpos".to_string();
+        req.template = "\x1B@a\n\x1Bd\x0A".to_string();
+        let documents = render_documents(&req, &state, &config, "Kitchen").unwrap();
+        assert_eq!(documents, vec!["\x1B@a\n\x1Bd\x0A"]);
+    }
+
+    #[test]
+    fn test_zpl_format_job_checks_data() {
+        let printers = MockPrinterManager::new().with_printer("Label");
+        let state = mock_state(&printers, AppConfig::default());
+        let config = AppConfig::default();
+
+        let mut req = request(json!("Label"));
+        req.template_type = "zpl".to_string();
+        req.template = "^XA^FO50,50^A0N,30,30^FN1^FS^XZ".to_string();
+        req.schema = Some(json!({"type": "object", "required": ["sku"]}));
+        req.options.zpl_format = Some(ZplFormat {
+            name: "PRICE".to_string(),
+            fields: BTreeMap::from([(1, "Milk^XZ".to_string())]),
+            force_download: false,
+        });
+        state.zpl_formats.mark_downloaded("Label", "PRICE");
+
+        // 格式已下载时仍校验数据
+        let error = render_documents(&req, &state, &config, "Label").unwrap_err();
+        assert_eq!(error.code, "DATA_VALIDATION");
+
+        req.data = json!({"sku": "A1"});
+        let documents = render_documents(&req, &state, &config, "Label").unwrap();
+        assert_eq!(
+            documents,
+            vec!["^XA^XFR:PRICE.ZPL^FS\n^FN1^FH^FDMilk_5EXZ^FS\n^XZ"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_completion_releases_printer_lock() {
+        let gate = JobGate::new();
+        let printers = MockPrinterManager::new()
+            .with_printer("Kitchen")
+            .with_job_gate(gate.clone());
+        let state = mock_state(&printers, AppConfig::default());
+        let mut req = request(json!("Kitchen"));
+        req.options.wait_completion = true;
+
+        // 等待打印完成时不持有写入锁：两个任务都写入打印队列后才放行第一个任务的完成
+        let prints =
+            async { tokio::join!(run_print(&req, &state, None), run_print(&req, &state, None)) };
+        let release = async {
+            while printers.calls().len() < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            gate.open();
+        };
+        let ((first, second), ()) = tokio::join!(prints, release);
+        let mut jobs = [first.unwrap().jobs, second.unwrap().jobs].concat();
+        jobs.sort();
+        assert_eq!(jobs, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_orientation_requires_text_datatype() {
+        let printers = MockPrinterManager::new().with_printer("Receipt");
+        let state = mock_state(&printers, AppConfig::default());
+        let mut req = request(json!("Receipt"));
+        req.template_type = "text".to_string();
+        req.template = "Order".to_string();
+        req.options.orientation = Some(Orientation::Landscape);
+
+        // RAW 任务的纸张方向会被打印后台忽略
+        let error = run_print(&req, &state, None).await.unwrap_err();
+        assert_eq!(error.code, OPTION_NOT_SUPPORTED);
+        assert!(printers.calls().is_empty());
+
+        req.options.datatype = Some(PrintDatatype::Text);
+        run_print(&req, &state, None).await.unwrap();
+        assert_eq!(
+            printers.calls()[0].orientation,
+            Some(Orientation::Landscape)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tray_requires_text_datatype() {
+        let printers = MockPrinterManager::new().with_printer("Office");
+        let state = mock_state(&printers, AppConfig::default());
+        let mut req = request(json!("Office"));
+        req.options.tray = Some(258);
+
+        // ESC/POS 任务总是以 RAW 提交，纸盒设置无效
+        let error = run_print(&req, &state, None).await.unwrap_err();
+        assert_eq!(error.code, OPTION_NOT_SUPPORTED);
+
+        req.template_type = "text".to_string();
+        let error = run_print(&req, &state, None).await.unwrap_err();
+        assert_eq!(error.code, OPTION_NOT_SUPPORTED);
+        assert!(printers.calls().is_empty());
+
+        req.options.datatype = Some(PrintDatatype::Text);
+        run_print(&req, &state, None).await.unwrap();
+        assert_eq!(printers.calls()[0].tray, Some(258));
+    }
+
+    #[tokio::test]
+    async fn test_text_copies_by_datatype() {
+        let printers = MockPrinterManager::new().with_printer("Receipt");
+        let state = mock_state(&printers, AppConfig::default());
+        let mut req = request(json!("Receipt"));
+        req.template_type = "text".to_string();
+        req.template = "Order".to_string();
+        req.options.copies = 3;
+
+        // RAW 忽略 DEVMODE 份数，逐份提交
+        run_print(&req, &state, None).await.unwrap();
+        let calls = printers.calls();
+        assert_eq!(calls.len(), 3);
+        assert!(calls.iter().all(|call| call.copies == 1));
+
+        // TEXT 由驱动处理份数，只提交一个任务
+        req.options.datatype = Some(PrintDatatype::Text);
+        run_print(&req, &state, None).await.unwrap();
+        let calls = printers.calls();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[3].copies, 3);
+    }
+
+    #[tokio::test]
+    async fn test_print_multiple_documents() {
+        let printers = MockPrinterManager::new().with_printer("Receipt");
+        let state = mock_state(&printers, AppConfig::default());
+
+        let mut req = request(json!("Receipt"));
+        req.template = "Kitchen {{no}}\n".to_string();
+        req.data = json!({"no": 7});
+        req.documents = serde_json::from_value(json!([
+            {"template": "Receipt {{no}}\n", "data": {"no": 7}}
+        ]))
+        .unwrap();
+        req.options.copies = 2;
+
+        // 默认每个文档单独提交，每份依次打印所有文档
+        let sent = run_print(&req, &state, None).await.unwrap();
+        assert_eq!(sent.bytes, 20);
+        let data: Vec<Vec<u8>> = printers.calls().into_iter().map(|c| c.data).collect();
+        assert_eq!(
+            data,
+            [
+                &b"Kitchen 7\n"[..],
+                b"Receipt 7\n",
+                b"Kitchen 7\n",
+                b"Receipt 7\n"
+            ]
+        );
+
+        // 合并为一个任务
+        req.options.copies = 1;
+        req.options.single_job = true;
+        run_print(&req, &state, None).await.unwrap();
+        let calls = printers.calls();
+        assert_eq!(calls.len(), 5);
+        assert_eq!(calls[4].data, b"Kitchen 7\nReceipt 7\n");
+    }
+
+    #[tokio::test]
+    async fn test_receipt_width_by_model() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Front")
+            .with_driver("Front", "EPSON TM-T88V Receipt")
+            .with_printer("Mobile");
+        let mut config = AppConfig::default();
+        config.printer.receipt_width = 32;
+        config.printer.builtin_model_widths = true;
+        let state = mock_state(&printers, config);
+
+        for printer in ["Front", "Mobile"] {
+            let mut req = request(json!(printer));
+            req.template = "{{hr}}".to_string();
+            run_print(&req, &state, None).await.unwrap();
+        }
+
+        let calls = printers.calls();
+        assert_eq!(calls[0].data, "-".repeat(48).as_bytes());
+        assert_eq!(calls[1].data, "-".repeat(32).as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_print_datatype() {
+        let printers = MockPrinterManager::new().with_printer("Dot Matrix");
+        let mut config = AppConfig::default();
+        config
+            .printer
+            .datatypes
+            .insert("Dot Matrix".to_string(), PrintDatatype::Text);
+        let state = mock_state(&printers, config);
+
+        // 使用打印机配置的数据类型，补打时沿用
+        let req = request(json!("Dot Matrix"));
+        run_print(&req, &state, None).await.unwrap();
+        reprint_last(&state, Some("Dot Matrix")).await.unwrap();
+
+        // 请求指定的数据类型优先（不区分大小写）
+        let mut req = request(json!("Dot Matrix"));
+        req.options = serde_json::from_value(json!({"datatype": "raw"})).unwrap();
+        run_print(&req, &state, None).await.unwrap();
+
+        let datatypes: Vec<PrintDatatype> = printers.calls().iter().map(|c| c.datatype).collect();
+        assert_eq!(
+            datatypes,
+            [PrintDatatype::Text, PrintDatatype::Text, PrintDatatype::Raw]
+        );
+
+        // 只接受已知的数据类型
+        assert!(serde_json::from_value::<PrintOptions>(json!({"datatype": "EMF"})).is_err());
+    }
+
+    #[test]
+    fn test_sent_data_checksum() {
+        // CRC-32 (IEEE) 标准校验值
+        let sent = SentData::new(b"123456789");
+        assert_eq!(sent.bytes, 9);
+        assert_eq!(sent.checksum, "cbf43926");
+    }
+
+    #[test]
+    fn test_mock_state_templates_isolated() {
+        let printers = MockPrinterManager::new();
+        let first = mock_state(&printers, AppConfig::default());
+        let second = mock_state(&printers, AppConfig::default());
+        assert_ne!(first.templates.dir(), second.templates.dir());
+        assert!(!first.templates.dir().exists());
+        assert!(first.templates.list().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_print_timeout() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Slow")
+            .with_delay(Duration::from_millis(200));
+        let state = mock_state(&printers, AppConfig::default());
+
+        let mut req = request(json!("Slow"));
+        req.options.timeout_ms = Some(20);
+        let error = run_print(&req, &state, None).await.unwrap_err();
+        assert_eq!(error.code, PRINT_TIMEOUT);
+
+        // 超时返回后仍在写入打印队列的任务计入进行中的任务，停机等待其完成
+        assert_eq!(state.active_jobs.count(), 1);
+        assert!(state
+            .active_jobs
+            .drain(Duration::from_secs(5))
+            .await
+            .is_empty());
+        assert_eq!(printers.calls().len(), 1);
+        state.active_jobs.resume();
+
+        // 未指定时使用服务配置的超时
+        req.options.timeout_ms = None;
+        run_print(&req, &state, None).await.unwrap();
+    }
+
+    /// 在随机端口上启动 HTTP 路由，返回基础地址
+    async fn serve(state: Arc<ServerState>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+        base
+    }
+
+    #[tokio::test]
+    async fn test_config_handler() {
+        let mut config = AppConfig::default();
+        config.server.api_token = Some("s3cret".to_string());
+        let state = mock_state(&MockPrinterManager::new(), config);
+        let base = serve(state.clone()).await;
+        let client = reqwest::Client::new();
+        let get_config = |authorization: Option<&str>| {
+            let mut request = client.get(format!("{}/config", base));
+            if let Some(value) = authorization {
+                request = request.header(header::AUTHORIZATION, value);
+            }
+            async move { request.send().await.unwrap() }
+        };
+
+        let response = get_config(None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        let response = get_config(Some("Bearer wrong")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // 返回的配置中令牌已脱敏
+        let response = get_config(Some("Bearer s3cret")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let value: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(value["server"]["api_token"], "***");
+        assert_eq!(value["server"]["port"], 9100);
+
+        // webhook 地址可能携带凭据，同样脱敏
+        state.config.write().await.server.webhook_url = Some("http://u:p@hook".to_string());
+        let response = get_config(Some("Bearer s3cret")).await;
+        let value: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(value["server"]["webhook_url"], "***");
+
+        // 未配置令牌时，监听非回环地址则拒绝，只监听回环地址时无需认证
+        state.config.write().await.server.api_token = None;
+        assert_eq!(get_config(None).await.status(), StatusCode::FORBIDDEN);
+        state.config.write().await.server.host = "127.0.0.1".to_string();
+        assert_eq!(get_config(None).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_config_not_cross_origin() {
+        let state = mock_state(&MockPrinterManager::new(), AppConfig::default());
+        state.config.write().await.server.host = "localhost".to_string();
+        let base = serve(state).await;
+
+        let client = reqwest::Client::new();
+        let cors = |path: &str| {
+            let request = client
+                .get(format!("{}{}", base, path))
+                .header(header::ORIGIN, "http://example.com");
+            async move {
+                let response = request.send().await.unwrap();
+                response
+                    .headers()
+                    .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            }
+        };
+
+        assert!(cors("/health").await);
+        assert!(!cors("/config").await);
+        assert!(!cors("/print/receipt").await);
+    }
+
+    #[tokio::test]
+    async fn test_api_key_header() {
+        let mut config = AppConfig::default();
+        config.server.api_key = Some("k3y".to_string());
+        let state = mock_state(&MockPrinterManager::new(), config);
+        let base = serve(state.clone()).await;
+
+        let client = reqwest::Client::new();
+        let get = |path: &str, key: Option<&str>| {
+            let mut request = client.get(format!("{}{}", base, path));
+            if let Some(key) = key {
+                request = request.header("X-API-Key", key);
+            }
+            async move { request.send().await.unwrap().status() }
+        };
+
+        // REST 端点需要正确的 API key，健康检查不需要
+        assert_eq!(get("/config", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            get("/config", Some("wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(get("/print/receipt", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get("/config", Some("k3y")).await, StatusCode::OK);
+        assert_eq!(get("/health", None).await, StatusCode::OK);
+
+        // SSE 事件流同样需要认证
+        assert_eq!(get("/events", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get("/events", Some("k3y")).await, StatusCode::OK);
+
+        // 无法设置请求头的设备通过查询参数携带 API key
+        let status = |url: String| {
+            let request = client.get(url);
+            async move { request.send().await.unwrap().status() }
+        };
+        assert_eq!(
+            status(format!("{}/print/receipt?_key=wrong", base)).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_ne!(
+            status(format!("{}/print/receipt?_key=k3y", base)).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(format!("{}/events?_key=k3y", base)).await,
+            StatusCode::OK
+        );
+
+        // 未配置任何凭据时拒绝 GET 打印，防止网页跨站触发
+        state.config.write().await.server.api_key = None;
+        assert_eq!(get("/print/receipt", None).await, StatusCode::FORBIDDEN);
+        state.config.write().await.server.api_key = Some("k3y".to_string());
+
+        // 同时配置了令牌时，API key 与 Bearer 令牌任一匹配即可
+        state.config.write().await.server.api_token = Some("s3cret".to_string());
+        assert_eq!(get("/config", Some("k3y")).await, StatusCode::OK);
+        let response = client
+            .get(format!("{}/print/receipt", base))
+            .bearer_auth("s3cret")
+            .send()
+            .await
+            .unwrap();
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // 请求头名称可配置
+        state.config.write().await.server.auth_header = "X-Gateway-Key".to_string();
+        assert_eq!(get("/config", Some("k3y")).await, StatusCode::UNAUTHORIZED);
+        let response = client
+            .get(format!("{}/config", base))
+            .header("X-Gateway-Key", "k3y")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("k3y", "k3y"));
+        assert!(!constant_time_eq("k3x", "k3y"));
+        assert!(!constant_time_eq("k3", "k3y"));
+        assert!(!constant_time_eq("", "k3y"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_print_jobs() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Kitchen")
+            .with_delay(Duration::from_millis(100));
+        let state = mock_state(&printers, AppConfig::default());
+        let mut shutdown_rx = state.shutdown_tx.subscribe();
+
+        // 停机前已接受的任务完成后才关闭连接，停机中的新任务被拒绝
+        let req = request(json!("Kitchen"));
+        let print = tokio::spawn({
+            let (req, state) = (req.clone(), state.clone());
+            async move { run_print(&req, &state, None).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let shutdown = tokio::spawn({
+            let state = state.clone();
+            async move { notify_shutdown(&state).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let error = run_print(&req, &state, None).await.unwrap_err();
+        assert_eq!(error.code, SHUTTING_DOWN);
+        assert!(!*shutdown_rx.borrow_and_update());
+
+        print.await.unwrap().unwrap();
+        shutdown.await.unwrap();
+        assert!(*shutdown_rx.borrow_and_update());
+        assert_eq!(printers.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_prints_serialized_per_printer() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Kitchen")
+            .with_delay(Duration::from_millis(100));
+        let state = mock_state(&printers, AppConfig::default());
+
+        // 同一打印机的两个任务依次执行
+        let req = request(json!("Kitchen"));
+        let (first, second) =
+            tokio::join!(run_print(&req, &state, None), run_print(&req, &state, None));
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(printers.max_concurrent_calls(), 1);
+        assert_eq!(printers.calls().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_job_interval_per_printer() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Kitchen")
+            .with_printer("Bar");
+        let mut config = AppConfig::default();
+        config
+            .printer
+            .min_job_interval_ms
+            .insert("Kitchen".to_string(), 150);
+        let state = mock_state(&printers, config);
+
+        // 配置了间隔的打印机在连续任务之间等待，其他打印机不受限制（暂停的时钟只在等待间隔时推进）
+        let started = tokio::time::Instant::now();
+        for printer in ["Bar", "Bar"] {
+            run_print(&request(json!(printer)), &state, None)
+                .await
+                .unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_millis(150));
+
+        for printer in ["Kitchen", "Kitchen"] {
+            run_print(&request(json!(printer)), &state, None)
+                .await
+                .unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(printers.calls().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_get_detailed_status() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Kitchen")
+            .with_printer("Receipt")
+            .with_default_printer("Receipt");
+        let state = mock_state(&printers, AppConfig::default());
+        let mut conn = ConnectionState::default();
+
+        run_print(&request(json!("Kitchen")), &state, None)
+            .await
+            .unwrap();
+        assert!(run_print(&request(json!("Missing")), &state, None)
+            .await
+            .is_err());
+
+        let msg = r#"{"type": "get_detailed_status"}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_message(msg, &state, &mut conn).await).unwrap();
+        assert_eq!(response["type"], "detailed_status");
+        assert_eq!(response["server"]["status"], "online");
+        assert!(response["server"]["uptime_secs"].is_u64());
+        assert_eq!(response["jobs"]["total"], 2);
+        assert_eq!(response["jobs"]["failed"], 1);
+        assert_eq!(response["jobs"]["active"], 0);
+        assert_eq!(response["jobs"]["scheduled"], 0);
+        assert_eq!(response["printers"]["default_printer"], "Receipt");
+        assert_eq!(response["printers"]["count"], 2);
+        assert!(response["printers"]["error"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_hold_and_release_job() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Office")
+            .with_default_printer("Office");
+        let state = mock_state(&printers, AppConfig::default());
+        let mut conn = ConnectionState::default();
+        let mut complete_rx = state.print_complete_tx.subscribe();
+
+        let msg = json!({
+            "type": "print",
+            "id": "payslip-1",
+            "template_type": "escpos",
+            "template": "Payslip",
+            "data": {},
+            "printer": "Office",
+            "options": {"hold": true, "copies": 2, "wait_completion": true}
+        });
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_message(&msg.to_string(), &state, &mut conn).await)
+                .unwrap();
+        assert_eq!(response["status"], "held");
+        assert_eq!(response["held_jobs"], json!([1, 2]));
+        assert_eq!(state.held_jobs.printer(2).as_deref(), Some("Office"));
+        // 任务在写入数据前由打印机后端暂停
+        assert!(printers.calls().iter().all(|call| call.hold));
+
+        // 暂停的任务尚未打印，不报告为成功
+        let complete = complete_rx.try_recv().unwrap();
+        assert_eq!(complete.status, "held");
+        assert!(!complete.success);
+
+        // 释放时使用提交任务的打印机
+        let msg = r#"{"type": "release_job", "job_id": 2}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_message(msg, &state, &mut conn).await).unwrap();
+        assert_eq!(response["type"], "job_updated");
+        assert_eq!(state.held_jobs.printer(2), None);
+        assert_eq!(state.held_jobs.printer(1).as_deref(), Some("Office"));
+
+        // 未暂停的任务结果中没有 held_jobs
+        let response = print_outcome(request(json!("Office")), &state, None).await;
+        let response = serde_json::to_value(response).unwrap();
+        assert_eq!(response["status"], "success");
+        assert!(response.get("held_jobs").is_none());
+        // 成功结果中带有提交的任务 ID
+        assert_eq!(response["jobs"], json!([3]));
+
+        // 在打印机旁释放的任务在下次暂停任务时清理
+        printers.resume_job("Office", 1).unwrap();
+        let mut req = request(json!("Office"));
+        req.options.hold = true;
+        run_print(&req, &state, None).await.unwrap();
+        assert_eq!(state.held_jobs.printer(1), None);
+        assert_eq!(state.held_jobs.printer(4).as_deref(), Some("Office"));
+    }
+
+    #[tokio::test]
+    async fn test_get_escpos_status() {
+        let status = EscposStatus {
+            online: true,
+            paper_near_end: true,
+            ..Default::default()
+        };
+        let printers = MockPrinterManager::new()
+            .with_printer("Receipt")
+            .with_default_printer("Receipt")
+            .with_escpos_status(status);
+        let state = mock_state(&printers, AppConfig::default());
+        let mut conn = ConnectionState::default();
+
+        let response = handle_message(r#"{"type": "get_escpos_status"}"#, &state, &mut conn).await;
+        let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["type"], "escpos_status");
+        assert_eq!(response["printer"], "Receipt");
+        assert_eq!(response["status"]["paper_near_end"], true);
+        assert_eq!(response["status"]["cover_open"], false);
+
+        // 通过打印后台连接的打印机不支持状态查询
+        let state = mock_state(
+            &MockPrinterManager::new().with_printer("Office"),
+            AppConfig::default(),
+        );
+        let msg = r#"{"type": "get_escpos_status", "printer": "Office"}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_message(msg, &state, &mut conn).await).unwrap();
+        assert_eq!(response["code"], STATUS_NOT_SUPPORTED);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_printers() {
+        let printers = MockPrinterManager::new().with_printer("Office");
+        let state = mock_state(&printers, AppConfig::default());
+        assert!(wait_for_printers(&state, Duration::ZERO).await);
+
+        let state = mock_state(&MockPrinterManager::new(), AppConfig::default());
+        assert!(!wait_for_printers(&state, Duration::from_millis(50)).await);
+    }
+
+    #[tokio::test]
+    async fn test_print_complete_notification() {
+        let printers = MockPrinterManager::new().with_printer("Office");
+        let state = mock_state(&printers, AppConfig::default());
+        let mut complete_rx = state.print_complete_tx.subscribe();
+
+        run_print(&request(json!("Office")), &state, None)
+            .await
+            .unwrap();
+        let complete = complete_rx.try_recv().unwrap();
+        assert_eq!(complete.id.as_deref(), Some("1"));
+        assert_eq!(complete.printer.as_deref(), Some("Office"));
+        assert!(complete.success);
+
+        run_print(&request(json!("Missing")), &state, None)
+            .await
+            .unwrap_err();
+        let complete = complete_rx.try_recv().unwrap();
+        assert!(!complete.success);
+        assert!(complete.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_print() {
+        let printers = MockPrinterManager::new()
+            .with_printer("Office")
+            .with_default_printer("Office");
+        let state = mock_state(&printers, AppConfig::default());
+
+        let mut req = request(json!(null));
+        req.schedule_at = Some(now_millis() + 60_000);
+        let ServerMessage::PrintResult(result) = print_response(req.clone(), &state, None).await
+        else {
+            panic!("expected print result");
+        };
+        assert_eq!(result.status, "scheduled");
+        assert!(printers.calls().is_empty());
+        assert_eq!(state.scheduled.next_due(), req.schedule_at);
+
+        // 到期后执行并广播结果
+        let mut results = state.broadcast_tx.subscribe();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let scheduler = tokio::spawn({
+            let state = state.clone();
+            async move {
+                run_scheduled_jobs(&state, stop_rx.map(|_| ())).await;
+            }
+        });
+        req.schedule_at = Some(now_millis() + 20);
+        state.scheduled.schedule(req);
+
+        let message = results.recv().await.unwrap();
+        assert_eq!(message.origin, None);
+        assert!(message.text.contains("\"status\":\"success\""));
+        assert_eq!(printers.calls().len(), 1);
+        assert_eq!(state.scheduled.next_due(), None);
+
+        stop_tx.send(()).unwrap();
+        scheduler.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_messages_end_on_shutdown() {
+        let state = mock_state(&MockPrinterManager::new(), AppConfig::default());
+        let mut messages = Box::pin(broadcast_messages(&state));
+
+        state.broadcast("first".to_string());
+        assert_eq!(messages.next().await.as_deref(), Some("first"));
+
+        // 停机前排队的消息仍会发送
+        state.broadcast("shutdown".to_string());
+        state.shutdown_tx.send(true).unwrap();
+        assert_eq!(messages.next().await.as_deref(), Some("shutdown"));
+        assert_eq!(messages.next().await, None);
+    }
 }
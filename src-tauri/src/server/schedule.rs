@@ -0,0 +1,229 @@
+//! 定时打印
+//!
+//! 指定了 `schedule_at` 的打印请求按到期时间排序保存，到期后由服务执行；
+//! 排程会写入文件，服务或应用重启后继续等待。到期的任务执行完成后才从文件中删除，
+//! 执行中途退出时下次启动会重新执行
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::protocol::PrintRequest;
+use crate::storage::write_atomic;
+
+/// 等待执行的定时打印任务
+#[derive(Default)]
+pub struct ScheduledJobs {
+    /// 按（到期时间, 请求 ID）排序的任务
+    jobs: Mutex<BTreeMap<(u64, String), PrintRequest>>,
+    /// 已取出、正在执行的任务（执行完成前仍保存在文件中）
+    running: Mutex<BTreeMap<(u64, String), PrintRequest>>,
+    /// 持久化文件（加载后设置）
+    path: Mutex<Option<PathBuf>>,
+    /// 任务变化时唤醒调度
+    changed: Notify,
+}
+
+impl ScheduledJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从文件加载上次保存的排程（只在第一次调用时加载），之后的变化都会写回该文件
+    pub fn load(&self, path: &Path) {
+        {
+            let mut current = self.path.lock().unwrap();
+            if current.is_some() {
+                return;
+            }
+            *current = Some(path.to_path_buf());
+        }
+
+        let requests = match read_jobs(path) {
+            Ok(requests) => requests,
+            Err(e) => {
+                warn!("Failed to load scheduled jobs: {}", e);
+                return;
+            }
+        };
+        if !requests.is_empty() {
+            info!("Loaded {} scheduled print jobs", requests.len());
+        }
+
+        let mut jobs = self.jobs.lock().unwrap();
+        for req in requests {
+            jobs.insert(job_key(&req), req);
+        }
+        drop(jobs);
+        self.changed.notify_one();
+    }
+
+    /// 添加定时任务（相同 ID 的任务会被替换）
+    pub fn schedule(&self, req: PrintRequest) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|(_, id), _| id != &req.id);
+        jobs.insert(job_key(&req), req);
+        self.persist(&jobs);
+        drop(jobs);
+        self.changed.notify_one();
+    }
+
+    /// 取消定时任务，任务不存在（或已执行）时返回 false
+    pub fn cancel(&self, id: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        let before = jobs.len();
+        jobs.retain(|(_, job_id), _| job_id != id);
+        if jobs.len() == before {
+            return false;
+        }
+
+        self.persist(&jobs);
+        drop(jobs);
+        self.changed.notify_one();
+        true
+    }
+
+    /// 取出所有已到期的任务（按到期时间顺序），执行完成后需调用 `finish`
+    pub fn take_due(&self, now: u64) -> Vec<PrintRequest> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let pending = jobs.split_off(&(now.saturating_add(1), String::new()));
+        let due = std::mem::replace(&mut *jobs, pending);
+        self.running.lock().unwrap().extend(due.clone());
+        due.into_values().collect()
+    }
+
+    /// 到期任务执行完成（无论成功与否），从文件中删除
+    pub fn finish(&self, id: &str) {
+        let jobs = self.jobs.lock().unwrap();
+        let mut running = self.running.lock().unwrap();
+        let before = running.len();
+        running.retain(|(_, job_id), _| job_id != id);
+        if running.len() != before {
+            drop(running);
+            self.persist(&jobs);
+        }
+    }
+
+    /// 等待执行的任务数
+    pub fn count(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+
+    /// 最早的到期时间（Unix 毫秒）
+    pub fn next_due(&self) -> Option<u64> {
+        self.jobs.lock().unwrap().keys().next().map(|(at, _)| *at)
+    }
+
+    /// 等待任务变化（添加、取消或加载）
+    pub fn changed(&self) -> Notified<'_> {
+        self.changed.notified()
+    }
+
+    /// 将当前任务写入文件（尚未加载时不写入）
+    fn persist(&self, jobs: &BTreeMap<(u64, String), PrintRequest>) {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return;
+        };
+
+        let running = self.running.lock().unwrap();
+        let requests: Vec<&PrintRequest> = running.values().chain(jobs.values()).collect();
+        let result = serde_json::to_string(&requests)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                // 先写入临时文件再替换，写入中途失败不会截断已保存的任务
+                write_atomic(&path, content.as_bytes()).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Failed to save scheduled jobs to {:?}: {}", path, e);
+        }
+    }
+}
+
+/// 任务的排序键
+fn job_key(req: &PrintRequest) -> (u64, String) {
+    (req.schedule_at.unwrap_or_default(), req.id.clone())
+}
+
+/// 读取保存的任务，文件不存在时返回空列表
+fn read_jobs(path: &Path) -> Result<Vec<PrintRequest>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(id: &str, at: u64) -> PrintRequest {
+        serde_json::from_value(json!({
+            "id": id,
+            "template_type": "text",
+            "template": "report",
+            "data": {},
+            "schedule_at": at,
+        }))
+        .unwrap()
+    }
+
+    fn ids(requests: &[PrintRequest]) -> Vec<&str> {
+        requests.iter().map(|req| req.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_take_due_in_time_order() {
+        let jobs = ScheduledJobs::new();
+        jobs.schedule(request("late", 3000));
+        jobs.schedule(request("early", 1000));
+        jobs.schedule(request("middle", 2000));
+        assert_eq!(jobs.next_due(), Some(1000));
+
+        assert!(jobs.take_due(999).is_empty());
+        assert_eq!(ids(&jobs.take_due(2000)), ["early", "middle"]);
+        assert_eq!(jobs.next_due(), Some(3000));
+
+        // 相同 ID 重新排程时替换原任务
+        jobs.schedule(request("late", 4000));
+        assert!(jobs.take_due(3000).is_empty());
+
+        assert!(jobs.cancel("late"));
+        assert!(!jobs.cancel("late"));
+        assert_eq!(jobs.next_due(), None);
+    }
+
+    #[test]
+    fn test_persist_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scheduled.json");
+
+        let jobs = ScheduledJobs::new();
+        jobs.load(&path);
+        jobs.schedule(request("a", 1000));
+        jobs.schedule(request("b", 2000));
+        jobs.cancel("a");
+
+        let restored = ScheduledJobs::new();
+        restored.load(&path);
+        assert_eq!(restored.next_due(), Some(2000));
+        assert_eq!(ids(&restored.take_due(u64::MAX)), ["b"]);
+
+        // 执行完成前退出时，下次启动重新执行
+        let reloaded = ScheduledJobs::new();
+        reloaded.load(&path);
+        assert_eq!(reloaded.next_due(), Some(2000));
+
+        // 执行完成后的任务不会再次加载
+        restored.finish("b");
+        let reloaded = ScheduledJobs::new();
+        reloaded.load(&path);
+        assert_eq!(reloaded.next_due(), None);
+    }
+}
@@ -0,0 +1,68 @@
+//! 打印任务统计
+//!
+//! 记录服务启动以来的打印任务数和失败数，供监控看板通过 `get_detailed_status` 查询
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// 打印任务计数（应用运行期间累计，重启服务不清零）
+pub struct JobStats {
+    started_at: Instant,
+    total: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl Default for JobStats {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            total: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+}
+
+impl JobStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一个已完成的打印任务
+    pub fn record(&self, success: bool) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if !success {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 打印任务总数
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// 失败的打印任务数
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// 运行时间（秒）
+    pub fn uptime_secs(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_stats() {
+        let stats = JobStats::new();
+        stats.record(true);
+        stats.record(false);
+        stats.record(true);
+        assert_eq!(stats.total(), 3);
+        assert_eq!(stats.failed(), 1);
+        assert_eq!(stats.uptime_secs(), 0);
+    }
+}
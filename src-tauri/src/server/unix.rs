@@ -0,0 +1,189 @@
+//! Unix domain socket 监听
+//!
+//! 本机进程可通过 socket 文件访问与 TCP 相同的路由（包括 WebSocket），无需开放网络端口。
+//! socket 文件权限由 `unix_socket_mode` 决定，默认仅当前用户可访问；socket 先在仅当前用户可访问的
+//! 临时目录中创建并设置权限，再移动到配置的路径，不会以更宽松的权限短暂出现
+
+use std::future::Future;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use axum::Router;
+use hyper::server::conn::http1;
+use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+use tokio::net::UnixListener;
+use tracing::{debug, info, warn};
+
+/// 在 Unix domain socket 上提供服务，直到 `shutdown` 完成。`mode` 为八进制文件权限（如 `600`）
+pub async fn serve(
+    path: &str,
+    mode: &str,
+    app: Router,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = Path::new(path);
+    let mode = parse_mode(mode)?;
+    remove_stale_socket(path)?;
+
+    let listener = bind_private(path, mode)?;
+    info!(
+        "WebSocket server listening on unix socket {:?} (mode {:o})",
+        path, mode
+    );
+
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Failed to accept unix socket connection: {}", e);
+                        continue;
+                    }
+                };
+
+                let service = TowerToHyperService::new(app.clone());
+                tokio::spawn(async move {
+                    let result = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .with_upgrades()
+                        .await;
+                    if let Err(e) = result {
+                        debug!("Unix socket connection closed with error: {}", e);
+                    }
+                });
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    let _ = std::fs::remove_file(path);
+    info!("Unix socket listener stopped");
+    Ok(())
+}
+
+/// 在 socket 同目录下权限为 700 的临时目录中绑定并设置权限，再移动到 `path`
+///
+/// 进程的 umask 对所有线程生效，不能为绑定临时修改；目录权限保证设置权限前其他用户无法连接
+fn bind_private(path: &Path, mode: u32) -> Result<UnixListener, String> {
+    let staging = staging_dir(path);
+    if staging.exists() {
+        // 同一进程 ID 上次未正常退出时残留
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .map_err(|e| format!("Failed to create {:?}: {}", staging, e))?;
+
+    let temp = staging.join("socket");
+    let result = UnixListener::bind(&temp)
+        .map_err(|e| format!("Failed to bind {:?}: {}", path, e))
+        .and_then(|listener| {
+            std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(mode))
+                .map_err(|e| format!("Failed to set permissions on {:?}: {}", path, e))?;
+            std::fs::rename(&temp, path)
+                .map_err(|e| format!("Failed to move socket to {:?}: {}", path, e))?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_file(&temp);
+    let _ = std::fs::remove_dir(&staging);
+    result
+}
+
+/// 绑定 socket 使用的临时目录（与 socket 位于同一目录，移动时不跨文件系统）
+fn staging_dir(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// 删除上次未正常退出时残留的 socket 文件（不会删除普通文件）
+fn remove_stale_socket(path: &Path) -> Result<(), String> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(format!("{:?} exists and is not a socket", path));
+    }
+    std::fs::remove_file(path).map_err(|e| format!("Failed to remove stale socket: {}", e))
+}
+
+/// 解析八进制权限（`600`、`0660`、`0o660`）
+fn parse_mode(mode: &str) -> Result<u32, String> {
+    let digits = mode.trim().trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o777)
+        .ok_or_else(|| format!("Invalid server.unix_socket_mode: {}", mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixStream;
+    use tokio::sync::oneshot;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("600").unwrap(), 0o600);
+        assert_eq!(parse_mode("0660").unwrap(), 0o660);
+        assert_eq!(parse_mode("0o666").unwrap(), 0o666);
+        assert!(parse_mode("").is_err());
+        assert!(parse_mode("800").is_err());
+        assert!(parse_mode("7777").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serve_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rprint.sock");
+        let socket = path.to_str().unwrap().to_string();
+
+        let app = Router::new().route("/health", get(|| async { "ok" }));
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve(&socket, "600", app, async {
+                let _ = stopped.await;
+            })
+            .await
+            .map_err(|e| e.to_string())
+        });
+
+        // 等待 socket 文件创建，出现时已设置好权限
+        let connect = async {
+            loop {
+                if let Ok(meta) = std::fs::metadata(&path) {
+                    assert_eq!(meta.permissions().mode() & 0o777, 0o600);
+                    if let Ok(stream) = UnixStream::connect(&path).await {
+                        break stream;
+                    }
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+        let mut stream = tokio::time::timeout(std::time::Duration::from_secs(5), connect)
+            .await
+            .expect("unix socket not ready");
+
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("ok"), "{}", response);
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        assert!(!path.exists());
+        assert!(!staging_dir(&path).exists());
+    }
+}
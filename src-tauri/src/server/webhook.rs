@@ -0,0 +1,158 @@
+//! 打印完成 Webhook
+//!
+//! 每个打印任务结束后向配置的 `webhook_url` POST 一条 JSON 通知，中心系统无需轮询或维护 WebSocket 连接。
+//! 通知在后台发送，失败时按指数退避重试，不会阻塞打印
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::config::ServerConfig;
+
+/// 首次重试前的等待时间（之后每次翻倍）
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// 打印完成通知
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WebhookPayload {
+    /// 请求 ID
+    pub id: String,
+    /// 打印机名称（选择打印机失败时为空）
+    pub printer: Option<String>,
+    /// 打印结果: success, held（已暂停等待释放）, error
+    pub status: String,
+    /// 失败原因
+    pub error: Option<String>,
+    /// 完成时间（Unix 毫秒）
+    pub timestamp: u64,
+}
+
+/// Webhook 发送器（共用 HTTP 连接池）
+#[derive(Default)]
+pub struct Webhooks {
+    client: reqwest::Client,
+}
+
+impl Webhooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 在后台发送通知（未配置 `webhook_url` 时忽略）
+    pub fn notify(&self, config: &ServerConfig, payload: WebhookPayload) {
+        let Some(url) = config.webhook_url.clone().filter(|url| !url.is_empty()) else {
+            return;
+        };
+
+        let client = self.client.clone();
+        let timeout = Duration::from_millis(config.webhook_timeout_ms);
+        let retries = config.webhook_retries;
+        tokio::spawn(async move {
+            if let Err(e) = deliver(&client, &url, &payload, timeout, retries, RETRY_BACKOFF).await
+            {
+                warn!("Webhook for job '{}' failed: {}", payload.id, e);
+            }
+        });
+    }
+}
+
+/// 发送通知，失败时最多重试 `retries` 次（每次等待时间翻倍）
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    payload: &WebhookPayload,
+    timeout: Duration,
+    retries: u32,
+    backoff: Duration,
+) -> Result<(), String> {
+    let mut attempt = 0;
+    loop {
+        let result = client
+            .post(url)
+            .timeout(timeout)
+            .json(payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        match result {
+            Ok(_) => {
+                debug!("Webhook delivered for job '{}'", payload.id);
+                return Ok(());
+            }
+            Err(e) if attempt >= retries => return Err(e.to_string()),
+            Err(e) => {
+                let wait = backoff * 2u32.saturating_pow(attempt);
+                debug!(
+                    "Webhook attempt {} for job '{}' failed: {}, retrying in {:?}",
+                    attempt + 1,
+                    payload.id,
+                    e,
+                    wait
+                );
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    type Received = Arc<Mutex<Vec<serde_json::Value>>>;
+
+    /// 记录收到的通知，第一次请求返回 500
+    async fn receive(
+        State(received): State<Received>,
+        Json(body): Json<serde_json::Value>,
+    ) -> StatusCode {
+        let mut received = received.lock().unwrap();
+        received.push(body);
+        if received.len() == 1 {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deliver_retries() {
+        let received = Received::default();
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let payload = WebhookPayload {
+            id: "order-1".to_string(),
+            printer: Some("Receipt".to_string()),
+            status: "success".to_string(),
+            error: None,
+            timestamp: 1,
+        };
+        let client = reqwest::Client::new();
+        let timeout = Duration::from_secs(5);
+        let backoff = Duration::from_millis(1);
+
+        deliver(&client, &url, &payload, timeout, 1, backoff)
+            .await
+            .unwrap();
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[1]["id"], "order-1");
+        assert_eq!(received[1]["printer"], "Receipt");
+
+        // 不重试时第一次失败即返回错误
+        let missing = url.replace("/hook", "/missing");
+        assert!(deliver(&client, &missing, &payload, timeout, 0, backoff)
+            .await
+            .is_err());
+    }
+}
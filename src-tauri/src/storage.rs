@@ -0,0 +1,131 @@
+//! 磁盘写入
+//!
+//! 识别磁盘空间不足导致的写入失败，以 `DISK_FULL` 错误返回并向前端推送 `disk-full` 事件；
+//! 配置等重要文件先写入临时文件再重命名替换，磁盘写满时不会截断原文件
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tracing::error;
+
+use crate::protocol::{PrintError, DISK_FULL};
+
+/// 磁盘空间不足时推送到前端的事件名
+pub const DISK_FULL_EVENT: &str = "disk-full";
+
+/// 应用启动完成后设置，之后的磁盘空间不足错误会推送到前端
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+/// 磁盘空间不足事件
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskFull {
+    /// 写入失败的文件
+    pub path: String,
+    /// 错误消息
+    pub message: String,
+}
+
+/// 开始向前端推送磁盘空间不足事件
+pub fn attach(app: AppHandle) {
+    let _ = APP.set(app);
+}
+
+/// 错误是否由磁盘空间（或配额）不足引起
+pub fn is_disk_full(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded
+    )
+}
+
+/// 生成写入失败的错误消息：磁盘空间不足时以 `DISK_FULL` 开头并通知前端
+pub fn write_error(context: &str, path: &Path, e: io::Error) -> String {
+    if !is_disk_full(&e) {
+        return format!("{}: {}", context, e);
+    }
+
+    let message = format!("{}: disk is full ({:?})", context, path);
+    error!("{}", message);
+    if let Some(app) = APP.get() {
+        let event = DiskFull {
+            path: path.display().to_string(),
+            message: message.clone(),
+        };
+        let _ = app.emit(DISK_FULL_EVENT, event);
+    }
+    PrintError::new(DISK_FULL, message).to_string()
+}
+
+/// 将写入错误消息转换为打印错误（磁盘空间不足时为 `DISK_FULL`，否则使用 `code`）
+pub fn write_print_error(message: String, code: &str) -> PrintError {
+    match message
+        .strip_prefix(DISK_FULL)
+        .and_then(|m| m.strip_prefix(": "))
+    {
+        Some(message) => PrintError::new(DISK_FULL, message),
+        None => PrintError::new(code, message),
+    }
+}
+
+/// 原子写入：先写入同目录下的临时文件并刷新到磁盘，再重命名替换原文件，失败时原文件保持不变
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    let temp = path.with_file_name(file_name);
+
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ARCHIVE_FAILED;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let path = dir.join("config.json");
+
+        std::fs::write(&path, "old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert!(!dir.join("config.json.tmp").exists());
+
+        // 写入失败时原文件不变
+        assert!(write_atomic(&dir.join("missing").join("config.json"), b"x").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+    }
+
+    #[test]
+    fn test_disk_full_error() {
+        let path = Path::new("config.json");
+        let full = io::Error::from(io::ErrorKind::StorageFull);
+        assert!(is_disk_full(&full));
+
+        let message = write_error("Failed to write config file", path, full);
+        assert!(message.starts_with(DISK_FULL));
+        assert_eq!(write_print_error(message, ARCHIVE_FAILED).code, DISK_FULL);
+
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        let message = write_error("Failed to write config file", path, denied);
+        assert!(message.starts_with("Failed to write config file: "));
+        assert_eq!(
+            write_print_error(message, ARCHIVE_FAILED).code,
+            ARCHIVE_FAILED
+        );
+    }
+}
@@ -0,0 +1,544 @@
+//! 模板库模块
+//!
+//! 从配置的 `templates_dir` 目录加载模板文件，并按文件修改时间缓存。
+//! 模板可附带以模板名称命名的 JSON Schema 文件（如 `receipt.schema.json`、`label.zpl.schema.json`），
+//! 用于校验模板数据；通过界面保存的模板另有同名的元数据文件（如 `receipt.meta.json`）记录模板类型
+
+mod samples;
+mod schema;
+
+pub use samples::{load_sample, render_samples, SampleResult};
+pub use schema::check_data;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::storage::write_atomic;
+
+/// 模板文件的默认扩展名（请求中省略扩展名时尝试）
+const TEMPLATE_EXTENSION: &str = "hbs";
+
+/// 模板 Schema 文件后缀
+const SCHEMA_SUFFIX: &str = "schema.json";
+
+/// 模板元数据文件后缀
+const META_SUFFIX: &str = "meta.json";
+
+/// 可保存的模板类型（与打印请求的 template_type 一致）
+const TEMPLATE_TYPES: &[&str] = &["escpos", "zpl", "text", "html", "pdf"];
+
+/// 模板库中的模板
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateInfo {
+    /// 模板名称（用作打印请求的 template_name）
+    pub name: String,
+    /// 模板类型（未通过界面保存的模板为空）
+    pub template_type: Option<String>,
+    /// 最后修改时间（Unix 毫秒）
+    pub modified: u64,
+}
+
+/// 模板内容及其信息
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedTemplate {
+    #[serde(flatten)]
+    pub info: TemplateInfo,
+    /// 模板内容
+    pub content: String,
+}
+
+/// 模板元数据文件内容
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TemplateMeta {
+    #[serde(default)]
+    template_type: Option<String>,
+}
+
+/// 已缓存的模板
+struct CachedTemplate {
+    /// 模板内容
+    content: String,
+    /// 加载时的文件修改时间
+    modified: SystemTime,
+}
+
+/// 模板库
+///
+/// 按名称读取模板目录中的文件，文件修改时间变化时自动重新加载
+pub struct TemplateStore {
+    /// 模板目录
+    dir: RwLock<PathBuf>,
+    /// 模板缓存（键为文件路径）
+    cache: Mutex<HashMap<PathBuf, CachedTemplate>>,
+}
+
+impl TemplateStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir: RwLock::new(dir),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 当前模板目录
+    pub fn dir(&self) -> PathBuf {
+        self.dir.read().unwrap().clone()
+    }
+
+    /// 切换模板目录（同时清空缓存）
+    pub fn set_dir(&self, dir: PathBuf) {
+        let mut current = self.dir.write().unwrap();
+        if *current != dir {
+            info!("Templates directory changed to: {:?}", dir);
+            *current = dir;
+            self.cache.lock().unwrap().clear();
+        }
+    }
+
+    /// 清空缓存，下次使用时重新从磁盘加载，返回被清除的模板数量
+    pub fn reload(&self) -> usize {
+        let mut cache = self.cache.lock().unwrap();
+        let count = cache.len();
+        cache.clear();
+        info!("Template cache cleared ({} entries)", count);
+        count
+    }
+
+    /// 将模板目录中的所有模板载入缓存（启动预热用），返回载入的模板数量
+    pub fn preload(&self) -> Result<usize, String> {
+        let templates = self.list()?;
+        let mut count = 0;
+        for template in &templates {
+            match self.get(&template.name) {
+                Ok(_) => count += 1,
+                Err(e) => debug!("Skipped preloading template: {}", e),
+            }
+        }
+        Ok(count)
+    }
+
+    /// 按名称获取模板内容
+    pub fn get(&self, name: &str) -> Result<String, String> {
+        let path = self.resolve_path(name)?;
+        self.read_cached(&path)
+            .map_err(|e| format!("Failed to read template '{}': {}", name, e))
+    }
+
+    /// 获取模板附带的 JSON Schema（不存在时返回 None）
+    pub fn get_schema(&self, name: &str) -> Result<Option<Value>, String> {
+        let path = self.resolve_path(name)?;
+        let schema_path = sidecar_path(&path, SCHEMA_SUFFIX);
+        if !schema_path.is_file() {
+            return Ok(None);
+        }
+
+        let content = self
+            .read_cached(&schema_path)
+            .map_err(|e| format!("Failed to read schema for '{}': {}", name, e))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid schema for '{}': {}", name, e))
+    }
+
+    /// 保存模板（已存在时覆盖），省略扩展名时使用默认扩展名 `.hbs`
+    pub fn save(
+        &self,
+        name: &str,
+        content: &str,
+        template_type: &str,
+    ) -> Result<TemplateInfo, String> {
+        if !is_valid_template_name(name) || is_sidecar_file(name) {
+            return Err(format!("Invalid template name: {}", name));
+        }
+        if !TEMPLATE_TYPES.contains(&template_type) {
+            return Err(format!(
+                "Invalid template type: {} (expected one of {})",
+                template_type,
+                TEMPLATE_TYPES.join(", ")
+            ));
+        }
+
+        let dir = self.dir();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create templates directory: {}", e))?;
+
+        let path = if Path::new(name).extension().is_none() {
+            dir.join(format!("{}.{}", name, TEMPLATE_EXTENSION))
+        } else {
+            dir.join(name)
+        };
+        write_atomic(&path, content.as_bytes())
+            .map_err(|e| format!("Failed to save template '{}': {}", name, e))?;
+        self.cache.lock().unwrap().remove(&path);
+
+        let meta = TemplateMeta {
+            template_type: Some(template_type.to_string()),
+        };
+        let meta = serde_json::to_string_pretty(&meta).map_err(|e| e.to_string())?;
+        write_atomic(&sidecar_path(&path, META_SUFFIX), meta.as_bytes())
+            .map_err(|e| format!("Failed to save template '{}': {}", name, e))?;
+
+        info!("Template saved: {:?}", path);
+        template_info(&path)
+    }
+
+    /// 列出模板目录中的所有模板（按名称排序，目录不存在时为空）
+    pub fn list(&self) -> Result<Vec<TemplateInfo>, String> {
+        let dir = self.dir();
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read templates directory: {}", e))?;
+        let mut templates = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if !path.is_file() || file_name.starts_with('.') || is_sidecar_file(&file_name) {
+                continue;
+            }
+            templates.push(template_info(&path)?);
+        }
+
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    /// 按名称获取模板内容及其信息
+    pub fn get_saved(&self, name: &str) -> Result<SavedTemplate, String> {
+        let path = self.resolve_path(name)?;
+        let content = self
+            .read_cached(&path)
+            .map_err(|e| format!("Failed to read template '{}': {}", name, e))?;
+        Ok(SavedTemplate {
+            info: template_info(&path)?,
+            content,
+        })
+    }
+
+    /// 删除模板及其 Schema、元数据文件
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        let path = self.resolve_path(name)?;
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to delete template '{}': {}", name, e))?;
+        for suffix in [SCHEMA_SUFFIX, META_SUFFIX] {
+            let _ = std::fs::remove_file(sidecar_path(&path, suffix));
+        }
+
+        self.cache.lock().unwrap().remove(&path);
+        info!("Template deleted: {:?}", path);
+        Ok(())
+    }
+
+    /// 读取文件内容，文件修改时间未变化时使用缓存
+    fn read_cached(&self, path: &Path) -> std::io::Result<String> {
+        let modified = std::fs::metadata(path)?.modified()?;
+
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(cached) = cache.get(path) {
+            if cached.modified == modified {
+                return Ok(cached.content.clone());
+            }
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        debug!("Loaded template file {:?}", path);
+
+        cache.insert(
+            path.to_path_buf(),
+            CachedTemplate {
+                content: content.clone(),
+                modified,
+            },
+        );
+
+        Ok(content)
+    }
+
+    /// 将模板名称解析为模板目录下的文件路径（Schema 和元数据文件不能作为模板访问）
+    fn resolve_path(&self, name: &str) -> Result<PathBuf, String> {
+        if !is_valid_template_name(name) || is_sidecar_file(name) {
+            return Err(format!("Invalid template name: {}", name));
+        }
+
+        let dir = self.dir();
+        let path = dir.join(name);
+        if path.is_file() {
+            return Ok(path);
+        }
+
+        // 省略扩展名时尝试默认扩展名
+        if Path::new(name).extension().is_none() {
+            let path = dir.join(format!("{}.{}", name, TEMPLATE_EXTENSION));
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+
+        Err(format!("Template not found: {}", name))
+    }
+}
+
+/// 模板附带的文件路径，按模板名称区分（如 `receipt.hbs` -> `receipt.schema.json`，
+/// `receipt.zpl` -> `receipt.zpl.schema.json`），主干相同的模板不会共用 Schema 和元数据
+fn sidecar_path(path: &Path, suffix: &str) -> PathBuf {
+    path.with_file_name(format!("{}.{}", template_name(path), suffix))
+}
+
+/// 模板文件对应的模板名称（默认扩展名的模板以文件名主干作为名称）
+fn template_name(path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match file_name.strip_suffix(&format!(".{}", TEMPLATE_EXTENSION)) {
+        Some(stem) => stem.to_string(),
+        None => file_name,
+    }
+}
+
+/// 是否为模板附带的 Schema 或元数据文件
+fn is_sidecar_file(file_name: &str) -> bool {
+    [SCHEMA_SUFFIX, META_SUFFIX]
+        .iter()
+        .any(|suffix| file_name.ends_with(&format!(".{}", suffix)))
+}
+
+/// 读取模板文件的信息
+fn template_info(path: &Path) -> Result<TemplateInfo, String> {
+    let name = template_name(path);
+
+    let modified = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| format!("Failed to read template '{}': {}", name, e))?;
+    let modified = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+
+    // 元数据缺失或无法解析时视为未记录类型
+    let meta: TemplateMeta = std::fs::read_to_string(sidecar_path(path, META_SUFFIX))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    Ok(TemplateInfo {
+        name,
+        template_type: meta.template_type,
+        modified,
+    })
+}
+
+/// Windows 保留的设备名称（不区分大小写，带扩展名时同样指向设备）
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// 模板名称只能是模板目录下的普通文件名：
+/// 不允许路径分隔符、驱动器或数据流（`:`）、Windows 文件名中的非法字符和控制字符、
+/// 以点开头（含 `.`、`..`）或以点、空格结尾的名称，以及 Windows 保留的设备名称
+fn is_valid_template_name(name: &str) -> bool {
+    if name.trim().is_empty() || name.starts_with('.') || name.ends_with(['.', ' ']) {
+        return false;
+    }
+    if name.chars().any(|c| {
+        c.is_control() || matches!(c, '/' | '\\' | ':' | '<' | '>' | '"' | '|' | '?' | '*')
+    }) {
+        return false;
+    }
+
+    let device = name.split('.').next().unwrap_or_default().trim_end();
+    !RESERVED_NAMES
+        .iter()
+        .any(|reserved| device.eq_ignore_ascii_case(reserved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_get_template_with_and_without_extension() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("receipt.hbs"), "Hello, {{name}}!").unwrap();
+
+        let store = TemplateStore::new(dir.to_path_buf());
+        assert_eq!(store.get("receipt").unwrap(), "Hello, {{name}}!");
+        assert_eq!(store.get("receipt.hbs").unwrap(), "Hello, {{name}}!");
+        assert!(store.get("missing").is_err());
+    }
+
+    #[test]
+    fn test_preload_templates() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("receipt.hbs"), "Receipt").unwrap();
+        std::fs::write(dir.join("label.zpl"), "^XA^XZ").unwrap();
+        std::fs::write(dir.join("receipt.schema.json"), "{}").unwrap();
+
+        let store = TemplateStore::new(dir.to_path_buf());
+        assert_eq!(store.preload().unwrap(), 2);
+        assert_eq!(store.reload(), 2);
+
+        let store = TemplateStore::new(dir.join("missing"));
+        assert_eq!(store.preload().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_template_reloaded_when_modified() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("label.hbs");
+        std::fs::write(&path, "v1").unwrap();
+
+        let store = TemplateStore::new(dir.to_path_buf());
+        assert_eq!(store.get("label").unwrap(), "v1");
+
+        // 写入新内容并显式推进修改时间，避免文件系统时间精度影响
+        std::fs::write(&path, "v2").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        drop(file);
+
+        assert_eq!(store.get("label").unwrap(), "v2");
+    }
+
+    #[test]
+    fn test_get_schema_alongside_template() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("order.hbs"), "{{order_no}}").unwrap();
+        std::fs::write(
+            dir.join("order.schema.json"),
+            r#"{"type": "object", "required": ["order_no"]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("plain.hbs"), "plain").unwrap();
+
+        let store = TemplateStore::new(dir.to_path_buf());
+        let schema = store.get_schema("order").unwrap().unwrap();
+        assert_eq!(schema["required"][0], "order_no");
+        assert!(store.get_schema("plain").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_save_list_and_delete_templates() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("templates");
+        let store = TemplateStore::new(dir.clone());
+        assert!(store.list().unwrap().is_empty());
+
+        let saved = store.save("receipt", "Hello, {{name}}!", "escpos").unwrap();
+        assert_eq!(saved.name, "receipt");
+        assert_eq!(saved.template_type.as_deref(), Some("escpos"));
+        assert!(dir.join("receipt.hbs").is_file());
+        store.save("label.zpl", "^XA^XZ", "zpl").unwrap();
+        std::fs::write(dir.join("label.zpl.schema.json"), "{}").unwrap();
+        assert!(store.save("bad", "", "docx").is_err());
+        assert!(store.save("../bad", "", "text").is_err());
+
+        let names: Vec<_> = store.list().unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["label.zpl", "receipt"]);
+
+        let template = store.get_saved("receipt").unwrap();
+        assert_eq!(template.content, "Hello, {{name}}!");
+        assert_eq!(template.info.template_type.as_deref(), Some("escpos"));
+
+        // 覆盖保存后读取到新内容
+        store.save("receipt", "Hi, {{name}}!", "text").unwrap();
+        assert_eq!(store.get("receipt").unwrap(), "Hi, {{name}}!");
+
+        store.delete("label.zpl").unwrap();
+        assert!(!dir.join("label.zpl.schema.json").exists());
+        assert!(!dir.join("label.zpl.meta.json").exists());
+        assert!(store.delete("label.zpl").is_err());
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        // 不能直接读取、覆盖或删除其他模板的 Schema 和元数据
+        assert!(store.get_saved("receipt.meta.json").is_err());
+        assert!(store.save("receipt.schema.json", "{}", "text").is_err());
+        assert!(store.delete("receipt.meta.json").is_err());
+        assert!(dir.join("receipt.meta.json").is_file());
+    }
+
+    #[test]
+    fn test_sidecars_per_template() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let store = TemplateStore::new(dir.to_path_buf());
+        store.save("receipt", "Receipt", "escpos").unwrap();
+        store.save("receipt.zpl", "^XA^XZ", "zpl").unwrap();
+        std::fs::write(dir.join("receipt.schema.json"), "{}").unwrap();
+
+        // 主干相同的模板各自记录类型，删除其中一个不影响另一个的 Schema 和元数据
+        let types: Vec<_> = store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.template_type.unwrap())
+            .collect();
+        assert_eq!(types, ["escpos", "zpl"]);
+        store.delete("receipt.zpl").unwrap();
+        assert!(store.get_schema("receipt").unwrap().is_some());
+        let receipt = store.get_saved("receipt").unwrap();
+        assert_eq!(receipt.info.template_type.as_deref(), Some("escpos"));
+    }
+
+    #[test]
+    fn test_reject_path_traversal() {
+        let temp = tempfile::tempdir().unwrap();
+        let store = TemplateStore::new(temp.path().to_path_buf());
+        assert!(store.get("../config.json").is_err());
+        assert!(store.get("..\\config.json").is_err());
+        assert!(store.get("..").is_err());
+        assert!(store.get("").is_err());
+
+        // Windows 驱动器相对路径、备用数据流、设备名称和控制字符
+        for name in [
+            "C:evil",
+            "receipt:stream",
+            "CON",
+            "nul.hbs",
+            "com1",
+            "LPT9.zpl",
+            "aux .txt",
+            "receipt\0",
+            "line\nbreak",
+            ".hidden",
+            "receipt.",
+            "a|b",
+        ] {
+            assert!(
+                !is_valid_template_name(name),
+                "{:?} should be rejected",
+                name
+            );
+            assert!(store.get(name).is_err());
+        }
+        for name in [
+            "receipt",
+            "label.zpl",
+            "order-v2_final",
+            "销售小票",
+            "console",
+            "com10",
+        ] {
+            assert!(
+                is_valid_template_name(name),
+                "{:?} should be accepted",
+                name
+            );
+        }
+    }
+}
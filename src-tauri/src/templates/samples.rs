@@ -0,0 +1,115 @@
+//! 样例数据测试
+//!
+//! 设计模板时使用保存在磁盘上的样例 JSON 数据渲染模板，批量找出渲染失败的数据，
+//! 无需逐个粘贴到界面中预览
+
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::renderer::{render_template_limited, HelperProfile};
+
+/// 样例数据文件的大小上限（字节）
+const MAX_SAMPLE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// 单个样例的渲染结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SampleResult {
+    /// 样例文件名
+    pub file: String,
+    /// 是否渲染成功
+    pub success: bool,
+    /// 读取、解析或渲染失败的原因
+    pub error: Option<String>,
+}
+
+/// 读取样例 JSON 数据
+pub fn load_sample(path: &Path) -> Result<Value, String> {
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    if metadata.len() > MAX_SAMPLE_BYTES {
+        return Err(format!(
+            "Sample {:?} exceeds the limit of {} bytes",
+            path, MAX_SAMPLE_BYTES
+        ));
+    }
+
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid JSON in {:?}: {}", path, e))
+}
+
+/// 使用目录中的每个 JSON 文件渲染模板（按文件名排序，不包含子目录），
+/// 小票宽度和渲染大小上限与实际打印一致
+pub fn render_samples(
+    template: &str,
+    dir: &Path,
+    receipt_width: usize,
+    max_size: usize,
+    profile: &HelperProfile,
+) -> Result<Vec<SampleResult>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    Ok(paths
+        .iter()
+        .map(|path| {
+            let result = load_sample(path).and_then(|data| {
+                render_template_limited(template, &data, receipt_width, max_size, profile)
+                    .map_err(|e| e.to_string())
+            });
+            SampleResult {
+                file: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                success: result.is_ok(),
+                error: result.err(),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{DEFAULT_MAX_RENDER_SIZE, DEFAULT_RECEIPT_WIDTH};
+
+    #[test]
+    fn test_render_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        std::fs::write(dir.join("b.json"), r#"{"name": 1}"#).unwrap();
+        std::fs::write(dir.join("a.json"), r#"{"name": "Alice"}"#).unwrap();
+        std::fs::write(dir.join("c.json"), "{").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let template = "{{truncate name 3}}";
+        let profile = HelperProfile::default();
+        assert_eq!(load_sample(&dir.join("a.json")).unwrap()["name"], "Alice");
+
+        let render = |dir: &Path, max_size| {
+            render_samples(template, dir, DEFAULT_RECEIPT_WIDTH, max_size, &profile)
+        };
+        let results = render(dir, DEFAULT_MAX_RENDER_SIZE).unwrap();
+        let files: Vec<&str> = results.iter().map(|r| r.file.as_str()).collect();
+        assert_eq!(files, ["a.json", "b.json", "c.json"]);
+        assert_eq!(results[0].error, None);
+        assert!(!results[1].success && !results[2].success);
+        assert!(results[2].error.as_ref().unwrap().contains("Invalid JSON"));
+
+        // 超过渲染大小上限的样例渲染失败
+        let results = render(dir, 2).unwrap();
+        let error = results[0].error.as_deref().unwrap();
+        assert!(error.starts_with("RENDER_TOO_LARGE"));
+
+        assert!(render(&dir.join("missing"), DEFAULT_MAX_RENDER_SIZE).is_err());
+    }
+}
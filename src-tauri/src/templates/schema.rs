@@ -0,0 +1,82 @@
+//! 模板数据校验
+//!
+//! 使用 JSON Schema 在渲染前校验 `data`，避免缺失字段导致输出错误
+
+use serde_json::Value;
+
+use crate::protocol::PrintError;
+
+/// 数据校验失败的错误码
+pub const DATA_VALIDATION: &str = "DATA_VALIDATION";
+
+/// 使用 JSON Schema 校验模板数据
+///
+/// 校验失败时返回每个不合法字段的描述（字段路径: 原因）
+pub fn validate_data(schema: &Value, data: &Value) -> Result<(), Vec<String>> {
+    let validator =
+        jsonschema::validator_for(schema).map_err(|e| vec![format!("Invalid schema: {}", e)])?;
+
+    let errors: Vec<String> = validator
+        .iter_errors(data)
+        .map(|e| {
+            let path = e.instance_path.to_string();
+            let path = if path.is_empty() { "/" } else { path.as_str() };
+            format!("{}: {}", path, e)
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// 校验模板数据，失败时返回 `DATA_VALIDATION` 错误
+pub fn check_data(schema: &Value, data: &Value) -> Result<(), PrintError> {
+    validate_data(schema, data).map_err(|errors| {
+        PrintError::new(
+            DATA_VALIDATION,
+            format!("Data validation failed: {}", errors.join("; ")),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn order_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["order_no", "total"],
+            "properties": {
+                "order_no": { "type": "string" },
+                "total": { "type": "number" }
+            }
+        })
+    }
+
+    #[test]
+    fn test_valid_data() {
+        let data = json!({"order_no": "A001", "total": 12.5});
+        assert!(validate_data(&order_schema(), &data).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_data_reports_fields() {
+        let data = json!({"order_no": 1001});
+        let errors = validate_data(&order_schema(), &data).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.starts_with("/order_no:")));
+        assert!(errors.iter().any(|e| e.contains("total")));
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        let schema = json!({"type": 42});
+        let errors = validate_data(&schema, &json!({})).unwrap_err();
+        assert!(errors[0].starts_with("Invalid schema"));
+    }
+}
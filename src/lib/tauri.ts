@@ -4,8 +4,24 @@ import { listen, UnlistenFn } from "@tauri-apps/api/event";
 // 打印机信息类型
 export interface PrinterInfo {
   name: string;
+  id: string;
   is_default: boolean;
   status: string;
+  driver: string | null;
+  port: string | null;
+  color: boolean | null;
+}
+
+export interface PaperSettings {
+  paper_size: number;
+  form_name: string;
+  tray: number;
+  trays: TrayInfo[];
+}
+
+export interface TrayInfo {
+  id: number;
+  name: string;
 }
 
 // 配置类型
@@ -19,6 +35,25 @@ export interface ServerConfig {
   port: number;
   host: string;
   auto_start: boolean;
+  auto_start_delay_ms: number;
+  auto_start_wait_printers_secs: number;
+  warm_up_on_start: boolean;
+  shutdown_drain_timeout_secs: number;
+  max_render_bytes: number;
+  tcp_enabled: boolean;
+  tcp_nodelay: boolean;
+  tcp_keepalive_secs: number;
+  unix_socket: string | null;
+  unix_socket_mode: string;
+  api_token: string | null;
+  api_key: string | null;
+  auth_header: string;
+  log_connections: boolean;
+  audit_log_path: string | null;
+  audit_include_data: boolean;
+  webhook_url: string | null;
+  webhook_timeout_ms: number;
+  webhook_retries: number;
 }
 
 export interface PrinterConfig {
@@ -26,6 +61,44 @@ export interface PrinterConfig {
   pdf_printer: string | null;
   escpos_printer: string | null;
   zpl_printer: string | null;
+  default_paper_size: string;
+  strict_paper_size: boolean;
+  debug_print_window: boolean;
+  require_silent_print: boolean;
+  archive: boolean;
+  archive_dir: string | null;
+  commands: Record<string, PrinterCommands>;
+  receipt_width: number;
+  receipt_widths: Record<string, number>;
+  model_receipt_widths: Record<string, number>;
+  builtin_model_widths: boolean;
+  datatypes: Record<string, PrintDatatype>;
+  min_job_interval_ms: Record<string, number>;
+  routes: PrinterRoute[];
+  helper_profile: string | null;
+  helper_profiles: Record<string, HelperProfile>;
+}
+
+/** 模板 helper profile */
+export interface HelperProfile {
+  currency_symbol: string;
+  currency_decimals: number;
+  currency_rounding: 'half_up' | 'half_even' | 'ceil' | 'floor' | null;
+  utc_offset_minutes: number;
+}
+
+/** Windows 打印数据类型 */
+export type PrintDatatype = 'RAW' | 'TEXT' | 'XPS_PASS';
+
+export interface PrinterRoute {
+  field: string;
+  equals: unknown;
+  printer: string;
+}
+
+export interface PrinterCommands {
+  prologue: string | null;
+  epilogue: string | null;
 }
 
 export interface UiConfig {
@@ -33,6 +106,7 @@ export interface UiConfig {
   minimize_on_close: boolean;
   auto_launch: boolean;
   history_limit: number;
+  play_sound_on_complete: boolean;
 }
 
 // Tauri 命令调用
@@ -53,6 +127,10 @@ export async function updateConfig(config: AppConfig): Promise<void> {
   return invoke("update_config", { newConfig: config });
 }
 
+export async function resetConfig(): Promise<AppConfig> {
+  return invoke("reset_config");
+}
+
 export async function startWsServer(): Promise<string> {
   return invoke("start_ws_server");
 }
@@ -64,6 +142,7 @@ export async function stopWsServer(): Promise<string> {
 export async function getServerStatus(): Promise<{
   running: boolean;
   port: number;
+  printers_available: boolean;
 }> {
   return invoke("get_server_status");
 }
@@ -78,6 +157,57 @@ export async function getDefaultPrinter(): Promise<string | null> {
   return invoke("get_default_printer");
 }
 
+export async function printerExists(name: string): Promise<boolean> {
+  return invoke("printer_exists", { name });
+}
+
+export async function getPaperSettings(
+  printerName: string
+): Promise<PaperSettings> {
+  return invoke("get_paper_settings", { printerName });
+}
+
+export async function detectPrinterType(
+  printerName: string
+): Promise<"epl" | "zpl" | "escpos" | "normal"> {
+  return invoke("detect_printer_type", { printerName });
+}
+
+// ZPL 内置字体（用于 ^A 字体选择）
+export interface ZplFont {
+  name: string;
+  description: string;
+  height: number;
+  width: number;
+  scalable: boolean;
+}
+
+export async function getZplFonts(printerName: string): Promise<ZplFont[]> {
+  return invoke("get_zpl_fonts", { printerName });
+}
+
+export async function reprintLastJob(printerName?: string): Promise<string> {
+  return invoke("reprint_last_job", { printerName });
+}
+
+export interface PrintBenchmark {
+  printer: string;
+  iterations: number;
+  dry_run: boolean;
+  min_ms: number;
+  avg_ms: number;
+  max_ms: number;
+  p95_ms: number;
+}
+
+export async function benchmarkPrint(
+  iterations: number,
+  printerName?: string,
+  dryRun = true
+): Promise<PrintBenchmark> {
+  return invoke("benchmark_print", { printerName, iterations, dryRun });
+}
+
 export async function printRaw(printerName: string, data: number[]): Promise<void> {
   return invoke("print_raw", { printerName, data });
 }
@@ -94,6 +224,48 @@ export async function printWithTemplate(
   return invoke("print_with_template", { printerName, template, data });
 }
 
+// 使用模板库中的模板打印
+export async function printWithSavedTemplate(
+  printerName: string,
+  templateName: string,
+  data: Record<string, unknown>
+): Promise<void> {
+  return invoke("print_with_template", { printerName, templateName, data });
+}
+
+// 模板库
+export type TemplateType = "escpos" | "zpl" | "text" | "html" | "pdf";
+
+export interface TemplateInfo {
+  name: string;
+  template_type: TemplateType | null;
+  modified: number;
+}
+
+export interface SavedTemplate extends TemplateInfo {
+  content: string;
+}
+
+export async function saveTemplate(
+  name: string,
+  content: string,
+  templateType: TemplateType
+): Promise<TemplateInfo> {
+  return invoke("save_template", { name, content, templateType });
+}
+
+export async function listTemplates(): Promise<TemplateInfo[]> {
+  return invoke("list_templates");
+}
+
+export async function getTemplate(name: string): Promise<SavedTemplate> {
+  return invoke("get_template", { name });
+}
+
+export async function deleteTemplate(name: string): Promise<void> {
+  return invoke("delete_template", { name });
+}
+
 // 模板预览（不打印，只返回渲染结果）
 export async function previewTemplate(
   template: string,
@@ -102,13 +274,69 @@ export async function previewTemplate(
   return invoke("preview_template", { template, data });
 }
 
+// 使用磁盘上的样例 JSON 数据渲染模板
+export async function renderTemplateWithFile(
+  template: string,
+  dataPath: string
+): Promise<string> {
+  return invoke("render_template_with_file", { template, dataPath });
+}
+
+// 样例数据的渲染结果
+export interface SampleResult {
+  file: string;
+  success: boolean;
+  error: string | null;
+}
+
+// 使用目录中的每个样例 JSON 文件渲染模板
+export async function renderAllSamples(
+  template: string,
+  dir: string
+): Promise<SampleResult[]> {
+  return invoke("render_all_samples", { template, dir });
+}
+
+// HTML/PDF 打印结果（请求静默打印但平台不支持时 silent 为 false 并带有警告）
+export interface HtmlPrintResult {
+  silent: boolean;
+  warnings: string[];
+}
+
+// 当前平台的打印能力
+export interface PrintCapabilities {
+  silent_html_print: boolean;
+}
+
+export async function getPrintCapabilities(): Promise<PrintCapabilities> {
+  return invoke("get_print_capabilities");
+}
+
+// 纸张实际尺寸（毫米，已按方向交换宽高）
+export interface PaperDimensions {
+  width_mm: number;
+  height_mm: number;
+  landscape: boolean;
+}
+
+export async function resolvePaperSize(size: string): Promise<PaperDimensions> {
+  return invoke("resolve_paper_size", { size });
+}
+
+// 打印缩放：scale 直接指定比例，dpi 为内容排版时的显示器 DPI（按 96/dpi 缩放），两者只能指定一个
+export interface PrintScale {
+  scale?: number;
+  dpi?: number;
+}
+
 // PDF/HTML 打印
 export async function printPdf(
   htmlContent: string,
   paperSize?: string,
-  silent?: boolean
-): Promise<void> {
-  return invoke("print_pdf", { htmlContent, paperSize, silent });
+  silent?: boolean,
+  scaling: PrintScale = {}
+): Promise<HtmlPrintResult> {
+  return invoke("print_pdf", { htmlContent, paperSize, silent, ...scaling });
 }
 
 // 使用模板渲染并打印为 PDF
@@ -116,9 +344,21 @@ export async function printTemplateAsPdf(
   template: string,
   data: Record<string, unknown>,
   paperSize?: string,
-  silent?: boolean
-): Promise<void> {
-  return invoke("print_template_as_pdf", { template, data, paperSize, silent });
+  silent?: boolean,
+  scaling: PrintScale = {}
+): Promise<HtmlPrintResult> {
+  return invoke("print_template_as_pdf", {
+    template,
+    data,
+    paperSize,
+    silent,
+    ...scaling,
+  });
+}
+
+// 取消进行中的 PDF 打印，对应的打印调用以 CANCELLED 错误结束
+export async function cancelPdfPrint(): Promise<void> {
+  return invoke("cancel_pdf_print");
 }
 
 // 事件监听
@@ -126,6 +366,8 @@ export async function printTemplateAsPdf(
 export interface ServerStatusEvent {
   status: "online" | "offline";
   port?: number;
+  // 服务异常停止的原因（如 "PORT_IN_USE: ..."）
+  error?: string;
 }
 
 export function onServerStatus(
@@ -136,6 +378,45 @@ export function onServerStatus(
   });
 }
 
+// 打印完成事件（需开启 ui.play_sound_on_complete）
+export interface PrintCompleteEvent {
+  id: string | null;
+  printer: string | null;
+  status: "success" | "held" | "error";
+  success: boolean;
+  error: string | null;
+}
+
+export function onPrintComplete(
+  callback: (event: PrintCompleteEvent) => void
+): Promise<UnlistenFn> {
+  return listen<PrintCompleteEvent>("print-complete", (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onConfigUpdated(
+  callback: (config: AppConfig) => void
+): Promise<UnlistenFn> {
+  return listen<AppConfig>("config-updated", (event) => {
+    callback(event.payload);
+  });
+}
+
+// 磁盘空间不足事件（配置、归档或审计日志写入失败）
+export interface DiskFullEvent {
+  path: string;
+  message: string;
+}
+
+export function onDiskFull(
+  callback: (event: DiskFullEvent) => void
+): Promise<UnlistenFn> {
+  return listen<DiskFullEvent>("disk-full", (event) => {
+    callback(event.payload);
+  });
+}
+
 // 自启动管理
 
 export async function setAutostart(enabled: boolean): Promise<void> {
@@ -151,3 +432,29 @@ export async function getAutostart(): Promise<boolean> {
 export async function getLogDir(): Promise<string> {
   return invoke("get_log_dir");
 }
+
+export async function getRecentLogs(count: number): Promise<string[]> {
+  return invoke("get_recent_logs", { count });
+}
+
+// 诊断信息（敏感配置已脱敏，可直接复制给技术支持）
+export interface Diagnostics {
+  os: { name: string; family: string; arch: string };
+  app: { name: string; version: string; description: string };
+  config: AppConfig;
+  printers: PrinterInfo[] | { error: string };
+  server: { running: boolean; port: number };
+  log_dir: string | null;
+}
+
+export async function getDiagnostics(): Promise<Diagnostics> {
+  return invoke("get_diagnostics");
+}
+
+export function onLogLine(
+  callback: (line: string) => void
+): Promise<UnlistenFn> {
+  return listen<string>("log-line", (event) => {
+    callback(event.payload);
+  });
+}